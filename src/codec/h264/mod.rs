@@ -1 +1,23 @@
-pub mod sequence_parameter_set;
\ No newline at end of file
+use crate::{error::CustomError, util::bit_reader::BitReader};
+
+pub mod picture_parameter_set;
+pub mod picture_order_count;
+pub mod sequence_parameter_set;
+pub mod slice_header;
+pub mod supplemental_enhancement_information;
+
+/// Read past a scaling_list. The values are only needed to decode, so they're dropped. ITU-T H.264; 7.3.2.1.1.1
+pub fn skip_scaling_list(bit_reader: &mut BitReader, size_of_scaling_list: usize) -> Result<(), CustomError> {
+  let mut last_scale = 8isize;
+  let mut next_scale = 8isize;
+  for _ in 0..size_of_scaling_list {
+    if next_scale != 0 {
+      let delta_scale = bit_reader.signed_exp_golomb()?;
+      next_scale = (last_scale + delta_scale + 256) % 256;
+    }
+    if next_scale != 0 {
+      last_scale = next_scale;
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,180 @@
+use crate::codec::h264::{sequence_parameter_set::SequenceParameterSet, slice_header::SliceHeader};
+
+/// Keeps the state of the previous pictures needed to derive the picture order count (PicOrderCnt) of each picture
+/// in decoding order. ITU-T H.264; 8.2.1
+///
+/// Memory management control operation 5 isn't taken into account since the dec_ref_pic_marking isn't parsed.
+#[derive(Debug, Default)]
+pub struct PicOrderCounter {
+  // pic_order_cnt_type 0
+  prev_pic_order_cnt_msb: i64,
+  prev_pic_order_cnt_lsb: i64,
+  // pic_order_cnt_type 1 and 2
+  prev_frame_num_offset: i64,
+  prev_frame_num: i64,
+}
+
+impl PicOrderCounter {
+  pub fn create() -> PicOrderCounter {
+    PicOrderCounter::default()
+  }
+
+  /// PicOrderCnt of the picture of the first slice. For a frame it's the smallest of the top and bottom field
+  /// order counts.
+  pub fn get_pic_order_cnt(&mut self, sps: &SequenceParameterSet, slice_header: &SliceHeader) -> i64 {
+    let (top_field_order_cnt, bottom_field_order_cnt) = match sps.pic_order_cnt_type {
+      0 => self.get_type_0_field_order_cnts(sps, slice_header),
+      1 => self.get_type_1_field_order_cnts(sps, slice_header),
+      _ => self.get_type_2_field_order_cnts(sps, slice_header),
+    };
+    if slice_header.field_pic_flag == 0 {
+      top_field_order_cnt.min(bottom_field_order_cnt)
+    } else if slice_header.bottom_field_flag == 1 {
+      bottom_field_order_cnt
+    } else {
+      top_field_order_cnt
+    }
+  }
+
+  // ITU-T H.264; 8.2.1.1
+  fn get_type_0_field_order_cnts(&mut self, sps: &SequenceParameterSet, slice_header: &SliceHeader) -> (i64, i64) {
+    if slice_header.is_idr() {
+      self.prev_pic_order_cnt_msb = 0;
+      self.prev_pic_order_cnt_lsb = 0;
+    }
+    let max_pic_order_cnt_lsb = 1i64 << (sps.log2_max_pic_order_cnt_lsb_minus4 + 4);
+    let pic_order_cnt_lsb = slice_header.pic_order_cnt_lsb as i64;
+    let pic_order_cnt_msb = if pic_order_cnt_lsb < self.prev_pic_order_cnt_lsb &&
+      (self.prev_pic_order_cnt_lsb - pic_order_cnt_lsb) >= max_pic_order_cnt_lsb / 2 {
+      self.prev_pic_order_cnt_msb + max_pic_order_cnt_lsb
+    } else if pic_order_cnt_lsb > self.prev_pic_order_cnt_lsb &&
+      (pic_order_cnt_lsb - self.prev_pic_order_cnt_lsb) > max_pic_order_cnt_lsb / 2 {
+      self.prev_pic_order_cnt_msb - max_pic_order_cnt_lsb
+    } else {
+      self.prev_pic_order_cnt_msb
+    };
+    // Only reference pictures are the previous picture of the next one
+    if slice_header.nal_ref_idc != 0 {
+      self.prev_pic_order_cnt_msb = pic_order_cnt_msb;
+      self.prev_pic_order_cnt_lsb = pic_order_cnt_lsb;
+    }
+    let top_field_order_cnt = pic_order_cnt_msb + pic_order_cnt_lsb;
+    let bottom_field_order_cnt = if slice_header.field_pic_flag == 0 {
+      top_field_order_cnt + slice_header.delta_pic_order_cnt_bottom as i64
+    } else {
+      top_field_order_cnt
+    };
+    (top_field_order_cnt, bottom_field_order_cnt)
+  }
+
+  // ITU-T H.264; 8.2.1.2
+  fn get_type_1_field_order_cnts(&mut self, sps: &SequenceParameterSet, slice_header: &SliceHeader) -> (i64, i64) {
+    let frame_num_offset = self.get_frame_num_offset(sps, slice_header);
+    let num_ref_frames_in_pic_order_cnt_cycle = sps.offset_for_ref_frame.len() as i64;
+    let mut abs_frame_num = if num_ref_frames_in_pic_order_cnt_cycle != 0 {
+      frame_num_offset + slice_header.frame_num as i64
+    } else {
+      0
+    };
+    if slice_header.nal_ref_idc == 0 && abs_frame_num > 0 {
+      abs_frame_num -= 1;
+    }
+    let mut expected_pic_order_cnt = 0i64;
+    if abs_frame_num > 0 {
+      let pic_order_cnt_cycle_cnt = (abs_frame_num - 1) / num_ref_frames_in_pic_order_cnt_cycle;
+      let frame_num_in_pic_order_cnt_cycle = ((abs_frame_num - 1) % num_ref_frames_in_pic_order_cnt_cycle) as usize;
+      let expected_delta_per_pic_order_cnt_cycle: i64 = sps.offset_for_ref_frame.iter().map(|offset|*offset as i64).sum();
+      expected_pic_order_cnt = pic_order_cnt_cycle_cnt * expected_delta_per_pic_order_cnt_cycle +
+        sps.offset_for_ref_frame[0..=frame_num_in_pic_order_cnt_cycle].iter().map(|offset|*offset as i64).sum::<i64>();
+    }
+    if slice_header.nal_ref_idc == 0 {
+      expected_pic_order_cnt += sps.offset_for_non_ref_pic as i64;
+    }
+    self.prev_frame_num_offset = frame_num_offset;
+    self.prev_frame_num = slice_header.frame_num as i64;
+
+    let offset_for_top_to_bottom_field = sps.offset_for_top_to_bottom_field as i64;
+    if slice_header.field_pic_flag == 0 {
+      let top_field_order_cnt = expected_pic_order_cnt + slice_header.delta_pic_order_cnt[0] as i64;
+      let bottom_field_order_cnt = top_field_order_cnt + offset_for_top_to_bottom_field + slice_header.delta_pic_order_cnt[1] as i64;
+      (top_field_order_cnt, bottom_field_order_cnt)
+    } else {
+      let top_field_order_cnt = expected_pic_order_cnt + slice_header.delta_pic_order_cnt[0] as i64;
+      let bottom_field_order_cnt = expected_pic_order_cnt + offset_for_top_to_bottom_field + slice_header.delta_pic_order_cnt[0] as i64;
+      (top_field_order_cnt, bottom_field_order_cnt)
+    }
+  }
+
+  // ITU-T H.264; 8.2.1.3
+  fn get_type_2_field_order_cnts(&mut self, sps: &SequenceParameterSet, slice_header: &SliceHeader) -> (i64, i64) {
+    let frame_num_offset = self.get_frame_num_offset(sps, slice_header);
+    let temp_pic_order_cnt = if slice_header.is_idr() {
+      0
+    } else if slice_header.nal_ref_idc == 0 {
+      2 * (frame_num_offset + slice_header.frame_num as i64) - 1
+    } else {
+      2 * (frame_num_offset + slice_header.frame_num as i64)
+    };
+    self.prev_frame_num_offset = frame_num_offset;
+    self.prev_frame_num = slice_header.frame_num as i64;
+    (temp_pic_order_cnt, temp_pic_order_cnt)
+  }
+
+  // FrameNumOffset grows by MaxFrameNum every time frame_num wraps
+  fn get_frame_num_offset(&self, sps: &SequenceParameterSet, slice_header: &SliceHeader) -> i64 {
+    let max_frame_num = 1i64 << (sps.log2_max_frame_num_minus4 + 4);
+    if slice_header.is_idr() {
+      0
+    } else if self.prev_frame_num > slice_header.frame_num as i64 {
+      self.prev_frame_num_offset + max_frame_num
+    } else {
+      self.prev_frame_num_offset
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::codec::h264::{picture_parameter_set::{PictureParameterSet, tests::get_pps}, sequence_parameter_set::tests::get_sps, slice_header::tests::create_slice};
+
+  #[test]
+  fn test_pic_order_cnt_type_2() {
+    let sps = SequenceParameterSet::parse(&get_sps()).unwrap();
+    let pps = PictureParameterSet::parse(&get_pps(), &sps).unwrap();
+    let mut pic_order_counter = PicOrderCounter::create();
+    // IDR, P, non reference P and P again after frame_num wraps at 16
+    let slices = [create_slice(0x65, 7, 0), create_slice(0x41, 5, 1), create_slice(0x01, 5, 2), create_slice(0x41, 5, 15), create_slice(0x41, 5, 0)];
+    let pic_order_cnts: Vec<i64> = slices
+      .iter()
+      .map(|slice|{
+        let slice_header = SliceHeader::parse(slice, &sps, &pps).unwrap();
+        pic_order_counter.get_pic_order_cnt(&sps, &slice_header)
+      })
+      .collect();
+    assert_eq!(pic_order_cnts, vec![0, 2, 3, 30, 32]);
+  }
+
+  #[test]
+  fn test_pic_order_cnt_type_0() {
+    let mut sps = SequenceParameterSet::parse(&get_sps()).unwrap();
+    sps.pic_order_cnt_type = 0;
+    let pps = PictureParameterSet::parse(&get_pps(), &sps).unwrap();
+    let mut pic_order_counter = PicOrderCounter::create();
+    let mut slice_header = SliceHeader::parse(&create_slice(0x65, 7, 0), &sps, &pps).unwrap();
+    let mut get_pic_order_cnt = |nal_ref_idc: u8, is_idr: bool, pic_order_cnt_lsb: usize| {
+      slice_header.nal_ref_idc = nal_ref_idc;
+      slice_header.nal_unit_type = if is_idr { 5 } else { 1 };
+      slice_header.pic_order_cnt_lsb = pic_order_cnt_lsb;
+      pic_order_counter.get_pic_order_cnt(&sps, &slice_header)
+    };
+    assert_eq!(get_pic_order_cnt(3, true, 0), 0);
+    assert_eq!(get_pic_order_cnt(2, false, 6), 6);
+    assert_eq!(get_pic_order_cnt(0, false, 2), 2);
+    // pic_order_cnt_lsb wraps at 16
+    assert_eq!(get_pic_order_cnt(2, false, 12), 12);
+    assert_eq!(get_pic_order_cnt(2, false, 2), 18);
+    assert_eq!(get_pic_order_cnt(0, false, 14), 14);
+    assert_eq!(get_pic_order_cnt(3, true, 4), 4);
+  }
+}
@@ -0,0 +1,217 @@
+use crate::codec::{h264::{sequence_parameter_set::SequenceParameterSet, skip_scaling_list}, nal_unit_to_rbsp};
+use crate::{error::CustomError, util::bit_reader::BitReader};
+
+#[derive(Eq, PartialEq, Debug)]
+pub struct PictureParameterSet {
+  pub pic_parameter_set_id: usize,              // variable
+  pub seq_parameter_set_id: usize,              // variable
+  pub entropy_coding_mode_flag: u8,             // 1 bit
+  pub bottom_field_pic_order_in_frame_present_flag: u8, // 1 bit
+  pub num_slice_groups_minus1: usize,           // variable
+  pub num_ref_idx_l0_default_active_minus1: usize, // variable
+  pub num_ref_idx_l1_default_active_minus1: usize, // variable
+  pub weighted_pred_flag: u8,                   // 1 bit
+  pub weighted_bipred_idc: u8,                  // 2 bit
+  pub pic_init_qp_minus26: isize,               // variable
+  pub pic_init_qs_minus26: isize,               // variable
+  pub chroma_qp_index_offset: isize,            // variable
+  pub deblocking_filter_control_present_flag: u8, // 1 bit
+  pub constrained_intra_pred_flag: u8,          // 1 bit
+  pub redundant_pic_cnt_present_flag: u8,       // 1 bit
+  pub transform_8x8_mode_flag: u8,              // 1 bit
+  pub pic_scaling_matrix_present_flag: u8,      // 1 bit
+  pub second_chroma_qp_index_offset: isize,     // variable
+}
+
+impl PictureParameterSet {
+  /// pic_parameter_set_id and seq_parameter_set_id of the PPS NAL unit (NAL unit header included). The SPS has to be
+  /// known to parse the rest.
+  pub fn get_ids(data: &[u8]) -> Result<(usize, usize), CustomError> {
+    let rbsp = nal_unit_to_rbsp(data);
+    let mut bit_reader = BitReader::create_bit_reader(&rbsp);
+    bit_reader.read_bits(8)?; // nal unit header
+    let pic_parameter_set_id = bit_reader.unsigned_exp_golomb()?;
+    let seq_parameter_set_id = bit_reader.unsigned_exp_golomb()?;
+    Ok((pic_parameter_set_id, seq_parameter_set_id))
+  }
+
+  /// Parse the PPS NAL unit (NAL unit header included). The SPS it refers to is needed for the size of the scaling
+  /// matrix. ITU-T H.264; 7.3.2.2
+  pub fn parse(data: &[u8], sps: &SequenceParameterSet) -> Result<PictureParameterSet, CustomError> {
+    let rbsp = nal_unit_to_rbsp(data);
+    let mut bit_reader = BitReader::create_bit_reader(&rbsp);
+    bit_reader.read_bits(8)?; // skip the nal unit header (forbidden_zero_bit (1), nal_ref_idc(2), nal_unit_type(5))
+    let pic_parameter_set_id = bit_reader.unsigned_exp_golomb()?;
+    let seq_parameter_set_id = bit_reader.unsigned_exp_golomb()?;
+    let entropy_coding_mode_flag = bit_reader.read_bits(1)? as u8;
+    let bottom_field_pic_order_in_frame_present_flag = bit_reader.read_bits(1)? as u8;
+    let num_slice_groups_minus1 = bit_reader.unsigned_exp_golomb()?;
+    if num_slice_groups_minus1 > 0 {
+      PictureParameterSet::skip_slice_group_map(&mut bit_reader, num_slice_groups_minus1)?;
+    }
+    let num_ref_idx_l0_default_active_minus1 = bit_reader.unsigned_exp_golomb()?;
+    let num_ref_idx_l1_default_active_minus1 = bit_reader.unsigned_exp_golomb()?;
+    let weighted_pred_flag = bit_reader.read_bits(1)? as u8;
+    let weighted_bipred_idc = bit_reader.read_bits(2)? as u8;
+    let pic_init_qp_minus26 = bit_reader.signed_exp_golomb()?;
+    let pic_init_qs_minus26 = bit_reader.signed_exp_golomb()?;
+    let chroma_qp_index_offset = bit_reader.signed_exp_golomb()?;
+    let deblocking_filter_control_present_flag = bit_reader.read_bits(1)? as u8;
+    let constrained_intra_pred_flag = bit_reader.read_bits(1)? as u8;
+    let redundant_pic_cnt_present_flag = bit_reader.read_bits(1)? as u8;
+
+    // The High profile extension is only there when there is more data before the rbsp_trailing_bits
+    let mut transform_8x8_mode_flag = 0u8;
+    let mut pic_scaling_matrix_present_flag = 0u8;
+    let mut second_chroma_qp_index_offset = chroma_qp_index_offset;
+    if PictureParameterSet::more_rbsp_data(&rbsp, &bit_reader) {
+      transform_8x8_mode_flag = bit_reader.read_bits(1)? as u8;
+      pic_scaling_matrix_present_flag = bit_reader.read_bits(1)? as u8;
+      if pic_scaling_matrix_present_flag == 1 {
+        let scaling_8x8_list_count = if sps.chroma_format_idc != 3 { 2 } else { 6 };
+        let scaling_list_count = 6 + scaling_8x8_list_count * transform_8x8_mode_flag as usize;
+        for index in 0..scaling_list_count {
+          let pic_scaling_list_present_flag = bit_reader.read_bits(1)?;
+          if pic_scaling_list_present_flag == 1 {
+            let size_of_scaling_list = if index < 6 { 16 } else { 64 };
+            skip_scaling_list(&mut bit_reader, size_of_scaling_list)?;
+          }
+        }
+      }
+      second_chroma_qp_index_offset = bit_reader.signed_exp_golomb()?;
+    }
+
+    Ok(PictureParameterSet {
+      pic_parameter_set_id,
+      seq_parameter_set_id,
+      entropy_coding_mode_flag,
+      bottom_field_pic_order_in_frame_present_flag,
+      num_slice_groups_minus1,
+      num_ref_idx_l0_default_active_minus1,
+      num_ref_idx_l1_default_active_minus1,
+      weighted_pred_flag,
+      weighted_bipred_idc,
+      pic_init_qp_minus26,
+      pic_init_qs_minus26,
+      chroma_qp_index_offset,
+      deblocking_filter_control_present_flag,
+      constrained_intra_pred_flag,
+      redundant_pic_cnt_present_flag,
+      transform_8x8_mode_flag,
+      pic_scaling_matrix_present_flag,
+      second_chroma_qp_index_offset,
+    })
+  }
+
+  // Slice group map of FMO (Baseline and Extended profile only). ITU-T H.264; 7.3.2.2
+  fn skip_slice_group_map(bit_reader: &mut BitReader, num_slice_groups_minus1: usize) -> Result<(), CustomError> {
+    let slice_group_map_type = bit_reader.unsigned_exp_golomb()?;
+    match slice_group_map_type {
+      0 => {
+        for _ in 0..=num_slice_groups_minus1 {
+          // run_length_minus1
+          bit_reader.unsigned_exp_golomb()?;
+        }
+      }
+      2 => {
+        for _ in 0..num_slice_groups_minus1 {
+          // top_left, bottom_right
+          bit_reader.unsigned_exp_golomb()?;
+          bit_reader.unsigned_exp_golomb()?;
+        }
+      }
+      3..=5 => {
+        // slice_group_change_direction_flag, slice_group_change_rate_minus1
+        bit_reader.read_bits(1)?;
+        bit_reader.unsigned_exp_golomb()?;
+      }
+      6 => {
+        let pic_size_in_map_units_minus1 = bit_reader.unsigned_exp_golomb()?;
+        // slice_group_id is Ceil(Log2(num_slice_groups_minus1 + 1)) bits
+        let slice_group_id_size = (usize::BITS - num_slice_groups_minus1.leading_zeros()) as usize;
+        for _ in 0..=pic_size_in_map_units_minus1 {
+          bit_reader.read_bits(slice_group_id_size)?;
+        }
+      }
+      _ => {}
+    }
+    Ok(())
+  }
+
+  // more_rbsp_data(). Whether there is anything before the rbsp_stop_one_bit (the last bit set). ITU-T H.264; 7.2
+  fn more_rbsp_data(rbsp: &[u8], bit_reader: &BitReader) -> bool {
+    let stop_bit_position = rbsp
+      .iter()
+      .rposition(|byte|*byte != 0)
+      .map(|index|index * 8 + 7 - rbsp[index].trailing_zeros() as usize);
+    let position = rbsp.len() * 8 - bit_reader.remaining_bits();
+    stop_bit_position.is_some_and(|stop_bit_position|position < stop_bit_position)
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::codec::h264::sequence_parameter_set::tests::get_sps;
+
+  /// PPS of the Baseline profile SPS (CAVLC, no High profile extension)
+  pub fn get_pps() -> Vec<u8> {
+    vec![0x68, 0xCB, 0x8C, 0xB2]
+  }
+
+  /// The same PPS with a pic_parameter_set_id of 1
+  pub fn get_pps_with_id_1() -> Vec<u8> {
+    vec![0x68, 0x52, 0xE3, 0x2C, 0x80]
+  }
+
+  #[test]
+  fn test_parse_pps() {
+    let sps = SequenceParameterSet::parse(&get_sps()).unwrap();
+    let pps = PictureParameterSet::parse(&get_pps(), &sps).unwrap();
+    assert_eq!(pps, PictureParameterSet {
+      pic_parameter_set_id: 0,
+      seq_parameter_set_id: 0,
+      entropy_coding_mode_flag: 0,
+      bottom_field_pic_order_in_frame_present_flag: 0,
+      num_slice_groups_minus1: 0,
+      num_ref_idx_l0_default_active_minus1: 2,
+      num_ref_idx_l1_default_active_minus1: 0,
+      weighted_pred_flag: 0,
+      weighted_bipred_idc: 0,
+      pic_init_qp_minus26: 0,
+      pic_init_qs_minus26: 0,
+      chroma_qp_index_offset: -2,
+      deblocking_filter_control_present_flag: 1,
+      constrained_intra_pred_flag: 0,
+      redundant_pic_cnt_present_flag: 0,
+      transform_8x8_mode_flag: 0,
+      pic_scaling_matrix_present_flag: 0,
+      second_chroma_qp_index_offset: -2,
+    });
+  }
+
+  #[test]
+  fn test_parse_pps_ids() {
+    let sps = SequenceParameterSet::parse(&get_sps()).unwrap();
+    assert_eq!(PictureParameterSet::get_ids(&get_pps()).unwrap(), (0, 0));
+    assert_eq!(PictureParameterSet::get_ids(&get_pps_with_id_1()).unwrap(), (1, 0));
+    let pps = PictureParameterSet::parse(&get_pps_with_id_1(), &sps).unwrap();
+    assert_eq!(pps.pic_parameter_set_id, 1);
+    assert_eq!(pps.num_ref_idx_l0_default_active_minus1, 2);
+    assert_eq!(pps.second_chroma_qp_index_offset, -2);
+  }
+
+  #[test]
+  fn test_parse_high_profile_pps() {
+    // CABAC with transform_8x8_mode_flag and second_chroma_qp_index_offset (x264 High profile)
+    let pps = [0x68, 0xEB, 0xEC, 0xB2, 0x2C];
+    let sps = SequenceParameterSet::parse(&get_sps()).unwrap();
+    let pps = PictureParameterSet::parse(&pps, &sps).unwrap();
+    assert_eq!(pps.entropy_coding_mode_flag, 1);
+    assert_eq!(pps.num_ref_idx_l0_default_active_minus1, 2);
+    assert_eq!(pps.weighted_pred_flag, 1);
+    assert_eq!(pps.weighted_bipred_idc, 2);
+    assert_eq!(pps.transform_8x8_mode_flag, 1);
+    assert_eq!(pps.second_chroma_qp_index_offset, -2);
+  }
+}
@@ -1,4 +1,170 @@
-use crate::{error::CustomError, util::bit_reader::BitReader};
+use crate::codec::{h264::skip_scaling_list, nal_unit_to_rbsp};
+use crate::error::{CustomError, construct_error, error_code::{MajorCode, NalMinorCode}};
+use crate::util::bit_reader::BitReader;
+
+// log2_max_frame_num_minus4 and log2_max_pic_order_cnt_lsb_minus4 are in the range of 0 to 12. ITU-T H.264; 7.4.2.1.1
+static MAX_LOG2_MINUS4: usize = 12;
+
+/// Timing information of the VUI. ITU-T H.264; E.2.1
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct TimingInfo {
+  pub num_units_in_tick: u32,                   // 32 bit
+  pub time_scale: u32,                          // 32 bit
+  pub fixed_frame_rate_flag: u8,                // 1 bit
+}
+
+impl TimingInfo {
+  /// Duration of a frame in the given timescale. A frame is 2 ticks (one per field).
+  pub fn get_frame_duration(&self, timescale: u32) -> Option<u32> {
+    if self.num_units_in_tick == 0 || self.time_scale == 0 {
+      return None;
+    }
+    Some((2 * self.num_units_in_tick as u64 * timescale as u64 / self.time_scale as u64) as u32)
+  }
+}
+
+/// Colour description of the VUI. ITU-T H.264; E.2.1
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ColourDescription {
+  pub colour_primaries: u8,                     // 8 bit
+  pub transfer_characteristics: u8,             // 8 bit
+  pub matrix_coefficients: u8,                  // 8 bit
+}
+
+/// Bitstream restriction of the VUI. ITU-T H.264; E.2.1
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct BitstreamRestriction {
+  pub motion_vectors_over_pic_boundaries_flag: u8, // 1 bit
+  pub max_bytes_per_pic_denom: usize,           // variable
+  pub max_bits_per_mb_denom: usize,             // variable
+  pub log2_max_mv_length_horizontal: usize,     // variable
+  pub log2_max_mv_length_vertical: usize,       // variable
+  pub max_num_reorder_frames: usize,            // variable
+  pub max_dec_frame_buffering: usize,           // variable
+}
+
+/// Video usability information. ITU-T H.264; E.1.1
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct VUIParameters {
+  pub aspect_ratio_idc: u8,                     // 8 bit
+  pub sar_width: u16,                           // 16 bit
+  pub sar_height: u16,                          // 16 bit
+  pub overscan_appropriate_flag: Option<u8>,    // 1 bit
+  pub video_format: u8,                         // 3 bit
+  pub video_full_range_flag: u8,                // 1 bit
+  pub colour_description: Option<ColourDescription>,
+  pub chroma_sample_loc_type_top_field: usize,  // variable
+  pub chroma_sample_loc_type_bottom_field: usize, // variable
+  pub timing_info: Option<TimingInfo>,
+  pub nal_hrd_parameters_present_flag: u8,      // 1 bit
+  pub vcl_hrd_parameters_present_flag: u8,      // 1 bit
+  pub low_delay_hrd_flag: u8,                   // 1 bit
+  pub pic_struct_present_flag: u8,              // 1 bit
+  pub bitstream_restriction: Option<BitstreamRestriction>,
+}
+
+// Sample aspect ratios of aspect_ratio_idc 1 to 16. ITU-T H.264; Table E-1
+static SAMPLE_ASPECT_RATIOS: [(u16, u16); 16] = [
+  (1, 1), (12, 11), (10, 11), (16, 11), (40, 33), (24, 11), (20, 11), (32, 11),
+  (80, 33), (18, 11), (15, 11), (64, 33), (160, 99), (4, 3), (3, 2), (2, 1),
+];
+static EXTENDED_SAR: usize = 255;
+
+impl VUIParameters {
+  fn parse(bit_reader: &mut BitReader) -> Result<VUIParameters, CustomError> {
+    let mut vui_parameters = VUIParameters {
+      video_format: 5, // Unspecified video format
+      ..Default::default()
+    };
+    let aspect_ratio_info_present_flag = bit_reader.read_bits(1)?;
+    if aspect_ratio_info_present_flag == 1 {
+      let aspect_ratio_idc = bit_reader.read_bits(8)?;
+      vui_parameters.aspect_ratio_idc = aspect_ratio_idc as u8;
+      if aspect_ratio_idc == EXTENDED_SAR {
+        vui_parameters.sar_width = bit_reader.read_bits(16)? as u16;
+        vui_parameters.sar_height = bit_reader.read_bits(16)? as u16;
+      } else if let Some((sar_width, sar_height)) = aspect_ratio_idc
+        .checked_sub(1)
+        .and_then(|index|SAMPLE_ASPECT_RATIOS.get(index)) {
+        vui_parameters.sar_width = *sar_width;
+        vui_parameters.sar_height = *sar_height;
+      }
+    }
+    let overscan_info_present_flag = bit_reader.read_bits(1)?;
+    if overscan_info_present_flag == 1 {
+      vui_parameters.overscan_appropriate_flag = Some(bit_reader.read_bits(1)? as u8);
+    }
+    let video_signal_type_present_flag = bit_reader.read_bits(1)?;
+    if video_signal_type_present_flag == 1 {
+      vui_parameters.video_format = bit_reader.read_bits(3)? as u8;
+      vui_parameters.video_full_range_flag = bit_reader.read_bits(1)? as u8;
+      let colour_description_present_flag = bit_reader.read_bits(1)?;
+      if colour_description_present_flag == 1 {
+        vui_parameters.colour_description = Some(ColourDescription {
+          colour_primaries: bit_reader.read_bits(8)? as u8,
+          transfer_characteristics: bit_reader.read_bits(8)? as u8,
+          matrix_coefficients: bit_reader.read_bits(8)? as u8,
+        });
+      }
+    }
+    let chroma_loc_info_present_flag = bit_reader.read_bits(1)?;
+    if chroma_loc_info_present_flag == 1 {
+      vui_parameters.chroma_sample_loc_type_top_field = bit_reader.unsigned_exp_golomb()?;
+      vui_parameters.chroma_sample_loc_type_bottom_field = bit_reader.unsigned_exp_golomb()?;
+    }
+    let timing_info_present_flag = bit_reader.read_bits(1)?;
+    if timing_info_present_flag == 1 {
+      vui_parameters.timing_info = Some(TimingInfo {
+        num_units_in_tick: bit_reader.read_bits(32)? as u32,
+        time_scale: bit_reader.read_bits(32)? as u32,
+        fixed_frame_rate_flag: bit_reader.read_bits(1)? as u8,
+      });
+    }
+    vui_parameters.nal_hrd_parameters_present_flag = bit_reader.read_bits(1)? as u8;
+    if vui_parameters.nal_hrd_parameters_present_flag == 1 {
+      VUIParameters::skip_hrd_parameters(bit_reader)?;
+    }
+    vui_parameters.vcl_hrd_parameters_present_flag = bit_reader.read_bits(1)? as u8;
+    if vui_parameters.vcl_hrd_parameters_present_flag == 1 {
+      VUIParameters::skip_hrd_parameters(bit_reader)?;
+    }
+    if vui_parameters.nal_hrd_parameters_present_flag == 1 || vui_parameters.vcl_hrd_parameters_present_flag == 1 {
+      vui_parameters.low_delay_hrd_flag = bit_reader.read_bits(1)? as u8;
+    }
+    vui_parameters.pic_struct_present_flag = bit_reader.read_bits(1)? as u8;
+    let bitstream_restriction_flag = bit_reader.read_bits(1)?;
+    if bitstream_restriction_flag == 1 {
+      vui_parameters.bitstream_restriction = Some(BitstreamRestriction {
+        motion_vectors_over_pic_boundaries_flag: bit_reader.read_bits(1)? as u8,
+        max_bytes_per_pic_denom: bit_reader.unsigned_exp_golomb()?,
+        max_bits_per_mb_denom: bit_reader.unsigned_exp_golomb()?,
+        log2_max_mv_length_horizontal: bit_reader.unsigned_exp_golomb()?,
+        log2_max_mv_length_vertical: bit_reader.unsigned_exp_golomb()?,
+        max_num_reorder_frames: bit_reader.unsigned_exp_golomb()?,
+        max_dec_frame_buffering: bit_reader.unsigned_exp_golomb()?,
+      });
+    }
+    Ok(vui_parameters)
+  }
+
+  // hrd_parameters. ITU-T H.264; E.1.2
+  fn skip_hrd_parameters(bit_reader: &mut BitReader) -> Result<(), CustomError> {
+    let cpb_cnt_minus1 = bit_reader.unsigned_exp_golomb()?;
+    // bit_rate_scale, cpb_size_scale
+    bit_reader.read_bits(8)?;
+    for _ in 0..=cpb_cnt_minus1 {
+      // bit_rate_value_minus1, cpb_size_value_minus1
+      bit_reader.unsigned_exp_golomb()?;
+      bit_reader.unsigned_exp_golomb()?;
+      // cbr_flag
+      bit_reader.read_bits(1)?;
+    }
+    // initial_cpb_removal_delay_length_minus1, cpb_removal_delay_length_minus1, dpb_output_delay_length_minus1,
+    // time_offset_length
+    bit_reader.read_bits(20)?;
+    Ok(())
+  }
+}
 
 #[derive(Eq, PartialEq, Debug)]
 pub struct SequenceParameterSet {
@@ -11,23 +177,40 @@ pub struct SequenceParameterSet {
   pub constraint_set5_flag: u8,                 // 1 bit
   pub level_idc: u8,                            // 8 bit
   pub seq_parameter_set_id: usize,              // variable
+  pub chroma_format_idc: usize,                 // variable
+  pub separate_colour_plane_flag: u8,           // 1 bit
+  pub bit_depth_luma_minus8: usize,             // variable
+  pub bit_depth_chroma_minus8: usize,           // variable
+  pub seq_scaling_matrix_present_flag: u8,      // 1 bit
   pub log2_max_frame_num_minus4: usize,         // variable
+  pub pic_order_cnt_type: usize,                // variable
+  pub log2_max_pic_order_cnt_lsb_minus4: usize, // variable
+  pub delta_pic_order_always_zero_flag: u8,     // 1 bit
+  pub offset_for_non_ref_pic: isize,            // variable
+  pub offset_for_top_to_bottom_field: isize,    // variable
+  pub offset_for_ref_frame: Vec<isize>,         // variable
   pub max_num_ref_frames: usize,                // variable
   pub gaps_in_frame_num_value_allowed_flag: u8, // 1 bit
   pub direct_8x8_inference_flag: u8,            // 1 bit
   pub pic_width_in_mbs_minus1: usize,           // variable
   pub pic_height_in_map_units_minus1: usize,    // variable
   pub frame_mbs_only_flag: usize,               // variable
+  pub mb_adaptive_frame_field_flag: u8,         // 1 bit
 
   pub frame_crop_left_offset: usize,
   pub frame_crop_right_offset: usize,
   pub frame_crop_top_offset: usize,
   pub frame_crop_bottom_offset: usize,
+
+  pub vui_parameters_present_flag: u8,          // 1 bit
+  pub vui_parameters: Option<VUIParameters>,
 }
 
 impl SequenceParameterSet {
+  /// Parse the SPS NAL unit (NAL unit header included). ITU-T H.264; 7.3.2.1.1
   pub fn parse(data: &[u8]) -> Result<SequenceParameterSet, CustomError> {
-    let mut bit_reader = BitReader::create_bit_reader(data);
+    let rbsp = nal_unit_to_rbsp(data);
+    let mut bit_reader = BitReader::create_bit_reader(&rbsp);
     bit_reader.read_bits(8)?; // skip the nal unit header (forbidden_zero_bit (1), nal_ref_idc(2), nal_unit_type(5))
     let profile_idc = bit_reader.read_bits(8)? as u8;
     let constraint_set0_flag = bit_reader.read_bits(1)? as u8;
@@ -40,39 +223,64 @@ impl SequenceParameterSet {
     let level_idc = bit_reader.read_bits(8)? as u8;
     let seq_parameter_set_id = bit_reader.unsigned_exp_golomb()?;
 
+    // 4:2:0 8 bit unless the profile signals otherwise
+    let mut chroma_format_idc = 1usize;
+    let mut separate_colour_plane_flag = 0u8;
+    let mut bit_depth_luma_minus8 = 0usize;
+    let mut bit_depth_chroma_minus8 = 0usize;
+    let mut seq_scaling_matrix_present_flag = 0u8;
     if profile_idc == 100 || profile_idc == 110 || profile_idc == 122 ||
        profile_idc == 244 || profile_idc == 44 || profile_idc == 83 ||
        profile_idc == 86 || profile_idc == 118 || profile_idc == 128 ||
        profile_idc == 138 || profile_idc == 139 || profile_idc == 134 ||
        profile_idc == 135
        {
-        let chroma_format_idc = bit_reader.unsigned_exp_golomb()?;
+        chroma_format_idc = bit_reader.unsigned_exp_golomb()?;
         if chroma_format_idc == 3 {
-          let _separate_colour_plane_flag = bit_reader.read_bits(1);
+          separate_colour_plane_flag = bit_reader.read_bits(1)? as u8;
         }
-        let _bit_depth_luma_minus8 = bit_reader.unsigned_exp_golomb()?;
-        let _bit_depth_chroma_minus8 = bit_reader.unsigned_exp_golomb()?;
+        bit_depth_luma_minus8 = bit_reader.unsigned_exp_golomb()?;
+        bit_depth_chroma_minus8 = bit_reader.unsigned_exp_golomb()?;
         let _qpprime_y_zero_transform_bypass_flag = bit_reader.read_bits(1)?;
-        let seq_scaling_matrix_present_flag = bit_reader.read_bits(1)?;
+        seq_scaling_matrix_present_flag = bit_reader.read_bits(1)? as u8;
         if seq_scaling_matrix_present_flag == 1 {
-          todo!("Need to implement seq_scaling_matrix in the sps")
+          let scaling_list_count = if chroma_format_idc != 3 { 8 } else { 12 };
+          for index in 0..scaling_list_count {
+            let seq_scaling_list_present_flag = bit_reader.read_bits(1)?;
+            if seq_scaling_list_present_flag == 1 {
+              let size_of_scaling_list = if index < 6 { 16 } else { 64 };
+              skip_scaling_list(&mut bit_reader, size_of_scaling_list)?;
+            }
+          }
         }
        }
-    
-    let log2_max_frame_num_minus4 = bit_reader.unsigned_exp_golomb()?;
+
+    let log2_max_frame_num_minus4 = read_log2_minus4(&mut bit_reader, "log2_max_frame_num_minus4")?;
     let pic_order_cnt_type = bit_reader.unsigned_exp_golomb()?;
+    let mut log2_max_pic_order_cnt_lsb_minus4 = 0usize;
+    let mut delta_pic_order_always_zero_flag = 0u8;
+    let mut offset_for_non_ref_pic = 0isize;
+    let mut offset_for_top_to_bottom_field = 0isize;
+    let mut offset_for_ref_frame: Vec<isize> = vec![];
     if pic_order_cnt_type == 0 {
-      let log2_max_pic_order_cnt_lsb_minus4 = bit_reader.unsigned_exp_golomb()?;
+      log2_max_pic_order_cnt_lsb_minus4 = read_log2_minus4(&mut bit_reader, "log2_max_pic_order_cnt_lsb_minus4")?;
     } else if pic_order_cnt_type == 1 {
-      todo!("Need to implement pic_order_cnt_type 1 in the sps")
+      delta_pic_order_always_zero_flag = bit_reader.read_bits(1)? as u8;
+      offset_for_non_ref_pic = bit_reader.signed_exp_golomb()?;
+      offset_for_top_to_bottom_field = bit_reader.signed_exp_golomb()?;
+      let num_ref_frames_in_pic_order_cnt_cycle = bit_reader.unsigned_exp_golomb()?;
+      for _ in 0..num_ref_frames_in_pic_order_cnt_cycle {
+        offset_for_ref_frame.push(bit_reader.signed_exp_golomb()?);
+      }
     }
     let max_num_ref_frames = bit_reader.unsigned_exp_golomb()?;
     let gaps_in_frame_num_value_allowed_flag = bit_reader.read_bits(1)? as u8;
     let pic_width_in_mbs_minus1 = bit_reader.unsigned_exp_golomb()?;
     let pic_height_in_map_units_minus1 = bit_reader.unsigned_exp_golomb()?;
     let frame_mbs_only_flag = bit_reader.read_bits(1)?;
+    let mut mb_adaptive_frame_field_flag = 0u8;
     if frame_mbs_only_flag == 0 {
-      todo!("Need to implement mb_adaptive_frame_field_flag in the sps")
+      mb_adaptive_frame_field_flag = bit_reader.read_bits(1)? as u8;
     }
     let direct_8x8_inference_flag = bit_reader.read_bits(1)? as u8;
     let  frame_cropping_flag = bit_reader.read_bits(1)? as u8;
@@ -86,6 +294,11 @@ impl SequenceParameterSet {
       frame_crop_top_offset = bit_reader.unsigned_exp_golomb()?;
       frame_crop_bottom_offset = bit_reader.unsigned_exp_golomb()?;
     }
+    let vui_parameters_present_flag = bit_reader.read_bits(1)? as u8;
+    let mut vui_parameters: Option<VUIParameters> = None;
+    if vui_parameters_present_flag == 1 {
+      vui_parameters = Some(VUIParameters::parse(&mut bit_reader)?);
+    }
     Ok(SequenceParameterSet{
       profile_idc,
       constraint_set0_flag,
@@ -96,26 +309,62 @@ impl SequenceParameterSet {
       constraint_set5_flag,
       level_idc,
       seq_parameter_set_id,
+      chroma_format_idc,
+      separate_colour_plane_flag,
+      bit_depth_luma_minus8,
+      bit_depth_chroma_minus8,
+      seq_scaling_matrix_present_flag,
       log2_max_frame_num_minus4,
+      pic_order_cnt_type,
+      log2_max_pic_order_cnt_lsb_minus4,
+      delta_pic_order_always_zero_flag,
+      offset_for_non_ref_pic,
+      offset_for_top_to_bottom_field,
+      offset_for_ref_frame,
       max_num_ref_frames,
       gaps_in_frame_num_value_allowed_flag,
       direct_8x8_inference_flag,
       pic_width_in_mbs_minus1,
       pic_height_in_map_units_minus1,
       frame_mbs_only_flag,
+      mb_adaptive_frame_field_flag,
       frame_crop_left_offset,
       frame_crop_right_offset,
       frame_crop_top_offset,
       frame_crop_bottom_offset,
+      vui_parameters_present_flag,
+      vui_parameters,
     })
   }
 
+  pub fn get_timing_info(&self) -> Option<&TimingInfo> {
+    self.vui_parameters.as_ref().and_then(|vui_parameters|vui_parameters.timing_info.as_ref())
+  }
+
+  // CropUnitX and CropUnitY. ITU-T H.264; 7.4.2.1.1
+  fn get_crop_units(&self) -> (usize, usize) {
+    let frame_height_factor = 2 - self.frame_mbs_only_flag;
+    if self.chroma_format_idc == 0 || self.separate_colour_plane_flag == 1 {
+      return (1, frame_height_factor);
+    }
+    // SubWidthC and SubHeightC. ITU-T H.264; Table 6-1
+    match self.chroma_format_idc {
+      1 => (2, 2 * frame_height_factor),
+      2 => (2, frame_height_factor),
+      _ => (1, frame_height_factor),
+    }
+  }
+
   pub fn width(&self) -> usize {
-    ((self.pic_width_in_mbs_minus1 + 1) * 16) - self.frame_crop_left_offset * 2 - self.frame_crop_right_offset * 2
+    let (crop_unit_x, _) = self.get_crop_units();
+    ((self.pic_width_in_mbs_minus1 + 1) * 16)
+      .saturating_sub(crop_unit_x * (self.frame_crop_left_offset + self.frame_crop_right_offset))
   }
 
   pub fn height(&self) -> usize {
-    ((2 - self.frame_mbs_only_flag)* (self.pic_height_in_map_units_minus1 +1) * 16) - (self.frame_crop_top_offset * 2) - (self.frame_crop_bottom_offset * 2)
+    let (_, crop_unit_y) = self.get_crop_units();
+    ((2 - self.frame_mbs_only_flag) * (self.pic_height_in_map_units_minus1 + 1) * 16)
+      .saturating_sub(crop_unit_y * (self.frame_crop_top_offset + self.frame_crop_bottom_offset))
   }
 
   pub fn profile_compatability(&self) -> u8 {
@@ -128,8 +377,23 @@ impl SequenceParameterSet {
   }
 }
 
+// The slice header reads log2_max_frame_num and log2_max_pic_order_cnt_lsb bits, so they can't be left unchecked
+fn read_log2_minus4(bit_reader: &mut BitReader, name: &str) -> Result<usize, CustomError> {
+  let value = bit_reader.unsigned_exp_golomb()?;
+  if value > MAX_LOG2_MINUS4 {
+    return Err(construct_error(
+      MajorCode::NAL,
+      Box::new(NalMinorCode::INVALID_PARAMETER_SET_ERROR),
+      format!("{} of {} is more than {}", name, value, MAX_LOG2_MINUS4),
+      file!(),
+      line!()
+    ));
+  }
+  Ok(value)
+}
+
 #[cfg(test)]
-mod tests {
+pub mod tests {
 
   fn get_expected_sps() -> SequenceParameterSet {
     return  SequenceParameterSet{
@@ -142,31 +406,200 @@ mod tests {
       constraint_set5_flag: 0,
       level_idc: 30,
       seq_parameter_set_id: 0,
+      chroma_format_idc: 1,
+      separate_colour_plane_flag: 0,
+      bit_depth_luma_minus8: 0,
+      bit_depth_chroma_minus8: 0,
+      seq_scaling_matrix_present_flag: 0,
       log2_max_frame_num_minus4: 0,
+      pic_order_cnt_type: 2,
+      log2_max_pic_order_cnt_lsb_minus4: 0,
+      delta_pic_order_always_zero_flag: 0,
+      offset_for_non_ref_pic: 0,
+      offset_for_top_to_bottom_field: 0,
+      offset_for_ref_frame: vec![],
       max_num_ref_frames: 3,
       gaps_in_frame_num_value_allowed_flag: 0,
       direct_8x8_inference_flag: 1,
       pic_width_in_mbs_minus1: 29,
       pic_height_in_map_units_minus1: 16,
       frame_mbs_only_flag: 1,
+      mb_adaptive_frame_field_flag: 0,
       frame_crop_left_offset: 0,
       frame_crop_right_offset: 0,
       frame_crop_top_offset: 0,
       frame_crop_bottom_offset: 1,
+      vui_parameters_present_flag: 1,
+      vui_parameters: Some(VUIParameters {
+        aspect_ratio_idc: 1,
+        sar_width: 1,
+        sar_height: 1,
+        video_format: 5,
+        timing_info: Some(TimingInfo {
+          num_units_in_tick: 1,
+          time_scale: 60,
+          fixed_frame_rate_flag: 0,
+        }),
+        bitstream_restriction: Some(BitstreamRestriction {
+          motion_vectors_over_pic_boundaries_flag: 1,
+          max_bytes_per_pic_denom: 0,
+          max_bits_per_mb_denom: 0,
+          log2_max_mv_length_horizontal: 10,
+          log2_max_mv_length_vertical: 10,
+          max_num_reorder_frames: 0,
+          max_dec_frame_buffering: 3,
+        }),
+        ..Default::default()
+      }),
     }
   }
   use super::*;
+  use crate::util::bit_writer::BitWriter;
+
+  /// Baseline profile, 480x270 at 30 fps (num_units_in_tick 1, time_scale 60)
+  pub fn get_sps() -> Vec<u8> {
+    vec![
+      0x67, 0x42, 0xC0, 0x1E, 0xD9, 0x01, 0xE0, 0x8F, 0xEB, 0x01, 0x10, 0x00, 0x00, 0x03, 0x00, 0x10, 0x00, 0x00, 0x03, 0x03, 0xC0, 0xF1, 0x62, 0xE4, 0x80
+    ]
+  }
+
+  /// Same picture as get_sps, with pic_order_cnt_type 0 (6 bit pic_order_cnt_lsb) and no VUI
+  pub fn get_sps_with_pic_order_cnt_lsb() -> Vec<u8> {
+    vec![0x67, 0x42, 0xC0, 0x1E, 0xEC, 0x80, 0xF0, 0x47, 0xF4, 0x80]
+  }
+
+  pub fn write_unsigned_exp_golomb(bit_writer: &mut BitWriter, value: usize) {
+    let code = value + 1;
+    let length = usize::BITS as usize - code.leading_zeros() as usize;
+    bit_writer.write_bits(0, length - 1).write_bits(code, length);
+  }
+
+  pub fn write_signed_exp_golomb(bit_writer: &mut BitWriter, value: isize) {
+    let code_num = if value > 0 { value * 2 - 1 } else { -value * 2 };
+    write_unsigned_exp_golomb(bit_writer, code_num as usize);
+  }
 
   #[test]
   fn test_parse_sps() {
-    let sps:[u8; 25] = [
-      0x67, 0x42, 0xC0, 0x1E, 0xD9, 0x01, 0xE0, 0x8F, 0xEB, 0x01, 0x10, 0x00, 0x00, 0x03, 0x00, 0x10, 0x00, 0x00, 0x03, 0x03, 0xC0, 0xF1, 0x62, 0xE4, 0x80
-    ];
+    let sps = get_sps();
     let expected_sps: SequenceParameterSet = get_expected_sps();
     let actual_sps = SequenceParameterSet::parse(&sps).unwrap();
     assert_eq!(actual_sps.width(), 480);
     assert_eq!(actual_sps.height(), 270);
     assert_eq!(actual_sps.profile_compatability(), 192);
     assert_eq!(actual_sps, expected_sps);
+    assert_eq!(actual_sps.get_timing_info().unwrap().get_frame_duration(90000), Some(3000));
+  }
+
+  #[test]
+  fn test_parse_high_profile_interlaced_sps() {
+    // High profile 1920x1080i with scaling matrices, POC type 1 and a full VUI
+    let mut bit_writer = BitWriter::create_bit_writer();
+    bit_writer
+      .write_bits(0x67, 8)
+      .write_bits(100, 8) // profile_idc
+      .write_bits(0, 8) // constraint flags and reserved
+      .write_bits(40, 8); // level_idc
+    write_unsigned_exp_golomb(&mut bit_writer, 0); // seq_parameter_set_id
+    write_unsigned_exp_golomb(&mut bit_writer, 1); // chroma_format_idc
+    write_unsigned_exp_golomb(&mut bit_writer, 0); // bit_depth_luma_minus8
+    write_unsigned_exp_golomb(&mut bit_writer, 0); // bit_depth_chroma_minus8
+    bit_writer.write_bits(0, 1).write_bits(1, 1); // qpprime_y_zero_transform_bypass_flag, seq_scaling_matrix_present_flag
+    // The first 4x4 list and the first 8x8 list are present. The 4x4 one stops early with a delta to 0.
+    bit_writer.write_bits(1, 1);
+    write_signed_exp_golomb(&mut bit_writer, 8);
+    write_signed_exp_golomb(&mut bit_writer, -16);
+    bit_writer.write_bits(0, 5);
+    bit_writer.write_bits(1, 1);
+    for _ in 0..64 {
+      write_signed_exp_golomb(&mut bit_writer, 1);
+    }
+    bit_writer.write_bits(0, 1);
+    write_unsigned_exp_golomb(&mut bit_writer, 4); // log2_max_frame_num_minus4
+    write_unsigned_exp_golomb(&mut bit_writer, 1); // pic_order_cnt_type
+    bit_writer.write_bits(0, 1); // delta_pic_order_always_zero_flag
+    write_signed_exp_golomb(&mut bit_writer, -2); // offset_for_non_ref_pic
+    write_signed_exp_golomb(&mut bit_writer, 1); // offset_for_top_to_bottom_field
+    write_unsigned_exp_golomb(&mut bit_writer, 2); // num_ref_frames_in_pic_order_cnt_cycle
+    write_signed_exp_golomb(&mut bit_writer, 2);
+    write_signed_exp_golomb(&mut bit_writer, 4);
+    write_unsigned_exp_golomb(&mut bit_writer, 4); // max_num_ref_frames
+    bit_writer.write_bits(0, 1); // gaps_in_frame_num_value_allowed_flag
+    write_unsigned_exp_golomb(&mut bit_writer, 119); // pic_width_in_mbs_minus1
+    write_unsigned_exp_golomb(&mut bit_writer, 33); // pic_height_in_map_units_minus1
+    bit_writer.write_bits(0, 1).write_bits(1, 1); // frame_mbs_only_flag, mb_adaptive_frame_field_flag
+    bit_writer.write_bits(1, 1).write_bits(1, 1); // direct_8x8_inference_flag, frame_cropping_flag
+    write_unsigned_exp_golomb(&mut bit_writer, 0);
+    write_unsigned_exp_golomb(&mut bit_writer, 0);
+    write_unsigned_exp_golomb(&mut bit_writer, 0);
+    write_unsigned_exp_golomb(&mut bit_writer, 2); // frame_crop_bottom_offset (4 lines per unit)
+    bit_writer.write_bits(1, 1); // vui_parameters_present_flag
+    bit_writer.write_bits(1, 1).write_bits(255, 8).write_bits(4, 16).write_bits(3, 16); // Extended_SAR 4:3
+    bit_writer.write_bits(0, 1); // overscan_info_present_flag
+    bit_writer.write_bits(1, 1).write_bits(5, 3).write_bits(0, 1).write_bits(1, 1).write_bits(1, 8).write_bits(1, 8).write_bits(1, 8);
+    bit_writer.write_bits(0, 1); // chroma_loc_info_present_flag
+    bit_writer.write_bits(1, 1).write_bits(1001, 32).write_bits(60000, 32).write_bits(1, 1);
+    bit_writer.write_bits(1, 1); // nal_hrd_parameters_present_flag
+    write_unsigned_exp_golomb(&mut bit_writer, 0); // cpb_cnt_minus1
+    bit_writer.write_bits(0x44, 8);
+    write_unsigned_exp_golomb(&mut bit_writer, 1000);
+    write_unsigned_exp_golomb(&mut bit_writer, 2000);
+    bit_writer.write_bits(0, 1).write_bits(0x5EF7B, 20);
+    bit_writer.write_bits(0, 1).write_bits(0, 1).write_bits(1, 1); // vcl_hrd, low_delay_hrd_flag, pic_struct_present
+    bit_writer.write_bits(1, 1).write_bits(1, 1); // bitstream_restriction_flag, motion_vectors_over_pic_boundaries_flag
+    for value in [2, 1, 16, 16, 2, 4] {
+      write_unsigned_exp_golomb(&mut bit_writer, value);
+    }
+    bit_writer.write_bits(1, 1); // rbsp_stop_one_bit
+
+    let sps = SequenceParameterSet::parse(&bit_writer.finish()).unwrap();
+    assert_eq!(sps.seq_scaling_matrix_present_flag, 1);
+    assert_eq!(sps.pic_order_cnt_type, 1);
+    assert_eq!((sps.offset_for_non_ref_pic, sps.offset_for_top_to_bottom_field), (-2, 1));
+    assert_eq!(sps.offset_for_ref_frame, vec![2, 4]);
+    assert_eq!(sps.frame_mbs_only_flag, 0);
+    assert_eq!(sps.mb_adaptive_frame_field_flag, 1);
+    assert_eq!(sps.width(), 1920);
+    assert_eq!(sps.height(), 1080);
+    let vui_parameters = sps.vui_parameters.unwrap();
+    assert_eq!((vui_parameters.sar_width, vui_parameters.sar_height), (4, 3));
+    assert_eq!(vui_parameters.colour_description.unwrap().colour_primaries, 1);
+    assert_eq!(vui_parameters.timing_info.unwrap().get_frame_duration(90000), Some(3003));
+    assert_eq!(vui_parameters.pic_struct_present_flag, 1);
+    let bitstream_restriction = vui_parameters.bitstream_restriction.unwrap();
+    assert_eq!(bitstream_restriction.max_num_reorder_frames, 2);
+    assert_eq!(bitstream_restriction.max_dec_frame_buffering, 4);
+  }
+
+  #[test]
+  fn test_parse_sps_with_pic_order_cnt_lsb() {
+    let sps = SequenceParameterSet::parse(&get_sps_with_pic_order_cnt_lsb()).unwrap();
+    assert_eq!(sps.pic_order_cnt_type, 0);
+    assert_eq!(sps.log2_max_pic_order_cnt_lsb_minus4, 2);
+    assert_eq!(sps.vui_parameters, None);
+    assert_eq!((sps.width(), sps.height()), (480, 270));
+  }
+
+  #[test]
+  fn test_parse_sps_with_invalid_log2_max_frame_num() {
+    let mut bit_writer = BitWriter::create_bit_writer();
+    bit_writer
+      .write_bits(0x67, 8)
+      .write_bits(66, 8) // profile_idc
+      .write_bits(0, 8) // constraint flags and reserved
+      .write_bits(30, 8); // level_idc
+    write_unsigned_exp_golomb(&mut bit_writer, 0); // seq_parameter_set_id
+    write_unsigned_exp_golomb(&mut bit_writer, 60); // log2_max_frame_num_minus4
+    bit_writer.write_bits(0xFFFF, 16);
+
+    let err = SequenceParameterSet::parse(&bit_writer.finish()).unwrap_err();
+    assert_eq!(err.major, MajorCode::NAL);
+    assert_eq!(err.minor, NalMinorCode::INVALID_PARAMETER_SET_ERROR as u8);
+  }
+
+  #[test]
+  fn test_parse_truncated_sps() {
+    let sps = get_sps();
+    assert!(SequenceParameterSet::parse(&sps[0..8]).is_err());
   }
 }
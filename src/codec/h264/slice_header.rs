@@ -0,0 +1,232 @@
+use crate::codec::{h264::{picture_parameter_set::PictureParameterSet, sequence_parameter_set::SequenceParameterSet}, nal_unit_to_rbsp};
+use crate::error::{CustomError, construct_error, error_code::{MajorCode, NalMinorCode}};
+use crate::util::bit_reader::BitReader;
+
+static IDR_NAL_UNIT_TYPE: u8 = 5;
+// Only the first bytes of the slice are needed for the syntax elements up to delta_pic_order_cnt
+static MAX_SLICE_HEADER_SIZE: usize = 64;
+
+/// ITU-T H.264; Table 7-6. slice_type values 5 to 9 mean every slice of the picture has the same type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum SliceType {
+  P,
+  B,
+  I,
+  SP,
+  SI,
+}
+
+impl SliceType {
+  pub fn from(slice_type: usize) -> SliceType {
+    match slice_type % 5 {
+      0 => SliceType::P,
+      1 => SliceType::B,
+      2 => SliceType::I,
+      3 => SliceType::SP,
+      _ => SliceType::SI,
+    }
+  }
+
+  /// I and SI slices only use intra prediction
+  pub fn is_intra(&self) -> bool {
+    matches!(self, SliceType::I | SliceType::SI)
+  }
+}
+
+/// The start of the slice header, up to the picture order count syntax elements. ITU-T H.264; 7.3.3
+#[derive(Debug, PartialEq, Eq)]
+pub struct SliceHeader {
+  pub nal_ref_idc: u8,                          // 2 bit
+  pub nal_unit_type: u8,                        // 5 bit
+  pub first_mb_in_slice: usize,                 // variable
+  pub slice_type: SliceType,                    // variable
+  pub pic_parameter_set_id: usize,              // variable
+  pub colour_plane_id: u8,                      // 2 bit
+  pub frame_num: usize,                         // variable
+  pub field_pic_flag: u8,                       // 1 bit
+  pub bottom_field_flag: u8,                    // 1 bit
+  pub idr_pic_id: Option<usize>,                // variable
+  pub pic_order_cnt_lsb: usize,                 // variable
+  pub delta_pic_order_cnt_bottom: isize,        // variable
+  pub delta_pic_order_cnt: [isize; 2],          // variable
+}
+
+impl SliceHeader {
+  /// pic_parameter_set_id of a coded slice NAL unit (NAL unit header included), which tells the parameter sets needed
+  /// to parse the rest of the slice header
+  pub fn get_pic_parameter_set_id(data: &[u8]) -> Result<usize, CustomError> {
+    let rbsp = nal_unit_to_rbsp(&data[0..data.len().min(MAX_SLICE_HEADER_SIZE)]);
+    let mut bit_reader = BitReader::create_bit_reader(&rbsp);
+    bit_reader.read_bits(8)?; // nal unit header
+    bit_reader.unsigned_exp_golomb()?; // first_mb_in_slice
+    bit_reader.unsigned_exp_golomb()?; // slice_type
+    bit_reader.unsigned_exp_golomb()
+  }
+
+  /// Parse the slice header of a coded slice NAL unit (NAL unit header included) with the parameter sets it refers to
+  pub fn parse(data: &[u8], sps: &SequenceParameterSet, pps: &PictureParameterSet) -> Result<SliceHeader, CustomError> {
+    let rbsp = nal_unit_to_rbsp(&data[0..data.len().min(MAX_SLICE_HEADER_SIZE)]);
+    let mut bit_reader = BitReader::create_bit_reader(&rbsp);
+    bit_reader.read_bits(1)?; // forbidden_zero_bit
+    let nal_ref_idc = bit_reader.read_bits(2)? as u8;
+    let nal_unit_type = bit_reader.read_bits(5)? as u8;
+    let first_mb_in_slice = bit_reader.unsigned_exp_golomb()?;
+    let slice_type = SliceType::from(bit_reader.unsigned_exp_golomb()?);
+    let pic_parameter_set_id = bit_reader.unsigned_exp_golomb()?;
+    if pic_parameter_set_id != pps.pic_parameter_set_id || pps.seq_parameter_set_id != sps.seq_parameter_set_id {
+      return Err(construct_error(
+        MajorCode::NAL,
+        Box::new(NalMinorCode::MISSING_PARAMETER_SET_ERROR),
+        format!("Slice refers to PPS {}, but only PPS {} is available", pic_parameter_set_id, pps.pic_parameter_set_id),
+        file!(),
+        line!()
+      ));
+    }
+    let mut colour_plane_id = 0u8;
+    if sps.separate_colour_plane_flag == 1 {
+      colour_plane_id = bit_reader.read_bits(2)? as u8;
+    }
+    let frame_num = bit_reader.read_bits(sps.log2_max_frame_num_minus4 + 4)?;
+    let mut field_pic_flag = 0u8;
+    let mut bottom_field_flag = 0u8;
+    if sps.frame_mbs_only_flag == 0 {
+      field_pic_flag = bit_reader.read_bits(1)? as u8;
+      if field_pic_flag == 1 {
+        bottom_field_flag = bit_reader.read_bits(1)? as u8;
+      }
+    }
+    let mut idr_pic_id: Option<usize> = None;
+    if nal_unit_type == IDR_NAL_UNIT_TYPE {
+      idr_pic_id = Some(bit_reader.unsigned_exp_golomb()?);
+    }
+    let mut pic_order_cnt_lsb = 0usize;
+    let mut delta_pic_order_cnt_bottom = 0isize;
+    let mut delta_pic_order_cnt = [0isize; 2];
+    let has_bottom_field_delta = pps.bottom_field_pic_order_in_frame_present_flag == 1 && field_pic_flag == 0;
+    if sps.pic_order_cnt_type == 0 {
+      pic_order_cnt_lsb = bit_reader.read_bits(sps.log2_max_pic_order_cnt_lsb_minus4 + 4)?;
+      if has_bottom_field_delta {
+        delta_pic_order_cnt_bottom = bit_reader.signed_exp_golomb()?;
+      }
+    }
+    if sps.pic_order_cnt_type == 1 && sps.delta_pic_order_always_zero_flag == 0 {
+      delta_pic_order_cnt[0] = bit_reader.signed_exp_golomb()?;
+      if has_bottom_field_delta {
+        delta_pic_order_cnt[1] = bit_reader.signed_exp_golomb()?;
+      }
+    }
+
+    Ok(SliceHeader {
+      nal_ref_idc,
+      nal_unit_type,
+      first_mb_in_slice,
+      slice_type,
+      pic_parameter_set_id,
+      colour_plane_id,
+      frame_num,
+      field_pic_flag,
+      bottom_field_flag,
+      idr_pic_id,
+      pic_order_cnt_lsb,
+      delta_pic_order_cnt_bottom,
+      delta_pic_order_cnt,
+    })
+  }
+
+  pub fn is_idr(&self) -> bool {
+    self.nal_unit_type == IDR_NAL_UNIT_TYPE
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::codec::h264::picture_parameter_set::tests::get_pps;
+  use crate::codec::h264::sequence_parameter_set::tests::{get_sps, write_signed_exp_golomb, write_unsigned_exp_golomb};
+  use crate::util::bit_writer::BitWriter;
+
+  /// Slice of the Baseline profile SPS and PPS (log2_max_frame_num 4, pic_order_cnt_type 2)
+  pub fn create_slice(nal_header: u8, slice_type: usize, frame_num: usize) -> Vec<u8> {
+    create_slice_with_pic_order_cnt_lsb(nal_header, slice_type, frame_num, None)
+  }
+
+  /// Slice with a 6 bit pic_order_cnt_lsb, for the SPS with pic_order_cnt_type 0
+  pub fn create_slice_with_pic_order_cnt_lsb(
+    nal_header: u8,
+    slice_type: usize,
+    frame_num: usize,
+    pic_order_cnt_lsb: Option<usize>
+  ) -> Vec<u8> {
+    let mut bit_writer = BitWriter::create_bit_writer();
+    bit_writer.write_bits(nal_header as usize, 8);
+    write_unsigned_exp_golomb(&mut bit_writer, 0); // first_mb_in_slice
+    write_unsigned_exp_golomb(&mut bit_writer, slice_type);
+    write_unsigned_exp_golomb(&mut bit_writer, 0); // pic_parameter_set_id
+    bit_writer.write_bits(frame_num, 4);
+    if nal_header & 0x1F == IDR_NAL_UNIT_TYPE {
+      write_unsigned_exp_golomb(&mut bit_writer, 1); // idr_pic_id
+    }
+    if let Some(pic_order_cnt_lsb) = pic_order_cnt_lsb {
+      bit_writer.write_bits(pic_order_cnt_lsb, 6);
+    }
+    // Some slice data
+    bit_writer.write_bits(0xA5, 8).write_bits(1, 1);
+    bit_writer.finish()
+  }
+
+  #[test]
+  fn test_parse_slice_header() {
+    let sps = SequenceParameterSet::parse(&get_sps()).unwrap();
+    let pps = PictureParameterSet::parse(&get_pps(), &sps).unwrap();
+
+    assert_eq!(SliceHeader::get_pic_parameter_set_id(&create_slice(0x65, 7, 0)).unwrap(), 0);
+    let idr = SliceHeader::parse(&create_slice(0x65, 7, 0), &sps, &pps).unwrap();
+    assert!(idr.is_idr());
+    assert_eq!(idr.slice_type, SliceType::I);
+    assert!(idr.slice_type.is_intra());
+    assert_eq!(idr.idr_pic_id, Some(1));
+    assert_eq!(idr.nal_ref_idc, 3);
+
+    let b_slice = SliceHeader::parse(&create_slice(0x01, 1, 3), &sps, &pps).unwrap();
+    assert!(!b_slice.is_idr());
+    assert_eq!(b_slice.slice_type, SliceType::B);
+    assert_eq!(b_slice.frame_num, 3);
+    assert_eq!(b_slice.idr_pic_id, None);
+    assert_eq!(b_slice.nal_ref_idc, 0);
+  }
+
+  #[test]
+  fn test_parse_slice_header_pic_order_cnt() {
+    // pic_order_cnt_type 0 with log2_max_pic_order_cnt_lsb 6 and bottom field deltas
+    let mut sps = SequenceParameterSet::parse(&get_sps()).unwrap();
+    sps.pic_order_cnt_type = 0;
+    sps.log2_max_pic_order_cnt_lsb_minus4 = 2;
+    let mut pps = PictureParameterSet::parse(&get_pps(), &sps).unwrap();
+    pps.bottom_field_pic_order_in_frame_present_flag = 1;
+
+    let mut bit_writer = BitWriter::create_bit_writer();
+    bit_writer.write_bits(0x41, 8);
+    write_unsigned_exp_golomb(&mut bit_writer, 10); // first_mb_in_slice
+    write_unsigned_exp_golomb(&mut bit_writer, 5); // slice_type
+    write_unsigned_exp_golomb(&mut bit_writer, 0); // pic_parameter_set_id
+    bit_writer.write_bits(9, 4).write_bits(38, 6);
+    write_signed_exp_golomb(&mut bit_writer, -1);
+    bit_writer.write_bits(1, 1);
+    let slice_header = SliceHeader::parse(&bit_writer.finish(), &sps, &pps).unwrap();
+    assert_eq!(slice_header.first_mb_in_slice, 10);
+    assert_eq!(slice_header.slice_type, SliceType::P);
+    assert_eq!(slice_header.frame_num, 9);
+    assert_eq!(slice_header.pic_order_cnt_lsb, 38);
+    assert_eq!(slice_header.delta_pic_order_cnt_bottom, -1);
+
+    // Slice of a PPS that hasn't been seen
+    let mut bit_writer = BitWriter::create_bit_writer();
+    bit_writer.write_bits(0x41, 8);
+    write_unsigned_exp_golomb(&mut bit_writer, 0);
+    write_unsigned_exp_golomb(&mut bit_writer, 0);
+    write_unsigned_exp_golomb(&mut bit_writer, 3);
+    bit_writer.write_bits(0, 16);
+    assert!(SliceHeader::parse(&bit_writer.finish(), &sps, &pps).is_err());
+  }
+}
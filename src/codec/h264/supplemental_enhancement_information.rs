@@ -0,0 +1,111 @@
+use crate::codec::nal_unit_to_rbsp;
+use crate::error::{CustomError, construct_error, error_code::{MajorCode, NalMinorCode}};
+use crate::util::bit_reader::BitReader;
+
+/// payloadType of the recovery point SEI message. ITU-T H.264; D.1.8
+pub static RECOVERY_POINT_PAYLOAD_TYPE: usize = 6;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SEIMessage {
+  pub payload_type: usize,
+  pub payload: Vec<u8>,
+}
+
+/// Recovery point SEI message. Decoding from the picture it's sent with gives correct pictures after
+/// recovery_frame_cnt frames, which is how open GOP streams signal their random access points. ITU-T H.264; D.2.8
+#[derive(Debug, PartialEq, Eq)]
+pub struct RecoveryPoint {
+  pub recovery_frame_cnt: usize,                // variable
+  pub exact_match_flag: u8,                     // 1 bit
+  pub broken_link_flag: u8,                     // 1 bit
+  pub changing_slice_group_idc: u8,             // 2 bit
+}
+
+impl SEIMessage {
+  /// Parse every SEI message of the SEI NAL unit (NAL unit header included). ITU-T H.264; 7.3.2.3
+  pub fn parse(data: &[u8]) -> Result<Vec<SEIMessage>, CustomError> {
+    let rbsp = nal_unit_to_rbsp(data);
+    let mut index = 1usize;
+    let mut messages: Vec<SEIMessage> = vec![];
+    // Stop at the rbsp_trailing_bits
+    while index < rbsp.len() && rbsp[index] != 0x80 {
+      let payload_type = SEIMessage::read_value(&rbsp, &mut index)?;
+      let payload_size = SEIMessage::read_value(&rbsp, &mut index)?;
+      let end = index + payload_size;
+      if end > rbsp.len() {
+        return Err(construct_error(
+          MajorCode::NAL,
+          Box::new(NalMinorCode::UNEXPTED_NAL_UNIT_LENGTH_ERROR),
+          format!("SEI payload of {} bytes exceeds the NAL unit", payload_size),
+          file!(),
+          line!()
+        ));
+      }
+      messages.push(SEIMessage {
+        payload_type,
+        payload: rbsp[index..end].to_vec(),
+      });
+      index = end;
+    }
+    Ok(messages)
+  }
+
+  pub fn get_recovery_point(&self) -> Result<Option<RecoveryPoint>, CustomError> {
+    if self.payload_type != RECOVERY_POINT_PAYLOAD_TYPE {
+      return Ok(None);
+    }
+    let mut bit_reader = BitReader::create_bit_reader(&self.payload);
+    Ok(Some(RecoveryPoint {
+      recovery_frame_cnt: bit_reader.unsigned_exp_golomb()?,
+      exact_match_flag: bit_reader.read_bits(1)? as u8,
+      broken_link_flag: bit_reader.read_bits(1)? as u8,
+      changing_slice_group_idc: bit_reader.read_bits(2)? as u8,
+    }))
+  }
+
+  // payloadType and payloadSize are a run of 0xFF bytes (255 each) and a last byte
+  fn read_value(rbsp: &[u8], index: &mut usize) -> Result<usize, CustomError> {
+    let mut value = 0usize;
+    loop {
+      let byte = *rbsp.get(*index).ok_or_else(||construct_error(
+        MajorCode::NAL,
+        Box::new(NalMinorCode::UNEXPTED_NAL_UNIT_LENGTH_ERROR),
+        String::from("SEI message is cut off"),
+        file!(),
+        line!()
+      ))?;
+      *index += 1;
+      value += byte as usize;
+      if byte != 0xFF {
+        return Ok(value);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_sei() {
+    // User data unregistered of 17 bytes, then a recovery point (recovery_frame_cnt 0, exact_match_flag 1)
+    let mut sei = vec![0x06, 0x05, 0x11];
+    sei.extend_from_slice(&[0xAB; 17]);
+    sei.extend_from_slice(&[0x06, 0x01, 0xC4, 0x80]);
+    let messages = SEIMessage::parse(&sei).unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].payload_type, 5);
+    assert_eq!(messages[0].payload.len(), 17);
+    assert_eq!(messages[0].get_recovery_point().unwrap(), None);
+    assert_eq!(messages[1].get_recovery_point().unwrap(), Some(RecoveryPoint {
+      recovery_frame_cnt: 0,
+      exact_match_flag: 1,
+      broken_link_flag: 0,
+      changing_slice_group_idc: 0,
+    }));
+
+    // payloadSize bigger than what's left
+    assert!(SEIMessage::parse(&[0x06, 0x06, 0x05, 0xC4, 0x80]).is_err());
+  }
+}
@@ -0,0 +1,2 @@
+pub mod nal_unit_header;
+pub mod sequence_parameter_set;
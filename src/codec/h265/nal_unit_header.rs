@@ -0,0 +1,97 @@
+use crate::error::{CustomError, construct_error, error_code::{MajorCode, NalMinorCode}};
+
+// NAL unit types. ITU-T H.265; 7.4.2.2 Table 7-1
+pub static BLA_W_LP: u8 = 16;
+pub static CRA_NUT: u8 = 21;
+pub static RSV_IRAP_VCL23: u8 = 23;
+pub static VPS_NUT: u8 = 32;
+pub static SPS_NUT: u8 = 33;
+pub static PPS_NUT: u8 = 34;
+pub static AUD_NUT: u8 = 35;
+pub static PREFIX_SEI_NUT: u8 = 39;
+pub static FD_NUT: u8 = 38;
+
+/// NAL unit header. ITU-T H.265; 7.3.1.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NALUnitHeader {
+  pub nal_unit_type: u8,          // 6 bit
+  pub nuh_layer_id: u8,           // 6 bit
+  pub nuh_temporal_id_plus1: u8,  // 3 bit
+}
+
+impl NALUnitHeader {
+  pub fn parse(nal_unit: &[u8]) -> Result<NALUnitHeader, CustomError> {
+    if nal_unit.len() < 2 {
+      return Err(construct_error(
+        MajorCode::NAL,
+        Box::new(NalMinorCode::UKNOWN_NAL_UNIT_TYPE_ERROR),
+        format!("HEVC NAL unit is too short for the header: {} bytes", nal_unit.len()),
+        file!(),
+        line!()));
+    }
+    Ok(NALUnitHeader {
+      nal_unit_type: (nal_unit[0] >> 1) & 0x3F,
+      nuh_layer_id: ((nal_unit[0] & 0x1) << 5) | (nal_unit[1] >> 3),
+      nuh_temporal_id_plus1: nal_unit[1] & 0x7,
+    })
+  }
+
+  /// Slice segments of a picture
+  pub fn is_vcl(&self) -> bool {
+    self.nal_unit_type < VPS_NUT
+  }
+
+  /// Intra random access point pictures (BLA, IDR and CRA), which are the sync samples of the stream
+  pub fn is_irap(&self) -> bool {
+    self.nal_unit_type >= BLA_W_LP && self.nal_unit_type <= RSV_IRAP_VCL23
+  }
+
+  /// Sub-layer non-reference pictures (TRAIL_N, TSA_N, STSA_N, RADL_N, RASL_N and the reserved ones), which no
+  /// picture of the same sub-layer uses for prediction. ITU-T H.265; 7.4.2.2
+  pub fn is_sub_layer_non_reference(&self) -> bool {
+    self.nal_unit_type <= 14 && self.nal_unit_type.is_multiple_of(2)
+  }
+
+  pub fn is_parameter_set(&self) -> bool {
+    self.nal_unit_type == VPS_NUT || self.nal_unit_type == SPS_NUT || self.nal_unit_type == PPS_NUT
+  }
+
+  /// NAL units that can only come before the first slice segment of an access unit. ITU-T H.265; 7.4.2.4.4
+  pub fn is_access_unit_prefix(&self) -> bool {
+    self.is_parameter_set() ||
+      self.nal_unit_type == AUD_NUT ||
+      self.nal_unit_type == PREFIX_SEI_NUT ||
+      (41..=44).contains(&self.nal_unit_type) ||
+      (48..=55).contains(&self.nal_unit_type)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_nal_unit_header() {
+    // IDR_W_RADL
+    let idr = NALUnitHeader::parse(&[0x26, 0x01, 0xAF]).unwrap();
+    assert_eq!(idr.nal_unit_type, 19);
+    assert_eq!(idr.nuh_layer_id, 0);
+    assert_eq!(idr.nuh_temporal_id_plus1, 1);
+    assert!(idr.is_vcl());
+    assert!(idr.is_irap());
+
+    let sps = NALUnitHeader::parse(&[0x42, 0x01]).unwrap();
+    assert_eq!(sps.nal_unit_type, SPS_NUT);
+    assert!(!sps.is_vcl());
+    assert!(sps.is_access_unit_prefix());
+
+    // TRAIL_R
+    let trail = NALUnitHeader::parse(&[0x02, 0x01]).unwrap();
+    assert!(trail.is_vcl());
+    assert!(!trail.is_irap());
+    assert!(!trail.is_sub_layer_non_reference());
+    // TRAIL_N
+    assert!(NALUnitHeader::parse(&[0x00, 0x01]).unwrap().is_sub_layer_non_reference());
+    assert!(NALUnitHeader::parse(&[0x02]).is_err());
+  }
+}
@@ -0,0 +1,187 @@
+use crate::codec::nal_unit_to_rbsp;
+use crate::{error::CustomError, util::bit_reader::BitReader};
+
+/// General profile, tier and level of the stream. ITU-T H.265; 7.3.3
+#[derive(Eq, PartialEq, Debug, Default, Clone)]
+pub struct ProfileTierLevel {
+  pub general_profile_space: u8,                    // 2 bit
+  pub general_tier_flag: u8,                        // 1 bit
+  pub general_profile_idc: u8,                      // 5 bit
+  pub general_profile_compatibility_flags: u32,     // 32 bit
+  pub general_constraint_indicator_flags: u64,      // 48 bit
+  pub general_level_idc: u8,                        // 8 bit
+}
+
+impl ProfileTierLevel {
+  fn parse(bit_reader: &mut BitReader, max_sub_layers_minus1: usize) -> Result<ProfileTierLevel, CustomError> {
+    let general_profile_space = bit_reader.read_bits(2)? as u8;
+    let general_tier_flag = bit_reader.read_bits(1)? as u8;
+    let general_profile_idc = bit_reader.read_bits(5)? as u8;
+    let general_profile_compatibility_flags = bit_reader.read_bits(32)? as u32;
+    let general_constraint_indicator_flags = ((bit_reader.read_bits(16)? as u64) << 32) | bit_reader.read_bits(32)? as u64;
+    let general_level_idc = bit_reader.read_bits(8)? as u8;
+
+    let mut sub_layer_profile_present_flags: Vec<bool> = vec![];
+    let mut sub_layer_level_present_flags: Vec<bool> = vec![];
+    for _ in 0..max_sub_layers_minus1 {
+      sub_layer_profile_present_flags.push(bit_reader.read_bits(1)? == 1);
+      sub_layer_level_present_flags.push(bit_reader.read_bits(1)? == 1);
+    }
+    if max_sub_layers_minus1 > 0 {
+      // reserved_zero_2bits
+      bit_reader.read_bits(2 * (8 - max_sub_layers_minus1))?;
+    }
+    for index in 0..max_sub_layers_minus1 {
+      if sub_layer_profile_present_flags[index] {
+        // sub_layer_profile_space through sub_layer_inbld_flag
+        bit_reader.read_bits(32)?;
+        bit_reader.read_bits(32)?;
+        bit_reader.read_bits(24)?;
+      }
+      if sub_layer_level_present_flags[index] {
+        // sub_layer_level_idc
+        bit_reader.read_bits(8)?;
+      }
+    }
+
+    Ok(ProfileTierLevel {
+      general_profile_space,
+      general_tier_flag,
+      general_profile_idc,
+      general_profile_compatibility_flags,
+      general_constraint_indicator_flags,
+      general_level_idc,
+    })
+  }
+}
+
+/// HEVC sequence parameter set, up to the bit depths. ITU-T H.265; 7.3.2.2
+#[derive(Eq, PartialEq, Debug)]
+pub struct SequenceParameterSet {
+  pub sps_video_parameter_set_id: u8,     // 4 bit
+  pub sps_max_sub_layers_minus1: u8,      // 3 bit
+  pub sps_temporal_id_nesting_flag: u8,   // 1 bit
+  pub profile_tier_level: ProfileTierLevel,
+  pub sps_seq_parameter_set_id: usize,    // variable
+  pub chroma_format_idc: usize,           // variable
+  pub separate_colour_plane_flag: u8,     // 1 bit
+  pub pic_width_in_luma_samples: usize,   // variable
+  pub pic_height_in_luma_samples: usize,  // variable
+  pub conf_win_left_offset: usize,
+  pub conf_win_right_offset: usize,
+  pub conf_win_top_offset: usize,
+  pub conf_win_bottom_offset: usize,
+  pub bit_depth_luma_minus8: usize,       // variable
+  pub bit_depth_chroma_minus8: usize,     // variable
+}
+
+impl SequenceParameterSet {
+  /// Parse the SPS NAL unit (NAL unit header included)
+  pub fn parse(data: &[u8]) -> Result<SequenceParameterSet, CustomError> {
+    let rbsp = nal_unit_to_rbsp(data);
+    let mut bit_reader = BitReader::create_bit_reader(&rbsp);
+    bit_reader.read_bits(16)?; // skip the nal unit header
+    let sps_video_parameter_set_id = bit_reader.read_bits(4)? as u8;
+    let sps_max_sub_layers_minus1 = bit_reader.read_bits(3)? as u8;
+    let sps_temporal_id_nesting_flag = bit_reader.read_bits(1)? as u8;
+    let profile_tier_level = ProfileTierLevel::parse(&mut bit_reader, sps_max_sub_layers_minus1 as usize)?;
+    let sps_seq_parameter_set_id = bit_reader.unsigned_exp_golomb()?;
+    let chroma_format_idc = bit_reader.unsigned_exp_golomb()?;
+    let mut separate_colour_plane_flag = 0u8;
+    if chroma_format_idc == 3 {
+      separate_colour_plane_flag = bit_reader.read_bits(1)? as u8;
+    }
+    let pic_width_in_luma_samples = bit_reader.unsigned_exp_golomb()?;
+    let pic_height_in_luma_samples = bit_reader.unsigned_exp_golomb()?;
+    let conformance_window_flag = bit_reader.read_bits(1)?;
+    let mut conf_win_left_offset = 0usize;
+    let mut conf_win_right_offset = 0usize;
+    let mut conf_win_top_offset = 0usize;
+    let mut conf_win_bottom_offset = 0usize;
+    if conformance_window_flag == 1 {
+      conf_win_left_offset = bit_reader.unsigned_exp_golomb()?;
+      conf_win_right_offset = bit_reader.unsigned_exp_golomb()?;
+      conf_win_top_offset = bit_reader.unsigned_exp_golomb()?;
+      conf_win_bottom_offset = bit_reader.unsigned_exp_golomb()?;
+    }
+    let bit_depth_luma_minus8 = bit_reader.unsigned_exp_golomb()?;
+    let bit_depth_chroma_minus8 = bit_reader.unsigned_exp_golomb()?;
+
+    Ok(SequenceParameterSet {
+      sps_video_parameter_set_id,
+      sps_max_sub_layers_minus1,
+      sps_temporal_id_nesting_flag,
+      profile_tier_level,
+      sps_seq_parameter_set_id,
+      chroma_format_idc,
+      separate_colour_plane_flag,
+      pic_width_in_luma_samples,
+      pic_height_in_luma_samples,
+      conf_win_left_offset,
+      conf_win_right_offset,
+      conf_win_top_offset,
+      conf_win_bottom_offset,
+      bit_depth_luma_minus8,
+      bit_depth_chroma_minus8,
+    })
+  }
+
+  // SubWidthC and SubHeightC. ITU-T H.265; 6.2 Table 6-1
+  fn get_chroma_subsampling(&self) -> (usize, usize) {
+    if self.separate_colour_plane_flag == 1 {
+      return (1, 1);
+    }
+    match self.chroma_format_idc {
+      1 => (2, 2),
+      2 => (2, 1),
+      _ => (1, 1),
+    }
+  }
+
+  /// Width after the conformance window is applied
+  pub fn width(&self) -> usize {
+    let (sub_width_c, _) = self.get_chroma_subsampling();
+    self.pic_width_in_luma_samples.saturating_sub(sub_width_c * (self.conf_win_left_offset + self.conf_win_right_offset))
+  }
+
+  /// Height after the conformance window is applied
+  pub fn height(&self) -> usize {
+    let (_, sub_height_c) = self.get_chroma_subsampling();
+    self.pic_height_in_luma_samples.saturating_sub(sub_height_c * (self.conf_win_top_offset + self.conf_win_bottom_offset))
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  /// Main profile, level 4 (120), 4:2:0 8 bit, 1920x1088 with a conformance window down to 1920x1080
+  pub fn get_sps() -> Vec<u8> {
+    vec![
+      0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00, 0x03, 0x00, 0x78, 0xA0,
+      0x03, 0xC0, 0x80, 0x11, 0x07, 0xCB, 0xC0,
+    ]
+  }
+
+  #[test]
+  fn test_parse_hevc_sps() {
+    let sps = SequenceParameterSet::parse(&get_sps()).unwrap();
+    assert_eq!(sps.sps_max_sub_layers_minus1, 0);
+    assert_eq!(sps.sps_temporal_id_nesting_flag, 1);
+    assert_eq!(sps.profile_tier_level, ProfileTierLevel {
+      general_profile_space: 0,
+      general_tier_flag: 0,
+      general_profile_idc: 1,
+      general_profile_compatibility_flags: 0x60000000,
+      general_constraint_indicator_flags: 0x900000000000,
+      general_level_idc: 120,
+    });
+    assert_eq!(sps.chroma_format_idc, 1);
+    assert_eq!(sps.pic_width_in_luma_samples, 1920);
+    assert_eq!(sps.pic_height_in_luma_samples, 1088);
+    assert_eq!(sps.conf_win_bottom_offset, 4);
+    assert_eq!(sps.width(), 1920);
+    assert_eq!(sps.height(), 1080);
+    assert_eq!(sps.bit_depth_luma_minus8, 0);
+  }
+}
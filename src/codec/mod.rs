@@ -1,8 +1,24 @@
 pub mod h264;
+pub mod h265;
 
 #[allow(non_camel_case_types)]
 pub enum Codec {
   H_264,
   H_265,
   AAC,
-}
\ No newline at end of file
+}
+
+/// Strip the emulation prevention bytes (0x000003 -> 0x0000) from a NAL unit so the syntax elements can be read
+pub fn nal_unit_to_rbsp(nal_unit: &[u8]) -> Vec<u8> {
+  let mut rbsp: Vec<u8> = Vec::with_capacity(nal_unit.len());
+  let mut zero_count = 0usize;
+  for byte in nal_unit.iter() {
+    if zero_count >= 2 && *byte == 0x03 {
+      zero_count = 0;
+      continue;
+    }
+    zero_count = if *byte == 0x00 { zero_count + 1 } else { 0 };
+    rbsp.push(*byte);
+  }
+  rbsp
+}
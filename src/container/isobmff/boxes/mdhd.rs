@@ -172,14 +172,14 @@ impl MDHDReader {
 
 pub struct MDHDBuilder {
   timescale: u32,
-  language: &'static str,
+  language: String,
 }
 
 impl MDHDBuilder {
   pub fn create_builder() -> MDHDBuilder {
     MDHDBuilder{
       timescale: 0,
-      language: "und"
+      language: "und".to_string()
     }
   }
 
@@ -188,14 +188,15 @@ impl MDHDBuilder {
     self
   }
 
-  pub fn language(mut self, language: &'static str) -> MDHDBuilder {
-    self.language = language;
+  pub fn language(mut self, language: &str) -> MDHDBuilder {
+    self.language = language.to_string();
     self
   }
 
   pub fn build(&self) -> Result<Vec<u8>, CustomError>{
     let timescale_array = util::transform_u32_to_u8_array(self.timescale);
-    let langauge = ISO639::adjust_string_to_iso_639_2(self.language)?;
+    // mdhd takes the ISO-639-2/T code, transport streams commonly signal the /B one
+    let langauge = ISO639::adjust_string_to_iso_639_2(&ISO639::map_iso_639_2_to_639_2_t(&self.language))?;
 
     Ok(
       vec![
@@ -282,4 +283,26 @@ mod tests {
 
     assert_eq!(mdhd, expected_mdhd);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_build_mdhd_with_language() {
+    let mdhd = MDHDBuilder::create_builder()
+      .timescale(48000)
+      .language("spa")
+      .build()
+      .unwrap();
+
+    let mut mdhd_reader = MDHDReader::get_reader(&mdhd).unwrap();
+    assert_eq!(mdhd_reader.get_timescale().unwrap(), 48000);
+    assert_eq!(mdhd_reader.get_language().unwrap(), "spa");
+
+    let mdhd = MDHDBuilder::create_builder()
+      .timescale(48000)
+      .language("ger")
+      .build()
+      .unwrap();
+
+    let mut mdhd_reader = MDHDReader::get_reader(&mdhd).unwrap();
+    assert_eq!(mdhd_reader.get_language().unwrap(), "deu");
+  }
+}
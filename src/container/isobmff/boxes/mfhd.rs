@@ -1,13 +1,26 @@
+use crate::util;
+
 // MovieFragmentHeaderBox 14496-12; 8.8.5
 
-pub struct MFHDBuilder {}
+pub struct MFHDBuilder {
+  sequence_number: u32,
+}
 
 impl MFHDBuilder {
   pub fn create_builder() -> MFHDBuilder {
-    MFHDBuilder{}
+    MFHDBuilder{
+      sequence_number: 0,
+    }
+  }
+
+  /// Number of the fragment. Should increase with every fragment of a presentation.
+  pub fn sequence_number(mut self, sequence_number: u32) -> MFHDBuilder {
+    self.sequence_number = sequence_number;
+    self
   }
 
   pub fn build(&self) -> Vec<u8> {
+    let sequence_number_array = util::transform_u32_to_u8_array(self.sequence_number);
     vec![
       // size
       0x00, 0x00, 0x00, 0x10,
@@ -16,7 +29,7 @@ impl MFHDBuilder {
       // version and flags
       0x00, 0x00, 0x00, 0x00,
       // sequence_number
-      0x00, 0x00, 0x00, 0x00,
+      sequence_number_array[3], sequence_number_array[2], sequence_number_array[1], sequence_number_array[0],
     ]
   }
 }
@@ -40,4 +53,12 @@ mod tests {
       .build();
     assert_eq!(mfhd, expected_mfhd);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_build_mfhd_with_sequence_number() {
+    let mfhd = MFHDBuilder::create_builder()
+      .sequence_number(0x0102)
+      .build();
+    assert_eq!(mfhd[12..], [0x00, 0x00, 0x01, 0x02]);
+  }
+}
@@ -42,9 +42,22 @@ pub struct SampleFlag {
   // Skipping sample_degradation_priority for now
 }
 
+/// sample_depends_on = 2 (does not depend on others). Key frames and audio samples.
+pub static SYNC_SAMPLE_FLAGS: u32 = 0x02000000;
+/// sample_depends_on = 1 (depends on others) and sample_is_non_sync_sample
+pub static NON_SYNC_SAMPLE_FLAGS: u32 = 0x01010000;
+
 // ISAU of a SAP of type 1 or 2 as defined in Annex I
 
 impl SampleFlag {
+  /// Build the flags of a sample. A value of 0 for sample_depends_on or sample_is_depended_on means unknown.
+  /// ISO/IEC 14496-12; 8.8.3.1
+  pub fn generate_sample_flags(sample_depends_on: u8, sample_is_depended_on: u8, sample_is_non_sync_sample: bool) -> u32 {
+    ((sample_depends_on as u32 & 0x3) << 24) |
+      ((sample_is_depended_on as u32 & 0x3) << 22) |
+      ((sample_is_non_sync_sample as u32) << 16)
+  }
+
   pub fn parse(flag_data: u32) -> SampleFlag {
     SampleFlag{
       flag_data,
@@ -122,6 +135,16 @@ mod tests {
     assert_eq!(sample_flag.get_sample_is_non_sync_sample(), 0);
   }
 
+  #[test]
+  fn test_generate_sample_flags() {
+    assert_eq!(SampleFlag::generate_sample_flags(2, 0, false), super::SYNC_SAMPLE_FLAGS);
+    assert_eq!(SampleFlag::generate_sample_flags(1, 0, true), super::NON_SYNC_SAMPLE_FLAGS);
+    let mut sample_flag = SampleFlag::parse(SampleFlag::generate_sample_flags(1, 2, true));
+    assert_eq!(sample_flag.get_sample_depends_on(), 1);
+    assert_eq!(sample_flag.get_sample_is_depended_on(), 2);
+    assert_eq!(sample_flag.get_sample_is_non_sync_sample(), 1);
+  }
+
   #[test]
   fn test_something() {
     let mut sample_flag = SampleFlag::parse(0x1010000);
@@ -6,32 +6,85 @@ use crate::container::isobmff::boxes::traf::TRAFBuilder;
 
 /// MovieFragmentBox 14496-12; 8.8.4
 pub struct MOOFBuilder {
-  traf_builder: Option<TRAFBuilder>
+  sequence_number: u32,
+  traf_builders: Vec<TRAFBuilder>,
+  run_order: Option<Vec<usize>>,
 }
 
 impl MOOFBuilder {
   pub fn create_builder() -> MOOFBuilder {
     MOOFBuilder{
-      traf_builder: None
+      sequence_number: 0,
+      traf_builders: vec![],
+      run_order: None,
     }
   }
 
+  pub fn sequence_number(mut self, sequence_number: u32) -> MOOFBuilder {
+    self.sequence_number = sequence_number;
+    self
+  }
+
+  /// Add the traf of a track. Unless a run order is set, the samples of the tracks go in the mdat in the order the
+  /// trafs are added.
   pub fn traf(mut self, traf_builder: TRAFBuilder) -> MOOFBuilder {
-    self.traf_builder = Some(traf_builder);
+    self.traf_builders.push(traf_builder);
+    self
+  }
+
+  /// Order of the runs (truns) in the mdat, as the index of the traf every run belongs to. The runs of a traf keep
+  /// the order they were added in. Used to interleave the tracks.
+  pub fn run_order(mut self, run_order: Vec<usize>) -> MOOFBuilder {
+    self.run_order = Some(run_order);
     self
   }
 
   pub fn build(self) -> Result<Vec<u8>, CustomError> {
-    let mfhd = MFHDBuilder::create_builder().build();
-    let data_offset = 8 + mfhd.len();
-    let traf = self.traf_builder
-      .ok_or_else(||remux::generate_error(String::from("Missing traf_builder for MOOFBuilder")))?
-      .set_data_offset(data_offset)
-      .build()?;
+    if self.traf_builders.is_empty() {
+      return Err(remux::generate_error(String::from("Missing traf_builder for MOOFBuilder")));
+    }
+    let mfhd = MFHDBuilder::create_builder()
+      .sequence_number(self.sequence_number)
+      .build();
+    let traf_sizes = self.traf_builders
+      .iter()
+      .map(|traf_builder|traf_builder.get_size())
+      .collect::<Result<Vec<usize>, CustomError>>()?;
     let size = 
       8 + // header
       mfhd.len() +
-      traf.len();
+      traf_sizes.iter().sum::<usize>();
+
+    // The trun data offsets point into the mdat that follows the moof, past the samples of the runs before them
+    let run_sizes: Vec<Vec<usize>> = self.traf_builders
+      .iter()
+      .map(|traf_builder|traf_builder.get_sample_data_sizes())
+      .collect();
+    let run_order = self.run_order.unwrap_or_else(|| {
+      run_sizes
+        .iter()
+        .enumerate()
+        .flat_map(|(traf_index, sizes)|std::iter::repeat_n(traf_index, sizes.len()))
+        .collect()
+    });
+    let mut data_offsets: Vec<Vec<usize>> = vec![vec![]; run_sizes.len()];
+    let mut data_offset = size + 8; // mdat header
+    for traf_index in run_order {
+      let run_size = run_sizes
+        .get(traf_index)
+        .and_then(|sizes|sizes.get(data_offsets[traf_index].len()))
+        .ok_or_else(||remux::generate_error(format!("Run order has more runs than traf {}", traf_index)))?;
+      data_offsets[traf_index].push(data_offset);
+      data_offset += run_size;
+    }
+    if data_offsets.iter().zip(run_sizes.iter()).any(|(offsets, sizes)|offsets.len() != sizes.len()) {
+      return Err(remux::generate_error(String::from("Run order is missing runs of the trafs")));
+    }
+    let trafs = self.traf_builders
+      .into_iter()
+      .zip(data_offsets)
+      .map(|(traf_builder, data_offsets)|traf_builder.set_data_offsets(data_offsets).build())
+      .collect::<Result<Vec<Vec<u8>>, CustomError>>()?;
     let size_array = util::transform_usize_to_u8_array(size);
     Ok(
       [
@@ -42,7 +95,7 @@ impl MOOFBuilder {
           0x6D, 0x6F, 0x6F, 0x66,
         ],
         mfhd,
-        traf,
+        trafs.concat(),
       ].concat()
     )
   }
@@ -9,7 +9,7 @@ use crate::container::isobmff::boxes::mvex::MVEXBuilder;
 
 pub struct MOOVBuilder {
   mvhd_builder: Option<MVHDBuilder>,
-  trak_builder: Option<TRAKBuilder>,
+  trak_builders: Vec<TRAKBuilder>,
   mvex_builder: Option<MVEXBuilder>,
 }
 
@@ -17,7 +17,7 @@ impl MOOVBuilder {
   pub fn create_builder() -> MOOVBuilder {
     return MOOVBuilder{
       mvhd_builder: None,
-      trak_builder: None,
+      trak_builders: vec![],
       mvex_builder: None,
     }
   }
@@ -27,8 +27,9 @@ impl MOOVBuilder {
     self
   }
 
+  /// Add the trak of a track. Call it once per track.
   pub fn trak(mut self, trak_builder: TRAKBuilder) -> MOOVBuilder {
-    self.trak_builder = Some(trak_builder);
+    self.trak_builders.push(trak_builder);
     self
   }
 
@@ -41,9 +42,14 @@ impl MOOVBuilder {
     let mvhd = self.mvhd_builder.as_ref()
       .ok_or_else(||remux::generate_error(String::from("Missing mvhd_builder for MOOVBuilder")))?
       .build();
-    let trak = self.trak_builder.as_ref()
-      .ok_or_else(||remux::generate_error(String::from("Missing trak_builder for MOOVBuilder")))?
-      .build()?;
+    if self.trak_builders.is_empty() {
+      return Err(remux::generate_error(String::from("Missing trak_builder for MOOVBuilder")));
+    }
+    let trak = self.trak_builders
+      .iter()
+      .map(|trak_builder|trak_builder.build())
+      .collect::<Result<Vec<Vec<u8>>, CustomError>>()?
+      .concat();
     let mvex = self.mvex_builder.as_ref()
       .ok_or_else(||remux::generate_error(String::from("Missing mvex_builder for MOOVBuilder")))?
       .build()?;
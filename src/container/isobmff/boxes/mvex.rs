@@ -7,26 +7,31 @@ use crate::container::isobmff::boxes::trex::TREXBuilder;
 // MovieExtendsBox 14496-12; 8.8.1
 
 pub struct MVEXBuilder {
-  trex_builder: Option<TREXBuilder>,
+  trex_builders: Vec<TREXBuilder>,
 }
 
 impl MVEXBuilder {
   pub fn create_builder() -> MVEXBuilder {
     MVEXBuilder{
-      trex_builder: None,
+      trex_builders: vec![],
     }
   }
 
+  /// Add the trex of a track. Call it once per track.
   pub fn trex(mut self, trex_builder: TREXBuilder) -> MVEXBuilder {
-    self.trex_builder = Some(trex_builder);
+    self.trex_builders.push(trex_builder);
     self
   }
 
   pub fn build(&self) -> Result<Vec<u8>, CustomError> {
-    let trex = self.trex_builder.as_ref()
-      .ok_or_else(||remux::generate_error(String::from("Missing trex_builder for MVEXBuilder")))?
-      .build();
-   
+    if self.trex_builders.is_empty() {
+      return Err(remux::generate_error(String::from("Missing trex_builder for MVEXBuilder")));
+    }
+    let trex = self.trex_builders
+      .iter()
+      .map(|trex_builder|trex_builder.build())
+      .collect::<Vec<Vec<u8>>>()
+      .concat();
 
     let size = 
       8 + // header
@@ -57,6 +57,31 @@ impl PartialEq for TFHD {
 }
 
 impl TFHD {
+  pub fn get_track_id(&self) -> u32 {
+    self.track_id
+  }
+
+  pub fn get_base_data_offset(&self) -> Option<u64> {
+    self.base_data_offset
+  }
+
+  pub fn get_default_sample_duration(&self) -> Option<u32> {
+    self.default_sample_duration
+  }
+
+  pub fn get_default_sample_size(&self) -> Option<u32> {
+    self.default_sample_size
+  }
+
+  pub fn get_default_sample_flags(&self) -> Option<u32> {
+    self.default_sample_flags
+  }
+
+  /// Whether the data offsets are relative to the moof when there is no base data offset
+  pub fn is_default_base_moof(&self) -> bool {
+    self.default_base_is_moof
+  }
+
   pub fn parse(moof: &[u8]) -> Result<TFHD, CustomError> {
     let tfhd_option = find_box("traf", 8, moof)
       .and_then(|traf|find_box("tfhd", 8, traf));
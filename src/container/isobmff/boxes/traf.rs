@@ -6,8 +6,8 @@ use crate::util;
 pub struct TRAFBuilder {
   tfhd_builder: Option<TFHDBuilder>,
   tfdt_builder: Option<TFDTBuilder>,
-  trun_builder: Option<TRUNBuilder>,
-  data_offset: usize,
+  trun_builders: Vec<TRUNBuilder>,
+  data_offsets: Vec<usize>,
 }
 
 impl TRAFBuilder {
@@ -15,8 +15,8 @@ impl TRAFBuilder {
     TRAFBuilder{
       tfhd_builder: None,
       tfdt_builder: None,
-      trun_builder: None,
-      data_offset: 0,
+      trun_builders: vec![],
+      data_offsets: vec![],
     }
   }
 
@@ -30,16 +30,38 @@ impl TRAFBuilder {
     self
   }
 
+  /// Add a run of samples. A traf can have several, so the runs of the tracks can be interleaved in the mdat.
   pub fn trun(mut self, trun_builder: TRUNBuilder) -> TRAFBuilder {
-    self.trun_builder = Some(trun_builder);
+    self.trun_builders.push(trun_builder);
     self
   }
 
-  pub fn set_data_offset(mut self, data_offset: usize) -> TRAFBuilder {
-    self.data_offset = data_offset;
+  /// Where the samples of every trun start, counted from the start of the moof. Without them the samples of the
+  /// truns follow each other in an mdat right after the traf.
+  pub fn set_data_offsets(mut self, data_offsets: Vec<usize>) -> TRAFBuilder {
+    self.data_offsets = data_offsets;
     self
   }
 
+  pub fn get_size(&self) -> Result<usize, CustomError> {
+    let tfhd = self.tfhd_builder.as_ref()
+      .ok_or_else(||remux::generate_error(String::from("Missing tfhd_builder for TRAFBuilder")))?
+      .build();
+    let tfdt = self.tfdt_builder.as_ref()
+      .ok_or_else(||remux::generate_error(String::from("Missing tfdt_builder for TRAFBuilder")))?
+      .build();
+    if self.trun_builders.is_empty() {
+      return Err(remux::generate_error(String::from("Missing trun_builder for TRAFBuilder")));
+    }
+    let trun_size: usize = self.trun_builders.iter().map(|trun_builder|trun_builder.get_size()).sum();
+    Ok(8 + tfhd.len() + tfdt.len() + trun_size)
+  }
+
+  /// Number of bytes the samples of every trun take up in the mdat
+  pub fn get_sample_data_sizes(&self) -> Vec<usize> {
+    self.trun_builders.iter().map(|trun_builder|trun_builder.get_sample_data_size()).collect()
+  }
+
   pub fn build(self) -> Result<Vec<u8>, CustomError> {
     let tfhd = self.tfhd_builder.as_ref()
       .ok_or_else(||remux::generate_error(String::from("Missing tfhd_builder for STBLBuilder")))?
@@ -47,17 +69,20 @@ impl TRAFBuilder {
     let tfdt = self.tfdt_builder.as_ref()
       .ok_or_else(||remux::generate_error(String::from("Missing tfdt_builder for STBLBuilder")))?
       .build();
-    let data_offset = self.data_offset + tfhd.len() + tfdt.len() + 8;
-    let trun = self.trun_builder
-      .ok_or_else(||remux::generate_error(String::from("Missing trun_builder for STBLBuilder")))?
-      .data_offset(data_offset)
-      .build();
-
-    let size = 
-      8 + // header
-      tfhd.len() +
-      tfdt.len() +
-      trun.len();
+    let size = self.get_size()?;
+    let mut next_data_offset = size + 8; // mdat header
+    let mut truns: Vec<u8> = vec![];
+    for (index, trun_builder) in self.trun_builders.into_iter().enumerate() {
+      let data_offset = self.data_offsets.get(index).copied().unwrap_or(next_data_offset);
+      next_data_offset = data_offset + trun_builder.get_sample_data_size();
+      // The trun adds its own size and the mdat header to the data offset it's given
+      let trun_size = trun_builder.get_size();
+      truns.append(
+        &mut trun_builder
+          .data_offset(data_offset.saturating_sub(trun_size + 8))
+          .build()
+      );
+    }
     let size_array = util::transform_usize_to_u8_array(size);
 
     Ok(
@@ -70,7 +95,7 @@ impl TRAFBuilder {
         ],
         tfhd,
         tfdt,
-        trun
+        truns
       ].concat()
     )
   }
@@ -1,7 +1,31 @@
+use crate::error::CustomError;
 use crate::util;
 
 // TrackExtendsBox 14496-12; 8.8.3
 
+/// Defaults for the samples of a track in the movie fragments
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TREX {
+  pub track_id: u32,
+  pub default_sample_description_index: u32,
+  pub default_sample_duration: u32,
+  pub default_sample_size: u32,
+  pub default_sample_flags: u32,
+}
+
+impl TREX {
+  pub fn parse_trex(trex_data: &[u8]) -> Result<TREX, CustomError> {
+    // Skip size, type, version and flags
+    Ok(TREX {
+      track_id: util::get_u32(trex_data, 12)?,
+      default_sample_description_index: util::get_u32(trex_data, 16)?,
+      default_sample_duration: util::get_u32(trex_data, 20)?,
+      default_sample_size: util::get_u32(trex_data, 24)?,
+      default_sample_flags: util::get_u32(trex_data, 28)?,
+    })
+  }
+}
+
 pub struct TREXBuilder {
   track_id: usize,
   default_sample_duration: usize,
@@ -89,4 +113,20 @@ mod tests {
       .build();
     assert_eq!(trex, expected_trex);
   }
+
+  #[test]
+  fn test_parse_trex() {
+    let trex = TREXBuilder::create_builder()
+      .track_id(2)
+      .default_sample_duration(1024)
+      .default_sample_flags(0x02000000)
+      .build();
+    assert_eq!(TREX::parse_trex(&trex).unwrap(), TREX {
+      track_id: 2,
+      default_sample_description_index: 1,
+      default_sample_duration: 1024,
+      default_sample_size: 0,
+      default_sample_flags: 0x02000000,
+    });
+  }
 }
\ No newline at end of file
@@ -15,12 +15,12 @@ use crate::{
 static CLASS: &str = "TRUN";
 
 #[derive(Debug, Eq)]
-struct Sample {
+pub struct Sample {
     // All optional fields
-    sample_duration: Option<u32>,
-    sample_size: Option<u32>,
-    sample_flags: Option<u32>,
-    sample_composition_time_offset: Option<i32>,
+    pub sample_duration: Option<u32>,
+    pub sample_size: Option<u32>,
+    pub sample_flags: Option<u32>,
+    pub sample_composition_time_offset: Option<i32>,
 }
 
 impl PartialEq for Sample {
@@ -74,6 +74,21 @@ impl PartialEq for TRUN {
 }
 
 impl TRUN {
+    /// Offset of the first sample from the start of the moof
+    pub fn get_data_offset(&self) -> Option<i32> {
+        self.data_offset
+    }
+
+    pub fn get_samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// Flags of the first sample, if the trun has them. Otherwise the tfhd default applies.
+    pub fn get_first_sample_flags(&self) -> Option<u32> {
+        self.first_sample_flags
+            .or_else(|| self.samples.first().and_then(|sample| sample.sample_flags))
+    }
+
     pub fn parse(moof: &[u8]) -> Result<TRUN, CustomError> {
         let trun_option = find_box("traf", 8, moof).and_then(|traf| find_box("trun", 8, traf));
 
@@ -185,6 +200,7 @@ pub struct TRUNBuilder {
     first_sample_flags: Option<usize>,
     samples: Vec<SampleInfo>,
     sample_composition_time_offsets_present: bool,
+    sample_flags_present: bool,
 }
 
 impl TRUNBuilder {
@@ -196,6 +212,7 @@ impl TRUNBuilder {
             first_sample_flags: None,
             samples: vec![],
             sample_composition_time_offsets_present: false,
+            sample_flags_present: false,
         }
     }
 
@@ -229,6 +246,27 @@ impl TRUNBuilder {
         self
     }
 
+    /// Write the flags of every sample. Needed when the samples after the first one don't all match the tfhd default.
+    pub fn sample_flags_present(mut self, present: bool) -> TRUNBuilder {
+        self.sample_flags_present = present;
+        self
+    }
+
+    /// Size of the trun box. The data offset doesn't change it, so it's known before the moof is laid out.
+    pub fn get_size(&self) -> usize {
+        let (flags, data) = self.generate_flag();
+        12 + // header
+        4 + // sample_count
+        4 + // data_offset
+        data.len() +
+        self.calculate_sample_size(flags) * self.samples.len()
+    }
+
+    /// Number of bytes the samples take up in the mdat
+    pub fn get_sample_data_size(&self) -> usize {
+        self.samples.iter().map(|sample| sample.data.len()).sum()
+    }
+
     /// Generate the flag and values if they are present
     fn generate_flag(&self) -> (u32, Vec<u8>) {
         // Always start with data-offset-present set. Required for CMAF.
@@ -246,12 +284,12 @@ impl TRUNBuilder {
             .concat()
         }
 
-        // Check for sample metadata present if the samples array has more than 1 sample. The first sample will most likely utilize
-        // the first sample flag.
-        if self.samples.len() > 1 {
+        // Check for sample metadata present if the samples array has samples. The first sample will most likely utilize
+        // the first sample flag. A run of a single sample still needs its size, as there's no default size in the tfhd.
+        if !self.samples.is_empty() {
             println!("SAMPLES");
             flag += 0x000200; // Each sample info contains the u8 array of the sample so we will always have the sample size.
-            let sample_info = &self.samples[1];
+            let sample_info = self.samples.get(1).unwrap_or(&self.samples[0]);
             // If sample composition time offsets is present, we just need this, else just use duration
             println!("sample_composition_time_offsets_present: {}", self.sample_composition_time_offsets_present);
             if self.sample_composition_time_offsets_present {
@@ -262,7 +300,7 @@ impl TRUNBuilder {
                 flag += 0x000100;
             }
 
-            if let Some(_) = sample_info.sample_flags {
+            if self.sample_flags_present {
                 flag += 0x000400;
             }
         }
@@ -372,7 +410,8 @@ impl TRUNBuilder {
         let mut offset = 0usize;
         for sample_info in samples.iter() {
             let duration = sample_info.sample_duration.unwrap_or_default();
-            let sample = TRUNBuilder::create_sample(sample_info, sample_size, flags, version, duration, 0);
+            let sample_flag = sample_info.sample_flags.unwrap_or_default() as usize;
+            let sample = TRUNBuilder::create_sample(sample_info, sample_size, flags, version, duration, sample_flag);
             let end = offset + sample_size;
             data.splice(offset..end, sample);
             offset = end;
@@ -428,11 +467,12 @@ impl TRUNBuilder {
             println!("HERE!");
             let data: Vec<u8>;
             if version == 1 {
-                let diff = sample.pts as i32 - sample.dts as i32;
+                let diff = (sample.pts as i64 - sample.dts as i64) as i32;
                 println!("DIFF -- 1 -- : {}", diff);
                 data = util::transform_i32_to_u8_array(diff).to_vec();
             } else {
-                let diff = (sample.pts - sample.dts) as usize;
+                // The offset is unsigned in version 0, a pts before the dts can only be written with version 1
+                let diff = sample.pts.saturating_sub(sample.dts) as usize;
                 println!("DIFF -- 0 -- : {}", diff);
                 data = util::transform_usize_to_u8_array(diff).to_vec();
             }
@@ -1081,6 +1121,49 @@ mod tests {
         // TODO (benjamintoofer@gmail.com): Finish this unit test
     }
 
+    #[test]
+    fn test_create_sample_composition_time_offset() {
+        let create_sample_info = |pts: u64, dts: u64| SampleInfo {
+            sample_flags: None,
+            sample_duration: None,
+            data: vec![],
+            pts,
+            dts,
+        };
+        // The timestamps don't fit in an i32
+        let sample = create_sample_info(0x8000_0010, 0x7FFF_FFF0);
+        assert_eq!(TRUNBuilder::create_sample(&sample, 4, 0x000800, 1, 0, 0), vec![0x00, 0x00, 0x00, 0x20]);
+        let sample = create_sample_info(0x1_0000_0000, 0x1_0000_0BB8);
+        assert_eq!(TRUNBuilder::create_sample(&sample, 4, 0x000800, 1, 0, 0), (-3000i32).to_be_bytes().to_vec());
+        // A pts before the dts can't be written in version 0
+        assert_eq!(TRUNBuilder::create_sample(&sample, 4, 0x000800, 0, 0, 0), vec![0x00; 4]);
+    }
+
+    #[test]
+    fn test_build_trun_with_sample_flags() {
+        let samples: Vec<SampleInfo> = [0x02000000u32, 0x01010000, 0x01810000]
+            .iter()
+            .map(|sample_flags| SampleInfo {
+                sample_flags: Some(*sample_flags),
+                sample_duration: Some(3000),
+                data: vec![0; 10],
+                pts: 0,
+                dts: 0,
+            })
+            .collect();
+        let trun_data = TRUNBuilder::create_builder()
+            .sample_flags_present(true)
+            .samples(samples)
+            .build();
+        let trun = TRUN::parse_trun(&trun_data).unwrap();
+
+        assert_eq!(trun.get_flags(), 0x000701);
+        assert_eq!(trun.first_sample_flags, None);
+        assert_eq!(trun.get_first_sample_flags(), Some(0x02000000));
+        let sample_flags: Vec<Option<u32>> = trun.samples.iter().map(|sample| sample.sample_flags).collect();
+        assert_eq!(sample_flags, vec![Some(0x02000000), Some(0x01010000), Some(0x01810000)]);
+    }
+
     fn generate_test_nal_units() -> Vec<SampleInfo> {
         vec![
             SampleInfo {
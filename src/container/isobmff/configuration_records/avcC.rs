@@ -1,8 +1,6 @@
 
 use crate::{codec::h264::sequence_parameter_set::SequenceParameterSet, error::CustomError, util};
 
-static CLASS: &str = "AVCDecoderConfigurationRecord";
-
 /// AVCDecoderConfigurationRecord: 14496-15; 5.2.4.1
 #[derive(Debug)]
 pub struct AVCDecoderConfigurationRecord {
@@ -13,60 +11,82 @@ pub struct AVCDecoderConfigurationRecord {
   pub length_size_minus_one: u8,        // 2 bits
   num_of_sequence_parameter_sets: u8,   // 5 bits
   num_of_picture_parameter_sets: u8,    // 8 bits
+  pub sequence_parameter_sets: Vec<Vec<u8>>,
+  pub picture_parameter_sets: Vec<Vec<u8>>,
 }
 
 impl  AVCDecoderConfigurationRecord {
-  pub fn parse(data: &[u8]) -> AVCDecoderConfigurationRecord {
+  pub fn parse(data: &[u8]) -> Result<AVCDecoderConfigurationRecord, CustomError> {
     let mut start = 8usize;
     // Parse configuration version
-    let configuration_version = util::get_u8(data, start)
-      .expect(format!("{}.parse.configuration_version: cannot get u8 from start = {}",CLASS, start).as_ref());
+    let configuration_version = util::get_u8(data, start)?;
 
     start = start + 1;
     // Parse configuration version
-    let avc_profile_indication = util::get_u8(data, start)
-      .expect(format!("{}.parse.avc_profile_indication: cannot get u8 from start = {}",CLASS, start).as_ref());
+    let avc_profile_indication = util::get_u8(data, start)?;
 
     start = start + 1;
     // Parse profile compatability
-    let profile_compatability = util::get_u8(data, start)
-      .expect(format!("{}.parse.profile_compatability: cannot get u8 from start = {}",CLASS, start).as_ref());
+    let profile_compatability = util::get_u8(data, start)?;
 
     start = start + 1;
     // Parse AVC level indication
-    let avc_level_indication = util::get_u8(data, start)
-      .expect(format!("{}.parse.avc_level_indication: cannot get u8 from start = {}",CLASS, start).as_ref());
+    let avc_level_indication = util::get_u8(data, start)?;
 
     start = start + 1;
     // Parse length size minus one
-    let length_size_minus_one = util::get_u8(data, start)
-      .expect(format!("{}.parse.length_size_minus_one: cannot get u8 from start = {}",CLASS, start).as_ref());
-    let length_size_minus_one = length_size_minus_one & 0x2;
+    let length_size_minus_one = util::get_u8(data, start)?;
+    let length_size_minus_one = length_size_minus_one & 0x3;
 
     start = start + 1;
     // Parse num of sequence parameter sets
-    let num_of_sequence_parameter_sets = util::get_u8(data, start)
-      .expect(format!("{}.parse.num_of_sequence_parameter_sets: cannot get u8 from start = {}",CLASS, start).as_ref());
-    let num_of_sequence_parameter_sets = num_of_sequence_parameter_sets & 0x5;
+    let num_of_sequence_parameter_sets = util::get_u8(data, start)?;
+    let num_of_sequence_parameter_sets = num_of_sequence_parameter_sets & 0x1F;
+
+    start += 1;
+    let sequence_parameter_sets = AVCDecoderConfigurationRecord::parse_parameter_sets(data, &mut start, num_of_sequence_parameter_sets);
 
     // Parse num of pictures parameter sets
-    // todo
+    let num_of_picture_parameter_sets = util::get_u8(data, start).unwrap_or(0);
+    start += 1;
+    let picture_parameter_sets = AVCDecoderConfigurationRecord::parse_parameter_sets(data, &mut start, num_of_picture_parameter_sets);
 
-    AVCDecoderConfigurationRecord {
+    Ok(AVCDecoderConfigurationRecord {
       configuration_version,
       avc_profile_indication,
       profile_compatability,
       avc_level_indication,
       length_size_minus_one,
       num_of_sequence_parameter_sets,
-      num_of_picture_parameter_sets: 0u8
+      num_of_picture_parameter_sets,
+      sequence_parameter_sets,
+      picture_parameter_sets,
+    })
+  }
+
+  // Every parameter set NAL unit is preceded by a 16 bit length. A parameter set cut off by the end of the box is
+  // left out.
+  fn parse_parameter_sets(data: &[u8], start: &mut usize, count: u8) -> Vec<Vec<u8>> {
+    let mut parameter_sets: Vec<Vec<u8>> = vec![];
+    for _ in 0..count {
+      let length = match util::get_u16(data, *start) {
+        Ok(length) => length as usize,
+        Err(_) => break,
+      };
+      *start += 2;
+      match data.get(*start..(*start + length)) {
+        Some(parameter_set) => parameter_sets.push(parameter_set.to_vec()),
+        None => break,
+      }
+      *start += length;
     }
+    parameter_sets
   }
 }
 
 pub struct AVCDecoderConfigurationRecordBuilder {
-  sps_data: Vec<u8>,
-  pps_data: Vec<u8>,
+  sps_data: Vec<Vec<u8>>,
+  pps_data: Vec<Vec<u8>>,
 }
 
 impl AVCDecoderConfigurationRecordBuilder {
@@ -77,31 +97,32 @@ impl AVCDecoderConfigurationRecordBuilder {
     }
   }
 
+  /// Add a PPS. Every PPS added goes in the record.
   pub fn pps(mut self, pps_data: &[u8]) -> AVCDecoderConfigurationRecordBuilder {
-    self.pps_data = pps_data.to_vec();
+    self.pps_data.push(pps_data.to_vec());
     self
   }
 
+  /// Add a SPS. Every SPS added goes in the record, the profile and level are the ones of the first.
   pub fn sps(mut self, sps_data: &[u8]) -> AVCDecoderConfigurationRecordBuilder {
-    self.sps_data = sps_data.to_vec();
+    self.sps_data.push(sps_data.to_vec());
     self
   }
+
   pub fn build(&self) -> Result<Vec<u8>, CustomError> {
     // sps data
-    let sps = SequenceParameterSet::parse(&self.sps_data)?;
-    let sps_length = self.sps_data.len();
-    let sps_length_array = util::transform_usize_to_u8_array(sps_length);
+    let sps = SequenceParameterSet::parse(self.sps_data.first().map(Vec::as_slice).unwrap_or_default())?;
+    let sequence_parameter_sets = AVCDecoderConfigurationRecordBuilder::build_parameter_sets(&self.sps_data);
 
     // pps data
-    let pps_length = self.pps_data.len();
-    let pps_length_array = util::transform_usize_to_u8_array(pps_length);
+    let picture_parameter_sets = AVCDecoderConfigurationRecordBuilder::build_parameter_sets(&self.pps_data);
     // calculate size
     let size = 
       8 + // header
-      8 +
-      sps_length + 
-      3 + 
-      pps_length;
+      6 +
+      sequence_parameter_sets.len() + 
+      1 + 
+      picture_parameter_sets.len();
     let size_array = util::transform_usize_to_u8_array(size);
     let avcC: Vec<u8> = [
       vec![
@@ -119,23 +140,30 @@ impl AVCDecoderConfigurationRecordBuilder {
         sps.level_idc,
         // reserved = ‘111111’b + lengthSizeMinusOne = `11`b = 3
         0xFF,
-        // reserved = ‘111’b + numOfSequenceParameterSets = `1`b = 1
-        0xE1,
-        // sequenceParameterSetLength
-        sps_length_array[1], sps_length_array[0],
+        // reserved = ‘111’b + numOfSequenceParameterSets (up to 32 SPS ids)
+        0xE0 | self.sps_data.len() as u8,
       ],
-      self.sps_data.clone(),
+      sequence_parameter_sets,
       vec![
-        // numOfPictureParameterSets
-        0x01,
-        // pictureParameterSetLength
-        pps_length_array[1], pps_length_array[0],
+        // numOfPictureParameterSets (up to 256 PPS ids)
+        self.pps_data.len() as u8,
       ],
-      self.pps_data.clone(),
+      picture_parameter_sets,
     ].concat();
     
     Ok(avcC)
   }
+
+  // Every parameter set NAL unit preceded by a 16 bit length
+  fn build_parameter_sets(parameter_sets: &[Vec<u8>]) -> Vec<u8> {
+    parameter_sets
+      .iter()
+      .flat_map(|parameter_set| {
+        let length_array = util::transform_usize_to_u8_array(parameter_set.len());
+        [vec![length_array[1], length_array[0]], parameter_set.clone()].concat()
+      })
+      .collect()
+  }
 }
 
 #[cfg(test)]
@@ -162,4 +190,39 @@ mod tests {
     
     assert_eq!(avcC, expected_avcC);
   }
+
+  #[test]
+  fn test_parse_avcC() {
+    let sps: [u8; 25] = [
+      0x67, 0x42, 0xC0, 0x1E, 0xD9, 0x01, 0xE0, 0x8F, 0xEB, 0x01, 0x10, 0x00, 0x00, 0x03, 0x00, 0x10, 0x00, 0x00, 0x03, 0x03, 0xC0, 0xF1, 0x62, 0xE4, 0x80
+    ];
+    let pps: [u8; 4] = [
+      0x68, 0xcb, 0x8c, 0xb2
+    ];
+    let avcC = AVCDecoderConfigurationRecordBuilder::create_builder()
+      .sps(&sps)
+      .pps(&pps)
+      .build()
+      .unwrap();
+
+    let record = AVCDecoderConfigurationRecord::parse(&avcC).unwrap();
+    assert_eq!(record.avc_profile_indication, 0x42);
+    assert_eq!(record.length_size_minus_one, 3);
+    assert_eq!(record.sequence_parameter_sets, vec![sps.to_vec()]);
+    assert_eq!(record.picture_parameter_sets, vec![pps.to_vec()]);
+
+    // Every PPS added goes in the record
+    let other_pps: [u8; 4] = [
+      0x68, 0x4B, 0x8C, 0xB2
+    ];
+    let avcC = AVCDecoderConfigurationRecordBuilder::create_builder()
+      .sps(&sps)
+      .pps(&pps)
+      .pps(&other_pps)
+      .build()
+      .unwrap();
+    let record = AVCDecoderConfigurationRecord::parse(&avcC).unwrap();
+    assert_eq!(record.sequence_parameter_sets, vec![sps.to_vec()]);
+    assert_eq!(record.picture_parameter_sets, vec![pps.to_vec(), other_pps.to_vec()]);
+  }
 }
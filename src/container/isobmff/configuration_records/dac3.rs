@@ -0,0 +1,98 @@
+use crate::util;
+
+/// AC3SpecificBox: ETSI TS 102 366; F.4
+pub struct AC3SpecificBoxBuilder {
+  fscod: u8,
+  bsid: u8,
+  bsmod: u8,
+  acmod: u8,
+  lfeon: bool,
+  bit_rate_code: u8,
+}
+
+impl AC3SpecificBoxBuilder {
+  pub fn create_builder() -> AC3SpecificBoxBuilder {
+    AC3SpecificBoxBuilder {
+      fscod: 0,
+      bsid: 8,
+      bsmod: 0,
+      acmod: 0,
+      lfeon: false,
+      bit_rate_code: 0,
+    }
+  }
+
+  pub fn fscod(mut self, fscod: u8) -> AC3SpecificBoxBuilder {
+    self.fscod = fscod;
+    self
+  }
+
+  pub fn bsid(mut self, bsid: u8) -> AC3SpecificBoxBuilder {
+    self.bsid = bsid;
+    self
+  }
+
+  pub fn bsmod(mut self, bsmod: u8) -> AC3SpecificBoxBuilder {
+    self.bsmod = bsmod;
+    self
+  }
+
+  pub fn acmod(mut self, acmod: u8) -> AC3SpecificBoxBuilder {
+    self.acmod = acmod;
+    self
+  }
+
+  pub fn lfeon(mut self, lfeon: bool) -> AC3SpecificBoxBuilder {
+    self.lfeon = lfeon;
+    self
+  }
+
+  pub fn bit_rate_code(mut self, bit_rate_code: u8) -> AC3SpecificBoxBuilder {
+    self.bit_rate_code = bit_rate_code;
+    self
+  }
+
+  pub fn build(&self) -> Vec<u8> {
+    // fscod(2) bsid(5) bsmod(3) acmod(3) lfeon(1) bit_rate_code(5) reserved(5)
+    let specific = ((self.fscod as u32 & 0x3) << 22) |
+      ((self.bsid as u32 & 0x1F) << 17) |
+      ((self.bsmod as u32 & 0x7) << 14) |
+      ((self.acmod as u32 & 0x7) << 11) |
+      ((self.lfeon as u32) << 10) |
+      ((self.bit_rate_code as u32 & 0x1F) << 5);
+    let specific_array = util::transform_u32_to_u8_array(specific);
+    vec![
+      // size
+      0x00, 0x00, 0x00, 0x0B,
+      // dac3
+      0x64, 0x61, 0x63, 0x33,
+      specific_array[2], specific_array[1], specific_array[0],
+    ]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_build_dac3() {
+    let expected_dac3: [u8; 11] = [
+      // size
+      0x00, 0x00, 0x00, 0x0B,
+      // dac3
+      0x64, 0x61, 0x63, 0x33,
+      // 48 kHz, bsid 8, bsmod 0, 3/2 with LFE, 192 kbps
+      0x10, 0x3D, 0x40,
+    ];
+    let dac3 = AC3SpecificBoxBuilder::create_builder()
+      .fscod(0)
+      .bsid(8)
+      .bsmod(0)
+      .acmod(7)
+      .lfeon(true)
+      .bit_rate_code(10)
+      .build();
+    assert_eq!(dac3, expected_dac3);
+  }
+}
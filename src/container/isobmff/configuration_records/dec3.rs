@@ -0,0 +1,266 @@
+use crate::container::transport_stream::eac3::get_chan_loc_channel_count;
+use crate::error::{CustomError, construct_error, error_code::{MajorCode, ISOBMFFMinorCode}};
+use crate::util;
+use crate::util::{bit_reader::BitReader, bit_writer::BitWriter};
+
+/// An independent substream of the E-AC-3 stream and the dependent substreams that extend it
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EC3IndependentSubstream {
+  pub fscod: u8,                // 2 bit
+  pub bsid: u8,                 // 5 bit
+  pub asvc: bool,
+  pub bsmod: u8,                // 3 bit
+  pub acmod: u8,                // 3 bit
+  pub lfeon: bool,
+  pub num_dep_sub: u8,          // 4 bit
+  /// Channel locations added by the dependent substreams. Only present when there are dependent substreams.
+  pub chan_loc: u16,            // 9 bit
+}
+
+impl EC3IndependentSubstream {
+  /// Number of full bandwidth channels (from acmod) plus the LFE channel plus the channels of the dependent substreams
+  pub fn get_channel_count(&self) -> u32 {
+    let channel_count = match self.acmod {
+      0 => 2, // 1+1 dual mono
+      1 => 1,
+      2 => 2,
+      3 => 3,
+      4 => 3,
+      5 => 4,
+      6 => 4,
+      _ => 5,
+    };
+    let dependent_channel_count = if self.num_dep_sub > 0 { get_chan_loc_channel_count(self.chan_loc) } else { 0 };
+    channel_count + self.lfeon as u32 + dependent_channel_count
+  }
+}
+
+/// EC3SpecificBox: ETSI TS 102 366; F.6
+#[derive(Debug, Default)]
+pub struct EC3SpecificBox {
+  pub data_rate: u16,           // 13 bit
+  pub independent_substreams: Vec<EC3IndependentSubstream>,
+  /// complexity_index_type_a of the Joint Object Coding (Dolby Atmos) extension
+  pub complexity_index_type_a: Option<u8>,
+}
+
+impl EC3SpecificBox {
+  pub fn parse(data: &[u8]) -> Result<EC3SpecificBox, CustomError> {
+    let box_type = util::get_u32(data, 4)?;
+    // dec3
+    if box_type != 0x64656333 {
+      return Err(EC3SpecificBox::generate_error(format!("Expected a dec3 box. Found: {:#010X}", box_type)));
+    }
+    let size = (util::get_u32(data, 0)? as usize).min(data.len());
+    let mut bit_reader = BitReader::create_bit_reader(&data[8..size]);
+    let data_rate = bit_reader.read_bits(13)? as u16;
+    let num_ind_sub = bit_reader.read_bits(3)? + 1;
+    let mut independent_substreams: Vec<EC3IndependentSubstream> = vec![];
+    for _ in 0..num_ind_sub {
+      let fscod = bit_reader.read_bits(2)? as u8;
+      let bsid = bit_reader.read_bits(5)? as u8;
+      // reserved
+      bit_reader.read_bits(1)?;
+      let asvc = bit_reader.read_bits(1)? == 1;
+      let bsmod = bit_reader.read_bits(3)? as u8;
+      let acmod = bit_reader.read_bits(3)? as u8;
+      let lfeon = bit_reader.read_bits(1)? == 1;
+      // reserved
+      bit_reader.read_bits(3)?;
+      let num_dep_sub = bit_reader.read_bits(4)? as u8;
+      let chan_loc = if num_dep_sub > 0 {
+        bit_reader.read_bits(9)? as u16
+      } else {
+        // reserved
+        bit_reader.read_bits(1)?;
+        0
+      };
+      independent_substreams.push(EC3IndependentSubstream {
+        fscod,
+        bsid,
+        asvc,
+        bsmod,
+        acmod,
+        lfeon,
+        num_dep_sub,
+        chan_loc,
+      });
+    }
+
+    // ETSI TS 103 420; C.3 The JOC extension follows the substreams when there are at least 2 bytes left
+    let mut complexity_index_type_a = None;
+    if bit_reader.read_bits(16).is_ok() {
+      let mut bit_reader = BitReader::create_bit_reader(&data[(size - 2)..size]);
+      // reserved
+      bit_reader.read_bits(7)?;
+      if bit_reader.read_bits(1)? == 1 {
+        complexity_index_type_a = Some(bit_reader.read_bits(8)? as u8);
+      }
+    }
+
+    Ok(EC3SpecificBox {
+      data_rate,
+      independent_substreams,
+      complexity_index_type_a,
+    })
+  }
+
+  /// Channel count of the main program (the first independent substream and its dependent substreams)
+  pub fn get_channel_count(&self) -> u32 {
+    self.independent_substreams
+      .first()
+      .map(|substream|substream.get_channel_count())
+      .unwrap_or_default()
+  }
+
+  /// Value of the HLS CHANNELS attribute. Dolby Atmos (JOC) streams signal the number of objects instead of channels.
+  pub fn get_hls_channels(&self) -> String {
+    match self.complexity_index_type_a {
+      Some(complexity_index) => format!("{}/JOC", complexity_index),
+      None => self.get_channel_count().to_string(),
+    }
+  }
+
+  fn generate_error(message: String) -> CustomError {
+    construct_error(
+      MajorCode::ISOBMFF,
+      Box::new(ISOBMFFMinorCode::PARSE_BOX_ERROR),
+      message,
+      file!(),
+      line!())
+  }
+}
+
+pub struct EC3SpecificBoxBuilder {
+  data_rate: u16,
+  independent_substreams: Vec<EC3IndependentSubstream>,
+  complexity_index_type_a: Option<u8>,
+}
+
+impl EC3SpecificBoxBuilder {
+  pub fn create_builder() -> EC3SpecificBoxBuilder {
+    EC3SpecificBoxBuilder {
+      data_rate: 0,
+      independent_substreams: vec![],
+      complexity_index_type_a: None,
+    }
+  }
+
+  /// Data rate in kbps
+  pub fn data_rate(mut self, data_rate: u16) -> EC3SpecificBoxBuilder {
+    self.data_rate = data_rate;
+    self
+  }
+
+  pub fn independent_substream(mut self, independent_substream: EC3IndependentSubstream) -> EC3SpecificBoxBuilder {
+    self.independent_substreams.push(independent_substream);
+    self
+  }
+
+  pub fn complexity_index_type_a(mut self, complexity_index_type_a: Option<u8>) -> EC3SpecificBoxBuilder {
+    self.complexity_index_type_a = complexity_index_type_a;
+    self
+  }
+
+  pub fn build(&self) -> Vec<u8> {
+    let mut bit_writer = BitWriter::create_bit_writer();
+    bit_writer
+      .write_bits(self.data_rate as usize, 13)
+      .write_bits(self.independent_substreams.len().max(1) - 1, 3);
+    for substream in self.independent_substreams.iter() {
+      bit_writer
+        .write_bits(substream.fscod as usize, 2)
+        .write_bits(substream.bsid as usize, 5)
+        .write_bits(0, 1) // reserved
+        .write_bool(substream.asvc)
+        .write_bits(substream.bsmod as usize, 3)
+        .write_bits(substream.acmod as usize, 3)
+        .write_bool(substream.lfeon)
+        .write_bits(0, 3) // reserved
+        .write_bits(substream.num_dep_sub as usize, 4);
+      if substream.num_dep_sub > 0 {
+        bit_writer.write_bits(substream.chan_loc as usize, 9);
+      } else {
+        bit_writer.write_bits(0, 1); // reserved
+      }
+    }
+    if let Some(complexity_index_type_a) = self.complexity_index_type_a {
+      bit_writer
+        .write_bits(0, 7) // reserved
+        .write_bool(true) // flag_ec3_extension_type_a
+        .write_bits(complexity_index_type_a as usize, 8);
+    }
+    let specific = bit_writer.finish();
+    let size = 8 + specific.len();
+    let size_array = util::transform_usize_to_u8_array(size);
+
+    [
+      vec![
+        // size
+        size_array[3], size_array[2], size_array[1], size_array[0],
+        // dec3
+        0x64, 0x65, 0x63, 0x33,
+      ],
+      specific,
+    ].concat()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_build_and_parse_dec3_with_joc() {
+    let expected_dec3: [u8; 16] = [
+      // size
+      0x00, 0x00, 0x00, 0x10,
+      // dec3
+      0x64, 0x65, 0x63, 0x33,
+      // data_rate (768), num_ind_sub (0)
+      0x18, 0x00,
+      // 48 kHz, bsid 16, 3/2 with LFE, 1 dependent substream with Lrs/Rrs
+      0x20, 0x0F, 0x02, 0x80,
+      // flag_ec3_extension_type_a, complexity_index_type_a (16)
+      0x01, 0x10,
+    ];
+    let dec3 = EC3SpecificBoxBuilder::create_builder()
+      .data_rate(768)
+      .independent_substream(EC3IndependentSubstream {
+        fscod: 0,
+        bsid: 16,
+        acmod: 7,
+        lfeon: true,
+        num_dep_sub: 1,
+        chan_loc: 0x80,
+        ..Default::default()
+      })
+      .complexity_index_type_a(Some(16))
+      .build();
+    assert_eq!(dec3, expected_dec3);
+
+    let ec3_specific_box = EC3SpecificBox::parse(&dec3).unwrap();
+    assert_eq!(ec3_specific_box.data_rate, 768);
+    assert_eq!(ec3_specific_box.independent_substreams.len(), 1);
+    assert_eq!(ec3_specific_box.independent_substreams[0].chan_loc, 0x80);
+    assert_eq!(ec3_specific_box.get_channel_count(), 8);
+    assert_eq!(ec3_specific_box.complexity_index_type_a, Some(16));
+    assert_eq!(ec3_specific_box.get_hls_channels(), "16/JOC");
+  }
+
+  #[test]
+  fn test_parse_dec3_without_joc() {
+    let dec3 = [
+      0x00, 0x00, 0x00, 0x0D,
+      0x64, 0x65, 0x63, 0x33,
+      // data_rate (192), num_ind_sub (0)
+      0x06, 0x00,
+      // 48 kHz, bsid 16, 2/0, no dependent substreams
+      0x20, 0x04, 0x00,
+    ];
+    let ec3_specific_box = EC3SpecificBox::parse(&dec3).unwrap();
+    assert_eq!(ec3_specific_box.data_rate, 192);
+    assert_eq!(ec3_specific_box.complexity_index_type_a, None);
+    assert_eq!(ec3_specific_box.get_hls_channels(), "2");
+  }
+}
@@ -0,0 +1,230 @@
+use crate::codec::h265::sequence_parameter_set::SequenceParameterSet;
+use crate::codec::h265::nal_unit_header::{VPS_NUT, SPS_NUT, PPS_NUT};
+use crate::error::{CustomError, construct_error, error_code::{MajorCode, ISOBMFFMinorCode}};
+use crate::util;
+use crate::util::{bit_reader::BitReader, bit_writer::BitWriter};
+
+/// HEVCDecoderConfigurationRecord: 14496-15; 8.3.3.1
+#[derive(Debug)]
+pub struct HEVCDecoderConfigurationRecord {
+  pub configuration_version: u8,
+  pub general_profile_space: u8,                    // 2 bit
+  pub general_tier_flag: u8,                        // 1 bit
+  pub general_profile_idc: u8,                      // 5 bit
+  pub general_profile_compatibility_flags: u32,
+  pub general_constraint_indicator_flags: u64,      // 48 bit
+  pub general_level_idc: u8,
+  pub chroma_format_idc: u8,                        // 2 bit
+  pub bit_depth_luma_minus8: u8,                    // 3 bit
+  pub bit_depth_chroma_minus8: u8,                  // 3 bit
+  pub length_size_minus_one: u8,                    // 2 bit
+}
+
+impl HEVCDecoderConfigurationRecord {
+  pub fn parse(data: &[u8]) -> Result<HEVCDecoderConfigurationRecord, CustomError> {
+    let box_type = util::get_u32(data, 4)?;
+    // hvcC
+    if box_type != 0x68766343 {
+      return Err(construct_error(
+        MajorCode::ISOBMFF,
+        Box::new(ISOBMFFMinorCode::PARSE_BOX_ERROR),
+        format!("Expected a hvcC box. Found: {:#010X}", box_type),
+        file!(),
+        line!()));
+    }
+    let mut bit_reader = BitReader::create_bit_reader(&data[8..]);
+    let configuration_version = bit_reader.read_bits(8)? as u8;
+    let general_profile_space = bit_reader.read_bits(2)? as u8;
+    let general_tier_flag = bit_reader.read_bits(1)? as u8;
+    let general_profile_idc = bit_reader.read_bits(5)? as u8;
+    let general_profile_compatibility_flags = bit_reader.read_bits(32)? as u32;
+    let general_constraint_indicator_flags = ((bit_reader.read_bits(16)? as u64) << 32) | bit_reader.read_bits(32)? as u64;
+    let general_level_idc = bit_reader.read_bits(8)? as u8;
+    // reserved + min_spatial_segmentation_idc, reserved + parallelismType, reserved
+    bit_reader.read_bits(30)?;
+    let chroma_format_idc = bit_reader.read_bits(2)? as u8;
+    // reserved
+    bit_reader.read_bits(5)?;
+    let bit_depth_luma_minus8 = bit_reader.read_bits(3)? as u8;
+    // reserved
+    bit_reader.read_bits(5)?;
+    let bit_depth_chroma_minus8 = bit_reader.read_bits(3)? as u8;
+    // avgFrameRate, constantFrameRate, numTemporalLayers, temporalIdNested
+    bit_reader.read_bits(22)?;
+    let length_size_minus_one = bit_reader.read_bits(2)? as u8;
+
+    Ok(HEVCDecoderConfigurationRecord {
+      configuration_version,
+      general_profile_space,
+      general_tier_flag,
+      general_profile_idc,
+      general_profile_compatibility_flags,
+      general_constraint_indicator_flags,
+      general_level_idc,
+      chroma_format_idc,
+      bit_depth_luma_minus8,
+      bit_depth_chroma_minus8,
+      length_size_minus_one,
+    })
+  }
+
+  /// Codecs parameter of the stream (e.g. hvc1.1.6.L120.90). 14496-15; E.3
+  pub fn get_codec_string(&self, sample_entry_type: &str) -> String {
+    let profile_space = match self.general_profile_space {
+      1 => "A",
+      2 => "B",
+      3 => "C",
+      _ => "",
+    };
+    let tier = if self.general_tier_flag == 1 { "H" } else { "L" };
+    let constraint_bytes: Vec<u8> = (0..6)
+      .map(|index|((self.general_constraint_indicator_flags >> (40 - index * 8)) & 0xFF) as u8)
+      .collect();
+    // Trailing bytes that are zero are left out
+    let constraint_count = constraint_bytes.iter().rposition(|byte|*byte != 0).map_or(0, |index|index + 1);
+    let constraints: String = constraint_bytes[0..constraint_count]
+      .iter()
+      .map(|byte|format!(".{:X}", byte))
+      .collect();
+    format!("{}.{}{}.{:X}.{}{}{}",
+      sample_entry_type,
+      profile_space,
+      self.general_profile_idc,
+      self.general_profile_compatibility_flags.reverse_bits(),
+      tier,
+      self.general_level_idc,
+      constraints)
+  }
+}
+
+pub struct HEVCDecoderConfigurationRecordBuilder {
+  vps_data: Vec<u8>,
+  sps_data: Vec<u8>,
+  pps_data: Vec<u8>,
+  array_completeness: bool,
+}
+
+impl HEVCDecoderConfigurationRecordBuilder {
+  pub fn create_builder() -> HEVCDecoderConfigurationRecordBuilder {
+    HEVCDecoderConfigurationRecordBuilder {
+      vps_data: vec![],
+      sps_data: vec![],
+      pps_data: vec![],
+      array_completeness: true,
+    }
+  }
+
+  pub fn vps(mut self, vps_data: &[u8]) -> HEVCDecoderConfigurationRecordBuilder {
+    self.vps_data = vps_data.to_vec();
+    self
+  }
+
+  pub fn sps(mut self, sps_data: &[u8]) -> HEVCDecoderConfigurationRecordBuilder {
+    self.sps_data = sps_data.to_vec();
+    self
+  }
+
+  pub fn pps(mut self, pps_data: &[u8]) -> HEVCDecoderConfigurationRecordBuilder {
+    self.pps_data = pps_data.to_vec();
+    self
+  }
+
+  /// Whether the parameter sets are only in the configuration record (hvc1) and not in the samples too (hev1)
+  pub fn array_completeness(mut self, array_completeness: bool) -> HEVCDecoderConfigurationRecordBuilder {
+    self.array_completeness = array_completeness;
+    self
+  }
+
+  pub fn is_array_complete(&self) -> bool {
+    self.array_completeness
+  }
+
+  pub fn build(&self) -> Result<Vec<u8>, CustomError> {
+    let sps = SequenceParameterSet::parse(&self.sps_data)?;
+    let profile_tier_level = &sps.profile_tier_level;
+    let mut bit_writer = BitWriter::create_bit_writer();
+    bit_writer
+      .write_bits(1, 8) // configurationVersion
+      .write_bits(profile_tier_level.general_profile_space as usize, 2)
+      .write_bits(profile_tier_level.general_tier_flag as usize, 1)
+      .write_bits(profile_tier_level.general_profile_idc as usize, 5)
+      .write_bits(profile_tier_level.general_profile_compatibility_flags as usize, 32)
+      .write_bits(profile_tier_level.general_constraint_indicator_flags as usize, 48)
+      .write_bits(profile_tier_level.general_level_idc as usize, 8)
+      .write_bits(0xF, 4) // reserved
+      .write_bits(0, 12) // min_spatial_segmentation_idc
+      .write_bits(0x3F, 6) // reserved
+      .write_bits(0, 2) // parallelismType
+      .write_bits(0x3F, 6) // reserved
+      .write_bits(sps.chroma_format_idc, 2)
+      .write_bits(0x1F, 5) // reserved
+      .write_bits(sps.bit_depth_luma_minus8, 3)
+      .write_bits(0x1F, 5) // reserved
+      .write_bits(sps.bit_depth_chroma_minus8, 3)
+      .write_bits(0, 16) // avgFrameRate
+      .write_bits(0, 2) // constantFrameRate
+      .write_bits(sps.sps_max_sub_layers_minus1 as usize + 1, 3) // numTemporalLayers
+      .write_bits(sps.sps_temporal_id_nesting_flag as usize, 1)
+      .write_bits(3, 2) // lengthSizeMinusOne
+      .write_bits(3, 8); // numOfArrays
+    for (nal_unit_type, nal_unit) in [(VPS_NUT, &self.vps_data), (SPS_NUT, &self.sps_data), (PPS_NUT, &self.pps_data)] {
+      bit_writer
+        .write_bool(self.array_completeness)
+        .write_bits(0, 1) // reserved
+        .write_bits(nal_unit_type as usize, 6)
+        .write_bits(1, 16) // numNalus
+        .write_bits(nal_unit.len(), 16);
+      for byte in nal_unit.iter() {
+        bit_writer.write_bits(*byte as usize, 8);
+      }
+    }
+    let record = bit_writer.finish();
+    let size = 8 + record.len();
+    let size_array = util::transform_usize_to_u8_array(size);
+
+    Ok([
+      vec![
+        // size
+        size_array[3], size_array[2], size_array[1], size_array[0],
+        // hvcC
+        0x68, 0x76, 0x63, 0x43,
+      ],
+      record,
+    ].concat())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::codec::h265::sequence_parameter_set::tests::get_sps;
+
+  #[test]
+  fn test_build_and_parse_hvcC() {
+    let vps = [0x40, 0x01, 0x0C];
+    let pps = [0x44, 0x01, 0xC1, 0x72, 0xB4, 0x62, 0x40];
+    let sps = get_sps();
+    let hvcc = HEVCDecoderConfigurationRecordBuilder::create_builder()
+      .vps(&vps)
+      .sps(&sps)
+      .pps(&pps)
+      .build()
+      .unwrap();
+
+    assert_eq!(hvcc.len(), 8 + 23 + 3 * 5 + vps.len() + sps.len() + pps.len());
+    assert_eq!(hvcc[0..8], [0x00, 0x00, 0x00, 0x52, 0x68, 0x76, 0x63, 0x43]);
+    assert_eq!(hvcc[8..31], [
+      0x01, 0x01, 0x60, 0x00, 0x00, 0x00, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x78, 0xF0, 0x00, 0xFC, 0xFD, 0xF8, 0xF8,
+      0x00, 0x00, 0x0F, 0x03,
+    ]);
+    // VPS array
+    assert_eq!(hvcc[31..39], [0xA0, 0x00, 0x01, 0x00, 0x03, 0x40, 0x01, 0x0C]);
+
+    let record = HEVCDecoderConfigurationRecord::parse(&hvcc).unwrap();
+    assert_eq!(record.general_profile_idc, 1);
+    assert_eq!(record.general_level_idc, 120);
+    assert_eq!(record.chroma_format_idc, 1);
+    assert_eq!(record.length_size_minus_one, 3);
+    assert_eq!(record.get_codec_string("hvc1"), "hvc1.1.6.L120.90");
+  }
+}
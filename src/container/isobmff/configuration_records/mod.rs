@@ -1,2 +1,6 @@
 #[allow(non_snake_case)]
-pub mod avcC;
\ No newline at end of file
+pub mod avcC;
+pub mod dac3;
+pub mod dec3;
+#[allow(non_snake_case)]
+pub mod hvcC;
@@ -1,53 +1,160 @@
+use crate::container::isobmff::descriptors::get_expandable_size;
 use crate::error::CustomError;
-use crate::util;
+use crate::util::bit_reader::BitReader;
+use crate::util::bit_writer::BitWriter;
+
+// Audio object types. ISO/IEC 14496-3; 1.5.1.1 Table 1.1
+static AAC_LC: u8 = 2;
+static SBR: u8 = 5;
+static PS: u8 = 29;
+// syncExtensionType of the backward compatible SBR and PS signaling. ISO/IEC 14496-3; 1.6.6.2
+static SBR_SYNC_EXTENSION_TYPE: usize = 0x2B7;
+static PS_SYNC_EXTENSION_TYPE: usize = 0x548;
 
-static CLASS: &str = "AACAudioSpecificConfig";
 #[derive(Debug, PartialEq, Eq)]
 pub struct AACAudioSpecificConfig {
+  /// Object type of the core coder, so 2 (AAC LC) for HE-AAC
   pub audio_object_type: u8,                // 5 bit
   pub sampling_frequency_index: u8,         // 4 bit
   pub sampling_frequency: Option<u32>,      // 24 bit
   pub channel_configuration: u8,
+  /// Output sampling frequency index of the SBR tool
+  pub extension_sampling_frequency_index: Option<u8>,
+  pub sbr_present_flag: bool,
+  pub ps_present_flag: bool,
 }
 
 impl AACAudioSpecificConfig {
+  // 14496-3; 1.6.2.1 AudioSpecificConfig
   pub fn parse(data: &[u8]) -> Result<AACAudioSpecificConfig, CustomError> {
-    let offset = 2usize;
-    let temp = util::get_u8(data, offset)?;
-    let audio_object_type = AACAudioSpecificConfig::get_audio_object_type(temp);
-
-    // Assuming audio object is only 5 bits for now
-    let temp_16 = util::get_u16(data, offset)?;
-    let sampling_frequency_index = ((temp_16 & 0x780) >> 7) as u8;
-    let sampling_frequency: Option<u32> = Option::None;
-    if sampling_frequency_index == 0xf {
-      // TODO (benjamintoofer@gmail.com): Parse sampling_frequency
-      println!("AACAudioSpecificConfig: ERROR! Implement parsing sampling_frequency!");
-    }
-    let channel_configuration = ((temp_16 & 0x78) >> 3) as u8;
-    Ok(AACAudioSpecificConfig {
+    // Skip the DecSpecificInfoTag and the expandable size of the descriptor
+    let mut offset = 1usize;
+    let size = get_expandable_size(data, &mut offset) as usize;
+    offset += 1;
+    // Bound the reader to the descriptor so the sync extensions aren't looked for in the data after it
+    let end = (offset + size).min(data.len());
+    let mut bit_reader = BitReader::create_bit_reader(&data[offset.min(end)..end]);
+
+    let mut audio_object_type = AACAudioSpecificConfig::get_audio_object_type(&mut bit_reader)?;
+    let sampling_frequency_index = bit_reader.read_bits(4)? as u8;
+    let sampling_frequency = AACAudioSpecificConfig::get_sampling_frequency(&mut bit_reader, sampling_frequency_index)?;
+    let channel_configuration = bit_reader.read_bits(4)? as u8;
+
+    let mut config = AACAudioSpecificConfig {
       audio_object_type,
       sampling_frequency_index,
       sampling_frequency,
       channel_configuration,
-    })
+      extension_sampling_frequency_index: None,
+      sbr_present_flag: false,
+      ps_present_flag: false,
+    };
+    // Explicit hierarchical signaling. The SBR (and PS) object type comes first and is followed by the core one.
+    if audio_object_type == SBR || audio_object_type == PS {
+      config.sbr_present_flag = true;
+      config.ps_present_flag = audio_object_type == PS;
+      let extension_sampling_frequency_index = bit_reader.read_bits(4)? as u8;
+      AACAudioSpecificConfig::get_sampling_frequency(&mut bit_reader, extension_sampling_frequency_index)?;
+      config.extension_sampling_frequency_index = Some(extension_sampling_frequency_index);
+      audio_object_type = AACAudioSpecificConfig::get_audio_object_type(&mut bit_reader)?;
+      config.audio_object_type = audio_object_type;
+    }
+
+    // Only the GASpecificConfig of the AAC object types is read, the signaling after any other config can't be found
+    if !(1..=4).contains(&audio_object_type) || !AACAudioSpecificConfig::skip_ga_specific_config(&mut bit_reader, channel_configuration)? {
+      return Ok(config);
+    }
+
+    // Explicit backward compatible signaling, in a sync extension after the core config
+    if !config.sbr_present_flag && bit_reader.remaining_bits() >= 16 && bit_reader.read_bits(11)? == SBR_SYNC_EXTENSION_TYPE {
+      let extension_audio_object_type = AACAudioSpecificConfig::get_audio_object_type(&mut bit_reader)?;
+      if extension_audio_object_type == SBR && bit_reader.read_bits(1)? == 1 {
+        config.sbr_present_flag = true;
+        let extension_sampling_frequency_index = bit_reader.read_bits(4)? as u8;
+        AACAudioSpecificConfig::get_sampling_frequency(&mut bit_reader, extension_sampling_frequency_index)?;
+        config.extension_sampling_frequency_index = Some(extension_sampling_frequency_index);
+        if bit_reader.remaining_bits() >= 12 && bit_reader.read_bits(11)? == PS_SYNC_EXTENSION_TYPE {
+          config.ps_present_flag = bit_reader.read_bits(1)? == 1;
+        }
+      }
+    }
+    Ok(config)
+  }
+
+  /// Audio object type for the codecs string (mp4a.40.X). RFC 6381; 3.3
+  pub fn get_codec_audio_object_type(&self) -> u8 {
+    if self.ps_present_flag {
+      return PS;
+    }
+    if self.sbr_present_flag {
+      return SBR;
+    }
+    self.audio_object_type
+  }
+
+  /// Number of output channels. Parametric stereo makes stereo out of a mono core.
+  pub fn get_channel_count(&self) -> u8 {
+    if self.ps_present_flag && self.channel_configuration == 1 {
+      return 2;
+    }
+    self.channel_configuration
   }
 
   // 14496-3; 1.6.2.1 AudioSpecificConfig
   // Table 1.14 — Syntax of GetAudioObjectType()
-  fn get_audio_object_type(data: u8) -> u8 {
+  fn get_audio_object_type(bit_reader: &mut BitReader) -> Result<u8, CustomError> {
     // 5 bit
-    let audio_object_type: u8 = (data & 0xF8) >> 3;
+    let audio_object_type = bit_reader.read_bits(5)? as u8;
     if audio_object_type == 31 {
-      // audio_object_type = 32 + audio_object_type_ext;
+      return Ok(32 + bit_reader.read_bits(6)? as u8);
+    }
+    Ok(audio_object_type)
+  }
+
+  fn get_sampling_frequency(bit_reader: &mut BitReader, sampling_frequency_index: u8) -> Result<Option<u32>, CustomError> {
+    if sampling_frequency_index == 0xF {
+      return Ok(Some(bit_reader.read_bits(24)? as u32));
+    }
+    Ok(None)
+  }
+
+  // 14496-3; 4.4.1 Table 4.1 — Syntax of GASpecificConfig(). Returns false when the config has a
+  // program_config_element, which isn't parsed, so nothing after it can be read.
+  fn skip_ga_specific_config(bit_reader: &mut BitReader, channel_configuration: u8) -> Result<bool, CustomError> {
+    // frameLengthFlag
+    bit_reader.read_bits(1)?;
+    let depends_on_core_coder = bit_reader.read_bits(1)?;
+    if depends_on_core_coder == 1 {
+      // coreCoderDelay
+      bit_reader.read_bits(14)?;
+    }
+    let extension_flag = bit_reader.read_bits(1)?;
+    if channel_configuration == 0 {
+      return Ok(false);
+    }
+    if extension_flag == 1 {
+      // extensionFlag3
+      bit_reader.read_bits(1)?;
     }
-    audio_object_type
+    Ok(true)
   }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum SBRSignaling {
+  /// The SBR (or PS) object type comes first, so decoders without SBR don't try to play the stream
+  HIERARCHICAL,
+  /// An AAC LC config followed by a sync extension with the SBR (and PS) flags, which decoders without SBR skip
+  BACKWARD_COMPATIBLE,
+}
+
 pub struct AACAudioSpecificConfigBuilder {
   channel_count: u32,
-  sampling_frequency_index: u32
+  sampling_frequency_index: u32,
+  extension_sampling_frequency_index: Option<u32>,
+  ps_present: bool,
+  sbr_signaling: SBRSignaling,
 }
 
 impl AACAudioSpecificConfigBuilder {
@@ -56,6 +163,9 @@ impl AACAudioSpecificConfigBuilder {
     return AACAudioSpecificConfigBuilder {
       channel_count: 0,
       sampling_frequency_index: 0,
+      extension_sampling_frequency_index: None,
+      ps_present: false,
+      sbr_signaling: SBRSignaling::HIERARCHICAL,
     }
   }
 
@@ -64,29 +174,88 @@ impl AACAudioSpecificConfigBuilder {
     self
   }
 
+  /// Sampling frequency index of the core coder
   pub fn sampling_frequency_index(mut self, sampling_frequency_index: u32) -> AACAudioSpecificConfigBuilder {
     self.sampling_frequency_index = sampling_frequency_index;
     self
   }
+
+  /// HE-AAC, with the output sampling frequency index of the SBR tool
+  pub fn sbr(mut self, extension_sampling_frequency_index: u32) -> AACAudioSpecificConfigBuilder {
+    self.extension_sampling_frequency_index = Some(extension_sampling_frequency_index);
+    self
+  }
+
+  /// HE-AAC v2. Only signaled together with SBR.
+  pub fn ps(mut self, ps_present: bool) -> AACAudioSpecificConfigBuilder {
+    self.ps_present = ps_present;
+    self
+  }
+
+  /// Defaults to hierarchical signaling
+  pub fn sbr_signaling(mut self, sbr_signaling: SBRSignaling) -> AACAudioSpecificConfigBuilder {
+    self.sbr_signaling = sbr_signaling;
+    self
+  }
+
   /**
     audioObjectType = 2;          5 bits
     samplingFrequencyIndex;       4 bits
     channelConfiguration (1 | 2)  4 bits
     GASpecificConfig              3 bits
   */
-
   pub fn build(&self) -> Vec<u8> {
-    let sample_and_channel:u32 = (self.sampling_frequency_index << 4) | self.channel_count;
-    let mut result = 0x1000u32;
-    result = result | (sample_and_channel << 3);
-    let value = util::transform_u32_to_u8_array(result);
-    vec![
-      // DecSpecificInfoTag
-      0x05,
-      // length
-      0x80, 0x80, 0x80, 0x02,
-      value[1], value[0],
-    ]
+    let mut bit_writer = BitWriter::create_bit_writer();
+    match (self.extension_sampling_frequency_index, self.sbr_signaling) {
+      (Some(extension_sampling_frequency_index), SBRSignaling::HIERARCHICAL) => {
+        let audio_object_type = if self.ps_present { PS } else { SBR };
+        bit_writer
+          .write_bits(audio_object_type as usize, 5)
+          .write_bits(self.sampling_frequency_index as usize, 4)
+          .write_bits(self.channel_count as usize, 4)
+          .write_bits(extension_sampling_frequency_index as usize, 4)
+          .write_bits(AAC_LC as usize, 5)
+          // GASpecificConfig
+          .write_bits(0, 3);
+      }
+      (Some(extension_sampling_frequency_index), SBRSignaling::BACKWARD_COMPATIBLE) => {
+        AACAudioSpecificConfigBuilder::write_aac_lc_config(&mut bit_writer, self.sampling_frequency_index, self.channel_count);
+        bit_writer
+          .write_bits(SBR_SYNC_EXTENSION_TYPE, 11)
+          .write_bits(SBR as usize, 5)
+          // sbrPresentFlag
+          .write_bits(1, 1)
+          .write_bits(extension_sampling_frequency_index as usize, 4);
+        if self.ps_present {
+          bit_writer
+            .write_bits(PS_SYNC_EXTENSION_TYPE, 11)
+            // psPresentFlag
+            .write_bits(1, 1);
+        }
+      }
+      (None, _) => {
+        AACAudioSpecificConfigBuilder::write_aac_lc_config(&mut bit_writer, self.sampling_frequency_index, self.channel_count);
+      }
+    }
+    let audio_specific_config = bit_writer.finish();
+    [
+      vec![
+        // DecSpecificInfoTag
+        0x05,
+        // length
+        0x80, 0x80, 0x80, audio_specific_config.len() as u8,
+      ],
+      audio_specific_config
+    ].concat()
+  }
+
+  fn write_aac_lc_config(bit_writer: &mut BitWriter, sampling_frequency_index: u32, channel_count: u32) {
+    bit_writer
+      .write_bits(AAC_LC as usize, 5)
+      .write_bits(sampling_frequency_index as usize, 4)
+      .write_bits(channel_count as usize, 4)
+      // GASpecificConfig
+      .write_bits(0, 3);
   }
 }
 
@@ -106,8 +275,17 @@ mod tests {
       sampling_frequency_index: 3,
       sampling_frequency: Option::None,
       channel_configuration: 2,
+      extension_sampling_frequency_index: None,
+      sbr_present_flag: false,
+      ps_present_flag: false,
     };
     assert_eq!(AACAudioSpecificConfig::parse(&audio_specific_config).unwrap(), expected_config);
+
+    // Size written with the expandable 4 byte form
+    let audio_specific_config: [u8; 7] = [
+      0x05, 0x80, 0x80, 0x80, 0x02, 0x11, 0x90
+    ];
+    assert_eq!(AACAudioSpecificConfig::parse(&audio_specific_config).unwrap(), expected_config);
   }
 
   #[test]
@@ -123,4 +301,72 @@ mod tests {
       .build();
     assert_eq!(actual_audio_specific_config, expected_audio_specific_config);
   }
+
+  #[test]
+  fn test_parse_audio_specific_config_hierarchical_signaling() {
+    // HE-AAC v2, 24 kHz mono core with a 48 kHz SBR output rate
+    let audio_specific_config: [u8; 6] = [
+      0x05, 0x04, 0xEB, 0x09, 0x88, 0x00
+    ];
+    let config = AACAudioSpecificConfig::parse(&audio_specific_config).unwrap();
+    assert_eq!(config, AACAudioSpecificConfig{
+      audio_object_type: 2,
+      sampling_frequency_index: 6,
+      sampling_frequency: Option::None,
+      channel_configuration: 1,
+      extension_sampling_frequency_index: Some(3),
+      sbr_present_flag: true,
+      ps_present_flag: true,
+    });
+    assert_eq!(config.get_codec_audio_object_type(), 29);
+    assert_eq!(config.get_channel_count(), 2);
+  }
+
+  #[test]
+  fn test_parse_audio_specific_config_backward_compatible_signaling() {
+    // HE-AAC, 24 kHz stereo core with a 48 kHz SBR output rate
+    let audio_specific_config: [u8; 7] = [
+      0x05, 0x05, 0x13, 0x10, 0x56, 0xE5, 0x98
+    ];
+    let config = AACAudioSpecificConfig::parse(&audio_specific_config).unwrap();
+    assert_eq!(config, AACAudioSpecificConfig{
+      audio_object_type: 2,
+      sampling_frequency_index: 6,
+      sampling_frequency: Option::None,
+      channel_configuration: 2,
+      extension_sampling_frequency_index: Some(3),
+      sbr_present_flag: true,
+      ps_present_flag: false,
+    });
+    assert_eq!(config.get_codec_audio_object_type(), 5);
+    assert_eq!(config.get_channel_count(), 2);
+
+    // HE-AAC v2 adds the PS sync extension
+    let audio_specific_config: [u8; 9] = [
+      0x05, 0x07, 0x13, 0x08, 0x56, 0xE5, 0x9D, 0x48, 0x80
+    ];
+    let config = AACAudioSpecificConfig::parse(&audio_specific_config).unwrap();
+    assert!(config.sbr_present_flag && config.ps_present_flag);
+    assert_eq!(config.get_codec_audio_object_type(), 29);
+  }
+
+  #[test]
+  fn test_audio_specific_config_builder_sbr_signaling() {
+    let hierarchical = AACAudioSpecificConfigBuilder::create_builder()
+      .channel_count(1)
+      .sampling_frequency_index(6)
+      .sbr(3)
+      .ps(true)
+      .build();
+    assert_eq!(hierarchical, vec![0x05, 0x80, 0x80, 0x80, 0x04, 0xEB, 0x09, 0x88, 0x00]);
+
+    let backward_compatible = AACAudioSpecificConfigBuilder::create_builder()
+      .channel_count(2)
+      .sampling_frequency_index(6)
+      .sbr(3)
+      .sbr_signaling(SBRSignaling::BACKWARD_COMPATIBLE)
+      .build();
+    assert_eq!(backward_compatible, vec![0x05, 0x80, 0x80, 0x80, 0x05, 0x13, 0x10, 0x56, 0xE5, 0x98]);
+    assert_eq!(AACAudioSpecificConfig::parse(&backward_compatible).unwrap().get_codec_audio_object_type(), 5);
+  }
 }
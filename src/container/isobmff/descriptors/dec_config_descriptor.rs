@@ -1,12 +1,12 @@
 use crate::util;
 use crate::error::CustomError;
 use crate::container::remux;
-use super::{aac_audio_specific_config::AACAudioSpecificConfigBuilder, find_descriptor};
+use super::{aac_audio_specific_config::AACAudioSpecificConfigBuilder, find_descriptor, get_expandable_size};
 use super::DescriptorTags;
 use super::aac_audio_specific_config::AACAudioSpecificConfig;
 
 // 14496-1; 7.2.6.6
-static CLASS: &str = "DecoderConfigDescriptor";
+static AAC_OBJECT_TYPE_INDICATION: u8 = 0x40;
 #[derive(Debug)]
 pub struct DecoderConfigDescriptor {
   pub object_type_indication: u8,
@@ -15,43 +15,40 @@ pub struct DecoderConfigDescriptor {
   buffer_size_db: u32,          // 24 bit
   max_bitrate: u32,
   avg_bitrate: u32,
-  pub audio_sepcific_info: AACAudioSpecificConfig
+  /// Only AAC (objectTypeIndication 0x40) carries a DecoderSpecificInfo
+  pub audio_sepcific_info: Option<AACAudioSpecificConfig>
 }
 
 impl  DecoderConfigDescriptor {
-  pub fn parse(data: &[u8]) -> DecoderConfigDescriptor {
-    let mut start = 2usize;
+  pub fn parse(data: &[u8]) -> Result<DecoderConfigDescriptor, CustomError> {
+    // The length after the tag takes 1 to 4 bytes
+    let mut start = 1usize;
+    get_expandable_size(data, &mut start);
+    start += 1;
     // Parse object_type_indication
-    let object_type_indication = util::get_u8(data, start)
-      .expect(format!("{}.parse.object_type_indication: cannot get u8 from start = {}",CLASS, start).as_ref());
+    let object_type_indication = util::get_u8(data, start)?;
 
     start = start + 1;
-    let temp = util::get_u8(data, start)
-      .expect(format!("{}.parse.temp: cannot get u8 from start = {}",CLASS, start).as_ref());
+    let temp = util::get_u8(data, start)?;
     let stream_type = (temp & 0xFC) >> 2;
     let upstream = (temp & 0x2) != 0;
 
     let mut buffer_size_db: u32 = 0;
     for i in 0..3 {
       start = start +1;
-      let buff = util::get_u8(data, start)
-        .expect(format!("{}.parse.buff: cannot get u8 from start = {}",CLASS, start).as_ref());
+      let buff = util::get_u8(data, start)?;
         buffer_size_db =  buffer_size_db | (u32::from(buff) << (8 * (2 - i)));
     }
 
     start = start + 1;
-    let max_bitrate = util::get_u32(data, start)
-      .expect(format!("{}.parse.temp: cannot get u32 from start = {}",CLASS, start).as_ref());
+    let max_bitrate = util::get_u32(data, start)?;
 
     start = start + 4;
-    let avg_bitrate = util::get_u32(data, start)
-      .expect(format!("{}.parse.avg_bitrate: cannot get u32 from start = {}",CLASS, start).as_ref());
+    let avg_bitrate = util::get_u32(data, start)?;
 
     let audio_sepcific_info = find_descriptor(DescriptorTags::DEC_SPECIFIC_INFO, start + 4, data)
-      .and_then(|dec_info|Some(AACAudioSpecificConfig::parse(dec_info)))
-      .expect("No DecoderConfigDescriptor")
-      .unwrap();
-    DecoderConfigDescriptor {
+      .and_then(|dec_info|AACAudioSpecificConfig::parse(dec_info).ok());
+    Ok(DecoderConfigDescriptor {
       object_type_indication,
       stream_type,
       upstream,
@@ -59,30 +56,43 @@ impl  DecoderConfigDescriptor {
       max_bitrate,
       avg_bitrate,
       audio_sepcific_info
-    }
+    })
   }
 }
 
 pub struct DecoderConfigDescriptorBuilder {
+  object_type_indication: u8,
   aac_audio_specific_config_builder: Option<AACAudioSpecificConfigBuilder>
 }
 
 impl DecoderConfigDescriptorBuilder {
   pub fn create_builder() -> DecoderConfigDescriptorBuilder {
     return DecoderConfigDescriptorBuilder {
+      object_type_indication: AAC_OBJECT_TYPE_INDICATION,
       aac_audio_specific_config_builder: None
     }
   }
 
+  /// Defaults to 0x40 (Audio ISO/IEC 14496-3). 14496-1; Table 5
+  pub fn object_type_indication(mut self, object_type_indication: u8) -> DecoderConfigDescriptorBuilder {
+    self.object_type_indication = object_type_indication;
+    self
+  }
+
   pub fn aac_audio_specific_config(mut self, aac_config_builder: AACAudioSpecificConfigBuilder) -> DecoderConfigDescriptorBuilder {
     self.aac_audio_specific_config_builder = Some(aac_config_builder);
     self
   }
 
   pub fn build(&self) -> Result<Vec<u8>, CustomError> {
-    let aac_audio_specific_config = self.aac_audio_specific_config_builder.as_ref()
-      .ok_or_else(||remux::generate_error(String::from("Missing aac_audio_specific_config for DecoderConfigDescriptorBuilder")))?
-      .build();
+    // Only AAC needs the DecoderSpecificInfo. MPEG-1/2 audio decoders configure themselves from the frame headers.
+    let aac_audio_specific_config = if self.object_type_indication == AAC_OBJECT_TYPE_INDICATION {
+      self.aac_audio_specific_config_builder.as_ref()
+        .ok_or_else(||remux::generate_error(String::from("Missing aac_audio_specific_config for DecoderConfigDescriptorBuilder")))?
+        .build()
+    } else {
+      vec![]
+    };
 
     let length: u8 = 13 + aac_audio_specific_config.len() as u8;
     Ok([
@@ -92,7 +102,7 @@ impl DecoderConfigDescriptorBuilder {
         // length
         0x80, 0x80, 0x80, length,
         // objectTypeIndication (0x40 == Audio ISO/IEC 14496-3 (AAC audio specific config))
-        0x40,
+        self.object_type_indication,
         // bit(6) streamType (5 == audio stream); bit(1) upStream(0); const bit(1) reserved=1
         0x15,
         // bufferSizeDB
@@ -136,4 +146,27 @@ mod tests {
       .unwrap();
     assert_eq!(actual_decoder_config_descriptor, expected_decoder_config_descriptor);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_decoder_config_descriptor_builder_mpeg_audio() {
+    let decoder_config_descriptor = DecoderConfigDescriptorBuilder::create_builder()
+      .object_type_indication(0x6B)
+      .build()
+      .unwrap();
+    assert_eq!(decoder_config_descriptor, [
+      0x04,
+      0x80, 0x80, 0x80, 0x0D,
+      0x6B,
+      0x15,
+      0x00, 0x00, 0x00,
+      0x00, 0x00, 0x00, 0x00,
+      0x00, 0x00, 0x00, 0x00,
+    ]);
+    // No DecoderSpecificInfo follows the avgBitrate
+    let parsed = DecoderConfigDescriptor::parse(&[
+      0x04, 0x0D, 0x6B, 0x15, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ]).unwrap();
+    assert_eq!(parsed.object_type_indication, 0x6B);
+    assert!(parsed.audio_sepcific_info.is_none());
+  }
+}
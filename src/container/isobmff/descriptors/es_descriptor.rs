@@ -1,7 +1,8 @@
 use crate::util;
+use crate::error::{CustomError, construct_error, error_code::{ISOBMFFMinorCode, MajorCode}};
 use crate::container::remux;
 use crate::container::isobmff::descriptors::sl_config_descriptor::SLConfigDescriptorBuilder;
-use super::{DescriptorTags, dec_config_descriptor::DecoderConfigDescriptorBuilder, find_descriptor};
+use super::{DescriptorTags, dec_config_descriptor::DecoderConfigDescriptorBuilder, find_descriptor, get_expandable_size};
 use super::dec_config_descriptor::DecoderConfigDescriptor;
 
 static CLASS: &str = "ESDescriptor";
@@ -24,16 +25,17 @@ pub struct ESDescriptor {
 }
 
 impl ESDescriptor {
-  pub fn parse(data: &[u8]) -> ESDescriptor{
-    let mut start = 2usize;
+  pub fn parse(data: &[u8]) -> Result<ESDescriptor, CustomError> {
+    // The length after the tag takes 1 to 4 bytes
+    let mut start = 1usize;
+    get_expandable_size(data, &mut start);
+    start += 1;
     // Parse es id
-    let id = util::get_u16(data, start)
-      .expect(format!("{}.parse.id: cannot get u16 from start = {}",CLASS, start).as_ref());
+    let id = util::get_u16(data, start)?;
 
     start = start + 2;
     // Parse streamDependenceFlag, URL_Flag, OCRstreamFlag, and streamPriority
-    let flags = util::get_u8(data, start)
-      .expect(format!("{}.parse.flags: cannot get u16 from start = {}",CLASS, start).as_ref());
+    let flags = util::get_u8(data, start)?;
 
     let stream_dependence_flag = (flags & 0x80) != 0;
     let url_flag = (flags & 0x40) != 0;
@@ -51,11 +53,16 @@ impl ESDescriptor {
     if ocr_stream_flag {
       println!("OCR STREAM FLAG")
     }
-     let dec_config_descr = find_descriptor(DescriptorTags::DECODER_CONFIG_DESC, start + 1, data)
-      .and_then(|dec_desc|Some(DecoderConfigDescriptor::parse(dec_desc)))
-      .expect("No DecoderConfigDescriptor");
-    
-    ESDescriptor{
+    let dec_config_descr = find_descriptor(DescriptorTags::DECODER_CONFIG_DESC, start + 1, data)
+      .ok_or_else(||construct_error(
+        MajorCode::ISOBMFF,
+        Box::new(ISOBMFFMinorCode::PARSE_BOX_ERROR),
+        format!("{}.parse: No DecoderConfigDescriptor", CLASS),
+        file!(),
+        line!()))
+      .and_then(DecoderConfigDescriptor::parse)?;
+
+    Ok(ESDescriptor{
       id,
       stream_dependence_flag,
       url_flag,
@@ -65,7 +72,7 @@ impl ESDescriptor {
       depends_on_es_id: None,
       url_length: None,
       url_string: None,
-    }
+    })
   }
 }
 
@@ -37,21 +37,24 @@ pub fn find_descriptor<'a>(search_tag: DescriptorTags, offset: usize, current_bo
     let length = get_expandable_size(&current_box_data, &mut length_index) as usize;
     
     if tag == search_tag.value() {
-      return Some(current_box_data[tag_index..(length_index + 1 + length)].as_ref())
+      // None when the descriptor runs past the end of the data
+      return current_box_data.get(tag_index..(length_index + 1 + length))
     }
-    tag_index += length;
+    // Skip the whole descriptor (tag and length included)
+    tag_index = length_index + 1 + length;
   }
   None
 }
 
-// 14496-1; 8.3.3
+// 14496-1; 8.3.3. The bytes past the end of the data read as 0.
 pub fn get_expandable_size(data: &[u8], offset: &mut usize) -> u32 {
-  let mut next_byte = data[*offset] & 0x80;
-  let mut size_of_instance = data[*offset] as u32 & 0x7F as u32;
+  let get_byte = |index: usize| data.get(index).copied().unwrap_or(0);
+  let mut next_byte = get_byte(*offset) & 0x80;
+  let mut size_of_instance = get_byte(*offset) as u32 & 0x7F as u32;
   while next_byte != 0 {
     *offset += 1;
-    next_byte = data[*offset] & 0x80;
-    let size_byte = data[*offset] as u32 & 0x7F as u32;
+    next_byte = get_byte(*offset) & 0x80;
+    let size_byte = get_byte(*offset) as u32 & 0x7F as u32;
     size_of_instance = size_of_instance << 7 | size_byte;
   }
 
@@ -1,7 +1,8 @@
 use crate::{error::CustomError, media::TrackType};
 use crate::container::isobmff::boxes::{stts::STTSReader, stsd::STSD, sidx::SIDX, trun::TRUN, mvhd::MVHD};
-use crate::iso_box::{find_box, get_media_start};
-use self::{sample_entry::{avc_sample_entry::AVCSampleEntry, mp4a_sample_entry::MP4ASampleEntry}};
+use crate::iso_box::{find_box, get_box, get_media_start};
+use self::{sample_entry::{audio_sample_entry::AudioSampleEntry, avc_sample_entry::AVCSampleEntry, hevc_sample_entry::HEVCSampleEntry, mp4a_sample_entry::MP4ASampleEntry}};
+use self::configuration_records::dec3::EC3SpecificBox;
 
 pub mod boxes;
 pub mod sample_entry;
@@ -49,10 +50,17 @@ impl PartialEq<u32> for HandlerType {
 // NOTE (benjamintoofer@gmail.com): May want to use the handler rather than the TrackType
 pub fn get_codec(track_type: &TrackType, mp4: &[u8]) -> Result<String, CustomError> {
   if *track_type == TrackType::VIDEO {
+    let stsd = STSD::parse(mp4)?;
+    for codec_type in ["hvc1", "hev1"] {
+      if let Ok(hevc_data) = stsd.read_sample_entry(codec_type) {
+        let hevc_sample_entry = HEVCSampleEntry::parse(hevc_data)?;
+        return Ok(hevc_sample_entry.config.get_codec_string(codec_type));
+      }
+    }
     let codec_type = "avc1";
     let avc_config = STSD::parse(&mp4)
       .and_then(|stsd| stsd.read_sample_entry(codec_type).map(|x|x.to_vec()))
-      .map(|avc_data|AVCSampleEntry::parse(&avc_data))
+      .and_then(|avc_data|AVCSampleEntry::parse(&avc_data))
       .map(|avc_sample|avc_sample.config)?;
     let codec = format!("{}.{:02X}{:02X}{:02X}",
       codec_type, 
@@ -61,29 +69,56 @@ pub fn get_codec(track_type: &TrackType, mp4: &[u8]) -> Result<String, CustomErr
       avc_config.avc_level_indication);
     return Ok(codec);
   } else if *track_type == TrackType::AUDIO {
+    let stsd = STSD::parse(mp4)?;
+    // The codecs string of AC-3 and E-AC-3 is only the sample entry type. ETSI TS 102 366; F.2
+    for codec_type in ["ec-3", "ac-3"] {
+      if stsd.read_sample_entry(codec_type).is_ok() {
+        return Ok(codec_type.to_string());
+      }
+    }
     let codec_type = "mp4a";
     let aac_data = STSD::parse(&mp4)
       .and_then(|stsd| stsd.read_sample_entry("mp4a").map(|x|x.to_vec()))
-      .map(|mp4a_data|MP4ASampleEntry::parse(&mp4a_data))
+      .and_then(|mp4a_data|MP4ASampleEntry::parse(&mp4a_data))
       .map(|mp4a_sample|mp4a_sample.es_descriptor)?;
 
-    let codec = format!("{}.{:X}.{}",
-      codec_type, 
-      aac_data.dec_config_descr.object_type_indication, 
-      aac_data.dec_config_descr.audio_sepcific_info.audio_object_type);
+    // MPEG-1/2 audio (mp4a.6B, mp4a.69) has no audio object type
+    let codec = match aac_data.dec_config_descr.audio_sepcific_info {
+      Some(audio_specific_info) => format!("{}.{:X}.{}",
+        codec_type,
+        aac_data.dec_config_descr.object_type_indication,
+        audio_specific_info.get_codec_audio_object_type()),
+      None => format!("{}.{:X}", codec_type, aac_data.dec_config_descr.object_type_indication),
+    };
     return Ok(codec);
   } else {
     Ok("".to_string())
   }
 }
 
-pub fn get_channel_count(mp4: &[u8]) -> Result<u8, CustomError> {
-  let aac_data = STSD::parse(&mp4)
-    .and_then(|stsd| stsd.read_sample_entry("mp4a").map(|x|x.to_vec()))
-    .map(|mp4a_data|MP4ASampleEntry::parse(&mp4a_data))
-    .map(|mp4a_sample|mp4a_sample.es_descriptor)?;
-  
-  Ok(aac_data.dec_config_descr.audio_sepcific_info.channel_configuration)
+/// Channel count as the HLS CHANNELS attribute wants it. For Dolby Atmos (E-AC-3 with JOC) that is the number of
+/// objects followed by "/JOC".
+pub fn get_channel_count(mp4: &[u8]) -> Result<String, CustomError> {
+  let stsd = STSD::parse(mp4)?;
+  if let Ok(ec3_data) = stsd.read_sample_entry("ec-3") {
+    let (_, offset) = AudioSampleEntry::parse(ec3_data)?;
+    let ec3_specific_box = get_box("dec3", offset, ec3_data).and_then(EC3SpecificBox::parse)?;
+    return Ok(ec3_specific_box.get_hls_channels());
+  }
+  if let Ok(ac3_data) = stsd.read_sample_entry("ac-3") {
+    let (audio_sample_entry, _) = AudioSampleEntry::parse(ac3_data)?;
+    return Ok(audio_sample_entry.get_channel_count().to_string());
+  }
+
+  let mp4a_data = stsd.read_sample_entry("mp4a")?;
+  let aac_data = MP4ASampleEntry::parse(mp4a_data)?.es_descriptor;
+  match aac_data.dec_config_descr.audio_sepcific_info {
+    Some(audio_specific_info) => Ok(audio_specific_info.get_channel_count().to_string()),
+    None => {
+      let (audio_sample_entry, _) = AudioSampleEntry::parse(mp4a_data)?;
+      Ok(audio_sample_entry.get_channel_count().to_string())
+    }
+  }
 }
 
 pub fn get_frame_rate(mp4: &[u8]) -> Result<f32, CustomError> {
@@ -48,6 +48,7 @@ pub enum NALType {
   SPS, // Sequence parameter set 
   PPS, // Picture parameter set
   AUD, // Access unit delimiter 
+  Filler_Data, // Padding up to the bitrate
 }
 
 impl NALType {
@@ -59,6 +60,7 @@ impl NALType {
       NALType::SPS => {7}
       NALType::PPS => {8}
       NALType::AUD => {9}
+      NALType::Filler_Data => {12}
     }
   }
 
@@ -70,6 +72,7 @@ impl NALType {
       7 => Ok(NALType::SPS),
       8 => Ok(NALType::PPS),
       9 => Ok(NALType::AUD),
+      12 => Ok(NALType::Filler_Data),
       _ => Err(
         construct_error(
           crate::error::error_code::MajorCode::NAL,
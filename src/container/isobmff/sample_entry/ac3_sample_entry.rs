@@ -0,0 +1,119 @@
+use super::{audio_sample_entry::AudioSampleEntryBuilder, sample_entry::SampleEntryBuilder};
+use crate::container::isobmff::BoxBuilder;
+use crate::container::isobmff::configuration_records::dac3::AC3SpecificBoxBuilder;
+use crate::container::remux;
+use crate::util;
+use crate::error::CustomError;
+
+/// AC3SampleEntry: ETSI TS 102 366; F.3
+pub struct AC3SampleEntryBuilder {
+  sample_entry_builder: Option<SampleEntryBuilder>,
+  audio_sample_entry_builder: Option<AudioSampleEntryBuilder>,
+  dac3_builder: Option<AC3SpecificBoxBuilder>,
+}
+
+impl AC3SampleEntryBuilder {
+  pub fn create_builder() -> AC3SampleEntryBuilder {
+    AC3SampleEntryBuilder {
+      sample_entry_builder: None,
+      audio_sample_entry_builder: None,
+      dac3_builder: None,
+    }
+  }
+
+  pub fn sample_entry(mut self, sample_entry_builder: SampleEntryBuilder) -> AC3SampleEntryBuilder {
+    self.sample_entry_builder = Some(sample_entry_builder);
+    self
+  }
+
+  pub fn audio_sample_entry(mut self, audio_sample_entry_builder: AudioSampleEntryBuilder) -> AC3SampleEntryBuilder {
+    self.audio_sample_entry_builder = Some(audio_sample_entry_builder);
+    self
+  }
+
+  pub fn dac3(mut self, dac3_builder: AC3SpecificBoxBuilder) -> AC3SampleEntryBuilder {
+    self.dac3_builder = Some(dac3_builder);
+    self
+  }
+}
+
+impl BoxBuilder for AC3SampleEntryBuilder {
+  fn build(&self) -> Result<Vec<u8>, CustomError> {
+    let sample_entry = self.sample_entry_builder.as_ref()
+      .ok_or_else(||remux::generate_error(String::from("Missing sample_entry_builder for AC3SampleEntryBuilder")))?
+      .build();
+    let audio_sample_entry = self.audio_sample_entry_builder.as_ref()
+      .ok_or_else(||remux::generate_error(String::from("Missing audio_sample_entry_builder for AC3SampleEntryBuilder")))?
+      .build();
+    let dac3 = self.dac3_builder.as_ref()
+      .ok_or_else(||remux::generate_error(String::from("Missing dac3_builder for AC3SampleEntryBuilder")))?
+      .build();
+    let size =
+      8 + // header
+      sample_entry.len() +
+      audio_sample_entry.len() +
+      dac3.len();
+    let size_array = util::transform_usize_to_u8_array(size);
+
+    Ok([
+      vec![
+        // size
+        size_array[3], size_array[2], size_array[1], size_array[0],
+        // ac-3
+        0x61, 0x63, 0x2D, 0x33,
+      ],
+      sample_entry,
+      audio_sample_entry,
+      dac3,
+    ].concat())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_build_ac3_sample_entry() {
+    let expected_ac3_sample_entry: [u8; 47] = [
+      // ac-3
+      0x00, 0x00, 0x00, 0x2F,
+      0x61, 0x63, 0x2D, 0x33,
+      // sample entry
+      0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+      0x00, 0x01,
+      // audio sample entry
+      0x00, 0x00, 0x00, 0x00,
+      0x00, 0x00, 0x00, 0x00,
+      0x00, 0x02,
+      0x00, 0x10,
+      0x00, 0x00,
+      0x00, 0x00,
+      0x00, 0x00, 0xBB, 0x80,
+      // dac3
+      0x00, 0x00, 0x00, 0x0B,
+      0x64, 0x61, 0x63, 0x33,
+      0x10, 0x11, 0xC0,
+    ];
+    let ac3_sample_entry = AC3SampleEntryBuilder::create_builder()
+      .sample_entry(
+        SampleEntryBuilder::create_builder()
+      )
+      .audio_sample_entry(
+        AudioSampleEntryBuilder::create_builder()
+          .channel_count(2)
+          .sample_rate(48000)
+      )
+      .dac3(
+        AC3SpecificBoxBuilder::create_builder()
+          .fscod(0)
+          .bsid(8)
+          .acmod(2)
+          .bit_rate_code(14)
+      )
+      .build()
+      .unwrap();
+
+    assert_eq!(ac3_sample_entry, expected_ac3_sample_entry);
+  }
+}
@@ -1,6 +1,5 @@
-use crate::util;
+use crate::{error::CustomError, util};
 
-static CLASS: &str = "AudioSampleEntry";
 #[derive(Debug)]
 pub struct AudioSampleEntry {
   channel_count: u16,
@@ -9,29 +8,30 @@ pub struct AudioSampleEntry {
 }
 
 impl AudioSampleEntry {
-  pub fn parse(data: &[u8]) -> (AudioSampleEntry, usize) {
+  pub fn parse(data: &[u8]) -> Result<(AudioSampleEntry, usize), CustomError> {
     let offset = 24usize;
     let mut start = offset;
-    let channel_count = util::get_u16(data, start)
-      .expect(format!("{}.parse.channel_count: cannot get u16 from start = {}",CLASS, start).as_ref());
+    let channel_count = util::get_u16(data, start)?;
 
     start = start + 2;
     // Parse sample size
-    let sample_size = util::get_u16(data, start)
-      .expect(format!("{}.parse.sample_size: cannot get u16 from start = {}",CLASS, start).as_ref());
+    let sample_size = util::get_u16(data, start)?;
 
     start = start + 6;
     // Parse sample size
     let sample_rate = util::get_u32(data, start)
-      .and_then(|val| Ok(val >> 16u32))
-      .expect(format!("{}.parse.sample_rate: cannot get u32 from start = {}",CLASS, start).as_ref());
+      .and_then(|val| Ok(val >> 16u32))?;
 
 
-    (AudioSampleEntry {
+    Ok((AudioSampleEntry {
       channel_count,
       sample_size,
       sample_rate
-    }, start + 4)
+    }, start + 4))
+  }
+
+  pub fn get_channel_count(&self) -> u16 {
+    self.channel_count
   }
 }
 
@@ -1,7 +1,7 @@
 use crate::{container::isobmff::BoxBuilder, util};
 use super::sample_entry::{SampleEntry, SampleEntryBuilder};
 use super::visual_sample_entry:: {VisualSampleEntry, VisualSampleEntryBuilder};
-use crate::{container::isobmff::boxes::iso_box::find_box, error::CustomError};
+use crate::{container::isobmff::boxes::iso_box::get_box, error::CustomError};
 use crate::container::isobmff::configuration_records::avcC::{AVCDecoderConfigurationRecord, AVCDecoderConfigurationRecordBuilder};
 use crate::container::remux;
 #[derive(Debug)]
@@ -12,21 +12,18 @@ pub struct AVCSampleEntry {
 }
 
 impl AVCSampleEntry {
-  pub fn parse(data: &[u8]) -> AVCSampleEntry {
-    let sample_entry = SampleEntry::parse(data);
-    let (visual_sample_entry, offset) = VisualSampleEntry::parse(data);
+  pub fn parse(data: &[u8]) -> Result<AVCSampleEntry, CustomError> {
+    let sample_entry = SampleEntry::parse(data)?;
+    let (visual_sample_entry, offset) = VisualSampleEntry::parse(data)?;
     #[allow(non_snake_case)]
-    let avcC: AVCDecoderConfigurationRecord = find_box("avcC", offset, data)
-      .map(|avcc_data| AVCDecoderConfigurationRecord::parse(avcc_data))
-      // TODO (benjamintoofer@gmail.com): Add proper error handling around this.
-      .unwrap();
-      // .expect("No avcC box found in avc1");
+    let avcC = get_box("avcC", offset, data)
+      .and_then(AVCDecoderConfigurationRecord::parse)?;
 
-    AVCSampleEntry {
+    Ok(AVCSampleEntry {
       sample_entry,
       visual_sample_entry,
       config: avcC
-    }
+    })
   }
 }
 
@@ -34,6 +31,7 @@ pub struct AVCSampleEntryBuilder {
   pub sample_entry_builder: Option<SampleEntryBuilder>,
   visual_sample_entry_builder: Option<VisualSampleEntryBuilder>,
   avc_c_builder: Option<AVCDecoderConfigurationRecordBuilder>,
+  in_band_parameter_sets: bool,
 }
 
 impl AVCSampleEntryBuilder {
@@ -42,6 +40,7 @@ impl AVCSampleEntryBuilder {
       sample_entry_builder: None,
       visual_sample_entry_builder: None,
       avc_c_builder: None,
+      in_band_parameter_sets: false,
     }
   }
 
@@ -59,6 +58,12 @@ impl AVCSampleEntryBuilder {
     self.avc_c_builder = Some(avc_c_builder);
     self
   }
+
+  /// Whether the samples can carry parameter sets too (avc3), instead of them all being in the avcC (avc1)
+  pub fn in_band_parameter_sets(mut self, in_band_parameter_sets: bool) -> AVCSampleEntryBuilder {
+    self.in_band_parameter_sets = in_band_parameter_sets;
+    self
+  }
 }
 
 impl BoxBuilder for AVCSampleEntryBuilder {
@@ -78,14 +83,19 @@ impl BoxBuilder for AVCSampleEntryBuilder {
       visual_sample_entry.len() +
       avc_c.len();
     let size_array = util::transform_usize_to_u8_array(size);
+    // avc1 when the parameter sets are only in the avcC, avc3 when they can be in the samples too
+    let sample_entry_type = if self.in_band_parameter_sets {
+      [0x61, 0x76, 0x63, 0x33]
+    } else {
+      [0x61, 0x76, 0x63, 0x31]
+    };
 
     let avc1: Vec<u8> = [
       vec![
         // size
         size_array[3], size_array[2], size_array[1], size_array[0],
-        // avc1
-        0x61, 0x76, 0x63, 0x31,
       ],
+      sample_entry_type.to_vec(),
       sample_entry,
       visual_sample_entry,
       avc_c,
@@ -0,0 +1,123 @@
+use super::{audio_sample_entry::AudioSampleEntryBuilder, sample_entry::SampleEntryBuilder};
+use crate::container::isobmff::BoxBuilder;
+use crate::container::isobmff::configuration_records::dec3::EC3SpecificBoxBuilder;
+use crate::container::remux;
+use crate::util;
+use crate::error::CustomError;
+
+/// EC3SampleEntry: ETSI TS 102 366; F.5
+pub struct EC3SampleEntryBuilder {
+  sample_entry_builder: Option<SampleEntryBuilder>,
+  audio_sample_entry_builder: Option<AudioSampleEntryBuilder>,
+  dec3_builder: Option<EC3SpecificBoxBuilder>,
+}
+
+impl EC3SampleEntryBuilder {
+  pub fn create_builder() -> EC3SampleEntryBuilder {
+    EC3SampleEntryBuilder {
+      sample_entry_builder: None,
+      audio_sample_entry_builder: None,
+      dec3_builder: None,
+    }
+  }
+
+  pub fn sample_entry(mut self, sample_entry_builder: SampleEntryBuilder) -> EC3SampleEntryBuilder {
+    self.sample_entry_builder = Some(sample_entry_builder);
+    self
+  }
+
+  pub fn audio_sample_entry(mut self, audio_sample_entry_builder: AudioSampleEntryBuilder) -> EC3SampleEntryBuilder {
+    self.audio_sample_entry_builder = Some(audio_sample_entry_builder);
+    self
+  }
+
+  pub fn dec3(mut self, dec3_builder: EC3SpecificBoxBuilder) -> EC3SampleEntryBuilder {
+    self.dec3_builder = Some(dec3_builder);
+    self
+  }
+}
+
+impl BoxBuilder for EC3SampleEntryBuilder {
+  fn build(&self) -> Result<Vec<u8>, CustomError> {
+    let sample_entry = self.sample_entry_builder.as_ref()
+      .ok_or_else(||remux::generate_error(String::from("Missing sample_entry_builder for EC3SampleEntryBuilder")))?
+      .build();
+    let audio_sample_entry = self.audio_sample_entry_builder.as_ref()
+      .ok_or_else(||remux::generate_error(String::from("Missing audio_sample_entry_builder for EC3SampleEntryBuilder")))?
+      .build();
+    let dec3 = self.dec3_builder.as_ref()
+      .ok_or_else(||remux::generate_error(String::from("Missing dec3_builder for EC3SampleEntryBuilder")))?
+      .build();
+    let size =
+      8 + // header
+      sample_entry.len() +
+      audio_sample_entry.len() +
+      dec3.len();
+    let size_array = util::transform_usize_to_u8_array(size);
+
+    Ok([
+      vec![
+        // size
+        size_array[3], size_array[2], size_array[1], size_array[0],
+        // ec-3
+        0x65, 0x63, 0x2D, 0x33,
+      ],
+      sample_entry,
+      audio_sample_entry,
+      dec3,
+    ].concat())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::container::isobmff::configuration_records::dec3::EC3IndependentSubstream;
+
+  #[test]
+  fn test_build_ec3_sample_entry() {
+    let expected_ec3_sample_entry: [u8; 49] = [
+      // ec-3
+      0x00, 0x00, 0x00, 0x31,
+      0x65, 0x63, 0x2D, 0x33,
+      // sample entry
+      0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+      0x00, 0x01,
+      // audio sample entry
+      0x00, 0x00, 0x00, 0x00,
+      0x00, 0x00, 0x00, 0x00,
+      0x00, 0x02,
+      0x00, 0x10,
+      0x00, 0x00,
+      0x00, 0x00,
+      0x00, 0x00, 0xBB, 0x80,
+      // dec3
+      0x00, 0x00, 0x00, 0x0D,
+      0x64, 0x65, 0x63, 0x33,
+      0x06, 0x00,
+      0x20, 0x04, 0x00,
+    ];
+    let ec3_sample_entry = EC3SampleEntryBuilder::create_builder()
+      .sample_entry(
+        SampleEntryBuilder::create_builder()
+      )
+      .audio_sample_entry(
+        AudioSampleEntryBuilder::create_builder()
+          .channel_count(2)
+          .sample_rate(48000)
+      )
+      .dec3(
+        EC3SpecificBoxBuilder::create_builder()
+          .data_rate(192)
+          .independent_substream(EC3IndependentSubstream {
+            bsid: 16,
+            acmod: 2,
+            ..Default::default()
+          })
+      )
+      .build()
+      .unwrap();
+
+    assert_eq!(ec3_sample_entry, expected_ec3_sample_entry);
+  }
+}
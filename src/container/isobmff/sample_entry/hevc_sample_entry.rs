@@ -0,0 +1,140 @@
+use crate::{container::isobmff::BoxBuilder, util};
+use super::sample_entry::{SampleEntry, SampleEntryBuilder};
+use super::visual_sample_entry::{VisualSampleEntry, VisualSampleEntryBuilder};
+use crate::{container::isobmff::boxes::iso_box::get_box, error::CustomError};
+use crate::container::isobmff::configuration_records::hvcC::{HEVCDecoderConfigurationRecord, HEVCDecoderConfigurationRecordBuilder};
+use crate::container::remux;
+
+/// HEVCSampleEntry ('hvc1' or 'hev1'): 14496-15; 8.4.1
+#[derive(Debug)]
+pub struct HEVCSampleEntry {
+  pub sample_entry: SampleEntry,
+  pub visual_sample_entry: VisualSampleEntry,
+  pub config: HEVCDecoderConfigurationRecord
+}
+
+impl HEVCSampleEntry {
+  pub fn parse(data: &[u8]) -> Result<HEVCSampleEntry, CustomError> {
+    let sample_entry = SampleEntry::parse(data)?;
+    let (visual_sample_entry, offset) = VisualSampleEntry::parse(data)?;
+    let config = get_box("hvcC", offset, data)
+      .and_then(HEVCDecoderConfigurationRecord::parse)?;
+
+    Ok(HEVCSampleEntry {
+      sample_entry,
+      visual_sample_entry,
+      config
+    })
+  }
+}
+
+pub struct HEVCSampleEntryBuilder {
+  sample_entry_builder: Option<SampleEntryBuilder>,
+  visual_sample_entry_builder: Option<VisualSampleEntryBuilder>,
+  hvc_c_builder: Option<HEVCDecoderConfigurationRecordBuilder>,
+}
+
+impl HEVCSampleEntryBuilder {
+  pub fn create_builder() -> HEVCSampleEntryBuilder {
+    HEVCSampleEntryBuilder {
+      sample_entry_builder: None,
+      visual_sample_entry_builder: None,
+      hvc_c_builder: None,
+    }
+  }
+
+  pub fn sample_entry(mut self, sample_entry_builder: SampleEntryBuilder) -> HEVCSampleEntryBuilder {
+    self.sample_entry_builder = Some(sample_entry_builder);
+    self
+  }
+
+  pub fn visual_sample_entry(mut self, visual_sample_entry_builder: VisualSampleEntryBuilder) -> HEVCSampleEntryBuilder {
+    self.visual_sample_entry_builder = Some(visual_sample_entry_builder);
+    self
+  }
+
+  pub fn hvc_c(mut self, hvc_c_builder: HEVCDecoderConfigurationRecordBuilder) -> HEVCSampleEntryBuilder {
+    self.hvc_c_builder = Some(hvc_c_builder);
+    self
+  }
+}
+
+impl BoxBuilder for HEVCSampleEntryBuilder {
+  fn build(&self) -> Result<Vec<u8>, CustomError> {
+    let sample_entry = self.sample_entry_builder.as_ref()
+      .ok_or_else(||remux::generate_error(String::from("Missing sample_entry_builder for HEVCSampleEntryBuilder")))?
+      .build();
+    let visual_sample_entry = self.visual_sample_entry_builder.as_ref()
+      .ok_or_else(||remux::generate_error(String::from("Missing visual_sample_entry_builder for HEVCSampleEntryBuilder")))?
+      .build()?;
+    let hvc_c_builder = self.hvc_c_builder.as_ref()
+      .ok_or_else(||remux::generate_error(String::from("Missing hvcC_builder for HEVCSampleEntryBuilder")))?;
+    let hvc_c = hvc_c_builder.build()?;
+    let size =
+      8 + // header
+      sample_entry.len() +
+      visual_sample_entry.len() +
+      hvc_c.len();
+    let size_array = util::transform_usize_to_u8_array(size);
+    // hvc1 when the parameter sets are only in the hvcC, hev1 when they can be in the samples too
+    let sample_entry_type = if hvc_c_builder.is_array_complete() {
+      [0x68, 0x76, 0x63, 0x31]
+    } else {
+      [0x68, 0x65, 0x76, 0x31]
+    };
+
+    Ok([
+      vec![
+        // size
+        size_array[3], size_array[2], size_array[1], size_array[0],
+      ],
+      sample_entry_type.to_vec(),
+      sample_entry,
+      visual_sample_entry,
+      hvc_c,
+    ].concat())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::codec::h265::sequence_parameter_set::tests::get_sps;
+
+  #[test]
+  fn test_build_and_parse_hevc_sample_entry() {
+    let vps = [0x40, 0x01, 0x0C];
+    let pps = [0x44, 0x01, 0xC1, 0x72, 0xB4, 0x62, 0x40];
+    let sps = get_sps();
+    let build_sample_entry = |array_completeness: bool| {
+      HEVCSampleEntryBuilder::create_builder()
+        .sample_entry(
+          SampleEntryBuilder::create_builder()
+        )
+        .visual_sample_entry(
+          VisualSampleEntryBuilder::create_builder()
+            .dimensions(1920, 1080)
+        )
+        .hvc_c(
+          HEVCDecoderConfigurationRecordBuilder::create_builder()
+            .vps(&vps)
+            .sps(&sps)
+            .pps(&pps)
+            .array_completeness(array_completeness)
+        )
+        .build()
+        .unwrap()
+    };
+
+    let hvc1 = build_sample_entry(true);
+    assert_eq!(hvc1[4..8], [0x68, 0x76, 0x63, 0x31]);
+    // width and height of the visual sample entry
+    assert_eq!(hvc1[32..36], [0x07, 0x80, 0x04, 0x38]);
+    let hev1 = build_sample_entry(false);
+    assert_eq!(hev1[4..8], [0x68, 0x65, 0x76, 0x31]);
+    assert_eq!(hev1.len(), hvc1.len());
+
+    let hevc_sample_entry = HEVCSampleEntry::parse(&hvc1).unwrap();
+    assert_eq!(hevc_sample_entry.config.general_level_idc, 120);
+  }
+}
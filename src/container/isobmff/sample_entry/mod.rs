@@ -2,4 +2,6 @@ pub mod sample_entry;
 pub mod audio_sample_entry;
 pub mod visual_sample_entry;
 pub mod avc_sample_entry;
-pub mod mp4a_sample_entry;
\ No newline at end of file
+pub mod hevc_sample_entry;
+pub mod mp4a_sample_entry;pub mod ac3_sample_entry;
+pub mod ec3_sample_entry;
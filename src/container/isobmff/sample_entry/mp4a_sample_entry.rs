@@ -1,12 +1,12 @@
 use super::{audio_sample_entry::AudioSampleEntryBuilder, sample_entry::{SampleEntry, SampleEntryBuilder}};
 use super::audio_sample_entry::AudioSampleEntry;
 use crate::container::isobmff::{BoxBuilder, descriptors::es_descriptor::{ESDescriptor, ESDescriptorBuidler}};
-use crate::container::isobmff::boxes::iso_box::find_box;
+use crate::container::isobmff::boxes::iso_box::get_box;
 use crate::container::isobmff::descriptors::find_descriptor;
 use crate::container::isobmff::descriptors::DescriptorTags;
 use crate::container::remux;
 use crate::util;
-use crate::error::CustomError;
+use crate::error::{CustomError, construct_error, error_code::{ISOBMFFMinorCode, MajorCode}};
 
 #[derive(Debug)]
 pub struct MP4ASampleEntry {
@@ -16,21 +16,24 @@ pub struct MP4ASampleEntry {
 }
 
 impl MP4ASampleEntry {
-  pub fn parse(data: &[u8]) -> MP4ASampleEntry {
-    let sample_entry = SampleEntry::parse(data);
-    let (audio_sample_entry, offset) = AudioSampleEntry::parse(data);
-    let es_descriptor = find_box("esds", offset, data)
-      .and_then(|esds_data| find_descriptor(DescriptorTags::ES_DESC, 12, esds_data)) 
-      .map(|es_data| ESDescriptor::parse(es_data))
-      .expect("Cannot parse ESDescriptor");
+  pub fn parse(data: &[u8]) -> Result<MP4ASampleEntry, CustomError> {
+    let sample_entry = SampleEntry::parse(data)?;
+    let (audio_sample_entry, offset) = AudioSampleEntry::parse(data)?;
+    let es_descriptor = get_box("esds", offset, data)?;
+    let es_descriptor = find_descriptor(DescriptorTags::ES_DESC, 12, es_descriptor)
+      .ok_or_else(||construct_error(
+        MajorCode::ISOBMFF,
+        Box::new(ISOBMFFMinorCode::PARSE_BOX_ERROR),
+        "No ESDescriptor in the esds box".to_string(),
+        file!(),
+        line!()))
+      .and_then(ESDescriptor::parse)?;
 
-    // TODO (benjamintoofer@gmail.com): Add proper error handling around this.
-
-    MP4ASampleEntry {
+    Ok(MP4ASampleEntry {
       sample_entry,
       audio_sample_entry,
       es_descriptor
-    }
+    })
   }
 }
 
@@ -1,8 +1,6 @@
 use std::u16;
 
-use crate::util;
-
-static CLASS: &str = "SampleEntry";
+use crate::{error::CustomError, util};
 
 #[derive(Debug)]
 pub struct SampleEntry {
@@ -10,12 +8,11 @@ pub struct SampleEntry {
 }
 
 impl SampleEntry {
-  pub fn parse(data: &[u8]) -> SampleEntry {
+  pub fn parse(data: &[u8]) -> Result<SampleEntry, CustomError> {
     let start = 14usize;
-    let data_reference_index = util::get_u16(data, start)
-      .expect(format!("{}.parse_sample_entry_data.data_reference_index: cannot get u32 from start = {}",CLASS, start).as_ref());
+    let data_reference_index = util::get_u16(data, start)?;
     
-    SampleEntry{ data_reference_index }
+    Ok(SampleEntry{ data_reference_index })
   }
 }
 
@@ -1,6 +1,4 @@
 
-static CLASS: &str = "VisualSampleEntry";
-
 use crate::{error::CustomError, util};
 use crate::codec::h264::sequence_parameter_set::SequenceParameterSet;
 
@@ -19,54 +17,40 @@ pub struct VisualSampleEntry {
 }
 
 impl VisualSampleEntry {
-  pub fn parse(data: &[u8]) -> (VisualSampleEntry, usize) {
+  pub fn parse(data: &[u8]) -> Result<(VisualSampleEntry, usize), CustomError> {
     let offset = 32usize;
     let mut start = offset;
     // Parse width
-    let width = util::get_u16(data, start)
-      .expect(format!("{}.parse.width: cannot get u16 from start = {}",CLASS, start).as_ref());
+    let width = util::get_u16(data, start)?;
     
     start = start + 2;
     // Parse height
-    let height = util::get_u16(data, start)
-      .expect(format!("{}.parse.height: cannot get u16 from start = {}",CLASS, start).as_ref());
+    let height = util::get_u16(data, start)?;
 
     start = start + 2;
     // Parse horiz resolution
-    let horiz_resolution = util::get_u32(data, start)
-      .expect(format!("{}.parse.horiz_resolution: cannot get u32 from start = {}",CLASS, start).as_ref());
+    let horiz_resolution = util::get_u32(data, start)?;
 
     start = start + 4;
     // Parse vert resolution
-    let vert_resolution = util::get_u32(data, start)
-      .expect(format!("{}.parse.vert_resolution: cannot get u32 from start = {}",CLASS, start).as_ref());
+    let vert_resolution = util::get_u32(data, start)?;
 
     start = start + 8;
     // Parse frame count
-    let frame_count = util::get_u16(data, start)
-      .expect(format!("{}.parse.frame_count: cannot get u16 from start = {}",CLASS, start).as_ref());
+    let frame_count = util::get_u16(data, start)?;
 
     start = start + 2;
     // Parse compressor name size
-    let compressor_name_size = util::get_u8(data, start)
-      .expect(format!("{}.parse.compressor_name_size: cannot get u8 from start = {}",CLASS, start).as_ref());
+    let compressor_name_size = util::get_u8(data, start)?;
 
     start = start + 1;
-    let mut compressor_name = String::from("");
-    for i in 0..compressor_name_size {
-      let index = start + i as usize;
-      if !data[index].is_ascii() {
-        // Error("")
-        todo!()
-      }
-      let character = data[index] as char;
-      compressor_name.push(character);
-    }
+    // Only informative, so a name that isn't ASCII (or runs past the 31 bytes it has) is kept as far as it reads
+    let compressor_name_end = start + (compressor_name_size as usize).min(31);
+    let compressor_name = String::from_utf8_lossy(data.get(start..compressor_name_end).unwrap_or_default()).to_string();
     // Compressorname is formatted in a fixed 32-byte field. We already offset it by 1 for the name length
     start = start + 31;
     // Parse depth
-    let depth = util::get_u16(data, start)
-      .expect(format!("{}.parse.depth: cannot get u16 from start = {}",CLASS, start).as_ref());
+    let depth = util::get_u16(data, start)?;
 
     start = start + 2;
     // Skip predefined value
@@ -76,7 +60,7 @@ impl VisualSampleEntry {
     // Parse PixelAspectRatioBox
     // todo!();
     
-      (VisualSampleEntry {
+    Ok((VisualSampleEntry {
       width,
       height,
       horiz_resolution,
@@ -86,13 +70,14 @@ impl VisualSampleEntry {
       depth,
       clean_aperture_box: None,
       pixel_aspect_ratio_box: None
-    }, start)
+    }, start))
   }
 }
 
 #[derive(Debug)]
 pub struct VisualSampleEntryBuilder {
   sps_data: Vec<u8>,
+  dimensions: Option<(usize, usize)>,
 }
 
 impl VisualSampleEntryBuilder {
@@ -100,6 +85,7 @@ impl VisualSampleEntryBuilder {
   pub fn create_builder() -> VisualSampleEntryBuilder {
     return VisualSampleEntryBuilder {
       sps_data: vec![],
+      dimensions: None,
     }
   }
 
@@ -108,10 +94,22 @@ impl VisualSampleEntryBuilder {
     self
   }
 
+  /// Width and height of the pictures. Used instead of the (H.264) sps for other codecs.
+  pub fn dimensions(mut self, width: usize, height: usize) -> VisualSampleEntryBuilder {
+    self.dimensions = Some((width, height));
+    self
+  }
+
   pub fn build(&self) -> Result<Vec<u8>, CustomError> {
-    let sps = SequenceParameterSet::parse(&self.sps_data)?;
-    let width = util::transform_usize_to_u8_array(sps.width());
-    let height = util::transform_usize_to_u8_array(sps.height());
+    let (width, height) = match self.dimensions {
+      Some(dimensions) => dimensions,
+      None => {
+        let sps = SequenceParameterSet::parse(&self.sps_data)?;
+        (sps.width(), sps.height())
+      }
+    };
+    let width = util::transform_usize_to_u8_array(width);
+    let height = util::transform_usize_to_u8_array(height);
     Ok(vec![
       // int(16) pre_defined
       0x00, 0x00,
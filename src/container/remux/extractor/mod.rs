@@ -1,9 +1,14 @@
 use crate::container::remux::extractor::ts::{
-    aac_extractor::AACExtractor, avc_extractor::AVCExtractor,
+    aac_extractor::AACExtractor, ac3_extractor::AC3Extractor, avc_extractor::AVCExtractor,
+    eac3_extractor::EAC3Extractor, hevc_extractor::HEVCExtractor,
+    mpeg_audio_extractor::MPEGAudioExtractor,
 };
+use crate::container::remux::RemuxOptions;
+use crate::container::transport_stream::continuity_counter::{CorruptFrame, TimestampIssue};
 use crate::container::transport_stream::{
     elementary_stream_type::ElementaryStreamType, pes_packet::PESPacket,
 };
+use crate::container::writer::mp4_writer::Mp4Writer;
 use crate::error::error_code::{MajorCode, RemuxMinorCode};
 use crate::error::{construct_error, CustomError};
 
@@ -11,37 +16,85 @@ pub mod mp4;
 pub mod ts;
 
 pub trait TSExtractor {
+    /// Unwrap the first PES timestamp of the track relative to the reference (the first timestamp of the program), so
+    /// every track of the program ends up on the same side of a 33 bit wrap
+    fn seed_timestamps(&mut self, reference: u64);
     fn accumulate_pes_payload(&mut self, pes: PESPacket) -> Result<(), CustomError>;
     fn is_all_same_timestamps(&self) -> bool;
     fn is_signed_comp_offset(&self) -> bool;
     fn build_sample_entry(&mut self) -> Result<Vec<u8>, CustomError>;
+    /// Whether enough of the stream has been seen to build the sample entry (and so the init segment)
+    fn has_codec_config(&self) -> bool;
     fn flush_final_media(&mut self) -> Result<(), CustomError>;
-    fn get_init_segment(&mut self) -> Result<Vec<u8>, CustomError>;
-    fn get_media_segment(&mut self) -> Result<Vec<u8>, CustomError>;
+    /// Writer set up for the trak of the track, and its sample entry
+    fn get_init_writer(&mut self) -> Result<(Mp4Writer, Vec<u8>), CustomError>;
+    /// Writer set up for the traf of the track, with the samples gathered so far
+    fn get_media_writer(&mut self) -> Result<Mp4Writer, CustomError>;
+    fn get_init_segment(&mut self) -> Result<Vec<u8>, CustomError> {
+        let (writer, sample_entry_data) = self.get_init_writer()?;
+        writer.build_init_segment(sample_entry_data)
+    }
+    fn get_media_segment(&mut self, sequence_number: u32) -> Result<Vec<u8>, CustomError> {
+        self.get_media_writer()?
+            .sequence_number(sequence_number)
+            .build_media_segment()
+    }
     fn get_timescale(&self) -> u32;
     fn get_default_sample_duration(&self) -> u32;
+    /// Gaps and overlaps found in the PES timestamps since the last call, reported against the PID of the track
+    fn take_timestamp_issues(&mut self, _pid: u16) -> Vec<TimestampIssue> {
+        vec![]
+    }
+    /// Frames left out since the last call because their CRC didn't match, reported against the PID of the track
+    fn take_corrupt_frames(&mut self, _pid: u16) -> Vec<CorruptFrame> {
+        vec![]
+    }
+    /// Frames kept since the last call although their CRC couldn't be verified
+    fn take_unverified_frame_count(&mut self) -> usize {
+        0
+    }
 }
 
 pub fn get_ts_extractor(
     es_type: ElementaryStreamType,
+    track_id: usize,
+    language: String,
+    options: &RemuxOptions,
 ) -> Result<Box<dyn TSExtractor>, CustomError> {
     return match es_type {
         ElementaryStreamType::AAC => {
-            let extractor = Box::new(AACExtractor::create());
+            let extractor = Box::new(
+                AACExtractor::create(track_id, language)
+                    .verify_crc(options.verify_adts_crc)
+                    .samples_per_frame(options.aac_frame_length),
+            );
             Ok(extractor)
         }
         ElementaryStreamType::AC3 => {
-            todo!("Need to implement AC3 transport stream extractor");
+            let extractor = Box::new(AC3Extractor::create(track_id, language));
+            Ok(extractor)
         }
         ElementaryStreamType::E_AC3 => {
-            todo!("Need to implement E_AC3 transport stream extractor");
+            let extractor = Box::new(EAC3Extractor::create(track_id, language));
+            Ok(extractor)
+        }
+        ElementaryStreamType::MPEG1_AUDIO | ElementaryStreamType::MPEG2_AUDIO => {
+            let extractor = Box::new(MPEGAudioExtractor::create(track_id, language));
+            Ok(extractor)
         }
         ElementaryStreamType::H_264 => {
-            let extractor = Box::new(AVCExtractor::create());
+            let extractor = Box::new(
+                AVCExtractor::create(track_id, language)
+                    .in_band_parameter_sets(options.in_band_parameter_sets),
+            );
             Ok(extractor)
         }
         ElementaryStreamType::H_265 => {
-            todo!("Need to implement H_265 transport stream extractor");
+            let extractor = Box::new(
+                HEVCExtractor::create(track_id, language)
+                    .in_band_parameter_sets(options.in_band_parameter_sets),
+            );
+            Ok(extractor)
         }
         ElementaryStreamType::UNKNOWN => {
             return Err(construct_error(
@@ -54,3 +107,39 @@ pub fn get_ts_extractor(
         }
     };
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::h265::sequence_parameter_set::tests::get_sps;
+    use crate::container::transport_stream::test_util::{create_annex_b, create_pes};
+
+    #[test]
+    fn test_get_ts_extractor_hevc_in_band_parameter_sets() {
+        let vps = vec![0x40, 0x01, 0x0C];
+        let pps = vec![0x44, 0x01, 0xC1, 0x72, 0xB4, 0x62, 0x40];
+        let idr = vec![0x26, 0x01, 0xAF, 0x11];
+        let pes = create_pes(0xE0, Some(3000), &create_annex_b(&[vps, get_sps(), pps, idr]));
+        let options = RemuxOptions {
+            in_band_parameter_sets: true,
+            ..RemuxOptions::default()
+        };
+
+        let mut extractor = get_ts_extractor(ElementaryStreamType::H_265, 1, "und".to_string(), &options).unwrap();
+        extractor.accumulate_pes_payload(PESPacket::parse(&pes).unwrap()).unwrap();
+        extractor.flush_final_media().unwrap();
+
+        let init_segment = extractor.get_init_segment().unwrap();
+        assert!(init_segment.windows(4).any(|window|window == [0x68, 0x65, 0x76, 0x31]));
+        // The VPS is in the sample, prefixed with its size
+        let media_segment = extractor.get_media_segment(1).unwrap();
+        assert!(media_segment.windows(7).any(|window|window == [0x00, 0x00, 0x00, 0x03, 0x40, 0x01, 0x0C]));
+
+        let mut extractor = get_ts_extractor(ElementaryStreamType::H_265, 1, "und".to_string(), &RemuxOptions::default()).unwrap();
+        extractor.accumulate_pes_payload(PESPacket::parse(&pes).unwrap()).unwrap();
+        extractor.flush_final_media().unwrap();
+        let init_segment = extractor.get_init_segment().unwrap();
+        assert!(init_segment.windows(4).any(|window|window == [0x68, 0x76, 0x63, 0x31]));
+    }
+}
@@ -1,35 +1,53 @@
-use crate::{container::{isobmff::{descriptors::{aac_audio_specific_config::AACAudioSpecificConfigBuilder, dec_config_descriptor::DecoderConfigDescriptorBuilder, es_descriptor::ESDescriptorBuidler}, sample_entry::{audio_sample_entry::AudioSampleEntryBuilder, mp4a_sample_entry::MP4ASampleEntryBuilder, sample_entry::SampleEntryBuilder}, HandlerType}, remux::{extractor::TSExtractor, map_sample_frequency_index}, transport_stream::{adts::ADTSFrame, pes_packet, adts::ADTS}, writer::mp4_writer::{SampleInfo, Mp4Writer}}, error::CustomError};
+use crate::{container::{isobmff::{descriptors::{aac_audio_specific_config::AACAudioSpecificConfigBuilder, dec_config_descriptor::DecoderConfigDescriptorBuilder, es_descriptor::ESDescriptorBuidler}, sample_entry::{audio_sample_entry::AudioSampleEntryBuilder, mp4a_sample_entry::MP4ASampleEntryBuilder, sample_entry::SampleEntryBuilder}, HandlerType}, remux::{extractor::TSExtractor, map_sample_frequency_index}, transport_stream::{adts::ADTSFrame, pes_packet, adts::ADTS, continuity_counter::{CorruptFrame, TimestampIssue, TimestampIssueKind}, timestamp_unwrapper::TimestampUnwrapper}, writer::mp4_writer::{SampleInfo, Mp4Writer}}, error::CustomError};
 use crate::container::isobmff::BoxBuilder;
+use crate::container::isobmff::boxes::SYNC_SAMPLE_FLAGS;
+
+// Samples per AAC frame. ISO/IEC 14496-3; 4.5.1.1
+static AAC_FRAME_SAMPLES: u32 = 1024;
+static PES_TIMESCALE: u64 = 90000;
+// Frames that all have to carry SBR (and PS) data before the stream is signaled as HE-AAC (v2)
+static SBR_DETECTION_FRAMES: usize = 8;
 
 pub struct AACExtractor {
+  track_id: usize,
+  language: String,
   bucket: Vec<u8>,
   current_pts: u64,
-  current_dts: u64,
+  timestamp_unwrapper: TimestampUnwrapper,
   adts_frames: Vec<ADTSFrame>,
-  sample_frequency_index: Option<u8>
+  sample_frequency_index: Option<u8>,
+  verify_crc: bool,
+  samples_per_frame: u32,
+  // Timestamp (track timescale) at which the frame after the last one gathered starts
+  next_frame_timestamp: Option<u64>,
+  // Expected and actual PES timestamps (90 kHz) that didn't line up, not yet taken
+  timestamp_issues: Vec<(u64, u64)>,
+  // PES timestamps (90 kHz) of the ADTS frames left out because of their CRC (or the PES packets left out because
+  // their ADTS frames couldn't be parsed), not yet taken
+  corrupt_frames: Vec<u64>,
+  // ADTS frames kept without their crc_check being verified, not yet taken
+  unverified_frames: usize,
 }
 
 impl TSExtractor for AACExtractor {
+  fn seed_timestamps(&mut self, reference: u64) {
+    self.timestamp_unwrapper.seed(reference);
+  }
+
   fn accumulate_pes_payload(&mut self, pes: pes_packet::PESPacket) -> Result<(), CustomError> {
     // Flush bucket since we are encountering a new ADTS sequence
     if pes.pts.is_some() && !self.bucket.is_empty() {
-      let adts_packet = self.bucket.clone();
-      self.bucket.clear();
-      let mut adts_frames: Vec<ADTSFrame> = ADTS::parse(&adts_packet)?
-        .iter_mut()
-        .map(|frame|{
-          frame.set_pts(self.current_pts);
-          frame.set_dts(self.current_dts);
-          return std::mem::take(frame)
-        })
-        .collect();
-      self.adts_frames.append(&mut adts_frames);
+      let adts_packet = std::mem::take(&mut self.bucket);
+      if let Err(err) = self.add_adts_frames(&adts_packet) {
+        // Only the frames of the malformed PES packet are lost, the new one carries on
+        println!("AACExtractor :: accumulate_pes_payload :: {:?}", err);
+        self.corrupt_frames.push(self.current_pts);
+      }
     }
 
+    // Audio frames are presented in decoding order, so the DTS (if any) is the PTS
     if let Some(pts) = pes.pts {
-      let dts = pes.dts.map_or_else(||pts, |dts|dts);
-      self.current_dts = dts;
-      self.current_pts = pts;
+      self.current_pts = self.timestamp_unwrapper.unwrap_timestamp(pts);
     }
 
     self.bucket.append(&mut pes.payload_data.to_vec());
@@ -44,27 +62,45 @@ impl TSExtractor for AACExtractor {
     false
   }
 
+  fn has_codec_config(&self) -> bool {
+    !self.adts_frames.is_empty()
+  }
+
   fn build_sample_entry(&mut self) -> Result<Vec<u8>, CustomError> {
     if self.adts_frames.len() > 0 {
       let frame = &self.adts_frames[0];
+      let mut audio_specific_config = AACAudioSpecificConfigBuilder::create_builder()
+        .channel_count(frame.header.channel_configuration.into())
+        .sampling_frequency_index(frame.header.sampling_frequency_index.into());
+      let mut sample_rate = map_sample_frequency_index(frame.header.sampling_frequency_index);
+      let mut channel_count = frame.header.channel_configuration;
+      // HE-AAC is signaled explicitly with the SBR output rate, double the core rate, so it isn't taken for AAC LC at
+      // half the rate. The track timescale stays at the core rate, which the frame timestamps are in.
+      // With parametric stereo (HE-AAC v2) the mono core comes out as stereo.
+      if let Some(extension_sampling_frequency_index) = self.get_sbr_sampling_frequency_index() {
+        let has_ps_payload = self.has_ps_payload();
+        audio_specific_config = audio_specific_config
+          .sbr(extension_sampling_frequency_index.into())
+          .ps(has_ps_payload);
+        sample_rate = map_sample_frequency_index(extension_sampling_frequency_index);
+        if has_ps_payload {
+          channel_count = 2;
+        }
+      }
       return MP4ASampleEntryBuilder::create_builder()
         .sample_entry(
           SampleEntryBuilder::create_builder()
         )
         .audio_sample_entry(
           AudioSampleEntryBuilder::create_builder()
-            .channel_count(frame.header.channel_configuration.into())
-            .sample_rate(map_sample_frequency_index(frame.header.sampling_frequency_index))
+            .channel_count(channel_count.into())
+            .sample_rate(sample_rate)
         )
         .esds(
           ESDescriptorBuidler::create_builder()
             .dec_conf_desc(
               DecoderConfigDescriptorBuilder::create_builder()
-                .aac_audio_specific_config(
-                  AACAudioSpecificConfigBuilder::create_builder()
-                    .channel_count(frame.header.channel_configuration.into())
-                    .sampling_frequency_index(frame.header.sampling_frequency_index.into())
-                )
+                .aac_audio_specific_config(audio_specific_config)
             )
         )
         .build();
@@ -74,20 +110,8 @@ impl TSExtractor for AACExtractor {
   }
 
   fn flush_final_media(&mut self) -> Result<(), CustomError> {
-    let mut adts_frames = ADTS::parse(&self.bucket)?
-      .iter_mut()
-      .map(|frame|{
-        // If the sample_frequency_index has not been set yet, we will store it to be used to determine the timescale for later.
-        self.sample_frequency_index = Some(frame.header.sampling_frequency_index);
-        frame.set_pts(self.current_pts);
-        frame.set_dts(self.current_dts);
-        return std::mem::take(frame)
-      })
-      .collect();
-
-    self.adts_frames.append(&mut adts_frames);
-
-    Ok(())
+    let adts_packet = std::mem::take(&mut self.bucket);
+    self.add_adts_frames(&adts_packet)
   }
 
   fn get_timescale(&self) -> u32 {
@@ -96,58 +120,325 @@ impl TSExtractor for AACExtractor {
       .unwrap_or_default()
   }
 
-  fn get_init_segment(&mut self) -> Result<Vec<u8>, CustomError> {
+  fn get_init_writer(&mut self) -> Result<(Mp4Writer, Vec<u8>), CustomError> {
     let sample_entry_data = self.build_sample_entry()?;
-    let track_id = 2usize;
 
-    Mp4Writer::create_mp4_writer()
+    let writer = Mp4Writer::create_mp4_writer()
       .timescale(self.get_timescale())
       .handler(HandlerType::SOUN)
-      .track_id(track_id)
-      .build_init_segment(sample_entry_data)
+      .track_id(self.track_id)
+      .language(&self.language);
+    Ok((writer, sample_entry_data))
   }
 
-  fn get_media_segment(&mut self) -> Result<Vec<u8>, CustomError> {
-    let media_data = AACExtractor::convert_adts_frame_to_sample_infos(std::mem::take(&mut self.adts_frames));
-    let track_id = 2usize;
-    Mp4Writer::create_mp4_writer()
-      .track_id(track_id)
+  fn get_media_writer(&mut self) -> Result<Mp4Writer, CustomError> {
+    let media_data = AACExtractor::convert_adts_frame_to_sample_infos(std::mem::take(&mut self.adts_frames), self.samples_per_frame);
+    Ok(Mp4Writer::create_mp4_writer()
+      .track_id(self.track_id)
       .timescale(self.get_timescale())
       .default_sample_duration(self.get_default_sample_duration())
-      .samples(media_data)
-      .build_media_segment()
+      .samples(media_data))
   }
 
   fn get_default_sample_duration(&self) -> u32 {
-    return 1024
+    self.samples_per_frame
+  }
+
+  fn take_timestamp_issues(&mut self, pid: u16) -> Vec<TimestampIssue> {
+    std::mem::take(&mut self.timestamp_issues)
+      .into_iter()
+      .map(|(expected_timestamp, timestamp)|TimestampIssue {
+        pid,
+        expected_timestamp,
+        timestamp,
+        kind: if timestamp > expected_timestamp { TimestampIssueKind::GAP } else { TimestampIssueKind::OVERLAP },
+      })
+      .collect()
+  }
+
+  fn take_corrupt_frames(&mut self, pid: u16) -> Vec<CorruptFrame> {
+    std::mem::take(&mut self.corrupt_frames)
+      .into_iter()
+      .map(|timestamp|CorruptFrame { pid, timestamp })
+      .collect()
+  }
+
+  fn take_unverified_frame_count(&mut self) -> usize {
+    std::mem::take(&mut self.unverified_frames)
   }
 }
 
 impl AACExtractor {
-  pub fn create() -> AACExtractor {
+  pub fn create(track_id: usize, language: String) -> AACExtractor {
     AACExtractor {
+      track_id,
+      language,
       bucket: vec![],
       adts_frames: vec![],
       current_pts: 0,
-      current_dts: 0,
+      timestamp_unwrapper: TimestampUnwrapper::create(),
       sample_frequency_index: None,
+      verify_crc: false,
+      samples_per_frame: AAC_FRAME_SAMPLES,
+      next_frame_timestamp: None,
+      timestamp_issues: vec![],
+      corrupt_frames: vec![],
+      unverified_frames: 0,
+    }
+  }
+
+  /// For streams coded with 960 sample frames, which ADTS has no way of signaling
+  pub fn samples_per_frame(mut self, samples_per_frame: u32) -> AACExtractor {
+    self.samples_per_frame = samples_per_frame;
+    self
+  }
+
+  /// Leave out the ADTS frames with a CRC that doesn't match. They are reported as corrupt frames, and the ones whose
+  /// CRC can't be verified are counted as unverified.
+  pub fn verify_crc(mut self, verify_crc: bool) -> AACExtractor {
+    self.verify_crc = verify_crc;
+    self
+  }
+
+  /// Output sampling frequency index of the SBR tool when the first frames all carry SBR data. SBR doubles the core
+  /// rate, so there is none for core rates above 48 kHz.
+  fn get_sbr_sampling_frequency_index(&self) -> Option<u8> {
+    let sampling_frequency_index = self.adts_frames.first()?.header.sampling_frequency_index;
+    let has_sbr_payload = self.adts_frames
+      .iter()
+      .take(SBR_DETECTION_FRAMES)
+      .all(|frame|frame.has_sbr_payload());
+    if !has_sbr_payload || !(3..0xC).contains(&sampling_frequency_index) {
+      return None;
+    }
+    Some(sampling_frequency_index - 3)
+  }
+
+  /// Whether the first frames all carry parametric stereo in their SBR data
+  fn has_ps_payload(&self) -> bool {
+    self.adts_frames
+      .iter()
+      .take(SBR_DETECTION_FRAMES)
+      .all(|frame|frame.has_ps_payload())
+  }
+
+  fn parse_adts(&mut self, data: &[u8]) -> Result<Vec<ADTSFrame>, CustomError> {
+    if self.verify_crc {
+      let (adts_frames, crc_check) = ADTS::parse_with_crc_check(data)?;
+      // The frames in the bucket are all from the PES packet of the current PTS
+      self.corrupt_frames.extend(std::iter::repeat_n(self.current_pts, crc_check.dropped_frames));
+      self.unverified_frames += crc_check.unverified_frames;
+      return Ok(adts_frames);
     }
+    ADTS::parse(data)
   }
 
-  fn convert_adts_frame_to_sample_infos(adts_frames: Vec<ADTSFrame>) -> Vec<SampleInfo> {
+  /// The frames of a PES packet follow each other from the PES PTS, in the track timescale. A PTS within half a frame
+  /// of where the frames before it end is treated as rounding, so the frames stay back to back. Otherwise the gap or
+  /// overlap is reported and the frames start from the PTS, leaving out the ones that would start before the frames
+  /// already gathered end.
+  fn add_adts_frames(&mut self, data: &[u8]) -> Result<(), CustomError> {
+    let adts_frames = self.parse_adts(data)?;
+    let sampling_frequency_index = match adts_frames.first() {
+      Some(frame) => frame.header.sampling_frequency_index,
+      None => return Ok(()),
+    };
+    self.sample_frequency_index = Some(sampling_frequency_index);
+    let timescale = (self.get_timescale() as u64).max(1);
+    let samples_per_frame = self.samples_per_frame as u64;
+    let pes_timestamp = self.current_pts * timescale / PES_TIMESCALE;
+
+    let mut timestamp = match self.next_frame_timestamp {
+      Some(expected_timestamp) if expected_timestamp.abs_diff(pes_timestamp) <= samples_per_frame / 2 => expected_timestamp,
+      Some(expected_timestamp) => {
+        self.timestamp_issues.push((expected_timestamp * PES_TIMESCALE / timescale, self.current_pts));
+        pes_timestamp
+      }
+      None => pes_timestamp,
+    };
+    let earliest_timestamp = self.next_frame_timestamp
+      .map(|next_frame_timestamp|next_frame_timestamp.saturating_sub(samples_per_frame / 2))
+      .unwrap_or(0);
+    for mut frame in adts_frames {
+      let frame_timestamp = timestamp;
+      timestamp += samples_per_frame;
+      if frame_timestamp < earliest_timestamp {
+        continue;
+      }
+      frame.set_pts(frame_timestamp);
+      frame.set_dts(frame_timestamp);
+      self.adts_frames.push(frame);
+    }
+    self.next_frame_timestamp = Some(timestamp.max(self.next_frame_timestamp.unwrap_or(0)));
+    Ok(())
+  }
+
+  /// The ADTS frame timestamps are already in the timescale of the track (sample rate). The durations only get
+  /// written when a gap makes a frame last longer than the others.
+  fn convert_adts_frame_to_sample_infos(adts_frames: Vec<ADTSFrame>, samples_per_frame: u32) -> Vec<SampleInfo> {
+    let durations: Vec<u32> = adts_frames
+      .iter()
+      .enumerate()
+      .map(|(index, af)|{
+        adts_frames
+          .get(index + 1)
+          .map(|next|next.dts.saturating_sub(af.dts) as u32)
+          .unwrap_or(samples_per_frame)
+      })
+      .collect();
+    let has_gap = durations.iter().any(|duration|*duration != samples_per_frame);
     let sample_infos: Vec<SampleInfo> = adts_frames
-      .iter() 
-      .map(|af| {
+      .into_iter()
+      .zip(durations)
+      .map(|(af, duration)| {
         // Create the sample data
-        return SampleInfo{
-          sample_flags: None, // Nothing for now. Determine later if this needs to be set
-          sample_duration: None,
-          data: af.data.to_owned(),
+        SampleInfo{
+          // Every audio frame can be decoded on its own
+          sample_flags: Some(SYNC_SAMPLE_FLAGS),
+          sample_duration: if has_gap { Some(duration) } else { None },
           dts: af.dts,
           pts: af.pts,
+          data: af.data,
         }
       })
       .collect();
     sample_infos
   }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::container::isobmff::sample_entry::mp4a_sample_entry::MP4ASampleEntry;
+  use crate::container::transport_stream::{adts::ADTSHeader, pes_packet::PESPacket, test_util::create_pes};
+
+  // 48 kHz ADTS frame with 2 bytes of raw data
+  static ADTS_FRAME: [u8; 9] = [0xFF, 0xF1, 0x4C, 0x80, 0x01, 0x3F, 0xFC, 0x01, 0x02];
+
+  fn create_adts_pes(pts: u64, frame_count: usize) -> Vec<u8> {
+    create_pes(0xC0, Some(pts), &ADTS_FRAME.repeat(frame_count))
+  }
+
+  #[test]
+  fn test_aac_extractor_frame_timestamps() {
+    let mut extractor = AACExtractor::create(1, "und".to_string());
+    // Back to back, then a gap of 9000 and a PES packet starting a frame before the end of the previous one
+    let pes_packets = [create_adts_pes(3000, 2), create_adts_pes(6840, 1), create_adts_pes(17760, 1), create_adts_pes(17760, 2)];
+    for pes in pes_packets.iter() {
+      extractor.accumulate_pes_payload(PESPacket::parse(pes).unwrap()).unwrap();
+    }
+    extractor.flush_final_media().unwrap();
+
+    let timestamps: Vec<u64> = extractor.adts_frames.iter().map(|frame|frame.pts).collect();
+    // The overlapping frame is left out
+    assert_eq!(timestamps, vec![1600, 2624, 3648, 9472, 10496]);
+    assert_eq!(extractor.take_timestamp_issues(0x101), vec![
+      TimestampIssue { pid: 0x101, expected_timestamp: 8760, timestamp: 17760, kind: TimestampIssueKind::GAP },
+      TimestampIssue { pid: 0x101, expected_timestamp: 19680, timestamp: 17760, kind: TimestampIssueKind::OVERLAP },
+    ]);
+    assert!(extractor.take_timestamp_issues(0x101).is_empty());
+
+    // The frame before the gap lasts until the frame after it
+    let durations: Vec<Option<u32>> = AACExtractor::convert_adts_frame_to_sample_infos(std::mem::take(&mut extractor.adts_frames), 1024)
+      .iter()
+      .map(|sample_info|sample_info.sample_duration)
+      .collect();
+    assert_eq!(durations, vec![Some(1024), Some(1024), Some(5824), Some(1024), Some(1024)]);
+  }
+
+  #[test]
+  fn test_aac_extractor_he_aac_sample_entry() {
+    // ID_SCE, ID_FIL with a count of 1 and EXT_SBR_DATA, ID_END
+    let sbr_raw_data_block = vec![0x01, 0x52, 0xE1, 0xDA, 0xE0];
+    let mut extractor = AACExtractor::create(1, "und".to_string());
+    for _ in 0..2 {
+      extractor.adts_frames.push(ADTSFrame {
+        header: ADTSHeader { profile: 1, sampling_frequency_index: 6, channel_configuration: 2, ..Default::default() },
+        data: sbr_raw_data_block.clone(),
+        ..Default::default()
+      });
+    }
+    assert!(extractor.adts_frames[0].has_sbr_payload());
+    let sample_entry = extractor.build_sample_entry().unwrap();
+    let mp4a_sample_entry = MP4ASampleEntry::parse(&sample_entry).unwrap();
+    let audio_specific_config = mp4a_sample_entry.es_descriptor.dec_config_descr.audio_sepcific_info.unwrap();
+    assert_eq!(audio_specific_config.get_codec_audio_object_type(), 5);
+    assert_eq!(audio_specific_config.extension_sampling_frequency_index, Some(3));
+    // The timescale stays at the core rate
+    extractor.sample_frequency_index = Some(6);
+    assert_eq!(extractor.get_timescale(), 24000);
+
+    // A single frame without SBR data keeps it AAC LC
+    extractor.adts_frames[1].data = vec![0x01, 0x52, 0xF0];
+    let sample_entry = extractor.build_sample_entry().unwrap();
+    let mp4a_sample_entry = MP4ASampleEntry::parse(&sample_entry).unwrap();
+    assert_eq!(mp4a_sample_entry.es_descriptor.dec_config_descr.audio_sepcific_info.unwrap().get_codec_audio_object_type(), 2);
+  }
+
+  #[test]
+  fn test_aac_extractor_he_aac_v2_sample_entry() {
+    // ID_SCE, ID_FIL with a count of 4 and EXT_SBR_DATA whose extension is EXTENSION_ID_PS, ID_END
+    let ps_raw_data_block = vec![0x01, 0x52, 0xE4, 0xD5, 0x4B, 0x1A, 0xD0, 0xE0];
+    let mut extractor = AACExtractor::create(1, "und".to_string());
+    for _ in 0..2 {
+      extractor.adts_frames.push(ADTSFrame {
+        header: ADTSHeader { profile: 1, sampling_frequency_index: 6, channel_configuration: 1, ..Default::default() },
+        data: ps_raw_data_block.clone(),
+        ..Default::default()
+      });
+    }
+    let sample_entry = extractor.build_sample_entry().unwrap();
+    let mp4a_sample_entry = MP4ASampleEntry::parse(&sample_entry).unwrap();
+    assert_eq!(mp4a_sample_entry.audio_sample_entry.get_channel_count(), 2);
+    let audio_specific_config = mp4a_sample_entry.es_descriptor.dec_config_descr.audio_sepcific_info.unwrap();
+    assert_eq!(audio_specific_config.get_codec_audio_object_type(), 29);
+    assert_eq!(audio_specific_config.extension_sampling_frequency_index, Some(3));
+    assert_eq!(audio_specific_config.get_channel_count(), 2);
+  }
+
+  #[test]
+  fn test_aac_extractor_reports_corrupt_frames() {
+    // ADTS frame with 2 raw data blocks whose header crc_check doesn't match
+    let corrupt_frame = [
+      0xFF, 0xF0, 0x4C, 0x80, 0x02, 0x7F, 0xFD,
+      // raw_data_block_position of the second block
+      0x00, 0x04,
+      // crc_check of the header
+      0x00, 0x00,
+      0x11, 0x12, 0xAB, 0xCD,
+      0x21, 0x22, 0xAB, 0xCD,
+    ];
+    let mut extractor = AACExtractor::create(1, "und".to_string()).verify_crc(true);
+    let first_pes = create_pes(0xC0, Some(3000), &[ADTS_FRAME.to_vec(), corrupt_frame.to_vec()].concat());
+    extractor.accumulate_pes_payload(PESPacket::parse(&first_pes).unwrap()).unwrap();
+    extractor.accumulate_pes_payload(PESPacket::parse(&create_adts_pes(4920, 1)).unwrap()).unwrap();
+    extractor.flush_final_media().unwrap();
+
+    assert_eq!(extractor.adts_frames.len(), 2);
+    assert_eq!(extractor.take_corrupt_frames(0x101), vec![CorruptFrame { pid: 0x101, timestamp: 3000 }]);
+    assert!(extractor.take_corrupt_frames(0x101).is_empty());
+
+    // The crc_check of a single raw data block is kept unverified
+    let single_block_header = ADTSHeader { profile: 1, sampling_frequency_index: 3, frame_length: 11, crc: Some(0xABCD), ..Default::default() };
+    let single_block_frame = [single_block_header.build(), vec![0x01, 0x02]].concat();
+    extractor.accumulate_pes_payload(PESPacket::parse(&create_pes(0xC0, Some(6840), &single_block_frame)).unwrap()).unwrap();
+    extractor.flush_final_media().unwrap();
+    assert_eq!(extractor.adts_frames.len(), 3);
+    assert_eq!(extractor.take_unverified_frame_count(), 1);
+    assert_eq!(extractor.take_unverified_frame_count(), 0);
+  }
+
+  #[test]
+  fn test_aac_extractor_malformed_pes_packet() {
+    let mut extractor = AACExtractor::create(1, "und".to_string());
+    let pes_packets = [create_adts_pes(3000, 1), create_pes(0xC0, Some(4920), &[0x00, 0x01, 0x02, 0x03]), create_adts_pes(6840, 1)];
+    for pes in pes_packets.iter() {
+      extractor.accumulate_pes_payload(PESPacket::parse(pes).unwrap()).unwrap();
+    }
+    extractor.flush_final_media().unwrap();
+
+    // Only the malformed PES packet is left out
+    let timestamps: Vec<u64> = extractor.adts_frames.iter().map(|frame|frame.pts).collect();
+    assert_eq!(timestamps, vec![1600, 3648]);
+    assert_eq!(extractor.take_corrupt_frames(0x101), vec![CorruptFrame { pid: 0x101, timestamp: 4920 }]);
+  }
+}
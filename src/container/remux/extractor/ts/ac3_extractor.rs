@@ -0,0 +1,219 @@
+use crate::container::isobmff::{BoxBuilder, HandlerType};
+use crate::container::isobmff::boxes::SYNC_SAMPLE_FLAGS;
+use crate::container::isobmff::configuration_records::dac3::AC3SpecificBoxBuilder;
+use crate::container::isobmff::sample_entry::{ac3_sample_entry::AC3SampleEntryBuilder, audio_sample_entry::AudioSampleEntryBuilder, sample_entry::SampleEntryBuilder};
+use crate::container::remux::extractor::{TSExtractor, ts::pes_timestamps::PESTimestamps};
+use crate::container::transport_stream::{ac3::{AC3, AC3SyncFrame, AC3SyncFrameHeader, AC3_SAMPLES_PER_FRAME}, pes_packet};
+use crate::container::writer::mp4_writer::{Mp4Writer, SampleInfo};
+use crate::error::CustomError;
+
+pub struct AC3Extractor {
+  track_id: usize,
+  language: String,
+  // Start of a sync frame that continues in the next PES packet
+  bucket: Vec<u8>,
+  pes_timestamps: PESTimestamps,
+  sync_frames: Vec<AC3SyncFrame>,
+  // Header of the first sync frame, which describes the stream for the sample entry
+  config: Option<AC3SyncFrameHeader>,
+}
+
+impl TSExtractor for AC3Extractor {
+  fn seed_timestamps(&mut self, reference: u64) {
+    self.pes_timestamps.seed(reference);
+  }
+
+  fn accumulate_pes_payload(&mut self, pes: pes_packet::PESPacket) -> Result<(), CustomError> {
+    self.pes_timestamps.push(&pes, self.bucket.len());
+    self.bucket.extend_from_slice(pes.payload_data);
+    self.parse_sync_frames()
+  }
+
+  fn is_all_same_timestamps(&self) -> bool {
+    true
+  }
+
+  fn is_signed_comp_offset(&self) -> bool {
+    false
+  }
+
+  fn has_codec_config(&self) -> bool {
+    self.config.is_some()
+  }
+
+  fn build_sample_entry(&mut self) -> Result<Vec<u8>, CustomError> {
+    if let Some(header) = self.config.as_ref() {
+      return AC3SampleEntryBuilder::create_builder()
+        .sample_entry(
+          SampleEntryBuilder::create_builder()
+        )
+        .audio_sample_entry(
+          AudioSampleEntryBuilder::create_builder()
+            .channel_count(header.get_channel_count())
+            .sample_rate(header.get_sample_rate())
+        )
+        .dac3(
+          AC3SpecificBoxBuilder::create_builder()
+            .fscod(header.fscod)
+            .bsid(header.bsid)
+            .bsmod(header.bsmod)
+            .acmod(header.acmod)
+            .lfeon(header.lfeon)
+            .bit_rate_code(header.get_bit_rate_code())
+        )
+        .build();
+    }
+    println!("AC3Extractor :: build_sample_entry :: No AC-3 sync frames available. Returning empty vector");
+    Ok(vec![])
+  }
+
+  fn flush_final_media(&mut self) -> Result<(), CustomError> {
+    // Every complete sync frame was already parsed, so whatever is left is a truncated sync frame
+    if !self.bucket.is_empty() {
+      println!("AC3Extractor :: flush_final_media :: Dropping {} bytes of an incomplete sync frame", self.bucket.len());
+      self.bucket.clear();
+    }
+    self.pes_timestamps.clear();
+    Ok(())
+  }
+
+  fn get_timescale(&self) -> u32 {
+    self.config
+      .as_ref()
+      .map(|header|header.get_sample_rate())
+      .unwrap_or_default()
+  }
+
+  fn get_init_writer(&mut self) -> Result<(Mp4Writer, Vec<u8>), CustomError> {
+    let sample_entry_data = self.build_sample_entry()?;
+
+    let writer = Mp4Writer::create_mp4_writer()
+      .timescale(self.get_timescale())
+      .handler(HandlerType::SOUN)
+      .track_id(self.track_id)
+      .language(&self.language);
+    Ok((writer, sample_entry_data))
+  }
+
+  fn get_media_writer(&mut self) -> Result<Mp4Writer, CustomError> {
+    let media_data = AC3Extractor::convert_sync_frames_to_sample_infos(std::mem::take(&mut self.sync_frames), self.get_timescale());
+    Ok(Mp4Writer::create_mp4_writer()
+      .track_id(self.track_id)
+      .timescale(self.get_timescale())
+      .default_sample_duration(self.get_default_sample_duration())
+      .samples(media_data))
+  }
+
+  fn get_default_sample_duration(&self) -> u32 {
+    AC3_SAMPLES_PER_FRAME
+  }
+}
+
+impl AC3Extractor {
+  pub fn create(track_id: usize, language: String) -> AC3Extractor {
+    AC3Extractor {
+      track_id,
+      language,
+      bucket: vec![],
+      pes_timestamps: PESTimestamps::create(),
+      sync_frames: vec![],
+      config: None,
+    }
+  }
+
+  /// Move the complete sync frames out of the bucket. A sync frame gets the timestamps of the PES packet it starts
+  /// in. The ones that start in a PES packet without timestamps (or after the first sync frame of one) are placed
+  /// 1536 samples after the previous sync frame.
+  fn parse_sync_frames(&mut self) -> Result<(), CustomError> {
+    let (mut sync_frames, size) = AC3::parse(&self.bucket)?;
+    for sync_frame in sync_frames.iter_mut() {
+      let (pts, dts) = self.pes_timestamps.get_frame_timestamps(
+        sync_frame.offset,
+        AC3_SAMPLES_PER_FRAME,
+        sync_frame.header.get_sample_rate()
+      );
+      sync_frame.set_pts(pts);
+      sync_frame.set_dts(dts);
+
+      if self.config.is_none() {
+        self.config = Some(sync_frame.header.clone());
+      }
+    }
+
+    self.sync_frames.append(&mut sync_frames);
+    self.bucket.drain(0..size);
+    self.pes_timestamps.drain(size);
+    Ok(())
+  }
+
+  /// The sync frame timestamps are in 90 kHz, so they get converted to the timescale of the track (sample rate)
+  fn convert_sync_frames_to_sample_infos(sync_frames: Vec<AC3SyncFrame>, timescale: u32) -> Vec<SampleInfo> {
+    sync_frames
+      .into_iter()
+      .map(|sync_frame| {
+        SampleInfo{
+          sample_flags: Some(SYNC_SAMPLE_FLAGS),
+          sample_duration: Some(AC3_SAMPLES_PER_FRAME),
+          dts: sync_frame.dts * timescale as u64 / 90000,
+          pts: sync_frame.pts * timescale as u64 / 90000,
+          data: sync_frame.data,
+        }
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::container::transport_stream::{pes_packet::PESPacket, test_util::create_pes};
+
+  // 48 kHz, 192 kbps (768 bytes), bsid 8, 2/0 (acmod 2)
+  fn create_sync_frame() -> Vec<u8> {
+    let mut sync_frame = vec![0x0B, 0x77, 0x00, 0x00, 0x14, 0x40, 0x40, 0x00];
+    sync_frame.resize(768, 0x00);
+    sync_frame
+  }
+
+  #[test]
+  fn test_ac3_extractor_sync_frame_timestamps() {
+    let frames = [create_sync_frame(), create_sync_frame(), create_sync_frame(), create_sync_frame()].concat();
+    let mut extractor = AC3Extractor::create(1, "eng".to_string());
+
+    // The third sync frame is split across the PES packets, so the PTS of the second PES belongs to the fourth
+    let first_pes = create_pes(0xBD, Some(9000), &frames[0..(768 * 2 + 100)]);
+    let second_pes = create_pes(0xBD, Some(17640), &frames[(768 * 2 + 100)..]);
+    extractor.accumulate_pes_payload(PESPacket::parse(&first_pes).unwrap()).unwrap();
+    assert_eq!(extractor.sync_frames.len(), 2);
+    assert!(extractor.has_codec_config());
+    extractor.accumulate_pes_payload(PESPacket::parse(&second_pes).unwrap()).unwrap();
+    extractor.flush_final_media().unwrap();
+
+    let dts: Vec<u64> = extractor.sync_frames.iter().map(|sync_frame|sync_frame.dts).collect();
+    assert_eq!(dts, vec![9000, 11880, 14760, 17640]);
+    assert_eq!(extractor.get_timescale(), 48000);
+    assert_eq!(extractor.get_default_sample_duration(), 1536);
+    assert!(extractor.get_init_segment().is_ok());
+    assert!(extractor.get_media_segment(1).is_ok());
+  }
+
+  #[test]
+  fn test_ac3_extractor_false_sync_word() {
+    // A sync word followed by a reserved fscod and frmsizecod
+    let junk = vec![0x0B, 0x77, 0x00, 0x00, 0xFF, 0x40, 0x00, 0x00, 0x12];
+    let frame = create_sync_frame();
+    let mut extractor = AC3Extractor::create(1, "eng".to_string());
+
+    // The junk is split across the second and third PES, so the third sync frame starts in the third PES
+    let first_pes = create_pes(0xBD, Some(9000), &[frame.clone(), frame[0..100].to_vec()].concat());
+    let second_pes = create_pes(0xBD, None, &[frame[100..].to_vec(), junk[0..5].to_vec()].concat());
+    let third_pes = create_pes(0xBD, Some(20000), &[junk[5..].to_vec(), frame.clone()].concat());
+    for pes in [first_pes, second_pes, third_pes] {
+      extractor.accumulate_pes_payload(PESPacket::parse(&pes).unwrap()).unwrap();
+    }
+
+    let dts: Vec<u64> = extractor.sync_frames.iter().map(|sync_frame|sync_frame.dts).collect();
+    assert_eq!(dts, vec![9000, 11880, 20000]);
+    assert!(extractor.bucket.is_empty());
+  }
+}
@@ -0,0 +1,184 @@
+use crate::container::isobmff::nal::nal_unit::NALUnit;
+use crate::container::writer::mp4_writer::SampleInfo;
+use crate::util;
+
+/// The NAL units of one picture. The codec specific details of the picture are in info.
+#[derive(Debug, Default)]
+pub struct AccessUnit<T> {
+    pub nal_units: Vec<Vec<u8>>,
+    pub pts: u64,
+    pub dts: u64,
+    pub duration: u32,
+    pub has_vcl: bool,
+    // Whether the timestamps came from the PES packet rather than being derived
+    pub has_timestamp: bool,
+    pub info: T,
+}
+
+/// Splits the Annex B byte stream of the PES payloads into NAL units and groups them into access units. Which NAL
+/// units start an access unit is up to the codec extractor. Every finished access unit is held until the next one
+/// starts, which determines its duration.
+pub struct AccessUnitAssembler<T> {
+    // Start of a NAL unit that continues in the next PES packet
+    bucket: Vec<u8>,
+    // Timestamps of the last PES packet, waiting for the access unit that starts in it
+    pending_timestamp: Option<(u64, u64)>,
+    // Access unit still collecting NAL units
+    current_access_unit: Option<AccessUnit<T>>,
+    // Finished access unit held until the next one starts, which determines its duration
+    hold_access_unit: Option<AccessUnit<T>>,
+    access_units: Vec<AccessUnit<T>>,
+}
+
+impl<T: Default> AccessUnitAssembler<T> {
+    pub fn create() -> AccessUnitAssembler<T> {
+        AccessUnitAssembler {
+            bucket: vec![],
+            pending_timestamp: None,
+            current_access_unit: None,
+            hold_access_unit: None,
+            access_units: vec![],
+        }
+    }
+
+    /// Add a PES payload to the byte stream and return the NAL units it completed. Every NAL unit followed by a start
+    /// code is complete. The last one (start code included) waits for more data.
+    pub fn split_nal_units(&mut self, payload: &[u8]) -> Vec<Vec<u8>> {
+        self.bucket.extend_from_slice(payload);
+        let mut last_start_code: Option<(usize, usize)> = None;
+        let mut index = 0usize;
+        let mut nal_units: Vec<Vec<u8>> = vec![];
+        while index + 3 < self.bucket.len() {
+            let boundary = NALUnit::find_boundary(index, &self.bucket);
+            if boundary == -1 {
+                index += 1;
+                continue;
+            }
+            if let Some((_, nal_start)) = last_start_code {
+                nal_units.push(AccessUnitAssembler::<T>::trim_nal_unit(&self.bucket[nal_start..index]));
+            }
+            last_start_code = Some((index, index + boundary as usize));
+            index += boundary as usize;
+        }
+        if let Some((start_code_index, _)) = last_start_code {
+            self.bucket.drain(0..start_code_index);
+        }
+        nal_units.retain(|nal_unit|!nal_unit.is_empty());
+        nal_units
+    }
+
+    /// Whatever is left in the bucket after its start code is one whole NAL unit
+    pub fn flush_bucket(&mut self) -> Option<Vec<u8>> {
+        let bucket = std::mem::take(&mut self.bucket);
+        let start = (0..bucket.len().saturating_sub(3))
+            .find_map(|index| {
+                let boundary = NALUnit::find_boundary(index, &bucket);
+                if boundary == -1 { None } else { Some(index + boundary as usize) }
+            })?;
+        let nal_unit = AccessUnitAssembler::<T>::trim_nal_unit(bucket.get(start..)?);
+        if nal_unit.is_empty() { None } else { Some(nal_unit) }
+    }
+
+    pub fn set_pending_timestamp(&mut self, pts: u64, dts: u64) {
+        self.pending_timestamp = Some((pts, dts));
+    }
+
+    pub fn take_pending_timestamp(&mut self) -> Option<(u64, u64)> {
+        self.pending_timestamp.take()
+    }
+
+    pub fn get_current_access_unit(&mut self) -> Option<&mut AccessUnit<T>> {
+        self.current_access_unit.as_mut()
+    }
+
+    pub fn get_hold_access_unit(&self) -> Option<&AccessUnit<T>> {
+        self.hold_access_unit.as_ref()
+    }
+
+    pub fn get_access_units(&self) -> &[AccessUnit<T>] {
+        &self.access_units
+    }
+
+    pub fn take_access_units(&mut self) -> Vec<AccessUnit<T>> {
+        std::mem::take(&mut self.access_units)
+    }
+
+    pub fn has_current_access_unit(&self) -> bool {
+        self.current_access_unit.is_some()
+    }
+
+    /// Whether the access unit collecting NAL units already has a slice
+    pub fn current_has_vcl(&self) -> bool {
+        self.current_access_unit.as_ref().is_some_and(|access_unit|access_unit.has_vcl)
+    }
+
+    pub fn start_access_unit(&mut self, pts: u64, dts: u64, has_timestamp: bool) {
+        self.current_access_unit = Some(AccessUnit {
+            pts,
+            dts,
+            has_timestamp,
+            ..Default::default()
+        });
+    }
+
+    /// Hold the current access unit and release the held one with the DTS delta to the current one as its duration.
+    /// Returns that duration. An access unit without slices is dropped.
+    pub fn finish_access_unit(&mut self) -> Option<u32> {
+        let access_unit = self.current_access_unit.take()?;
+        if !access_unit.has_vcl {
+            println!("AccessUnitAssembler :: finish_access_unit :: Dropping an access unit without slices");
+            return None;
+        }
+        let mut duration = None;
+        if let Some(mut held_access_unit) = self.hold_access_unit.take() {
+            held_access_unit.duration = access_unit.dts.saturating_sub(held_access_unit.dts) as u32;
+            duration = Some(held_access_unit.duration);
+            self.access_units.push(held_access_unit);
+        }
+        self.hold_access_unit = Some(access_unit);
+        duration
+    }
+
+    /// Release the held access unit. There is no next access unit to determine its duration, so it gets the one given.
+    pub fn finish_last_access_unit(&mut self, duration: u32) {
+        if let Some(mut access_unit) = self.hold_access_unit.take() {
+            access_unit.duration = duration;
+            self.access_units.push(access_unit);
+        }
+    }
+
+    // Drop the trailing zero bytes before the next start code
+    fn trim_nal_unit(nal_unit: &[u8]) -> Vec<u8> {
+        let end = nal_unit.iter().rposition(|byte|*byte != 0x00).map_or(0, |index|index + 1);
+        nal_unit[0..end].to_vec()
+    }
+}
+
+/// Every NAL unit of the sample is prefixed with its size (lengthSizeMinusOne = 3)
+pub fn convert_access_units_to_sample_infos<T>(
+    access_units: Vec<AccessUnit<T>>,
+    get_sample_flags: fn(&AccessUnit<T>) -> u32,
+) -> Vec<SampleInfo> {
+    access_units
+        .into_iter()
+        .map(|access_unit| {
+            let data: Vec<u8> = access_unit.nal_units
+                .iter()
+                .flat_map(|nal_unit| {
+                    let nal_size_array = util::transform_u32_to_u8_array(nal_unit.len() as u32);
+                    [
+                        vec![nal_size_array[3], nal_size_array[2], nal_size_array[1], nal_size_array[0]],
+                        nal_unit.to_owned(),
+                    ].concat()
+                })
+                .collect();
+            SampleInfo {
+                data,
+                dts: access_unit.dts,
+                pts: access_unit.pts,
+                sample_flags: Some(get_sample_flags(&access_unit)),
+                sample_duration: Some(access_unit.duration),
+            }
+        })
+        .collect()
+}
@@ -1,77 +1,83 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::codec::h264::{
+    picture_order_count::PicOrderCounter,
+    picture_parameter_set::PictureParameterSet,
+    sequence_parameter_set::{SequenceParameterSet, TimingInfo},
+    slice_header::{SliceHeader, SliceType},
+    supplemental_enhancement_information::SEIMessage,
+};
 use crate::container::isobmff::configuration_records::avcC::AVCDecoderConfigurationRecordBuilder;
-use crate::container::isobmff::nal::{nal_unit::NALUnit, NALType};
+use crate::container::isobmff::nal::NALType;
 use crate::container::isobmff::sample_entry::{
     avc_sample_entry::AVCSampleEntryBuilder, sample_entry::SampleEntryBuilder,
     visual_sample_entry::VisualSampleEntryBuilder,
 };
 use crate::container::isobmff::BoxBuilder;
-use crate::util;
+use crate::container::isobmff::boxes::SampleFlag;
 use crate::{
     container::{
-        isobmff::{nal::NalRep, HandlerType},
-        remux::extractor::TSExtractor,
-        transport_stream::pes_packet,
-        writer::mp4_writer::{Mp4Writer, SampleInfo},
+        isobmff::HandlerType,
+        remux::extractor::{TSExtractor, ts::access_unit::{self, AccessUnit, AccessUnitAssembler}},
+        transport_stream::{pes_packet, timestamp_unwrapper::TimestampUnwrapper},
+        writer::mp4_writer::Mp4Writer,
     },
     error::CustomError,
 };
 
+// Number of recent DTS deltas kept to estimate the frame duration when the SPS has no fixed frame rate
+static MAX_DTS_DELTAS: usize = 64;
+
+/// What the slices and the SEI of an access unit tell about the picture
+#[derive(Debug, Default)]
+struct AVCPicture {
+    is_idr: bool,
+    // Whether any slice has a nal_ref_idc other than 0, so other pictures can reference it
+    is_reference: bool,
+    // B if any slice is a B slice, then P if any slice is a P slice, otherwise I. None until a slice header is parsed.
+    slice_type: Option<SliceType>,
+    // Sent with a recovery point SEI that has a recovery_frame_cnt of 0 (open GOP random access point)
+    is_recovery_point: bool,
+}
+
 pub struct AVCExtractor {
-    sps_nal: Vec<u8>,
-    pps_nal: Vec<u8>,
-    hold_nal_rep: Option<NalRep>, // This is to hold the current nal rep until we reach the next nal and can determine its duration
-    media_nal: Vec<NalRep>,
-    bucket: Vec<u8>,
+    track_id: usize,
+    language: String,
+    // Parameter set NAL units by id, which all go in the avcC
+    sps_nals: BTreeMap<usize, Vec<u8>>,
+    pps_nals: BTreeMap<usize, Vec<u8>>,
+    // Keep the parameter sets in the samples and signal them as avc3 instead of avc1
+    in_band_parameter_sets: bool,
+    // An id was redefined with a different parameter set, so the parameter sets are kept in the samples from then on
+    changed_parameter_sets: bool,
+    access_unit_assembler: AccessUnitAssembler<AVCPicture>,
+    // VUI timing of the latest SPS
+    timing_info: Option<TimingInfo>,
+    // Parameter sets by id, needed to parse the slice headers
+    sequence_parameter_sets: HashMap<usize, SequenceParameterSet>,
+    picture_parameter_sets: HashMap<usize, PictureParameterSet>,
+    pic_order_counter: PicOrderCounter,
+    // PTS and PicOrderCnt of the latest picture with PES timestamps since the last IDR
+    timestamp_anchor: Option<(u64, i64)>,
+    // Recent DTS deltas between access units
+    dts_deltas: Vec<u32>,
     signed_comp_offset: bool,
     all_same_timestamps: bool,
-    current_pts: u64,
-    current_dts: u64,
+    timestamp_unwrapper: TimestampUnwrapper,
 }
 
 impl TSExtractor for AVCExtractor {
-    fn accumulate_pes_payload(&mut self, pes: pes_packet::PESPacket) -> Result<(), CustomError> {
-        let mut index: usize = 0;
-        let mut nal_start_index = index;
-        let pes_payload = pes.payload_data;
-
-        loop {
-            if (index + 4) >= pes_payload.len() {
-                // If the next 4 bytes is greater than the total payload, add it to the bucket for the next pes payload
-                let mut leftover: Vec<u8> =
-                    pes_payload[nal_start_index..pes_payload.len()].to_vec();
-                self.bucket.append(&mut leftover);
-                break;
-            }
-            let boundary = NALUnit::find_boundary(index, pes_payload);
-            if boundary == -1 {
-                // If no nal boundary is found, increment the index and continue searching for the next boundary
-                index += 1;
-                continue;
-            }
-            let mut nal_unit: Vec<u8> = vec![];
-            if !self.bucket.is_empty() {
-                nal_unit = self.bucket.clone();
-                self.bucket.clear();
-            }
-
-            if nal_start_index != index {
-                let mut part_payload: Vec<u8> = pes_payload[nal_start_index..index].to_vec();
-                nal_unit.append(&mut part_payload);
-            }
-
-            if !nal_unit.is_empty() {
-                let nal_unit_value = nal_unit[0] & 0x1F;
-                let nal_type = NALType::get_type(nal_unit_value)?;
-
-                self.handle_nal_unit(nal_type, &nal_unit);
-            }
-            index += boundary as usize;
-            nal_start_index = index;
-        }
+    fn seed_timestamps(&mut self, reference: u64) {
+        self.timestamp_unwrapper.seed(reference);
+    }
 
+    fn accumulate_pes_payload(&mut self, pes: pes_packet::PESPacket) -> Result<(), CustomError> {
         if let Some(pts) = pes.pts {
+            // The NAL unit in the bucket ends where this PES packet starts
+            self.flush_bucket();
             // Can assume dts is there because the pes parser will set it if its not there
-            let dts = pes.dts.unwrap();
+            let dts = self.timestamp_unwrapper.unwrap_timestamp(pes.dts.unwrap_or(pts));
+            let pts = self.timestamp_unwrapper.unwrap_timestamp(pts);
             // Set the flag that the composition offset will be negative. Will set the version in trun to 1
             if dts > pts {
                 self.signed_comp_offset = true;
@@ -82,10 +88,19 @@ impl TSExtractor for AVCExtractor {
                 self.all_same_timestamps = false;
             }
 
-            self.current_pts = pts;
-            self.current_dts = dts;
+            // A new PTS means a new picture, even when the stream has no AUDs
+            let is_new_picture = self.access_unit_assembler
+                .get_current_access_unit()
+                .is_some_and(|access_unit|access_unit.has_vcl && access_unit.pts != pts);
+            if is_new_picture {
+                self.finish_access_unit();
+            }
+            self.access_unit_assembler.set_pending_timestamp(pts, dts);
         }
 
+        for nal_unit in self.access_unit_assembler.split_nal_units(pes.payload_data) {
+            self.handle_nal_unit(nal_unit);
+        }
         Ok(())
     }
 
@@ -97,20 +112,21 @@ impl TSExtractor for AVCExtractor {
         self.signed_comp_offset
     }
 
+    fn has_codec_config(&self) -> bool {
+        !self.sps_nals.is_empty() && !self.pps_nals.is_empty()
+    }
+
     fn build_sample_entry(&mut self) -> Result<Vec<u8>, CustomError> {
-        if self.sps_nal.len() > 0 && self.pps_nal.len() > 0 {
-            let sps = self.sps_nal[0..].to_vec();
-            let pps = self.pps_nal[0..].to_vec();
-            self.sps_nal.clear();
-            self.pps_nal.clear();
+        if let Some(sps) = self.sps_nals.values().next().filter(|_|!self.pps_nals.is_empty()) {
+            let avc_c = self.sps_nals
+                .values()
+                .fold(AVCDecoderConfigurationRecordBuilder::create_builder(), |avc_c, sps|avc_c.sps(sps));
+            let avc_c = self.pps_nals.values().fold(avc_c, |avc_c, pps|avc_c.pps(pps));
             return AVCSampleEntryBuilder::create_builder()
                 .sample_entry(SampleEntryBuilder::create_builder())
-                .visual_sample_entry(VisualSampleEntryBuilder::create_builder().sps(&sps))
-                .avc_c(
-                    AVCDecoderConfigurationRecordBuilder::create_builder()
-                        .sps(&sps)
-                        .pps(&pps),
-                )
+                .visual_sample_entry(VisualSampleEntryBuilder::create_builder().sps(sps))
+                .avc_c(avc_c)
+                .in_band_parameter_sets(self.in_band_parameter_sets || self.changed_parameter_sets)
                 .build();
         }
 
@@ -121,22 +137,13 @@ impl TSExtractor for AVCExtractor {
     }
 
     fn flush_final_media(&mut self) -> Result<(), CustomError> {
-        let mut duration: u64 = 0;
-        if let Some(nal_rep) = self.hold_nal_rep.as_mut() {
-            duration = self.current_dts - nal_rep.dts;
-            nal_rep.duration = duration as u32;
-            self.media_nal.push(nal_rep.clone());
-        }
-
-        self.media_nal.push(NalRep {
-            nal_unit: self.bucket.to_vec(),
-            pts: self.current_pts,
-            dts: self.current_dts,
-            // This isn't great. I need to get the total duration of the segment (in PTS)(also not sure how to get that right now)
-            // and substract it from the current dts to determine the duration of the last sample
-            duration: duration as u32,
-        });
-
+        self.flush_bucket();
+        self.finish_access_unit();
+        // There is no next access unit to determine the duration of the last one, so it gets the frame duration
+        let duration = self.get_frame_duration()
+            .or_else(||self.access_unit_assembler.get_access_units().last().map(|previous|previous.duration))
+            .unwrap_or_default();
+        self.access_unit_assembler.finish_last_access_unit(duration);
         Ok(())
     }
 
@@ -144,110 +151,551 @@ impl TSExtractor for AVCExtractor {
         90000
     }
 
-    fn get_init_segment(&mut self) -> Result<Vec<u8>, CustomError> {
-        // let temp = self.into();
-        let sample_entry_data = self.build_sample_entry()?.clone();
-        let track_id = 1usize;
+    fn get_init_writer(&mut self) -> Result<(Mp4Writer, Vec<u8>), CustomError> {
+        let sample_entry_data = self.build_sample_entry()?;
 
-        Mp4Writer::create_mp4_writer()
+        let writer = Mp4Writer::create_mp4_writer()
             .timescale(self.get_timescale())
             .handler(HandlerType::VIDE)
-            .track_id(track_id)
-            .build_init_segment(sample_entry_data)
+            .track_id(self.track_id)
+            .language(&self.language);
+        Ok((writer, sample_entry_data))
     }
 
-    fn get_media_segment(&mut self) -> Result<Vec<u8>, CustomError> {
-        let media_data = AVCExtractor::convert_nal_units_to_sample_infos(self.media_nal.to_owned());
-        let track_id = 1usize;
+    fn get_media_writer(&mut self) -> Result<Mp4Writer, CustomError> {
+        let default_sample_duration = self.get_default_sample_duration();
+        let media_data = access_unit::convert_access_units_to_sample_infos(
+            self.access_unit_assembler.take_access_units(),
+            AVCExtractor::get_sample_flags,
+        );
         let trun_version = if self.is_signed_comp_offset() {1u8} else {0u8};
 
-        let mut writer = Mp4Writer::create_mp4_writer()
-            .track_id(track_id)
+        Ok(Mp4Writer::create_mp4_writer()
+            .track_id(self.track_id)
             .timescale(self.get_timescale())
             .is_all_same_timestamps(self.is_all_same_timestamps())
             .trun_version(trun_version)
-            .default_sample_duration(1500)
-            .samples(media_data);
-
-        if self.is_all_same_timestamps() {
-            writer = writer.default_sample_duration(self.get_default_sample_duration());
-        }
-
-        writer.build_media_segment()
+            .default_sample_duration(default_sample_duration)
+            .samples(media_data))
     }
 
     fn get_default_sample_duration(&self) -> u32 {
-        // self.get_timescale() / fps
-        0
+        self.get_frame_duration().unwrap_or_default()
     }
 }
 
 impl AVCExtractor {
-    pub fn create() -> AVCExtractor {
+    pub fn create(track_id: usize, language: String) -> AVCExtractor {
         AVCExtractor {
-            hold_nal_rep: None,
-            sps_nal: vec![],
-            pps_nal: vec![],
-            media_nal: vec![],
-            bucket: vec![],
+            track_id,
+            language,
+            sps_nals: BTreeMap::new(),
+            pps_nals: BTreeMap::new(),
+            in_band_parameter_sets: false,
+            changed_parameter_sets: false,
+            access_unit_assembler: AccessUnitAssembler::create(),
+            timing_info: None,
+            sequence_parameter_sets: HashMap::new(),
+            picture_parameter_sets: HashMap::new(),
+            pic_order_counter: PicOrderCounter::create(),
+            timestamp_anchor: None,
+            dts_deltas: vec![],
             all_same_timestamps: true,
             signed_comp_offset: false,
-            current_pts: 0,
-            current_dts: 0,
+            timestamp_unwrapper: TimestampUnwrapper::create(),
+        }
+    }
+
+    /// Keep the SPS and PPS in the samples and use the avc3 sample entry. For streams whose parameter sets change.
+    pub fn in_band_parameter_sets(mut self, in_band_parameter_sets: bool) -> AVCExtractor {
+        self.in_band_parameter_sets = in_band_parameter_sets;
+        self
+    }
+
+    fn flush_bucket(&mut self) {
+        if let Some(nal_unit) = self.access_unit_assembler.flush_bucket() {
+            self.handle_nal_unit(nal_unit);
         }
     }
 
-    fn handle_nal_unit(&mut self, nal_type: NALType, nal_unit: &[u8]) {
+    /// Group the NAL units into access units. ITU-T H.264; 7.4.1.2.3 A new access unit starts with an access unit
+    /// delimiter, an SEI, a parameter set, a NAL unit of type 14 to 18 or the first slice of a picture
+    /// (first_mb_in_slice == 0) that follow the slices of the previous picture. Other NAL units (end of sequence, SPS
+    /// extension, ...) stay with the access unit they are in, whether this extractor knows their type or not.
+    fn handle_nal_unit(&mut self, nal_unit: Vec<u8>) {
+        let nal_unit_type = nal_unit[0] & 0x1F;
+        let nal_type = NALType::get_type(nal_unit_type).ok();
+        if matches!(nal_type, Some(NALType::Filler_Data)) {
+            return;
+        }
+        let is_vcl = matches!(nal_type, Some(NALType::Non_IDR_Picture | NALType::IDR_Picture));
+        // first_mb_in_slice is ue(v), so a first bit of 1 means 0
+        let is_first_slice = is_vcl && nal_unit.len() > 1 && (nal_unit[1] & 0x80) != 0;
+        let starts_access_unit = matches!(nal_unit_type, 6..=9 | 14..=18);
+        let current_has_vcl = self.access_unit_assembler.current_has_vcl();
+        if matches!(nal_type, Some(NALType::AUD)) || (current_has_vcl && (starts_access_unit || is_first_slice)) {
+            self.finish_access_unit();
+        }
+
         match nal_type {
-            NALType::SPS => {
-                self.sps_nal = nal_unit.to_vec();
-            }
-            NALType::PPS => self.pps_nal = nal_unit.to_vec(),
-            NALType::AUD => {}
-            _ => {
-                if let Some(nal_rep) = self.hold_nal_rep.as_mut() {
-                    let duration = self.current_dts - nal_rep.dts;
-                    nal_rep.duration = duration as u32;
-                    self.media_nal.push(nal_rep.clone());
+            Some(NALType::SPS) => {
+                match SequenceParameterSet::parse(&nal_unit) {
+                    Ok(sps) => {
+                        self.timing_info = sps.get_timing_info().cloned();
+                        let is_changed = AVCExtractor::store_parameter_set(&mut self.sps_nals, sps.seq_parameter_set_id, &nal_unit);
+                        self.set_changed_parameter_sets(is_changed);
+                        self.sequence_parameter_sets.insert(sps.seq_parameter_set_id, sps);
+                    }
+                    Err(err) => println!("AVCExtractor :: handle_nal_unit :: Unable to parse the SPS: {:?}", err),
+                }
+                if !self.in_band_parameter_sets && !self.changed_parameter_sets {
+                    return;
                 }
-                // self.media_nal.push(NalRep {
-                //     nal_unit: nal_unit.to_vec(),
-                //     pts: self.current_pts,
-                //     dts: self.current_dts,
-                // })
-                self.hold_nal_rep = Some(NalRep {
-                    nal_unit: nal_unit.to_vec(),
-                    pts: self.current_pts,
-                    dts: self.current_dts,
-                    duration: 0,
+            }
+            Some(NALType::PPS) => {
+                let pps = PictureParameterSet::get_ids(&nal_unit).and_then(|(pic_parameter_set_id, seq_parameter_set_id)| {
+                    let is_changed = AVCExtractor::store_parameter_set(&mut self.pps_nals, pic_parameter_set_id, &nal_unit);
+                    self.set_changed_parameter_sets(is_changed);
+                    match self.sequence_parameter_sets.get(&seq_parameter_set_id) {
+                        Some(sps) => PictureParameterSet::parse(&nal_unit, sps).map(Some),
+                        None => Ok(None),
+                    }
                 });
-            },
+                match pps {
+                    Ok(Some(pps)) => {
+                        self.picture_parameter_sets.insert(pps.pic_parameter_set_id, pps);
+                    }
+                    Ok(None) => println!("AVCExtractor :: handle_nal_unit :: The SPS of the PPS is missing"),
+                    Err(err) => println!("AVCExtractor :: handle_nal_unit :: Unable to parse the PPS: {:?}", err),
+                }
+                if !self.in_band_parameter_sets && !self.changed_parameter_sets {
+                    return;
+                }
+            }
+            Some(NALType::AUD) => return,
+            _ => {}
+        }
+
+        if !self.access_unit_assembler.has_current_access_unit() {
+            let pending_timestamp = self.access_unit_assembler.take_pending_timestamp();
+            let has_timestamp = pending_timestamp.is_some();
+            let (pts, dts) = match pending_timestamp {
+                Some(timestamp) => timestamp,
+                None => {
+                    // Place it a frame after the previous access unit when the PES packet had no timestamps
+                    let frame_duration = self.get_frame_duration().unwrap_or_default() as u64;
+                    self.access_unit_assembler
+                        .get_hold_access_unit()
+                        .map(|access_unit|(access_unit.pts + frame_duration, access_unit.dts + frame_duration))
+                        .unwrap_or_default()
+                }
+            };
+            self.access_unit_assembler.start_access_unit(pts, dts, has_timestamp);
+        }
+        let is_recovery_point = matches!(nal_type, Some(NALType::SEI)) && AVCExtractor::has_recovery_point(&nal_unit);
+        let slice_header = if is_vcl { self.parse_slice_header(&nal_unit) } else { None };
+        if let Some(slice_header) = slice_header.as_ref() {
+            if slice_header.first_mb_in_slice == 0 {
+                self.set_pic_order_cnt(slice_header);
+            }
         }
+        if let Some(access_unit) = self.access_unit_assembler.get_current_access_unit() {
+            access_unit.has_vcl |= is_vcl;
+            let picture = &mut access_unit.info;
+            picture.is_idr |= matches!(nal_type, Some(NALType::IDR_Picture));
+            picture.is_reference |= is_vcl && (nal_unit[0] & 0x60) != 0;
+            picture.is_recovery_point |= is_recovery_point;
+            if let Some(slice_header) = slice_header {
+                picture.slice_type = Some(AVCExtractor::merge_slice_type(picture.slice_type, slice_header.slice_type));
+            }
+            // SEI stays in front of the slices it was sent with
+            access_unit.nal_units.push(nal_unit);
+        }
+    }
+
+    /// Keep the parameter set for the avcC under its id. Returns whether the id is already taken by a different one,
+    /// which the avcC can't hold next to it.
+    fn store_parameter_set(parameter_sets: &mut BTreeMap<usize, Vec<u8>>, id: usize, nal_unit: &[u8]) -> bool {
+        match parameter_sets.get(&id) {
+            Some(stored_nal) => stored_nal.as_slice() != nal_unit,
+            None => {
+                parameter_sets.insert(id, nal_unit.to_vec());
+                false
+            }
+        }
+    }
+
+    /// Once an id is redefined every parameter set stays in the samples, so the decoder always has the one in use
+    fn set_changed_parameter_sets(&mut self, is_changed: bool) {
+        if is_changed && !self.in_band_parameter_sets && !self.changed_parameter_sets {
+            println!("AVCExtractor :: set_changed_parameter_sets :: A parameter set id was redefined. Keeping the parameter sets in the samples, which needs avc3");
+        }
+        self.changed_parameter_sets |= is_changed;
     }
 
-    fn convert_nal_units_to_sample_infos(nal_units: Vec<NalRep>) -> Vec<SampleInfo> {
-        let sample_infos: Vec<SampleInfo> = nal_units
+    fn finish_access_unit(&mut self) {
+        if let Some(duration) = self.access_unit_assembler.finish_access_unit().filter(|duration|*duration > 0) {
+            if self.dts_deltas.len() == MAX_DTS_DELTAS {
+                self.dts_deltas.remove(0);
+            }
+            self.dts_deltas.push(duration);
+        }
+    }
+
+    /// Duration of a frame (90 kHz). A fixed frame rate from the SPS VUI wins. Otherwise the median DTS delta, and
+    /// then the VUI timing (the maximum frame rate when it isn't fixed).
+    fn get_frame_duration(&self) -> Option<u32> {
+        let timing_info = self.timing_info.as_ref();
+        if let Some(frame_duration) = timing_info
+            .filter(|timing_info|timing_info.fixed_frame_rate_flag == 1)
+            .and_then(|timing_info|timing_info.get_frame_duration(self.get_timescale())) {
+            return Some(frame_duration);
+        }
+        if !self.dts_deltas.is_empty() {
+            let mut dts_deltas = self.dts_deltas.clone();
+            dts_deltas.sort_unstable();
+            return Some(dts_deltas[dts_deltas.len() / 2]);
+        }
+        timing_info.and_then(|timing_info|timing_info.get_frame_duration(self.get_timescale()))
+    }
+
+    /// The slice refers to its PPS by id, which refers to its SPS
+    fn get_parameter_sets(&self, pic_parameter_set_id: usize) -> Option<(&SequenceParameterSet, &PictureParameterSet)> {
+        let pps = self.picture_parameter_sets.get(&pic_parameter_set_id)?;
+        let sps = self.sequence_parameter_sets.get(&pps.seq_parameter_set_id)?;
+        Some((sps, pps))
+    }
+
+    fn parse_slice_header(&self, nal_unit: &[u8]) -> Option<SliceHeader> {
+        let pic_parameter_set_id = SliceHeader::get_pic_parameter_set_id(nal_unit).ok()?;
+        let (sps, pps) = self.get_parameter_sets(pic_parameter_set_id)?;
+        match SliceHeader::parse(nal_unit, sps, pps) {
+            Ok(slice_header) => Some(slice_header),
+            Err(err) => {
+                println!("AVCExtractor :: parse_slice_header :: Unable to parse the slice header: {:?}", err);
+                None
+            }
+        }
+    }
+
+    fn has_recovery_point(nal_unit: &[u8]) -> bool {
+        SEIMessage::parse(nal_unit)
+            .unwrap_or_default()
             .iter()
-            .map(|nu| {
-                // Create the sample data
-                let nal_size: u32 = nu.nal_unit.len() as u32;
-                let nal_size_array = util::transform_u32_to_u8_array(nal_size).to_vec();
-                let sample = [
-                    vec![
-                        nal_size_array[3], nal_size_array[2], nal_size_array[1], nal_size_array[0],
-                    ],
-                    nu.nal_unit.to_owned(),
-                ]
-                .concat();
-                return SampleInfo {
-                    data: sample,
-                    dts: nu.dts,
-                    pts: nu.pts,
-                    sample_flags: None,
-                    sample_duration: Some(nu.duration),
-                };
-            })
+            .filter_map(|message|message.get_recovery_point().ok().flatten())
+            .any(|recovery_point|recovery_point.recovery_frame_cnt == 0)
+    }
+
+    fn merge_slice_type(picture_slice_type: Option<SliceType>, slice_type: SliceType) -> SliceType {
+        match (picture_slice_type, slice_type) {
+            (Some(SliceType::B), _) | (_, SliceType::B) => SliceType::B,
+            (Some(SliceType::P | SliceType::SP), _) => SliceType::P,
+            (_, slice_type) => slice_type,
+        }
+    }
+
+    /// Derive the PicOrderCnt of the current picture from its first slice. A picture without PES timestamps gets a
+    /// PTS from how far its PicOrderCnt is from the last picture that had one. The PicOrderCnt of consecutive frames
+    /// is assumed to be 2 apart (one per field).
+    fn set_pic_order_cnt(&mut self, slice_header: &SliceHeader) {
+        let sequence_parameter_sets = &self.sequence_parameter_sets;
+        let Some(sps) = self.picture_parameter_sets
+            .get(&slice_header.pic_parameter_set_id)
+            .and_then(|pps|sequence_parameter_sets.get(&pps.seq_parameter_set_id)) else {
+            return;
+        };
+        let pic_order_cnt = self.pic_order_counter.get_pic_order_cnt(sps, slice_header);
+        let frame_duration = self.get_frame_duration();
+        let Some(access_unit) = self.access_unit_assembler.get_current_access_unit() else {
+            return;
+        };
+        if access_unit.has_timestamp || slice_header.is_idr() {
+            self.timestamp_anchor = Some((access_unit.pts, pic_order_cnt));
+            return;
+        }
+        if let (Some((anchor_pts, anchor_pic_order_cnt)), Some(frame_duration)) = (self.timestamp_anchor, frame_duration) {
+            let pts = anchor_pts as i64 + (pic_order_cnt - anchor_pic_order_cnt) * frame_duration as i64 / 2;
+            if pts >= 0 {
+                access_unit.pts = pts as u64;
+                if access_unit.pts != access_unit.dts {
+                    self.all_same_timestamps = false;
+                }
+                if access_unit.dts > access_unit.pts {
+                    self.signed_comp_offset = true;
+                }
+            }
+        }
+    }
+
+    /// IDR pictures and I pictures at a recovery point are sync samples. Other I pictures don't depend on other
+    /// pictures but aren't random access points. Pictures with a nal_ref_idc of 0 are disposable.
+    fn get_sample_flags(access_unit: &AccessUnit<AVCPicture>) -> u32 {
+        let picture = &access_unit.info;
+        let sample_is_depended_on = if picture.is_reference { 1 } else { 2 };
+        let is_intra = picture.slice_type.is_some_and(|slice_type|slice_type.is_intra());
+        if picture.is_idr || (picture.is_recovery_point && is_intra) {
+            SampleFlag::generate_sample_flags(2, sample_is_depended_on, false)
+        } else if is_intra {
+            SampleFlag::generate_sample_flags(2, sample_is_depended_on, true)
+        } else {
+            SampleFlag::generate_sample_flags(1, sample_is_depended_on, true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::h264::picture_parameter_set::tests::{get_pps, get_pps_with_id_1};
+    use crate::codec::h264::sequence_parameter_set::tests::{get_sps, get_sps_with_pic_order_cnt_lsb};
+    use crate::codec::h264::slice_header::tests::create_slice_with_pic_order_cnt_lsb;
+    use crate::container::isobmff::configuration_records::avcC::AVCDecoderConfigurationRecord;
+    use crate::container::transport_stream::{pes_packet::PESPacket, test_util::{create_annex_b, create_pes}};
+
+    #[test]
+    fn test_avc_extractor_access_units() {
+        let aud = vec![0x09, 0xF0];
+        let sei = vec![0x06, 0x05, 0x01, 0x80];
+        // IDR with two slices (first_mb_in_slice 0 and 4)
+        let idr_first_slice = vec![0x65, 0x88, 0x84, 0x11];
+        let idr_second_slice = vec![0x65, 0x28, 0x84, 0x22];
+        let non_idr_first_slice = vec![0x41, 0x9A, 0x02, 0x33];
+        let non_idr_second_slice = vec![0x41, 0x28, 0x02, 0x44];
+
+        let first_pes = create_pes(
+            0xE0,
+            Some(3000),
+            &create_annex_b(&[aud.clone(), sei.clone(), idr_first_slice, idr_second_slice]),
+        );
+        // No AUD, so the SEI starts the second picture. Its second slice is in a PES packet without timestamps.
+        let second_pes = create_pes(0xE0, Some(6000), &create_annex_b(&[sei.clone(), non_idr_first_slice.clone()]));
+        let third_pes = [
+            vec![0x00, 0x00, 0x01, 0xE0, 0x00, 0x00, 0x80, 0x00, 0x00],
+            create_annex_b(std::slice::from_ref(&non_idr_second_slice)),
+        ].concat();
+        // Only the PTS change separates the third picture from the second
+        let fourth_pes = create_pes(0xE0, Some(9000), &create_annex_b(std::slice::from_ref(&non_idr_second_slice)));
+        let fifth_pes = create_pes(0xE0, Some(12000), &create_annex_b(&[aud, non_idr_first_slice]));
+
+        let mut extractor = AVCExtractor::create(1, "und".to_string());
+        for pes in [first_pes, second_pes, third_pes, fourth_pes, fifth_pes].iter() {
+            extractor.accumulate_pes_payload(PESPacket::parse(pes).unwrap()).unwrap();
+        }
+        extractor.flush_final_media().unwrap();
+
+        assert_eq!(extractor.access_unit_assembler.get_access_units().len(), 4);
+        let first = &extractor.access_unit_assembler.get_access_units()[0];
+        // The AUD is not part of the sample and the SEI stays in front of the slices
+        assert_eq!(first.nal_units.len(), 3);
+        assert_eq!(first.nal_units[0], sei);
+        assert_eq!((first.dts, first.duration), (3000, 3000));
+        let second = &extractor.access_unit_assembler.get_access_units()[1];
+        assert_eq!(second.nal_units.len(), 3);
+        assert_eq!((second.dts, second.duration), (6000, 3000));
+        assert_eq!(extractor.access_unit_assembler.get_access_units()[2].nal_units, vec![non_idr_second_slice]);
+        assert_eq!((extractor.access_unit_assembler.get_access_units()[2].dts, extractor.access_unit_assembler.get_access_units()[2].duration), (9000, 3000));
+        assert_eq!((extractor.access_unit_assembler.get_access_units()[3].dts, extractor.access_unit_assembler.get_access_units()[3].duration), (12000, 3000));
+
+        let sample_infos = access_unit::convert_access_units_to_sample_infos(extractor.access_unit_assembler.take_access_units(), AVCExtractor::get_sample_flags);
+        assert_eq!(sample_infos[0].data.len(), (4 + 4) * 3);
+        assert_eq!(sample_infos[0].data[0..4], [0x00, 0x00, 0x00, 0x04]);
+        // IDR, then non IDR pictures that others reference (nal_ref_idc 2)
+        assert_eq!(sample_infos[0].sample_flags, Some(0x02400000));
+        assert_eq!(sample_infos[1].sample_flags, Some(0x01410000));
+    }
+
+    #[test]
+    fn test_avc_extractor_other_nal_unit_types() {
+        let aud = vec![0x09, 0xF0];
+        let idr = vec![0x65, 0x88, 0x84, 0x11];
+        let non_idr = vec![0x41, 0x9A, 0x02, 0x33];
+        let filler_data = vec![0x0C, 0xFF, 0xFF, 0x80];
+        let end_of_sequence = vec![0x0A];
+        let prefix_nal_unit = vec![0x0E, 0x80, 0x00, 0x01];
+
+        let first_pes = create_pes(0xE0, Some(3000), &create_annex_b(&[
+            aud.clone(), idr.clone(), filler_data, end_of_sequence.clone(), prefix_nal_unit.clone(), non_idr.clone(),
+        ]));
+        let second_pes = create_pes(0xE0, Some(9000), &create_annex_b(&[aud, non_idr.clone()]));
+        let mut extractor = AVCExtractor::create(1, "und".to_string());
+        extractor.accumulate_pes_payload(PESPacket::parse(&first_pes).unwrap()).unwrap();
+        extractor.accumulate_pes_payload(PESPacket::parse(&second_pes).unwrap()).unwrap();
+        extractor.flush_final_media().unwrap();
+
+        // The filler data is dropped, the end of sequence stays with its picture and the prefix NAL unit starts one
+        let nal_units: Vec<&Vec<Vec<u8>>> = extractor.access_unit_assembler
+            .get_access_units()
+            .iter()
+            .map(|access_unit|&access_unit.nal_units)
+            .collect();
+        assert_eq!(nal_units, vec![
+            &vec![idr, end_of_sequence],
+            &vec![prefix_nal_unit, non_idr.clone()],
+            &vec![non_idr],
+        ]);
+    }
+
+    #[test]
+    fn test_avc_extractor_frame_duration() {
+        let idr = vec![0x65, 0x88, 0x84, 0x11];
+        let non_idr = vec![0x41, 0x9A, 0x02, 0x33];
+        let mut extractor = AVCExtractor::create(1, "und".to_string());
+        // The SPS says 30 fps but it isn't a fixed frame rate, so the DTS deltas win once there are some
+        let first_pes = create_pes(0xE0, Some(3000), &create_annex_b(&[get_sps(), idr]));
+        extractor.accumulate_pes_payload(PESPacket::parse(&first_pes).unwrap()).unwrap();
+        assert_eq!(extractor.get_default_sample_duration(), 3000);
+        // A picture in a PES packet without timestamps is placed a frame after the previous one
+        let second_pes = [
+            vec![0x00, 0x00, 0x01, 0xE0, 0x00, 0x00, 0x80, 0x00, 0x00],
+            create_annex_b(std::slice::from_ref(&non_idr)),
+        ].concat();
+        extractor.accumulate_pes_payload(PESPacket::parse(&second_pes).unwrap()).unwrap();
+        let third_pes = create_pes(0xE0, Some(9006), &create_annex_b(std::slice::from_ref(&non_idr)));
+        let fourth_pes = create_pes(0xE0, Some(12009), &create_annex_b(std::slice::from_ref(&non_idr)));
+        extractor.accumulate_pes_payload(PESPacket::parse(&third_pes).unwrap()).unwrap();
+        extractor.accumulate_pes_payload(PESPacket::parse(&fourth_pes).unwrap()).unwrap();
+        extractor.flush_final_media().unwrap();
+
+        let timestamps: Vec<(u64, u32)> = extractor.access_unit_assembler.get_access_units()
+            .iter()
+            .map(|access_unit|(access_unit.dts, access_unit.duration))
+            .collect();
+        // The last picture gets the median DTS delta
+        assert_eq!(timestamps, vec![(3000, 3000), (6000, 3006), (9006, 3003), (12009, 3003)]);
+        assert_eq!(extractor.get_default_sample_duration(), 3003);
+
+        // A fixed frame rate from the SPS is used as is
+        extractor.timing_info = Some(TimingInfo {
+            num_units_in_tick: 1001,
+            time_scale: 30000,
+            fixed_frame_rate_flag: 1,
+        });
+        assert_eq!(extractor.get_default_sample_duration(), 6006);
+    }
+
+    #[test]
+    fn test_avc_extractor_slice_headers() {
+        let no_timestamp_pes = |nal_units: &[Vec<u8>]| [
+            vec![0x00, 0x00, 0x01, 0xE0, 0x00, 0x00, 0x80, 0x00, 0x00],
+            create_annex_b(nal_units),
+        ].concat();
+        let recovery_point_sei = vec![0x06, 0x06, 0x01, 0xC4, 0x80];
+        let mut extractor = AVCExtractor::create(1, "und".to_string());
+        let first_pes = create_pes(0xE0, Some(3000), &create_annex_b(&[
+            get_sps_with_pic_order_cnt_lsb(),
+            get_pps(),
+            create_slice_with_pic_order_cnt_lsb(0x65, 7, 0, Some(0)),
+        ]));
+        extractor.accumulate_pes_payload(PESPacket::parse(&first_pes).unwrap()).unwrap();
+        extractor.timing_info = Some(TimingInfo {
+            num_units_in_tick: 1,
+            time_scale: 60,
+            fixed_frame_rate_flag: 1,
+        });
+        // P picture and two B pictures shown before it, all without PES timestamps
+        let pictures = [
+            create_slice_with_pic_order_cnt_lsb(0x41, 5, 1, Some(6)),
+            create_slice_with_pic_order_cnt_lsb(0x01, 6, 2, Some(2)),
+            create_slice_with_pic_order_cnt_lsb(0x01, 6, 2, Some(4)),
+        ];
+        for picture in pictures.iter() {
+            extractor.accumulate_pes_payload(PESPacket::parse(&no_timestamp_pes(std::slice::from_ref(picture))).unwrap()).unwrap();
+        }
+        // An I picture at a recovery point (open GOP) and one that isn't
+        let open_gop_pes = create_pes(0xE0, Some(18000), &create_annex_b(&[
+            recovery_point_sei,
+            create_slice_with_pic_order_cnt_lsb(0x61, 7, 2, Some(12)),
+        ]));
+        let intra_pes = create_pes(0xE0, Some(21000), &create_annex_b(&[create_slice_with_pic_order_cnt_lsb(0x61, 7, 3, Some(14))]));
+        extractor.accumulate_pes_payload(PESPacket::parse(&open_gop_pes).unwrap()).unwrap();
+        extractor.accumulate_pes_payload(PESPacket::parse(&intra_pes).unwrap()).unwrap();
+        extractor.flush_final_media().unwrap();
+
+        let slice_types: Vec<Option<SliceType>> = extractor.access_unit_assembler.get_access_units().iter().map(|access_unit|access_unit.info.slice_type).collect();
+        assert_eq!(slice_types, vec![
+            Some(SliceType::I), Some(SliceType::P), Some(SliceType::B), Some(SliceType::B), Some(SliceType::I), Some(SliceType::I)
+        ]);
+        let timestamps: Vec<(u64, u64)> = extractor.access_unit_assembler.get_access_units()
+            .iter()
+            .map(|access_unit|(access_unit.dts, access_unit.pts))
+            .collect();
+        // The composition offsets come from the PicOrderCnt
+        assert_eq!(timestamps[0..4], [(3000, 3000), (6000, 12000), (9000, 6000), (12000, 9000)]);
+        assert!(!extractor.is_all_same_timestamps());
+        assert!(extractor.is_signed_comp_offset());
+
+        let sample_flags: Vec<u32> = access_unit::convert_access_units_to_sample_infos(extractor.access_unit_assembler.take_access_units(), AVCExtractor::get_sample_flags)
+            .iter()
+            .map(|sample_info|sample_info.sample_flags.unwrap())
             .collect();
-        sample_infos
+        assert_eq!(sample_flags, vec![0x02400000, 0x01410000, 0x01810000, 0x01810000, 0x02400000, 0x02410000]);
+    }
+
+    #[test]
+    fn test_avc_extractor_parameter_sets_by_id() {
+        let mut extractor = AVCExtractor::create(1, "und".to_string());
+        // The slices refer to PPS 0, which isn't the latest PPS
+        let first_pes = create_pes(0xE0, Some(3000), &create_annex_b(&[
+            get_sps_with_pic_order_cnt_lsb(),
+            get_pps(),
+            get_pps_with_id_1(),
+            create_slice_with_pic_order_cnt_lsb(0x65, 7, 0, Some(0)),
+        ]));
+        let second_pes = create_pes(0xE0, Some(6000), &create_annex_b(&[create_slice_with_pic_order_cnt_lsb(0x41, 5, 1, Some(2))]));
+        extractor.accumulate_pes_payload(PESPacket::parse(&first_pes).unwrap()).unwrap();
+        extractor.accumulate_pes_payload(PESPacket::parse(&second_pes).unwrap()).unwrap();
+        extractor.flush_final_media().unwrap();
+
+        assert_eq!(extractor.picture_parameter_sets.len(), 2);
+        // Both PPS go in the avcC and stay out of the samples
+        assert_eq!(extractor.access_unit_assembler.get_access_units()[0].nal_units.len(), 1);
+        let init_segment = extractor.get_init_segment().unwrap();
+        assert!(init_segment.windows(4).any(|window|window == b"avc1"));
+        let avc_c_offset = init_segment.windows(4).position(|window|window == b"avcC").unwrap() - 4;
+        let record = AVCDecoderConfigurationRecord::parse(&init_segment[avc_c_offset..]).unwrap();
+        assert_eq!(record.sequence_parameter_sets, vec![get_sps_with_pic_order_cnt_lsb()]);
+        assert_eq!(record.picture_parameter_sets, vec![get_pps(), get_pps_with_id_1()]);
+        let slice_types: Vec<Option<SliceType>> = extractor.access_unit_assembler
+            .get_access_units()
+            .iter()
+            .map(|access_unit|access_unit.info.slice_type)
+            .collect();
+        assert_eq!(slice_types, vec![Some(SliceType::I), Some(SliceType::P)]);
+    }
+
+    #[test]
+    fn test_avc_extractor_redefined_parameter_set() {
+        // Another PPS with id 0
+        let redefined_pps = [get_pps(), vec![0x80]].concat();
+        let mut extractor = AVCExtractor::create(1, "und".to_string());
+        let first_pes = create_pes(0xE0, Some(3000), &create_annex_b(&[
+            get_sps_with_pic_order_cnt_lsb(),
+            get_pps(),
+            create_slice_with_pic_order_cnt_lsb(0x65, 7, 0, Some(0)),
+        ]));
+        let second_pes = create_pes(0xE0, Some(6000), &create_annex_b(&[
+            redefined_pps.clone(),
+            create_slice_with_pic_order_cnt_lsb(0x41, 5, 1, Some(2)),
+        ]));
+        // Back to the PPS of the avcC, which has to be in the sample as well now
+        let third_pes = create_pes(0xE0, Some(9000), &create_annex_b(&[
+            get_pps(),
+            create_slice_with_pic_order_cnt_lsb(0x41, 5, 2, Some(4)),
+        ]));
+        for pes in [first_pes, second_pes, third_pes].iter() {
+            extractor.accumulate_pes_payload(PESPacket::parse(pes).unwrap()).unwrap();
+        }
+        extractor.flush_final_media().unwrap();
+
+        let access_units = extractor.access_unit_assembler.get_access_units();
+        assert_eq!(access_units[0].nal_units.len(), 1);
+        assert_eq!(access_units[1].nal_units[0], redefined_pps);
+        assert_eq!(access_units[2].nal_units[0], get_pps());
+        // The avcC keeps the first PPS 0 for the samples before the redefinition
+        let init_segment = extractor.get_init_segment().unwrap();
+        assert!(init_segment.windows(4).any(|window|window == b"avc3"));
+        let avc_c_offset = init_segment.windows(4).position(|window|window == b"avcC").unwrap() - 4;
+        let record = AVCDecoderConfigurationRecord::parse(&init_segment[avc_c_offset..]).unwrap();
+        assert_eq!(record.picture_parameter_sets, vec![get_pps()]);
     }
 }
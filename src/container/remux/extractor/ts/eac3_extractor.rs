@@ -0,0 +1,296 @@
+use crate::container::isobmff::{BoxBuilder, HandlerType};
+use crate::container::isobmff::boxes::SYNC_SAMPLE_FLAGS;
+use crate::container::isobmff::configuration_records::dec3::{EC3IndependentSubstream, EC3SpecificBox, EC3SpecificBoxBuilder};
+use crate::container::isobmff::sample_entry::{audio_sample_entry::AudioSampleEntryBuilder, ec3_sample_entry::EC3SampleEntryBuilder, sample_entry::SampleEntryBuilder};
+use crate::container::remux::extractor::{TSExtractor, ts::pes_timestamps::PESTimestamps};
+use crate::container::transport_stream::{eac3::{EAC3, EAC3Sample, EAC3_SAMPLES_PER_SAMPLE}, pes_packet};
+use crate::container::writer::mp4_writer::{Mp4Writer, SampleInfo};
+use crate::error::CustomError;
+
+/// What the sample entry is built from. Taken from the first complete sample.
+struct EAC3Config {
+  sample_rate: u32,
+  dec3: EC3SpecificBox,
+}
+
+pub struct EAC3Extractor {
+  track_id: usize,
+  language: String,
+  // Start of a sync frame that continues in the next PES packet
+  bucket: Vec<u8>,
+  pes_timestamps: PESTimestamps,
+  // Sample still collecting sync frames. Dependent substreams of it can follow in the next PES packet.
+  current_sample: Option<EAC3Sample>,
+  samples: Vec<EAC3Sample>,
+  config: Option<EAC3Config>,
+}
+
+impl TSExtractor for EAC3Extractor {
+  fn seed_timestamps(&mut self, reference: u64) {
+    self.pes_timestamps.seed(reference);
+  }
+
+  fn accumulate_pes_payload(&mut self, pes: pes_packet::PESPacket) -> Result<(), CustomError> {
+    self.pes_timestamps.push(&pes, self.bucket.len());
+    self.bucket.extend_from_slice(pes.payload_data);
+    self.parse_sync_frames()
+  }
+
+  fn is_all_same_timestamps(&self) -> bool {
+    true
+  }
+
+  fn is_signed_comp_offset(&self) -> bool {
+    false
+  }
+
+  fn has_codec_config(&self) -> bool {
+    self.config.is_some()
+  }
+
+  fn build_sample_entry(&mut self) -> Result<Vec<u8>, CustomError> {
+    if let Some(config) = self.config.as_ref() {
+      let dec3_builder = config.dec3.independent_substreams
+        .iter()
+        .fold(
+          EC3SpecificBoxBuilder::create_builder()
+            .data_rate(config.dec3.data_rate)
+            .complexity_index_type_a(config.dec3.complexity_index_type_a),
+          |builder, substream|builder.independent_substream(substream.clone())
+        );
+      return EC3SampleEntryBuilder::create_builder()
+        .sample_entry(
+          SampleEntryBuilder::create_builder()
+        )
+        .audio_sample_entry(
+          AudioSampleEntryBuilder::create_builder()
+            .channel_count(config.dec3.get_channel_count())
+            .sample_rate(config.sample_rate)
+        )
+        .dec3(dec3_builder)
+        .build();
+    }
+    println!("EAC3Extractor :: build_sample_entry :: No E-AC-3 samples available. Returning empty vector");
+    Ok(vec![])
+  }
+
+  fn flush_final_media(&mut self) -> Result<(), CustomError> {
+    // Every complete sync frame was already parsed, so whatever is left is a truncated sync frame
+    if !self.bucket.is_empty() {
+      println!("EAC3Extractor :: flush_final_media :: Dropping {} bytes of an incomplete sync frame", self.bucket.len());
+      self.bucket.clear();
+    }
+    self.pes_timestamps.clear();
+    if let Some(sample) = self.current_sample.take() {
+      if sample.is_complete() {
+        self.push_sample(sample);
+      } else {
+        println!("EAC3Extractor :: flush_final_media :: Dropping a sample with only {} audio blocks", sample.get_number_of_blocks());
+      }
+    }
+    Ok(())
+  }
+
+  fn get_timescale(&self) -> u32 {
+    self.config
+      .as_ref()
+      .map(|config|config.sample_rate)
+      .unwrap_or_default()
+  }
+
+  fn get_init_writer(&mut self) -> Result<(Mp4Writer, Vec<u8>), CustomError> {
+    let sample_entry_data = self.build_sample_entry()?;
+
+    let writer = Mp4Writer::create_mp4_writer()
+      .timescale(self.get_timescale())
+      .handler(HandlerType::SOUN)
+      .track_id(self.track_id)
+      .language(&self.language);
+    Ok((writer, sample_entry_data))
+  }
+
+  fn get_media_writer(&mut self) -> Result<Mp4Writer, CustomError> {
+    let media_data = EAC3Extractor::convert_samples_to_sample_infos(std::mem::take(&mut self.samples), self.get_timescale());
+    Ok(Mp4Writer::create_mp4_writer()
+      .track_id(self.track_id)
+      .timescale(self.get_timescale())
+      .default_sample_duration(self.get_default_sample_duration())
+      .samples(media_data))
+  }
+
+  fn get_default_sample_duration(&self) -> u32 {
+    EAC3_SAMPLES_PER_SAMPLE
+  }
+}
+
+impl EAC3Extractor {
+  pub fn create(track_id: usize, language: String) -> EAC3Extractor {
+    EAC3Extractor {
+      track_id,
+      language,
+      bucket: vec![],
+      pes_timestamps: PESTimestamps::create(),
+      current_sample: None,
+      samples: vec![],
+      config: None,
+    }
+  }
+
+  /// Group the complete sync frames of the bucket into samples. A sample starts with independent substream 0 once
+  /// the previous sample has 6 audio blocks and gets the timestamps of the PES packet that sync frame starts in. The
+  /// ones that start in a PES packet without timestamps (or after the first sample of one) are placed 1536 samples
+  /// after the previous sample.
+  fn parse_sync_frames(&mut self) -> Result<(), CustomError> {
+    let (sync_frames, size) = EAC3::parse(&self.bucket)?;
+    for sync_frame in sync_frames {
+      let starts_sample = sync_frame.header.is_independent() &&
+        sync_frame.header.substreamid == 0 &&
+        self.current_sample.as_ref().is_none_or(|sample|sample.is_complete());
+
+      if starts_sample {
+        if let Some(sample) = self.current_sample.take() {
+          self.push_sample(sample);
+        }
+        let (pts, dts) = self.pes_timestamps.get_frame_timestamps(
+          sync_frame.offset,
+          EAC3_SAMPLES_PER_SAMPLE,
+          sync_frame.header.get_sample_rate()
+        );
+        let mut sample = EAC3Sample::default();
+        sample.set_pts(pts);
+        sample.set_dts(dts);
+        self.current_sample = Some(sample);
+      }
+
+      match self.current_sample.as_mut() {
+        Some(sample) => sample.sync_frames.push(sync_frame),
+        None => println!("EAC3Extractor :: parse_sync_frames :: Dropping a sync frame that comes before independent substream 0"),
+      }
+    }
+
+    self.bucket.drain(0..size);
+    self.pes_timestamps.drain(size);
+    Ok(())
+  }
+
+  fn push_sample(&mut self, sample: EAC3Sample) {
+    if self.config.is_none() {
+      self.config = EAC3Extractor::create_config(&sample);
+    }
+    self.samples.push(sample);
+  }
+
+  /// Describe the substreams of a sample for the dec3 box. Every independent substream is listed with the channel
+  /// locations of the dependent substreams that follow it.
+  fn create_config(sample: &EAC3Sample) -> Option<EAC3Config> {
+    let first_frame = sample.sync_frames.first()?;
+    let sample_rate = first_frame.header.get_sample_rate();
+    let size: usize = sample.sync_frames.iter().map(|sync_frame|sync_frame.data.len()).sum();
+    let mut independent_substreams: Vec<EC3IndependentSubstream> = vec![];
+    // The substreams repeat for every sync frame of independent substream 0 when they hold less than 6 blocks
+    let first_frames = sample.sync_frames
+      .iter()
+      .enumerate()
+      .take_while(|(index, sync_frame)|*index == 0 || !(sync_frame.header.is_independent() && sync_frame.header.substreamid == 0))
+      .map(|(_, sync_frame)|sync_frame);
+    for sync_frame in first_frames {
+      let header = &sync_frame.header;
+      if header.is_independent() {
+        independent_substreams.push(EC3IndependentSubstream {
+          fscod: header.fscod,
+          bsid: header.bsid,
+          asvc: false,
+          bsmod: header.bsmod,
+          acmod: header.acmod,
+          lfeon: header.lfeon,
+          num_dep_sub: 0,
+          chan_loc: 0,
+        });
+      } else if let Some(substream) = independent_substreams.last_mut() {
+        substream.num_dep_sub += 1;
+        substream.chan_loc |= header.get_chan_loc();
+      }
+    }
+
+    Some(EAC3Config {
+      sample_rate,
+      dec3: EC3SpecificBox {
+        // kbps
+        data_rate: (size as u64 * 8 * sample_rate as u64 / EAC3_SAMPLES_PER_SAMPLE as u64 / 1000) as u16,
+        independent_substreams,
+        complexity_index_type_a: first_frame.header.joc_complexity_index,
+      },
+    })
+  }
+
+  /// The sample timestamps are in 90 kHz, so they get converted to the timescale of the track (sample rate)
+  fn convert_samples_to_sample_infos(samples: Vec<EAC3Sample>, timescale: u32) -> Vec<SampleInfo> {
+    samples
+      .into_iter()
+      .map(|sample| {
+        SampleInfo{
+          sample_flags: Some(SYNC_SAMPLE_FLAGS),
+          sample_duration: Some(EAC3_SAMPLES_PER_SAMPLE),
+          dts: sample.dts * timescale as u64 / 90000,
+          pts: sample.pts * timescale as u64 / 90000,
+          data: sample.get_data(),
+        }
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::container::transport_stream::eac3::tests::{create_false_sync_frame, create_joc_sample};
+  use crate::container::transport_stream::{pes_packet::PESPacket, test_util::create_pes};
+
+  #[test]
+  fn test_eac3_extractor_samples_with_dependent_substreams() {
+    let samples = [create_joc_sample(), create_joc_sample(), create_joc_sample()].concat();
+    let mut extractor = EAC3Extractor::create(1, "eng".to_string());
+
+    // The dependent substream of the second sample is in the second PES packet
+    let first_pes = create_pes(0xBD, Some(9000), &samples[0..(512 + 256)]);
+    let second_pes = create_pes(0xBD, None, &samples[(512 + 256)..]);
+    extractor.accumulate_pes_payload(PESPacket::parse(&first_pes).unwrap()).unwrap();
+    assert_eq!(extractor.samples.len(), 1);
+    assert!(extractor.has_codec_config());
+    extractor.accumulate_pes_payload(PESPacket::parse(&second_pes).unwrap()).unwrap();
+    extractor.flush_final_media().unwrap();
+
+    assert_eq!(extractor.samples.len(), 3);
+    assert!(extractor.samples.iter().all(|sample|sample.sync_frames.len() == 2 && sample.get_data().len() == 512));
+    let dts: Vec<u64> = extractor.samples.iter().map(|sample|sample.dts).collect();
+    assert_eq!(dts, vec![9000, 11880, 14760]);
+
+    let config = extractor.config.as_ref().unwrap();
+    assert_eq!(config.dec3.data_rate, 128);
+    assert_eq!(config.dec3.independent_substreams.len(), 1);
+    assert_eq!(config.dec3.independent_substreams[0].num_dep_sub, 1);
+    assert_eq!(config.dec3.get_channel_count(), 8);
+    assert_eq!(config.dec3.get_hls_channels(), "16/JOC");
+    assert_eq!(extractor.get_timescale(), 48000);
+    assert!(extractor.get_init_segment().is_ok());
+    assert!(extractor.get_media_segment(1).is_ok());
+  }
+
+  #[test]
+  fn test_eac3_extractor_false_sync_word() {
+    let sample = create_joc_sample();
+    let junk = create_false_sync_frame();
+    let mut extractor = EAC3Extractor::create(1, "eng".to_string());
+
+    // The junk is split across the first and second PES, so the second sample starts in the second PES
+    let first_pes = create_pes(0xBD, Some(9000), &[sample.clone(), junk[0..4].to_vec()].concat());
+    let second_pes = create_pes(0xBD, Some(20000), &[junk[4..].to_vec(), sample.clone()].concat());
+    extractor.accumulate_pes_payload(PESPacket::parse(&first_pes).unwrap()).unwrap();
+    extractor.accumulate_pes_payload(PESPacket::parse(&second_pes).unwrap()).unwrap();
+    extractor.flush_final_media().unwrap();
+
+    let dts: Vec<u64> = extractor.samples.iter().map(|sample|sample.dts).collect();
+    assert_eq!(dts, vec![9000, 20000]);
+    assert!(extractor.samples.iter().all(|sample|sample.get_data().len() == 512));
+  }
+}
@@ -0,0 +1,362 @@
+use crate::codec::h265::nal_unit_header::{NALUnitHeader, AUD_NUT, FD_NUT, PPS_NUT, SPS_NUT, VPS_NUT};
+use crate::codec::h265::sequence_parameter_set::SequenceParameterSet;
+use crate::container::isobmff::configuration_records::hvcC::HEVCDecoderConfigurationRecordBuilder;
+use crate::container::isobmff::sample_entry::{
+    hevc_sample_entry::HEVCSampleEntryBuilder, sample_entry::SampleEntryBuilder,
+    visual_sample_entry::VisualSampleEntryBuilder,
+};
+use crate::container::isobmff::BoxBuilder;
+use crate::container::isobmff::boxes::SampleFlag;
+use crate::{
+    container::{
+        isobmff::HandlerType,
+        remux::extractor::{TSExtractor, ts::access_unit::{self, AccessUnit, AccessUnitAssembler}},
+        transport_stream::{pes_packet, timestamp_unwrapper::TimestampUnwrapper},
+        writer::mp4_writer::Mp4Writer,
+    },
+    error::CustomError,
+};
+
+/// What the slice segments of an access unit tell about the picture
+#[derive(Debug, Default)]
+struct HEVCPicture {
+    is_irap: bool,
+    // Whether any slice segment is of a picture that others can reference
+    is_reference: bool,
+}
+
+pub struct HEVCExtractor {
+    track_id: usize,
+    language: String,
+    // First parameter sets of the stream, which go in the hvcC
+    vps_nal: Vec<u8>,
+    sps_nal: Vec<u8>,
+    pps_nal: Vec<u8>,
+    // Keep the parameter sets in the samples and signal them as hev1 instead of hvc1
+    in_band_parameter_sets: bool,
+    // A parameter set differed from the one in the hvcC, so it was kept in the sample
+    changed_parameter_sets: bool,
+    access_unit_assembler: AccessUnitAssembler<HEVCPicture>,
+    signed_comp_offset: bool,
+    all_same_timestamps: bool,
+    timestamp_unwrapper: TimestampUnwrapper,
+}
+
+impl TSExtractor for HEVCExtractor {
+    fn seed_timestamps(&mut self, reference: u64) {
+        self.timestamp_unwrapper.seed(reference);
+    }
+
+    fn accumulate_pes_payload(&mut self, pes: pes_packet::PESPacket) -> Result<(), CustomError> {
+        if let Some(pts) = pes.pts {
+            // A PES packet with timestamps starts a new access unit, so the NAL unit in the bucket is complete
+            self.flush_bucket();
+            // Can assume dts is there because the pes parser will set it if its not there
+            let dts = self.timestamp_unwrapper.unwrap_timestamp(pes.dts.unwrap_or(pts));
+            let pts = self.timestamp_unwrapper.unwrap_timestamp(pts);
+            // Set the flag that the composition offset will be negative. Will set the version in trun to 1
+            if dts > pts {
+                self.signed_comp_offset = true;
+            }
+            if pts != dts {
+                self.all_same_timestamps = false;
+            }
+            self.access_unit_assembler.set_pending_timestamp(pts, dts);
+        }
+
+        for nal_unit in self.access_unit_assembler.split_nal_units(pes.payload_data) {
+            self.handle_nal_unit(nal_unit);
+        }
+        Ok(())
+    }
+
+    fn is_all_same_timestamps(&self) -> bool {
+        self.all_same_timestamps
+    }
+
+    fn is_signed_comp_offset(&self) -> bool {
+        self.signed_comp_offset
+    }
+
+    fn has_codec_config(&self) -> bool {
+        !self.vps_nal.is_empty() && !self.sps_nal.is_empty() && !self.pps_nal.is_empty()
+    }
+
+    fn build_sample_entry(&mut self) -> Result<Vec<u8>, CustomError> {
+        if self.has_codec_config() {
+            let sps = SequenceParameterSet::parse(&self.sps_nal)?;
+            return HEVCSampleEntryBuilder::create_builder()
+                .sample_entry(SampleEntryBuilder::create_builder())
+                .visual_sample_entry(
+                    VisualSampleEntryBuilder::create_builder().dimensions(sps.width(), sps.height()),
+                )
+                .hvc_c(
+                    HEVCDecoderConfigurationRecordBuilder::create_builder()
+                        .vps(&self.vps_nal)
+                        .sps(&self.sps_nal)
+                        .pps(&self.pps_nal)
+                        .array_completeness(!self.in_band_parameter_sets && !self.changed_parameter_sets),
+                )
+                .build();
+        }
+
+        println!(
+            "HEVCExtractor :: build_sample_entry :: No vps, sps or pps available. Returning empty vector"
+        );
+        Ok(vec![])
+    }
+
+    fn flush_final_media(&mut self) -> Result<(), CustomError> {
+        self.flush_bucket();
+        self.access_unit_assembler.finish_access_unit();
+        // There is no next access unit to determine the duration of the last one, so it gets the one before it
+        let duration = self.access_unit_assembler.get_access_units().last().map(|previous|previous.duration).unwrap_or_default();
+        self.access_unit_assembler.finish_last_access_unit(duration);
+        Ok(())
+    }
+
+    fn get_timescale(&self) -> u32 {
+        90000
+    }
+
+    fn get_init_writer(&mut self) -> Result<(Mp4Writer, Vec<u8>), CustomError> {
+        let sample_entry_data = self.build_sample_entry()?;
+        let (width, height) = SequenceParameterSet::parse(&self.sps_nal)
+            .map(|sps|(sps.width(), sps.height()))
+            .unwrap_or_default();
+
+        let writer = Mp4Writer::create_mp4_writer()
+            .timescale(self.get_timescale())
+            .handler(HandlerType::VIDE)
+            .track_id(self.track_id)
+            .language(&self.language)
+            .width(width)
+            .height(height);
+        Ok((writer, sample_entry_data))
+    }
+
+    fn get_media_writer(&mut self) -> Result<Mp4Writer, CustomError> {
+        let default_sample_duration = self.get_default_sample_duration();
+        let media_data = access_unit::convert_access_units_to_sample_infos(
+            self.access_unit_assembler.take_access_units(),
+            HEVCExtractor::get_sample_flags,
+        );
+        let trun_version = if self.is_signed_comp_offset() {1u8} else {0u8};
+
+        Ok(Mp4Writer::create_mp4_writer()
+            .track_id(self.track_id)
+            .timescale(self.get_timescale())
+            .is_all_same_timestamps(self.is_all_same_timestamps())
+            .trun_version(trun_version)
+            .default_sample_duration(default_sample_duration)
+            .samples(media_data))
+    }
+
+    fn get_default_sample_duration(&self) -> u32 {
+        self.access_unit_assembler.get_access_units().first().map(|access_unit|access_unit.duration).unwrap_or_default()
+    }
+}
+
+impl HEVCExtractor {
+    pub fn create(track_id: usize, language: String) -> HEVCExtractor {
+        HEVCExtractor {
+            track_id,
+            language,
+            vps_nal: vec![],
+            sps_nal: vec![],
+            pps_nal: vec![],
+            in_band_parameter_sets: false,
+            changed_parameter_sets: false,
+            access_unit_assembler: AccessUnitAssembler::create(),
+            all_same_timestamps: true,
+            signed_comp_offset: false,
+            timestamp_unwrapper: TimestampUnwrapper::create(),
+        }
+    }
+
+    /// Keep the VPS, SPS and PPS in the samples and use the hev1 sample entry. For streams whose parameter sets change.
+    pub fn in_band_parameter_sets(mut self, in_band_parameter_sets: bool) -> HEVCExtractor {
+        self.in_band_parameter_sets = in_band_parameter_sets;
+        self
+    }
+
+    fn flush_bucket(&mut self) {
+        if let Some(nal_unit) = self.access_unit_assembler.flush_bucket() {
+            self.handle_nal_unit(nal_unit);
+        }
+    }
+
+    /// Group the NAL units into access units. ITU-T H.265; 7.4.2.4.4 A new access unit starts with an access unit
+    /// delimiter, a parameter set, a prefix SEI or the first slice segment of a picture that follow a picture.
+    fn handle_nal_unit(&mut self, nal_unit: Vec<u8>) {
+        let header = match NALUnitHeader::parse(&nal_unit) {
+            Ok(header) => header,
+            Err(err) => {
+                println!("HEVCExtractor :: handle_nal_unit :: Dropping a NAL unit: {:?}", err);
+                return;
+            }
+        };
+        let current_has_vcl = self.access_unit_assembler.current_has_vcl();
+        let is_first_slice_segment = header.is_vcl() && nal_unit.len() > 2 && (nal_unit[2] & 0x80) != 0;
+        if (header.nal_unit_type == AUD_NUT || (header.is_access_unit_prefix() && current_has_vcl) ||
+            (is_first_slice_segment && current_has_vcl)) && self.access_unit_assembler.has_current_access_unit() {
+            self.access_unit_assembler.finish_access_unit();
+        }
+
+        // Once a parameter set changed they all stay in the samples, so the decoder always has the one in use
+        if header.is_parameter_set() {
+            self.store_parameter_set(header.nal_unit_type, &nal_unit);
+        }
+        if header.nal_unit_type == AUD_NUT || header.nal_unit_type == FD_NUT ||
+            (header.is_parameter_set() && !self.in_band_parameter_sets && !self.changed_parameter_sets) {
+            return;
+        }
+
+        if !self.access_unit_assembler.has_current_access_unit() {
+            let pending_timestamp = self.access_unit_assembler.take_pending_timestamp();
+            let has_timestamp = pending_timestamp.is_some();
+            let (pts, dts) = match pending_timestamp {
+                Some(timestamp) => timestamp,
+                None => {
+                    // Reuse the timestamps of the previous access unit when the PES packet had none
+                    self.access_unit_assembler
+                        .get_hold_access_unit()
+                        .map(|access_unit|(access_unit.pts, access_unit.dts))
+                        .unwrap_or_default()
+                }
+            };
+            self.access_unit_assembler.start_access_unit(pts, dts, has_timestamp);
+        }
+        if let Some(access_unit) = self.access_unit_assembler.get_current_access_unit() {
+            access_unit.has_vcl |= header.is_vcl();
+            access_unit.info.is_irap |= header.is_irap();
+            access_unit.info.is_reference |= header.is_vcl() && !header.is_sub_layer_non_reference();
+            access_unit.nal_units.push(nal_unit);
+        }
+    }
+
+    /// Keep the first parameter set of each type for the hvcC. One that differs from it has to stay in the sample for
+    /// the pictures that use it.
+    fn store_parameter_set(&mut self, nal_unit_type: u8, nal_unit: &[u8]) {
+        let stored_nal = if nal_unit_type == VPS_NUT {
+            &mut self.vps_nal
+        } else if nal_unit_type == SPS_NUT {
+            &mut self.sps_nal
+        } else if nal_unit_type == PPS_NUT {
+            &mut self.pps_nal
+        } else {
+            return;
+        };
+        if stored_nal.is_empty() {
+            *stored_nal = nal_unit.to_vec();
+            return;
+        }
+        if stored_nal.as_slice() == nal_unit {
+            return;
+        }
+        if !self.in_band_parameter_sets && !self.changed_parameter_sets {
+            println!("HEVCExtractor :: store_parameter_set :: The parameter sets changed. Keeping them in the samples, which needs hev1");
+        }
+        self.changed_parameter_sets = true;
+    }
+
+    /// IRAP pictures are sync samples. Sub-layer non-reference pictures are disposable.
+    fn get_sample_flags(access_unit: &AccessUnit<HEVCPicture>) -> u32 {
+        let sample_is_depended_on = if access_unit.info.is_reference { 1 } else { 2 };
+        if access_unit.info.is_irap {
+            SampleFlag::generate_sample_flags(2, sample_is_depended_on, false)
+        } else {
+            SampleFlag::generate_sample_flags(1, sample_is_depended_on, true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::h265::sequence_parameter_set::tests::get_sps;
+    use crate::container::transport_stream::{pes_packet::PESPacket, test_util::{create_annex_b, create_pes}};
+
+    #[test]
+    fn test_hevc_extractor_access_units() {
+        let aud = vec![0x46, 0x01, 0x50];
+        let vps = vec![0x40, 0x01, 0x0C];
+        let pps = vec![0x44, 0x01, 0xC1, 0x72, 0xB4, 0x62, 0x40];
+        // IDR_W_RADL with two slice segments (first_slice_segment_in_pic_flag set on the first)
+        let idr_first_slice = vec![0x26, 0x01, 0xAF, 0x11];
+        let idr_second_slice = vec![0x26, 0x01, 0x2F, 0x22];
+        // TRAIL_R
+        let trail = vec![0x02, 0x01, 0xD0, 0x33];
+
+        let first_pes = create_pes(
+            0xE0,
+            Some(3000),
+            &create_annex_b(&[aud.clone(), vps, get_sps(), pps, idr_first_slice, idr_second_slice]),
+        );
+        // The second picture has no AUD and its slice is split across the PES packet and the next one
+        let second_payload = create_annex_b(std::slice::from_ref(&trail));
+        let second_pes = create_pes(0xE0, Some(6000), &second_payload[0..5]);
+        let third_pes = [
+            vec![0x00, 0x00, 0x01, 0xE0, 0x00, 0x00, 0x80, 0x00, 0x00],
+            second_payload[5..].to_vec(),
+        ].concat();
+        let fourth_pes = create_pes(0xE0, Some(9000), &create_annex_b(&[aud, trail]));
+
+        let mut extractor = HEVCExtractor::create(1, "und".to_string());
+        for pes in [first_pes, second_pes, third_pes, fourth_pes].iter() {
+            extractor.accumulate_pes_payload(PESPacket::parse(pes).unwrap()).unwrap();
+        }
+        assert!(extractor.has_codec_config());
+        extractor.flush_final_media().unwrap();
+
+        assert_eq!(extractor.access_unit_assembler.get_access_units().len(), 3);
+        let first = &extractor.access_unit_assembler.get_access_units()[0];
+        // The parameter sets and the AUD are not part of the sample
+        assert_eq!(first.nal_units.len(), 2);
+        assert!(first.info.is_irap);
+        assert_eq!((first.dts, first.duration), (3000, 3000));
+        let second = &extractor.access_unit_assembler.get_access_units()[1];
+        assert_eq!(second.nal_units, vec![vec![0x02, 0x01, 0xD0, 0x33]]);
+        assert!(!second.info.is_irap);
+        assert_eq!((second.dts, second.duration), (6000, 3000));
+        assert_eq!((extractor.access_unit_assembler.get_access_units()[2].dts, extractor.access_unit_assembler.get_access_units()[2].duration), (9000, 3000));
+
+        let init_segment = extractor.get_init_segment().unwrap();
+        assert!(init_segment.windows(4).any(|window|window == [0x68, 0x76, 0x63, 0x31]));
+        let sample_infos = access_unit::convert_access_units_to_sample_infos(extractor.access_unit_assembler.take_access_units(), HEVCExtractor::get_sample_flags);
+        // Key frame that others depend on, then non key frames that others depend on
+        assert_eq!(sample_infos[0].sample_flags, Some(0x02400000));
+        assert_eq!(sample_infos[1].sample_flags, Some(0x01410000));
+        assert_eq!(sample_infos[0].data.len(), 4 + 4 + 4 + 4);
+    }
+
+    #[test]
+    fn test_hevc_extractor_changed_parameter_sets() {
+        let vps = vec![0x40, 0x01, 0x0C];
+        let pps = vec![0x44, 0x01, 0xC1, 0x72, 0xB4, 0x62, 0x40];
+        let changed_pps = vec![0x44, 0x01, 0xC1, 0x73, 0xB4, 0x62, 0x40];
+        let idr = vec![0x26, 0x01, 0xAF, 0x11];
+        let trail = vec![0x02, 0x01, 0xD0, 0x33];
+        let first_pes = create_pes(0xE0, Some(3000), &create_annex_b(&[vps.clone(), get_sps(), pps.clone(), idr]));
+        // The same VPS and SPS again, but a PPS that differs from the one in the hvcC
+        let second_pes = create_pes(0xE0, Some(6000), &create_annex_b(&[vps, get_sps(), changed_pps.clone(), trail.clone()]));
+        // Back to the PPS of the hvcC, which has to be in the sample as well now
+        let third_pes = create_pes(0xE0, Some(9000), &create_annex_b(&[pps.clone(), trail.clone()]));
+
+        let mut extractor = HEVCExtractor::create(1, "und".to_string());
+        for pes in [first_pes, second_pes, third_pes].iter() {
+            extractor.accumulate_pes_payload(PESPacket::parse(pes).unwrap()).unwrap();
+        }
+        extractor.flush_final_media().unwrap();
+
+        assert_eq!(extractor.pps_nal, pps);
+        let access_units = extractor.access_unit_assembler.get_access_units();
+        assert_eq!(access_units[0].nal_units.len(), 1);
+        assert_eq!(access_units[1].nal_units, vec![changed_pps, trail.clone()]);
+        assert_eq!(access_units[2].nal_units, vec![pps, trail]);
+        // The samples carry parameter sets now, which hvc1 doesn't allow
+        let init_segment = extractor.get_init_segment().unwrap();
+        assert!(init_segment.windows(4).any(|window|window == [0x68, 0x65, 0x76, 0x31]));
+        assert!(!init_segment.windows(4).any(|window|window == [0x68, 0x76, 0x63, 0x31]));
+    }
+}
@@ -1,2 +1,8 @@
+pub mod access_unit;
 pub mod aac_extractor;
-pub mod avc_extractor;
\ No newline at end of file
+pub mod ac3_extractor;
+pub mod eac3_extractor;
+pub mod avc_extractor;
+pub mod hevc_extractor;
+pub mod mpeg_audio_extractor;
+pub mod pes_timestamps;
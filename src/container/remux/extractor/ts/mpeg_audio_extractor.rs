@@ -0,0 +1,216 @@
+use crate::container::isobmff::{BoxBuilder, HandlerType};
+use crate::container::isobmff::boxes::SYNC_SAMPLE_FLAGS;
+use crate::container::isobmff::descriptors::{dec_config_descriptor::DecoderConfigDescriptorBuilder, es_descriptor::ESDescriptorBuidler};
+use crate::container::isobmff::sample_entry::{audio_sample_entry::AudioSampleEntryBuilder, mp4a_sample_entry::MP4ASampleEntryBuilder, sample_entry::SampleEntryBuilder};
+use crate::container::remux::extractor::{TSExtractor, ts::pes_timestamps::PESTimestamps};
+use crate::container::transport_stream::{mpeg_audio::{MPEGAudio, MPEGAudioFrame, MPEGAudioFrameHeader}, pes_packet};
+use crate::container::writer::mp4_writer::{Mp4Writer, SampleInfo};
+use crate::error::CustomError;
+
+/// Extracts MPEG-1/2 audio (MP2/MP3) frames into an mp4a track with an objectTypeIndication of 0x6B or 0x69
+pub struct MPEGAudioExtractor {
+  track_id: usize,
+  language: String,
+  // Start of a frame that continues in the next PES packet
+  bucket: Vec<u8>,
+  pes_timestamps: PESTimestamps,
+  frames: Vec<MPEGAudioFrame>,
+  // Header of the first frame, which describes the stream for the sample entry
+  config: Option<MPEGAudioFrameHeader>,
+}
+
+impl TSExtractor for MPEGAudioExtractor {
+  fn seed_timestamps(&mut self, reference: u64) {
+    self.pes_timestamps.seed(reference);
+  }
+
+  fn accumulate_pes_payload(&mut self, pes: pes_packet::PESPacket) -> Result<(), CustomError> {
+    self.pes_timestamps.push(&pes, self.bucket.len());
+    self.bucket.extend_from_slice(pes.payload_data);
+    self.parse_frames()
+  }
+
+  fn is_all_same_timestamps(&self) -> bool {
+    true
+  }
+
+  fn is_signed_comp_offset(&self) -> bool {
+    false
+  }
+
+  fn has_codec_config(&self) -> bool {
+    self.config.is_some()
+  }
+
+  fn build_sample_entry(&mut self) -> Result<Vec<u8>, CustomError> {
+    if let Some(header) = self.config.as_ref() {
+      return MP4ASampleEntryBuilder::create_builder()
+        .sample_entry(
+          SampleEntryBuilder::create_builder()
+        )
+        .audio_sample_entry(
+          AudioSampleEntryBuilder::create_builder()
+            .channel_count(header.get_channel_count())
+            .sample_rate(header.get_sample_rate())
+        )
+        .esds(
+          ESDescriptorBuidler::create_builder()
+            .dec_conf_desc(
+              DecoderConfigDescriptorBuilder::create_builder()
+                .object_type_indication(header.get_object_type_indication())
+            )
+        )
+        .build();
+    }
+    println!("MPEGAudioExtractor :: build_sample_entry :: No MPEG audio frames available. Returning empty vector");
+    Ok(vec![])
+  }
+
+  fn flush_final_media(&mut self) -> Result<(), CustomError> {
+    // Every complete frame was already parsed, so whatever is left is a truncated frame
+    if !self.bucket.is_empty() {
+      println!("MPEGAudioExtractor :: flush_final_media :: Dropping {} bytes of an incomplete frame", self.bucket.len());
+      self.bucket.clear();
+    }
+    self.pes_timestamps.clear();
+    Ok(())
+  }
+
+  fn get_timescale(&self) -> u32 {
+    self.config
+      .as_ref()
+      .map(|header|header.get_sample_rate())
+      .unwrap_or_default()
+  }
+
+  fn get_init_writer(&mut self) -> Result<(Mp4Writer, Vec<u8>), CustomError> {
+    let sample_entry_data = self.build_sample_entry()?;
+
+    let writer = Mp4Writer::create_mp4_writer()
+      .timescale(self.get_timescale())
+      .handler(HandlerType::SOUN)
+      .track_id(self.track_id)
+      .language(&self.language);
+    Ok((writer, sample_entry_data))
+  }
+
+  fn get_media_writer(&mut self) -> Result<Mp4Writer, CustomError> {
+    let media_data = MPEGAudioExtractor::convert_frames_to_sample_infos(std::mem::take(&mut self.frames), self.get_timescale());
+    Ok(Mp4Writer::create_mp4_writer()
+      .track_id(self.track_id)
+      .timescale(self.get_timescale())
+      .default_sample_duration(self.get_default_sample_duration())
+      .samples(media_data))
+  }
+
+  fn get_default_sample_duration(&self) -> u32 {
+    self.config
+      .as_ref()
+      .map(|header|header.get_samples_per_frame())
+      .unwrap_or_default()
+  }
+}
+
+impl MPEGAudioExtractor {
+  pub fn create(track_id: usize, language: String) -> MPEGAudioExtractor {
+    MPEGAudioExtractor {
+      track_id,
+      language,
+      bucket: vec![],
+      pes_timestamps: PESTimestamps::create(),
+      frames: vec![],
+      config: None,
+    }
+  }
+
+  /// Move the complete frames out of the bucket. A frame gets the timestamps of the PES packet it starts in. The
+  /// ones that start in a PES packet without timestamps (or after the first frame of one) are placed one frame
+  /// duration after the previous frame.
+  fn parse_frames(&mut self) -> Result<(), CustomError> {
+    let (mut frames, size) = MPEGAudio::parse(&self.bucket)?;
+    for frame in frames.iter_mut() {
+      let (pts, dts) = self.pes_timestamps.get_frame_timestamps(
+        frame.offset,
+        frame.header.get_samples_per_frame(),
+        frame.header.get_sample_rate()
+      );
+      frame.set_pts(pts);
+      frame.set_dts(dts);
+
+      if self.config.is_none() {
+        self.config = Some(frame.header.clone());
+      }
+    }
+
+    self.frames.append(&mut frames);
+    self.bucket.drain(0..size);
+    self.pes_timestamps.drain(size);
+    Ok(())
+  }
+
+  /// The frame timestamps are in 90 kHz, so they get converted to the timescale of the track (sample rate)
+  fn convert_frames_to_sample_infos(frames: Vec<MPEGAudioFrame>, timescale: u32) -> Vec<SampleInfo> {
+    frames
+      .into_iter()
+      .map(|frame| {
+        SampleInfo{
+          sample_flags: Some(SYNC_SAMPLE_FLAGS),
+          sample_duration: Some(frame.header.get_samples_per_frame()),
+          dts: frame.dts * timescale as u64 / 90000,
+          pts: frame.pts * timescale as u64 / 90000,
+          data: frame.data,
+        }
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::container::isobmff::{get_codec, get_channel_count};
+  use crate::media::TrackType;
+  use crate::container::transport_stream::mpeg_audio::tests::{create_false_sync_frame, create_mp2_frame};
+  use crate::container::transport_stream::{pes_packet::PESPacket, test_util::create_pes};
+
+  #[test]
+  fn test_mpeg_audio_extractor() {
+    let frames = [create_mp2_frame(), create_mp2_frame(), create_mp2_frame()].concat();
+    let mut extractor = MPEGAudioExtractor::create(1, "eng".to_string());
+
+    let first_pes = create_pes(0xC0, Some(9000), &frames[0..(576 + 100)]);
+    let second_pes = create_pes(0xC0, Some(13320), &frames[(576 + 100)..]);
+    extractor.accumulate_pes_payload(PESPacket::parse(&first_pes).unwrap()).unwrap();
+    extractor.accumulate_pes_payload(PESPacket::parse(&second_pes).unwrap()).unwrap();
+    extractor.flush_final_media().unwrap();
+
+    // The second frame starts in the first PES packet, so it's placed 1152 samples after the first one. The PTS of
+    // the second PES packet belongs to the third frame.
+    let dts: Vec<u64> = extractor.frames.iter().map(|frame|frame.dts).collect();
+    assert_eq!(dts, vec![9000, 11160, 13320]);
+    assert_eq!(extractor.get_timescale(), 48000);
+    assert_eq!(extractor.get_default_sample_duration(), 1152);
+
+    let init_segment = extractor.get_init_segment().unwrap();
+    assert_eq!(get_codec(&TrackType::AUDIO, &init_segment).unwrap(), "mp4a.6B");
+    assert_eq!(get_channel_count(&init_segment).unwrap(), "2");
+    assert!(extractor.get_media_segment(1).is_ok());
+  }
+
+  #[test]
+  fn test_mpeg_audio_extractor_false_sync_word() {
+    let frame = create_mp2_frame();
+    let junk = create_false_sync_frame();
+    let mut extractor = MPEGAudioExtractor::create(1, "eng".to_string());
+
+    // The junk is split across the PES packets, so the second frame starts in the second PES
+    let first_pes = create_pes(0xC0, Some(9000), &[frame.clone(), junk[0..2].to_vec()].concat());
+    let second_pes = create_pes(0xC0, Some(20000), &[junk[2..].to_vec(), frame.clone()].concat());
+    extractor.accumulate_pes_payload(PESPacket::parse(&first_pes).unwrap()).unwrap();
+    extractor.accumulate_pes_payload(PESPacket::parse(&second_pes).unwrap()).unwrap();
+
+    let dts: Vec<u64> = extractor.frames.iter().map(|frame|frame.dts).collect();
+    assert_eq!(dts, vec![9000, 20000]);
+    assert!(extractor.bucket.is_empty());
+  }
+}
@@ -0,0 +1,72 @@
+use crate::container::transport_stream::{pes_packet::PESPacket, timestamp_unwrapper::TimestampUnwrapper};
+
+/// Timestamps (unwrapped, 90 kHz) of a PES packet and where its payload starts in the bucket
+struct PESTimestamp {
+  offset: usize,
+  pts: u64,
+  dts: u64,
+}
+
+/// Hands out the timestamps of the audio frames parsed out of a bucket of PES payloads. A frame gets the timestamps
+/// of the PES packet it starts in. The ones that start in a PES packet without timestamps (or after the first frame
+/// of one) are placed right after the samples of the previous frames.
+pub struct PESTimestamps {
+  pes_timestamps: Vec<PESTimestamp>,
+  // Last PES timestamp applied to a frame and the number of samples since then
+  last_timestamp: Option<(u64, u64)>,
+  samples_since_timestamp: u64,
+  timestamp_unwrapper: TimestampUnwrapper,
+}
+
+impl PESTimestamps {
+  pub fn create() -> PESTimestamps {
+    PESTimestamps {
+      pes_timestamps: vec![],
+      last_timestamp: None,
+      samples_since_timestamp: 0,
+      timestamp_unwrapper: TimestampUnwrapper::create(),
+    }
+  }
+
+  /// Place the first timestamp on the side of the wrap closest to the reference (ex. the first timestamp of the program)
+  pub fn seed(&mut self, reference: u64) {
+    self.timestamp_unwrapper.seed(reference);
+  }
+
+  /// Queue the timestamps of a PES packet whose payload starts at offset in the bucket
+  pub fn push(&mut self, pes: &PESPacket, offset: usize) {
+    if let Some(pts) = pes.pts {
+      let dts = pes.dts.unwrap_or(pts);
+      self.pes_timestamps.push(PESTimestamp {
+        offset,
+        dts: self.timestamp_unwrapper.unwrap_timestamp(dts),
+        pts: self.timestamp_unwrapper.unwrap_timestamp(pts),
+      });
+    }
+  }
+
+  /// Timestamps (pts, dts) of a frame that starts at frame_start in the bucket and holds sample_count samples
+  pub fn get_frame_timestamps(&mut self, frame_start: usize, sample_count: u32, sample_rate: u32) -> (u64, u64) {
+    // A PES packet starting at or before this frame holds the start of it. The latest one wins.
+    while !self.pes_timestamps.is_empty() && self.pes_timestamps[0].offset <= frame_start {
+      let pes_timestamp = self.pes_timestamps.remove(0);
+      self.last_timestamp = Some((pes_timestamp.pts, pes_timestamp.dts));
+      self.samples_since_timestamp = 0;
+    }
+    let (pts, dts) = self.last_timestamp.unwrap_or((0, 0));
+    let offset = self.samples_since_timestamp * 90000 / sample_rate.max(1) as u64;
+    self.samples_since_timestamp += sample_count as u64;
+    (pts + offset, dts + offset)
+  }
+
+  /// The first size bytes were drained from the bucket
+  pub fn drain(&mut self, size: usize) {
+    for pes_timestamp in self.pes_timestamps.iter_mut() {
+      pes_timestamp.offset = pes_timestamp.offset.saturating_sub(size);
+    }
+  }
+
+  pub fn clear(&mut self) {
+    self.pes_timestamps.clear();
+  }
+}
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use crate::container::remux::ts_demuxer::{DemuxEvent, DemuxedTrack, TSDemuxer};
+use crate::container::remux::{
+    collect_track_segments, RemuxOptions, TrackSegments, REMUX_CHUNK_PACKETS, TS_PACKET_SIZE,
+};
+use crate::container::transport_stream::continuity_counter::ContinuityReport;
+use crate::error::CustomError;
+
+/// Remux session for a sequence of TS segments (ex. the segments of an HLS rendition) into one init segment per track
+/// followed by media only segments. The codec configuration, the timestamps and the mfhd sequence numbers carry over
+/// from one TS segment to the next, so the tfdt of every media segment continues where the previous one ended and
+/// the track ids stay the same for the whole sequence. AVC and HEVC tracks keep their parameter sets in the samples
+/// (avc3, hev1), as the init segment goes out before the parameter sets of the later TS segments are known.
+pub struct MediaOnlyRemuxer {
+    demuxer: TSDemuxer,
+    init_segments: BTreeMap<usize, Vec<u8>>,
+}
+
+impl MediaOnlyRemuxer {
+    pub fn create(options: RemuxOptions) -> MediaOnlyRemuxer {
+        MediaOnlyRemuxer {
+            demuxer: TSDemuxer::create(options),
+            init_segments: BTreeMap::new(),
+        }
+    }
+
+    pub fn get_tracks(&self) -> Vec<DemuxedTrack> {
+        self.demuxer.get_tracks()
+    }
+
+    /// Init segment of the track. Available once the codec configuration of the track has been found.
+    pub fn get_init_segment(&self, track_id: usize) -> Option<&[u8]> {
+        self.init_segments.get(&track_id).map(|data| data.as_slice())
+    }
+
+    pub fn get_continuity_report(&self) -> &ContinuityReport {
+        self.demuxer.get_continuity_report()
+    }
+
+    /// Remux the next TS segment of the sequence. The returned init segment of a track is only set for the TS
+    /// segment in which it was first found. The last sample of every track is held back until the next TS segment
+    /// (or finish), because its duration is only known once the sample after it is found.
+    pub fn push_segment(&mut self, ts_segment: &[u8]) -> Result<Vec<TrackSegments>, CustomError> {
+        let mut events: Vec<DemuxEvent> = vec![];
+        for chunk in ts_segment.chunks(TS_PACKET_SIZE * REMUX_CHUNK_PACKETS) {
+            events.append(&mut self.demuxer.push(chunk)?);
+        }
+        events.append(&mut self.demuxer.cut_media_segments()?);
+        Ok(self.collect_track_segments(events))
+    }
+
+    /// Remux the media still held back after the last TS segment of the sequence
+    pub fn finish(&mut self) -> Result<Vec<TrackSegments>, CustomError> {
+        let events = self.demuxer.finish()?;
+        Ok(self.collect_track_segments(events))
+    }
+
+    fn collect_track_segments(&mut self, events: Vec<DemuxEvent>) -> Vec<TrackSegments> {
+        for event in events.iter() {
+            if let DemuxEvent::INIT_SEGMENT { track_id, data, .. } = event {
+                self.init_segments.insert(*track_id, data.clone());
+            }
+        }
+        collect_track_segments(self.demuxer.get_tracks(), events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::h265::sequence_parameter_set::tests::get_sps;
+    use crate::container::isobmff::boxes::tfdt::TFDT;
+    use crate::container::remux::tests::{
+        create_aac_pes, create_es_packet, create_pat_packet, create_pmt_packet,
+    };
+    use crate::container::transport_stream::test_util::{create_annex_b, create_pes};
+    use crate::util;
+
+    fn create_aac_segment(first_continuity_counter: u8, pts: &[u64]) -> Vec<u8> {
+        let mut ts_segment = [
+            create_pat_packet(&[(1, 0x1000)]),
+            create_pmt_packet(0x1000, 1, &[(0x0F, 0x101)]),
+        ]
+        .concat();
+        for (index, pts) in pts.iter().enumerate() {
+            ts_segment.append(&mut create_es_packet(
+                0x101,
+                first_continuity_counter + index as u8,
+                &create_aac_pes(*pts),
+            ));
+        }
+        ts_segment
+    }
+
+    // (mfhd sequence_number, tfdt base_media_decode_time)
+    fn get_fragment_timing(media_segment: &[u8]) -> (u32, u64) {
+        let sequence_number = util::get_u32(media_segment, 20).unwrap();
+        let base_media_decode_time = TFDT::parse(media_segment)
+            .unwrap()
+            .get_base_media_decode_time();
+        (sequence_number, base_media_decode_time)
+    }
+
+    #[test]
+    fn test_media_only_remuxer_segment_sequence() {
+        let mut remuxer = MediaOnlyRemuxer::create(RemuxOptions::default());
+
+        let first = remuxer.push_segment(&create_aac_segment(0, &[0, 1920])).unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].track_id, 1);
+        assert!(first[0].init_segment.is_some());
+        assert_eq!(remuxer.get_init_segment(1), first[0].init_segment.as_deref());
+        assert_eq!(get_fragment_timing(first[0].media_segment.as_ref().unwrap()), (1, 0));
+
+        let second = remuxer.push_segment(&create_aac_segment(2, &[3840, 5760])).unwrap();
+        assert_eq!(second[0].track_id, 1);
+        assert!(second[0].init_segment.is_none());
+        // 1920 in 90 kHz is 1024 in the 48 kHz timescale of the track
+        assert_eq!(get_fragment_timing(second[0].media_segment.as_ref().unwrap()), (2, 1024));
+
+        let last = remuxer.finish().unwrap();
+        assert_eq!(get_fragment_timing(last[0].media_segment.as_ref().unwrap()), (3, 3072));
+        assert!(remuxer.get_continuity_report().is_clean());
+    }
+
+    #[test]
+    fn test_media_only_remuxer_hevc_parameter_set_change() {
+        let vps = vec![0x40, 0x01, 0x0C];
+        let pps = vec![0x44, 0x01, 0xC1, 0x72, 0xB4, 0x62, 0x40];
+        let changed_pps = vec![0x44, 0x01, 0xC1, 0x73, 0xB4, 0x62, 0x40];
+        let idr = vec![0x26, 0x01, 0xAF, 0x11];
+        let trail = vec![0x02, 0x01, 0xD0, 0x33];
+        let create_hevc_segment = |first_continuity_counter: u8, pictures: &[(u64, Vec<Vec<u8>>)]| {
+            let mut ts_segment = [
+                create_pat_packet(&[(1, 0x1000)]),
+                create_pmt_packet(0x1000, 1, &[(0x24, 0x100)]),
+            ]
+            .concat();
+            for (index, (pts, nal_units)) in pictures.iter().enumerate() {
+                ts_segment.append(&mut create_es_packet(
+                    0x100,
+                    first_continuity_counter + index as u8,
+                    &create_pes(0xE0, Some(*pts), &create_annex_b(nal_units)),
+                ));
+            }
+            ts_segment
+        };
+        let mut remuxer = MediaOnlyRemuxer::create(RemuxOptions::default());
+
+        let first = remuxer
+            .push_segment(&create_hevc_segment(0, &[
+                (3000, vec![vps.clone(), get_sps(), pps, idr]),
+                (6000, vec![trail.clone()]),
+            ]))
+            .unwrap();
+        // The init segment went out before the PPS changed, so the parameter sets stay in the samples
+        let init_segment = first[0].init_segment.as_ref().unwrap();
+        assert!(init_segment.windows(4).any(|window| window == b"hev1"));
+        assert!(!init_segment.windows(4).any(|window| window == b"hvc1"));
+
+        // The second segment starts with a PPS that differs from the one in the hvcC
+        let second = remuxer
+            .push_segment(&create_hevc_segment(2, &[
+                (9000, vec![vps, get_sps(), changed_pps.clone(), trail.clone()]),
+                (12000, vec![trail]),
+            ]))
+            .unwrap();
+        assert!(second[0].init_segment.is_none());
+        // The pictures at the end of the segment are held back until finish
+        let last = remuxer.finish().unwrap();
+        let media_segment = [
+            second[0].media_segment.clone().unwrap(),
+            last[0].media_segment.clone().unwrap(),
+        ]
+        .concat();
+        let sized_changed_pps = [vec![0x00, 0x00, 0x00, 0x07], changed_pps].concat();
+        assert!(media_segment.windows(sized_changed_pps.len()).any(|window| window == sized_changed_pps));
+    }
+}
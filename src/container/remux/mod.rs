@@ -1,132 +1,352 @@
-use crate::container::remux::extractor::{get_ts_extractor, TSExtractor};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use crate::container::remux::media_only_remuxer::MediaOnlyRemuxer;
+use crate::container::remux::ts_demuxer::{DemuxEvent, DemuxedTrack, TSDemuxer};
+use crate::container::transport_stream::continuity_counter::ContinuityReport;
 use crate::container::transport_stream::elementary_stream_type::ElementaryStreamType;
+use crate::container::transport_stream::packet_format::{TSPacketFormat, TS_PACKET_SIZE};
+use crate::container::transport_stream::sample_aes::SampleAESKey;
 use crate::container::transport_stream::{
-    pes_packet, program_association_table::ProgramAssociationTable,
-    program_map_table::ProgramMapTable, ts_packet,
+    program_association_table::ProgramAssociationTable, psi_assembler::PSIAssembler, ts_packet,
 };
 use crate::error::CustomError;
+use crate::manifest::hls::hls_key::{HLSKey, HLSKeyMethod};
+use crate::util;
+use crate::util::aes::AES128;
 use crate::error::{
     construct_error,
     error_code::{MajorCode, RemuxMinorCode},
 };
 
 pub mod extractor;
+pub mod media_only_remuxer;
+pub mod mp4_to_ts_remuxer;
+pub mod ts_demuxer;
 
-static SYNC_BYTE: u8 = 0x47;
-static TS_PACKET_SIZE: usize = 188;
+// Number of TS packets handed to the demuxer at a time when remuxing a whole file
+static REMUX_CHUNK_PACKETS: usize = 1024;
 
 pub struct TrackSegments {
+    pub track_id: usize,
+    pub pid: u16,
+    pub stream_type: ElementaryStreamType,
+    /// ISO 639-2 language from the PMT descriptors, "und" when not signaled
+    pub language: String,
     pub init_segment: Option<Vec<u8>>,
     pub media_segment: Option<Vec<u8>>
 }
 
+/// One TrackSegments per elementary stream of the program, in the order the streams are listed in the PMT
 pub struct Mp4Tracks {
-    pub video: TrackSegments,
-    pub audio: TrackSegments
+    pub tracks: Vec<TrackSegments>,
+    /// Lost, duplicated, out of order and corrupt packets found while demuxing
+    pub continuity_report: ContinuityReport,
 }
 
-pub fn remux_ts_to_mp4(ts_file: &[u8]) -> Result<Mp4Tracks, CustomError> {
-    let mut video_ts_extractor: Option<Box<dyn TSExtractor>> = None;
-    let mut audio_ts_extractor: Option<Box<dyn TSExtractor>> = None;
-    let mut index = 0usize;
-
-    let mut pat: ProgramAssociationTable;
-    let mut pmt: ProgramMapTable;
-
-    let mut program_map_pid: u16 = u16::max_value();
-    let mut video_elem_pid = u16::max_value();
-    let mut audio_elem_pid = u16::max_value();
-
-    while index < ts_file.len() {
-        if ts_file[index] != SYNC_BYTE {
-            // Out of sync so find the next sync point
-            index = index + 1;
-            continue;
-        }
-        let packet =
-            ts_packet::TransportPacket::parse(ts_file[index..(index + TS_PACKET_SIZE)].as_ref())?;
-        // ProgramAssociationTable
+impl Mp4Tracks {
+    pub fn get_video_tracks(&self) -> Vec<&TrackSegments> {
+        self.tracks
+            .iter()
+            .filter(|track| track.stream_type.is_video())
+            .collect()
+    }
+
+    pub fn get_audio_tracks(&self) -> Vec<&TrackSegments> {
+        self.tracks
+            .iter()
+            .filter(|track| track.stream_type.is_audio())
+            .collect()
+    }
+}
+
+/// Every track of the program in a single init segment and a single media segment
+pub struct MuxedMp4 {
+    /// moov with a trak per track
+    pub init_segment: Vec<u8>,
+    /// moof with a traf per track, followed by the mdat holding the samples of every track interleaved. One after the
+    /// other for every fragment when the media is fragmented.
+    pub media_segment: Vec<u8>,
+    /// Lost, duplicated, out of order and corrupt packets found while demuxing
+    pub continuity_report: ContinuityReport,
+}
+
+/// Selects which program of a (possibly multi-program) transport stream gets remuxed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum ProgramSelector {
+    /// The program with the lowest program_number listed in the PAT
+    FIRST,
+    /// The program with the matching program_number
+    PROGRAM_NUMBER(u16),
+}
+
+impl ProgramSelector {
+    fn select(&self, pat: &ProgramAssociationTable) -> Option<(u16, u16)> {
+        match self {
+            ProgramSelector::FIRST => pat.get_first_program(),
+            ProgramSelector::PROGRAM_NUMBER(program_number) => pat
+                .get_program_map_pid(*program_number)
+                .map(|pid| (*program_number, pid)),
+        }
+    }
+}
+
+/// Get every program (program_number -> program map PID) listed in the first PAT of the transport stream. The packets
+/// can be 188 byte TS, 192 byte M2TS or 204 byte DVB packets.
+pub fn get_ts_programs(ts_file: &[u8]) -> Result<BTreeMap<u16, u16>, CustomError> {
+    let (packet_format, offset) = TSPacketFormat::detect(ts_file, true).unwrap_or((TSPacketFormat::TS, 0));
+    let (packets, _) = packet_format.read_packets(ts_file, offset);
+    let mut psi_assembler = PSIAssembler::create();
+    for packet_data in packets {
+        // Malformed packets are skipped, the PAT is repeated
+        let packet = match ts_packet::TransportPacket::parse(packet_data) {
+            Ok(packet) => packet,
+            Err(_) => continue,
+        };
         if packet.pid == 0 {
-            pat = ProgramAssociationTable::parse(packet.data, packet.payload_unit_start_indicator)
-                .unwrap();
-            program_map_pid = pat.program_map_pid;
-        }
-
-        // ProgramMapTable
-        if packet.pid == program_map_pid {
-            pmt = ProgramMapTable::parse(packet.data, packet.payload_unit_start_indicator).unwrap();
-            // Video
-            if let Some(stream_info) = pmt.video_stream_info {
-                video_elem_pid = stream_info.pid;
-                if video_ts_extractor.is_none() {
-                    let video_extractor = init_video_extractor(stream_info.stream_type)?;
-                    video_ts_extractor = Some(video_extractor);
-                }
-            }
-            // Audio
-            if let Some(stream_info) = pmt.audio_stream_info {
-                audio_elem_pid = stream_info.pid;
-                if audio_ts_extractor.is_none() {
-                    let audio_extractor = init_audio_extractor(stream_info.stream_type)?;
-                    audio_ts_extractor = Some(audio_extractor);
-                }
+            let sections = psi_assembler.push(packet.pid, packet.payload_unit_start_indicator, packet.data)?;
+            if let Some(section) = sections.first() {
+                return Ok(ProgramAssociationTable::parse(section, false)?.programs);
             }
         }
+    }
 
-        // Video PES
-        if packet.pid == video_elem_pid {
-            let pes = pes_packet::PESPacket::parse(packet.data)?;
-            video_ts_extractor
-                .as_mut()
-                .and_then(|tse| tse.accumulate_pes_payload(pes).ok());
+    Err(construct_error(
+        MajorCode::REMUX,
+        Box::new(RemuxMinorCode::PROGRAM_NOT_FOUND_ERROR),
+        "No program association table found in the transport stream".to_string(),
+        file!(),
+        line!(),
+    ))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RemuxOptions {
+    pub program_selector: ProgramSelector,
+    /// Fail the remux on the first continuity problem instead of only reporting it
+    pub strict_continuity: bool,
+    /// Leave out the ADTS frames whose CRC doesn't match instead of remuxing them. Only the CRC of ADTS frames with
+    /// several raw data blocks can be checked without decoding the audio, frames with a single one are kept as is and
+    /// counted in the unverified frames of the continuity report.
+    pub verify_adts_crc: bool,
+    /// Keep the AVC and HEVC parameter sets in the samples and use the avc3 and hev1 sample entries instead of avc1 and
+    /// hvc1. Always the case when the init segment goes out before the end of the stream (MediaOnlyRemuxer or a
+    /// TSDemuxer that isn't set to the whole stream), since a parameter set that changes after it can only be carried
+    /// in the samples.
+    pub in_band_parameter_sets: bool,
+    /// Samples per AAC frame: 1024, or 960 for streams coded with 960 sample frames, which ADTS has no way of signaling
+    pub aac_frame_length: u32,
+    /// Key and IV to decrypt the samples of the SAMPLE-AES encrypted streams with
+    pub sample_aes_key: Option<SampleAESKey>,
+}
+
+impl Default for RemuxOptions {
+    fn default() -> Self {
+        RemuxOptions {
+            program_selector: ProgramSelector::FIRST,
+            strict_continuity: false,
+            verify_adts_crc: false,
+            in_band_parameter_sets: false,
+            aac_frame_length: 1024,
+            sample_aes_key: None,
         }
+    }
+}
+
+pub fn remux_ts_to_mp4(ts_file: &[u8]) -> Result<Mp4Tracks, CustomError> {
+    remux_ts_program_to_mp4(ts_file, ProgramSelector::FIRST)
+}
 
-        // Audio PES
-        if packet.pid == audio_elem_pid {
-            let pes = pes_packet::PESPacket::parse(packet.data)?;
-            audio_ts_extractor
-                .as_mut()
-                .and_then(|tse| tse.accumulate_pes_payload(pes).ok());
+/// Remux only the tracks that belong to the selected program
+pub fn remux_ts_program_to_mp4(
+    ts_file: &[u8],
+    program_selector: ProgramSelector,
+) -> Result<Mp4Tracks, CustomError> {
+    remux_ts_to_mp4_with_options(
+        ts_file,
+        RemuxOptions {
+            program_selector,
+            ..RemuxOptions::default()
+        },
+    )
+}
+
+pub fn remux_ts_to_mp4_with_options(
+    ts_file: &[u8],
+    options: RemuxOptions,
+) -> Result<Mp4Tracks, CustomError> {
+    let mut demuxer = TSDemuxer::create(options).whole_stream(true);
+    for chunk in ts_file.chunks(TS_PACKET_SIZE * REMUX_CHUNK_PACKETS) {
+        // Nothing but the PES packets gets sent while pushing when there is no fragment duration
+        demuxer.push(chunk)?;
+    }
+    let events = demuxer.finish()?;
+
+    Ok(Mp4Tracks {
+        tracks: collect_track_segments(demuxer.get_tracks(), events),
+        continuity_report: demuxer.take_continuity_report(),
+    })
+}
+
+/// Remux a TS segment of an encrypted HLS rendition, per the EXT-X-KEY that applies to it in the source playlist.
+/// The caller supplies the key fetched from the URI of the EXT-X-KEY.
+pub fn remux_encrypted_ts_to_mp4(
+    ts_file: &[u8],
+    hls_key: &HLSKey,
+    key: &[u8; 16],
+    media_sequence: u64,
+) -> Result<Mp4Tracks, CustomError> {
+    let (ts_file, options) = decrypt_ts_segment(ts_file, hls_key, key, media_sequence, RemuxOptions::default())?;
+    remux_ts_to_mp4_with_options(&ts_file, options)
+}
+
+/// Decryption stage in front of the remux. An AES-128 segment is decrypted as a whole. A SAMPLE-AES segment is
+/// returned as is, with the key and IV set in the options so the samples get decrypted while demuxing. Without a
+/// method there is nothing to decrypt.
+pub fn decrypt_ts_segment<'a>(
+    ts_segment: &'a [u8],
+    hls_key: &HLSKey,
+    key: &[u8; 16],
+    media_sequence: u64,
+    options: RemuxOptions,
+) -> Result<(Cow<'a, [u8]>, RemuxOptions), CustomError> {
+    if hls_key.method != HLSKeyMethod::NONE && !hls_key.is_identity_key_format() {
+        return Err(generate_encryption_error(format!(
+            "KEYFORMAT {} is not a plain key",
+            hls_key.key_format
+        )));
+    }
+    let iv = hls_key.get_iv(media_sequence);
+    match hls_key.method {
+        HLSKeyMethod::NONE => Ok((Cow::Borrowed(ts_segment), options)),
+        HLSKeyMethod::AES_128 => {
+            let decrypted_segment = AES128::create(key).decrypt_cbc(&iv, ts_segment)?;
+            Ok((Cow::Owned(decrypted_segment), options))
         }
+        HLSKeyMethod::SAMPLE_AES => Ok((
+            Cow::Borrowed(ts_segment),
+            RemuxOptions {
+                sample_aes_key: Some(SampleAESKey { key: *key, iv }),
+                ..options
+            },
+        )),
+        HLSKeyMethod::SAMPLE_AES_CTR => Err(generate_encryption_error(
+            "SAMPLE-AES-CTR is only used with fMP4 segments".to_string(),
+        )),
+    }
+}
 
-        index = index + TS_PACKET_SIZE;
+/// Remux all the tracks of the program into one muxed fMP4, for players that can't take a separate init and media
+/// segment per track
+pub fn remux_ts_to_muxed_mp4(ts_file: &[u8]) -> Result<MuxedMp4, CustomError> {
+    remux_ts_to_muxed_mp4_with_options(ts_file, RemuxOptions::default())
+}
+
+pub fn remux_ts_to_muxed_mp4_with_options(
+    ts_file: &[u8],
+    options: RemuxOptions,
+) -> Result<MuxedMp4, CustomError> {
+    let mut demuxer = TSDemuxer::create(options).muxed(true);
+    for chunk in ts_file.chunks(TS_PACKET_SIZE * REMUX_CHUNK_PACKETS) {
+        demuxer.push(chunk)?;
     }
+    let (init_segment, media_segment) = demuxer.finish_muxed()?;
+
+    Ok(MuxedMp4 {
+        init_segment,
+        media_segment,
+        continuity_report: demuxer.take_continuity_report(),
+    })
+}
 
-    video_ts_extractor
-        .as_mut()
-        .and_then(|tse| tse.flush_final_media().ok());
+/// Remux a single TS file into media segments (moof + mdat) only, one after the other for every track. The mfhd
+/// sequence numbers keep increasing across the tracks. Use a MediaOnlyRemuxer to remux a sequence of TS segments, so
+/// the timing and sequence numbers carry over.
+pub fn remux_ts_to_mp4_media_only(ts_file: &[u8]) -> Result<Vec<u8>, CustomError> {
+    let mut remuxer = MediaOnlyRemuxer::create(RemuxOptions::default());
+    let mut track_segments = remuxer.push_segment(ts_file)?;
+    track_segments.append(&mut remuxer.finish()?);
+    let mut media_segments = track_segments
+        .into_iter()
+        .filter_map(|track_segments| track_segments.media_segment)
+        .collect::<Vec<Vec<u8>>>()
+        .concat();
+    renumber_media_segments(&mut media_segments)?;
+    Ok(media_segments)
+}
 
-    audio_ts_extractor
-        .as_mut()
-        .and_then(|tse| tse.flush_final_media().ok());
+/// Number the moofs of the media segments 1, 2, 3... in the order they come in. Every track numbers its own media
+/// segments from 1, which repeats the sequence numbers once the tracks are put one after the other.
+fn renumber_media_segments(media_segments: &mut [u8]) -> Result<(), CustomError> {
+    let mut sequence_number = 1u32;
+    let mut offset = 0usize;
+    while offset < media_segments.len() {
+        let size = util::get_u32(media_segments, offset)? as usize;
+        if size < 8 {
+            return Err(generate_error(format!("Invalid box size {} at offset {}", size, offset)));
+        }
+        if media_segments.get((offset + 4)..(offset + 8)) == Some(b"moof".as_ref()) {
+            // The mfhd is the first box of the moof, and the sequence_number follows its version and flags
+            let sequence_number_offset = offset + 20;
+            if let Some(data) = media_segments.get_mut(sequence_number_offset..(sequence_number_offset + 4)) {
+                data.copy_from_slice(&sequence_number.to_be_bytes());
+            }
+            sequence_number += 1;
+        }
+        offset += size;
+    }
+    Ok(())
+}
 
+/// Remux a fragmented MP4 (an init segment and its media segments) into MPEG-TS, to serve TS HLS renditions from
+/// fMP4 masters. Only the AVC and AAC tracks are remuxed.
+pub fn remux_mp4_to_ts(init_segment: &[u8], media_segments: &[&[u8]]) -> Result<Vec<u8>, CustomError> {
+    mp4_to_ts_remuxer::remux(init_segment, media_segments)
+}
 
-        
-    Ok(
-        Mp4Tracks{
-            video: TrackSegments {
-                init_segment: video_ts_extractor
-                    .as_mut()
-                    .and_then(|ve| ve.get_init_segment().ok()),
-                media_segment: video_ts_extractor
-                    .as_mut()
-                    .and_then(|ve| ve.get_media_segment().ok())
-            },
-            audio: TrackSegments {
-                init_segment: audio_ts_extractor
-                    .as_mut()
-                    .and_then(|ae| ae.get_init_segment().ok()),
-                media_segment: audio_ts_extractor
-                    .as_mut()
-                    .and_then(|ae| ae.get_media_segment().ok())
-            } 
+/// One TrackSegments per track. Media segments sent for the same track are appended one after the other.
+fn collect_track_segments(tracks: Vec<DemuxedTrack>, events: Vec<DemuxEvent>) -> Vec<TrackSegments> {
+    let mut tracks: Vec<TrackSegments> = tracks
+        .into_iter()
+        .map(|track| TrackSegments {
+            track_id: track.track_id,
+            pid: track.pid,
+            stream_type: track.stream_type,
+            language: track.language,
+            init_segment: None,
+            media_segment: None,
+        })
+        .collect();
+    for event in events {
+        match event {
+            DemuxEvent::INIT_SEGMENT { track_id, data, .. } => {
+                if let Some(track_segments) = tracks.iter_mut().find(|track| track.track_id == track_id) {
+                    track_segments.init_segment = Some(data);
+                }
+            }
+            DemuxEvent::MEDIA_SEGMENT { track_id, mut data, .. } => {
+                if let Some(track_segments) = tracks.iter_mut().find(|track| track.track_id == track_id) {
+                    track_segments
+                        .media_segment
+                        .get_or_insert_with(Vec::new)
+                        .append(&mut data);
+                }
+            }
+            _ => {}
         }
-    )
+    }
+    tracks
 }
 
-pub fn remux_ts_to_mp4_media_only(ts_file: &[u8]) -> Result<Vec<u8>, CustomError> {
-    // TODO
-    Ok(vec![])
+fn generate_encryption_error(message: String) -> CustomError {
+    construct_error(
+        MajorCode::REMUX,
+        Box::new(RemuxMinorCode::UNSUPPORTED_ENCRYPTION_ERROR),
+        message,
+        file!(),
+        line!(),
+    )
 }
 
 pub fn generate_error(message: String) -> CustomError {
@@ -158,16 +378,420 @@ pub fn map_sample_frequency_index(index: u8) -> u32 {
     }
 }
 
-fn init_audio_extractor(
-    stream_type: ElementaryStreamType,
-) -> Result<Box<dyn TSExtractor>, CustomError> {
-    let audio_extractor = get_ts_extractor(stream_type)?;
-    Ok(audio_extractor)
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::isobmff::boxes::{iso_box::find_box, tfdt::TFDT, tfhd::TFHD, trun::TRUN};
+    use crate::container::transport_stream::continuity_counter::{ContinuityIssueKind, TimestampIssueKind};
+    use crate::container::transport_stream::{packet_format::SYNC_BYTE, test_util::{create_annex_b, create_pes, encrypt_cbc}};
+    use crate::codec::h264::picture_parameter_set::tests::get_pps;
+    use crate::codec::h264::sequence_parameter_set::tests::get_sps_with_pic_order_cnt_lsb;
+    use crate::codec::h264::slice_header::tests::create_slice_with_pic_order_cnt_lsb;
+    use crate::container::transport_stream::elementary_stream_type::ElementaryStreamType;
+    use crate::container::writer::ts_writer::TSWriter;
+    use crate::error::error_code::TransportStreamMinorCode;
+
+    pub(super) fn create_pat_packet(programs: &[(u16, u16)]) -> Vec<u8> {
+        let section_length = 5 + programs.len() * 4 + 4;
+        let mut packet = vec![
+            // header
+            SYNC_BYTE, 0x40, 0x00, 0x10,
+            // pointer_field
+            0x00,
+            // table_id
+            0x00,
+            0xB0, section_length as u8,
+            // transport_stream_id
+            0x00, 0x01,
+            0xC1, 0x00, 0x00,
+        ];
+        for (program_number, pid) in programs {
+            packet.append(&mut vec![
+                (program_number >> 8) as u8, (program_number & 0xFF) as u8,
+                0xE0 | (pid >> 8) as u8, (pid & 0xFF) as u8,
+            ]);
+        }
+        // CRC_32 of the section, from the table_id
+        let crc_32 = util::crc_32_mpeg2(&packet[5..]);
+        packet.extend_from_slice(&crc_32.to_be_bytes());
+        packet.resize(TS_PACKET_SIZE, 0xFF);
+        packet
+    }
+
+    pub(super) fn create_pmt_packet(pmt_pid: u16, program_number: u16, streams: &[(u8, u16)]) -> Vec<u8> {
+        let section_length = 9 + streams.len() * 5 + 4;
+        let mut packet = vec![
+            // header
+            SYNC_BYTE, 0x40 | (pmt_pid >> 8) as u8, (pmt_pid & 0xFF) as u8, 0x10,
+            // pointer_field
+            0x00,
+            // table_id
+            0x02,
+            0xB0, section_length as u8,
+            (program_number >> 8) as u8, (program_number & 0xFF) as u8,
+            0xC1, 0x00, 0x00,
+            // PCR_PID
+            0xE1, 0x00,
+            // program_info_length
+            0xF0, 0x00,
+        ];
+        for (stream_type, pid) in streams {
+            packet.append(&mut vec![
+                *stream_type,
+                0xE0 | (pid >> 8) as u8, (pid & 0xFF) as u8,
+                0xF0, 0x00,
+            ]);
+        }
+        // CRC_32 of the section, from the table_id
+        let crc_32 = util::crc_32_mpeg2(&packet[5..]);
+        packet.extend_from_slice(&crc_32.to_be_bytes());
+        packet.resize(TS_PACKET_SIZE, 0xFF);
+        packet
+    }
+
+    pub(super) fn create_es_packet(pid: u16, continuity_counter: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![
+            SYNC_BYTE, 0x40 | (pid >> 8) as u8, (pid & 0xFF) as u8, 0x10 | continuity_counter,
+        ];
+        packet.extend_from_slice(payload);
+        packet.resize(TS_PACKET_SIZE, 0xFF);
+        packet
+    }
+
+    // PES packet with a PTS holding a single 48 kHz ADTS frame
+    pub(super) fn create_aac_pes(pts: u64) -> Vec<u8> {
+        // ADTS frame with 2 bytes of raw data
+        create_pes(0xC0, Some(pts), &[0xFF, 0xF1, 0x4C, 0x80, 0x01, 0x3F, 0xFC, 0x01, 0x02])
+    }
+
+    #[test]
+    fn test_remux_ts_to_mp4_track_per_elementary_stream() {
+        let ts_file = [
+            create_pat_packet(&[(1, 0x1000)]),
+            create_pmt_packet(0x1000, 1, &[(0x1B, 0x100), (0x0F, 0x101), (0x0F, 0x102), (0x81, 0x103), (0x06, 0x104)]),
+            // Repeated PMT should not register the streams again
+            create_pmt_packet(0x1000, 1, &[(0x1B, 0x100), (0x0F, 0x101), (0x0F, 0x102), (0x81, 0x103), (0x06, 0x104)]),
+        ].concat();
+        let mp4_tracks = remux_ts_to_mp4(&ts_file).unwrap();
+        let track_ids: Vec<usize> = mp4_tracks.tracks.iter().map(|track| track.track_id).collect();
+        let pids: Vec<u16> = mp4_tracks.tracks.iter().map(|track| track.pid).collect();
+        assert_eq!(track_ids, vec![1, 2, 3, 4]);
+        assert_eq!(pids, vec![0x100, 0x101, 0x102, 0x103]);
+        assert_eq!(mp4_tracks.get_video_tracks().len(), 1);
+        assert_eq!(mp4_tracks.get_audio_tracks().len(), 3);
+    }
+
+    #[test]
+    fn test_get_ts_programs() {
+        let ts_file = create_pat_packet(&[(1, 0x100), (2, 0x200)]);
+        let programs = get_ts_programs(&ts_file).unwrap();
+        assert_eq!(programs.len(), 2);
+        assert_eq!(programs.get(&1), Some(&0x100));
+        assert_eq!(programs.get(&2), Some(&0x200));
+    }
+
+    #[test]
+    fn test_remux_ts_program_to_mp4_program_not_found() {
+        let ts_file = create_pat_packet(&[(1, 0x100), (2, 0x200)]);
+        let err = remux_ts_program_to_mp4(&ts_file, ProgramSelector::PROGRAM_NUMBER(3))
+            .err()
+            .unwrap();
+        assert_eq!(err.major, MajorCode::REMUX);
+        assert_eq!(err.minor, RemuxMinorCode::PROGRAM_NOT_FOUND_ERROR as u8);
+    }
+
+    #[test]
+    fn test_remux_ts_to_mp4_continuity_report() {
+        // PES packet header with no timestamps and an unbounded PES_packet_length
+        let pes_start: [u8; 9] = [0x00, 0x00, 0x01, 0xE0, 0x00, 0x00, 0x80, 0x00, 0x00];
+        let ts_file = [
+            create_pat_packet(&[(1, 0x1000)]),
+            create_pmt_packet(0x1000, 1, &[(0x1B, 0x100)]),
+            create_es_packet(0x100, 0, &pes_start),
+            create_es_packet(0x100, 1, &pes_start),
+            // Allowed duplicate
+            create_es_packet(0x100, 1, &pes_start),
+            // Packet 2 is lost
+            create_es_packet(0x100, 3, &pes_start),
+        ].concat();
 
-fn init_video_extractor(
-    stream_type: ElementaryStreamType,
-) -> Result<Box<dyn TSExtractor>, CustomError> {
-    let vid_extractor = get_ts_extractor(stream_type)?;
-    Ok(vid_extractor)
+        let mp4_tracks = remux_ts_to_mp4(&ts_file).unwrap();
+        let report = mp4_tracks.continuity_report;
+        assert_eq!(report.dropped_packets, 1);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.get_issues_for_pid(0x100)[0].kind, ContinuityIssueKind::LOST_PACKETS(1));
+        assert_eq!(report.issues[0].packet_index, 5);
+
+        let options = RemuxOptions {
+            strict_continuity: true,
+            ..RemuxOptions::default()
+        };
+        let err = remux_ts_to_mp4_with_options(&ts_file, options).err().unwrap();
+        assert_eq!(err.major, MajorCode::TRANSPORT_STREAM);
+        assert_eq!(err.minor, TransportStreamMinorCode::CONTINUITY_ERROR as u8);
+    }
+
+    #[test]
+    fn test_remux_ts_to_mp4_timestamp_issues() {
+        let mut ts_file = [
+            create_pat_packet(&[(1, 0x1000)]),
+            create_pmt_packet(0x1000, 1, &[(0x0F, 0x101)]),
+        ].concat();
+        // The third frame is 3 frames late
+        for (index, pts) in [0u64, 1920, 3840 + 3 * 1920].iter().enumerate() {
+            ts_file.append(&mut create_es_packet(0x101, index as u8, &create_aac_pes(*pts)));
+        }
+
+        let report = remux_ts_to_mp4(&ts_file).unwrap().continuity_report;
+        assert!(report.issues.is_empty());
+        assert!(!report.is_clean());
+        assert_eq!(report.timestamp_issues.len(), 1);
+        assert_eq!(report.timestamp_issues[0].pid, 0x101);
+        assert_eq!(report.timestamp_issues[0].kind, TimestampIssueKind::GAP);
+        assert_eq!(report.timestamp_issues[0].expected_timestamp, 3840);
+    }
+
+    #[test]
+    fn test_remux_ts_to_mp4_shared_timestamp_reference() {
+        let mut ts_file = [
+            create_pat_packet(&[(1, 0x1000)]),
+            create_pmt_packet(0x1000, 1, &[(0x0F, 0x101), (0x0F, 0x102)]),
+        ].concat();
+        // The first track starts right before the 33 bit wrap, the second one right after it
+        let wrap = 1u64 << 33;
+        let first_pes = [(0x101, wrap - 3840), (0x101, wrap - 1920), (0x101, 0), (0x102, 0), (0x101, 1920), (0x102, 1920)];
+        for (index, (pid, pts)) in first_pes.iter().enumerate() {
+            ts_file.append(&mut create_es_packet(*pid, index as u8, &create_aac_pes(*pts)));
+        }
+
+        let mp4_tracks = remux_ts_to_mp4(&ts_file).unwrap();
+        let base_media_decode_times: Vec<u64> = mp4_tracks
+            .tracks
+            .iter()
+            .map(|track| TFDT::parse(track.media_segment.as_ref().unwrap()).unwrap().get_base_media_decode_time())
+            .collect();
+        // Two frames of 1024 samples apart, instead of a wrap apart
+        assert_eq!(base_media_decode_times[1] - base_media_decode_times[0], 2048);
+    }
+
+    #[test]
+    fn test_remux_ts_to_mp4_aac_frame_length() {
+        let mut ts_file = [
+            create_pat_packet(&[(1, 0x1000)]),
+            create_pmt_packet(0x1000, 1, &[(0x0F, 0x101)]),
+        ].concat();
+        // 960 sample frames at 48 kHz
+        for index in 0..3u64 {
+            ts_file.append(&mut create_es_packet(0x101, index as u8, &create_aac_pes(index * 1800)));
+        }
+
+        for aac_frame_length in [960, 1024] {
+            let options = RemuxOptions {
+                aac_frame_length,
+                ..RemuxOptions::default()
+            };
+            let mp4_tracks = remux_ts_to_mp4_with_options(&ts_file, options).unwrap();
+            let media_segment = mp4_tracks.tracks[0].media_segment.as_ref().unwrap();
+            let moof = find_box("moof", 0, media_segment).unwrap();
+            assert_eq!(TFHD::parse(moof).unwrap().get_default_sample_duration(), Some(aac_frame_length));
+        }
+    }
+
+    // The truns of a traf, in order
+    fn read_truns(traf: &[u8]) -> Vec<TRUN> {
+        let mut truns: Vec<TRUN> = vec![];
+        let mut offset = 8usize;
+        while offset + 8 <= traf.len() {
+            let size = util::get_u32(traf, offset).unwrap() as usize;
+            if &traf[(offset + 4)..(offset + 8)] == b"trun" {
+                truns.push(TRUN::parse_trun(&traf[offset..(offset + size)]).unwrap());
+            }
+            offset += size;
+        }
+        truns
+    }
+
+    #[test]
+    fn test_remux_ts_to_muxed_mp4() {
+        // 20 pictures of 3000 and 30 AAC frames of 1920 (1024 samples at 48 kHz), a bit over 500 ms of both
+        let adts_frame = vec![0xFF, 0xF1, 0x4C, 0x80, 0x01, 0x3F, 0xFC, 0x01, 0x02];
+        let mut ts_writer = TSWriter::create_ts_writer()
+            .stream(0x100, ElementaryStreamType::H_264, "und")
+            .stream(0x101, ElementaryStreamType::AAC, "und");
+        let mut ts_file = ts_writer.write_program_tables();
+        for index in 0..20u64 {
+            let picture = if index == 0 {
+                create_annex_b(&[
+                    get_sps_with_pic_order_cnt_lsb(),
+                    get_pps(),
+                    create_slice_with_pic_order_cnt_lsb(0x65, 7, 0, Some(0)),
+                ])
+            } else {
+                create_annex_b(&[create_slice_with_pic_order_cnt_lsb(0x41, 5, 1, Some(2))])
+            };
+            ts_file.append(&mut ts_writer.write_pes(0x100, 3000 + index * 3000, None, &picture, index == 0).unwrap());
+        }
+        for index in 0..30u64 {
+            ts_file.append(&mut ts_writer.write_pes(0x101, 3000 + index * 1920, None, &adts_frame, true).unwrap());
+        }
+
+        let muxed_mp4 = remux_ts_to_muxed_mp4(&ts_file).unwrap();
+        let moov = find_box("moov", 0, &muxed_mp4.init_segment).unwrap();
+        let mvex = find_box("mvex", 8, moov).unwrap();
+        // A trex of 32 bytes per track
+        assert_eq!(mvex.len(), 8 + 32 * 2);
+
+        let moof = find_box("moof", 0, &muxed_mp4.media_segment).unwrap();
+        let video_traf = find_box("traf", 24, moof).unwrap();
+        let audio_traf = find_box("traf", 24 + video_traf.len(), moof).unwrap();
+        let video_truns = read_truns(video_traf);
+        let audio_truns = read_truns(audio_traf);
+        // A run per 500 ms of every track
+        let sample_counts: Vec<u32> = video_truns.iter().chain(audio_truns.iter()).map(|trun| trun.sample_count).collect();
+        assert_eq!(sample_counts, vec![15, 5, 24, 6]);
+
+        // The runs go in the mdat by decode time: video, audio, video, audio
+        let runs = [&video_truns[0], &audio_truns[0], &video_truns[1], &audio_truns[1]];
+        let mut data_offset = moof.len() + 8;
+        for trun in runs {
+            assert_eq!(trun.get_data_offset(), Some(data_offset as i32));
+            data_offset += trun.get_samples().iter().map(|sample| sample.sample_size.unwrap() as usize).sum::<usize>();
+        }
+        assert_eq!(data_offset, muxed_mp4.media_segment.len());
+        let audio_offset = audio_truns[0].get_data_offset().unwrap() as usize;
+        assert_eq!(muxed_mp4.media_segment[audio_offset..(audio_offset + 48)], [0x01, 0x02].repeat(24));
+        // The video sample after the audio run is a slice with its length in front
+        let video_offset = video_truns[1].get_data_offset().unwrap() as usize;
+        assert_eq!(muxed_mp4.media_segment[video_offset + 4], 0x41);
+    }
+
+    #[test]
+    fn test_remux_ts_to_mp4_media_only() {
+        let mut ts_file = [
+            create_pat_packet(&[(1, 0x1000)]),
+            create_pmt_packet(0x1000, 1, &[(0x0F, 0x101), (0x0F, 0x102)]),
+        ].concat();
+        for index in 0..4u64 {
+            ts_file.append(&mut create_es_packet(0x101, index as u8, &create_aac_pes(index * 1920)));
+            ts_file.append(&mut create_es_packet(0x102, index as u8, &create_aac_pes(index * 1920)));
+        }
+
+        let media_segments = remux_ts_to_mp4_media_only(&ts_file).unwrap();
+        let mut sequence_numbers: Vec<u32> = vec![];
+        let mut offset = 0usize;
+        while let Some(moof) = find_box("moof", offset, &media_segments) {
+            sequence_numbers.push(util::get_u32(moof, 20).unwrap());
+            let mdat = find_box("mdat", offset + moof.len(), &media_segments).unwrap();
+            offset += moof.len() + mdat.len();
+        }
+        // 2 media segments per track (the push and the finish), numbered across the tracks
+        assert_eq!(sequence_numbers, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_remux_ts_to_mp4_packet_formats() {
+        let mut packets = vec![
+            create_pat_packet(&[(1, 0x1000)]),
+            create_pmt_packet(0x1000, 1, &[(0x0F, 0x101)]),
+        ];
+        for index in 0..6u64 {
+            packets.push(create_es_packet(0x101, index as u8, &create_aac_pes(index * 1920)));
+        }
+        let expected = remux_ts_to_mp4(&packets.concat()).unwrap();
+        let expected_media_segment = expected.tracks[0].media_segment.as_ref().unwrap();
+
+        // M2TS with an arrival time stamp in front of every packet, and DVB with Reed-Solomon parity after it
+        let m2ts_file: Vec<u8> = packets
+            .iter()
+            .enumerate()
+            .flat_map(|(index, packet)| [vec![0x00, 0x00, 0x10, index as u8], packet.clone()].concat())
+            .collect();
+        let dvb_file: Vec<u8> = packets
+            .iter()
+            .flat_map(|packet| [packet.clone(), (0..16u8).collect()].concat())
+            .collect();
+        for ts_file in [m2ts_file, dvb_file] {
+            // Garbage in front and a truncated final packet
+            let ts_file = [vec![0x47, 0x00, 0x47], ts_file, vec![SYNC_BYTE, 0x41, 0x01]].concat();
+            let mp4_tracks = remux_ts_to_mp4(&ts_file).unwrap();
+            assert_eq!(mp4_tracks.tracks.len(), 1);
+            assert_eq!(mp4_tracks.tracks[0].media_segment.as_ref(), Some(expected_media_segment));
+            assert!(mp4_tracks.continuity_report.is_clean());
+            assert_eq!(get_ts_programs(&ts_file).unwrap().get(&1), Some(&0x1000));
+        }
+    }
+
+    // PES packet with a PTS holding a single 48 kHz ADTS frame of the raw data
+    fn create_aac_pes_with_raw_data(pts: u64, raw_data: &[u8]) -> Vec<u8> {
+        let frame_length = 7 + raw_data.len();
+        let adts_frame = [
+            vec![
+                0xFF, 0xF1, 0x4C, 0x80,
+                (frame_length >> 3) as u8, (((frame_length & 0x7) << 5) | 0x1F) as u8, 0xFC,
+            ],
+            raw_data.to_vec(),
+        ].concat();
+        create_pes(0xC0, Some(pts), &adts_frame)
+    }
+
+    #[test]
+    fn test_remux_encrypted_ts_to_mp4_aes_128() {
+        let key = [0x11u8; 16];
+        let mut clear_ts = [
+            create_pat_packet(&[(1, 0x1000)]),
+            create_pmt_packet(0x1000, 1, &[(0x0F, 0x101)]),
+        ].concat();
+        for index in 0..4u64 {
+            clear_ts.append(&mut create_es_packet(0x101, index as u8, &create_aac_pes(index * 1920)));
+        }
+        let expected_media_segment = remux_ts_to_mp4(&clear_ts).unwrap().tracks[0].media_segment.clone();
+
+        // The whole segment with PKCS#7 padding, the media sequence number being the IV
+        let hls_key = HLSKey::parse("#EXT-X-KEY:METHOD=AES-128,URI=\"https://domain.com/key\"").unwrap();
+        let padding = 16 - clear_ts.len() % 16;
+        let mut encrypted_ts = [clear_ts, vec![padding as u8; padding]].concat();
+        encrypt_cbc(&key, &hls_key.get_iv(5), &mut encrypted_ts);
+        let mp4_tracks = remux_encrypted_ts_to_mp4(&encrypted_ts, &hls_key, &key, 5).unwrap();
+        assert!(expected_media_segment.is_some());
+        assert_eq!(mp4_tracks.tracks[0].media_segment, expected_media_segment);
+
+        let err = remux_encrypted_ts_to_mp4(&encrypted_ts, &hls_key, &[0x22u8; 16], 5).err().unwrap();
+        assert_eq!(err.major, MajorCode::UTIL);
+
+        let hls_key = HLSKey::parse("#EXT-X-KEY:METHOD=AES-128,URI=\"skd://key\",KEYFORMAT=\"com.apple.streamingkeydelivery\"").unwrap();
+        let err = remux_encrypted_ts_to_mp4(&encrypted_ts, &hls_key, &key, 5).err().unwrap();
+        assert_eq!(err.minor, RemuxMinorCode::UNSUPPORTED_ENCRYPTION_ERROR as u8);
+    }
+
+    #[test]
+    fn test_remux_encrypted_ts_to_mp4_sample_aes() {
+        let key = [0x11u8; 16];
+        let hls_key = HLSKey::parse("#EXT-X-KEY:METHOD=SAMPLE-AES,URI=\"https://domain.com/key\",IV=0x0F0E0D0C0B0A09080706050403020100").unwrap();
+        let raw_data: Vec<u8> = (0..40u8).collect();
+        // The block after the 16 byte clear leader is encrypted, the 8 bytes after it are left clear
+        let mut encrypted_raw_data = raw_data.clone();
+        encrypt_cbc(&key, &hls_key.get_iv(0), &mut encrypted_raw_data[16..32]);
+
+        let create_ts = |stream_type: u8, raw_data: &[u8]| {
+            let mut ts_file = [
+                create_pat_packet(&[(1, 0x1000)]),
+                create_pmt_packet(0x1000, 1, &[(stream_type, 0x101)]),
+            ].concat();
+            for index in 0..4u64 {
+                ts_file.append(&mut create_es_packet(0x101, index as u8, &create_aac_pes_with_raw_data(index * 1920, raw_data)));
+            }
+            ts_file
+        };
+        let expected_media_segment = remux_ts_to_mp4(&create_ts(0x0F, &raw_data)).unwrap().tracks[0].media_segment.clone();
+        let encrypted_ts = create_ts(0xCF, &encrypted_raw_data);
+
+        let mp4_tracks = remux_encrypted_ts_to_mp4(&encrypted_ts, &hls_key, &key, 0).unwrap();
+        assert_eq!(mp4_tracks.tracks[0].stream_type, ElementaryStreamType::AAC);
+        assert!(expected_media_segment.is_some());
+        assert_eq!(mp4_tracks.tracks[0].media_segment, expected_media_segment);
+
+        // Without the key the encrypted samples aren't remuxed
+        let mp4_tracks = remux_ts_to_mp4(&encrypted_ts).unwrap();
+        assert_eq!(mp4_tracks.tracks[0].media_segment, None);
+    }
 }
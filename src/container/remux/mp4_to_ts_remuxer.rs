@@ -0,0 +1,639 @@
+use std::str;
+
+use crate::container::isobmff::boxes::iso_box::get_box;
+use crate::container::isobmff::boxes::{
+    mdhd::MDHDReader, stsd::STSD, tfdt::TFDT, tfhd::TFHD, tkhd::TKHDReader, trex::TREX, trun::TRUN,
+};
+use crate::container::isobmff::nal::NALType;
+use crate::container::isobmff::sample_entry::{
+    avc_sample_entry::AVCSampleEntry, mp4a_sample_entry::MP4ASampleEntry,
+};
+use crate::container::remux::map_sample_frequency_index;
+use crate::container::transport_stream::adts::ADTSHeader;
+use crate::container::transport_stream::elementary_stream_type::ElementaryStreamType;
+use crate::container::writer::mp4_writer::SampleInfo;
+use crate::container::writer::ts_writer::TSWriter;
+use crate::error::error_code::{ISOBMFFMinorCode, MajorCode, NalMinorCode, RemuxMinorCode};
+use crate::error::{construct_error, CustomError};
+
+// PID of the first elementary stream, the others follow it
+static FIRST_ELEMENTARY_STREAM_PID: u16 = 0x100;
+static TS_TIMESCALE: u64 = 90000;
+static START_CODE: [u8; 4] = [0x00, 0x00, 0x00, 0x01];
+// Access unit delimiter NAL unit with a primary_pic_type of 7 (any slice type)
+static ACCESS_UNIT_DELIMITER: [u8; 6] = [0x00, 0x00, 0x00, 0x01, 0x09, 0xF0];
+static ADTS_HEADER_SIZE: usize = 7;
+// Largest ADTS frame_length (13 bit)
+static MAX_ADTS_FRAME_LENGTH: usize = 0x1FFF;
+// Sampling frequency indexes 0xD and up are reserved or the escape value, which ADTS can't signal
+static ADTS_SAMPLING_FREQUENCY_INDEX_COUNT: u8 = 0xD;
+static NON_SYNC_SAMPLE_FLAG: u32 = 0x10000;
+
+#[allow(clippy::upper_case_acronyms)]
+enum TrackCodec {
+    AVC {
+        sequence_parameter_sets: Vec<Vec<u8>>,
+        picture_parameter_sets: Vec<Vec<u8>>,
+        nal_unit_length_size: usize,
+    },
+    AAC {
+        profile: u8,
+        sampling_frequency_index: u8,
+        channel_configuration: u8,
+    },
+}
+
+/// AVC or AAC track of a fragmented MP4, with the samples of every fragment
+struct Mp4Track {
+    track_id: u32,
+    timescale: u32,
+    language: String,
+    codec: TrackCodec,
+    defaults: TREX,
+    samples: Vec<SampleInfo>,
+    // Decode time after the last sample, where a fragment without a tfdt starts
+    next_dts: u64,
+}
+
+impl Mp4Track {
+    fn get_stream_type(&self) -> ElementaryStreamType {
+        match self.codec {
+            TrackCodec::AVC { .. } => ElementaryStreamType::H_264,
+            TrackCodec::AAC { .. } => ElementaryStreamType::AAC,
+        }
+    }
+
+    fn to_ts_timescale(&self, timestamp: u64) -> u64 {
+        (timestamp as u128 * TS_TIMESCALE as u128 / self.timescale.max(1) as u128) as u64
+    }
+}
+
+/// Remux the fragments of the media segments into one MPEG-TS program, with the tracks described by the init segment.
+/// Fragments that are part of the init segment itself are remuxed first.
+pub fn remux(init_segment: &[u8], media_segments: &[&[u8]]) -> Result<Vec<u8>, CustomError> {
+    let mut tracks = read_tracks(init_segment)?;
+    for mp4 in std::iter::once(&init_segment).chain(media_segments.iter()) {
+        for (box_type, moof_start, moof) in read_child_boxes(mp4, 0) {
+            if box_type == "moof" {
+                read_fragment(mp4, moof_start, moof, &mut tracks)?;
+            }
+        }
+    }
+    if tracks.is_empty() {
+        return Err(construct_error(
+            MajorCode::REMUX,
+            Box::new(RemuxMinorCode::NO_SUPPORTED_TRACK_ERROR),
+            "No AVC or AAC track to remux to MPEG-TS".to_string(),
+            file!(),
+            line!(),
+        ));
+    }
+
+    let mut ts_writer = TSWriter::create_ts_writer();
+    for (index, track) in tracks.iter().enumerate() {
+        ts_writer = ts_writer.stream(
+            FIRST_ELEMENTARY_STREAM_PID + index as u16,
+            track.get_stream_type(),
+            &track.language,
+        );
+    }
+    let mut ts = ts_writer.write_program_tables();
+
+    let mut samples: Vec<(u64, usize, SampleInfo)> = vec![];
+    for (index, track) in tracks.iter_mut().enumerate() {
+        for sample in std::mem::take(&mut track.samples) {
+            samples.push((track.to_ts_timescale(sample.dts), index, sample));
+        }
+    }
+    samples.sort_by_key(|(dts, _, _)| *dts);
+
+    for (dts, index, sample) in samples {
+        let track = &tracks[index];
+        let pid = FIRST_ELEMENTARY_STREAM_PID + index as u16;
+        let pts = track.to_ts_timescale(sample.pts);
+        let is_sync_sample = sample.sample_flags.unwrap_or(0) & NON_SYNC_SAMPLE_FLAG == 0;
+        let mut packets = match &track.codec {
+            TrackCodec::AVC {
+                sequence_parameter_sets,
+                picture_parameter_sets,
+                nal_unit_length_size,
+            } => {
+                let (access_unit, is_idr) = convert_to_annex_b(
+                    &sample.data,
+                    *nal_unit_length_size,
+                    sequence_parameter_sets,
+                    picture_parameter_sets,
+                )?;
+                ts_writer.write_pes(pid, pts, Some(dts), &access_unit, is_idr || is_sync_sample)?
+            }
+            TrackCodec::AAC {
+                profile,
+                sampling_frequency_index,
+                channel_configuration,
+            } => {
+                let frame_length = ADTS_HEADER_SIZE + sample.data.len();
+                if frame_length > MAX_ADTS_FRAME_LENGTH {
+                    return Err(generate_adts_error(format!(
+                        "AAC sample of {} bytes is too large for an ADTS frame",
+                        sample.data.len()
+                    )));
+                }
+                let adts_header = ADTSHeader {
+                    id_version: 0,
+                    profile: *profile,
+                    sampling_frequency_index: *sampling_frequency_index,
+                    channel_configuration: *channel_configuration,
+                    frame_length: frame_length as u16,
+                    crc: None,
+                };
+                let adts_frame = [adts_header.build(), sample.data].concat();
+                ts_writer.write_pes(pid, pts, None, &adts_frame, is_sync_sample)?
+            }
+        };
+        ts.append(&mut packets);
+    }
+
+    Ok(ts)
+}
+
+/// The AVC and AAC tracks of the moov
+fn read_tracks(init_segment: &[u8]) -> Result<Vec<Mp4Track>, CustomError> {
+    let moov = get_box("moov", 0, init_segment)?;
+    let trexes: Vec<TREX> = get_box("mvex", 8, moov)
+        .map(|mvex| {
+            read_child_boxes(mvex, 8)
+                .into_iter()
+                .filter(|(box_type, _, _)| *box_type == "trex")
+                .filter_map(|(_, _, trex)| TREX::parse_trex(trex).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut tracks: Vec<Mp4Track> = vec![];
+    for (box_type, _, trak) in read_child_boxes(moov, 8) {
+        if box_type != "trak" {
+            continue;
+        }
+        if let Some(track) = read_track(trak, &trexes)? {
+            tracks.push(track);
+        }
+    }
+    Ok(tracks)
+}
+
+fn read_track(trak: &[u8], trexes: &[TREX]) -> Result<Option<Mp4Track>, CustomError> {
+    let track_id = get_box("tkhd", 8, trak)
+        .and_then(TKHDReader::get_reader)?
+        .get_track_id()?;
+    let mdia = get_box("mdia", 8, trak)?;
+    let mut mdhd = get_box("mdhd", 8, mdia).and_then(MDHDReader::get_reader)?;
+    let stsd = get_box("minf", 8, mdia)
+        .and_then(|minf| get_box("stbl", 8, minf))
+        .and_then(|stbl| get_box("stsd", 8, stbl))
+        .and_then(STSD::parse_stsd)?;
+
+    let codec = if let Ok(avc_data) = stsd
+        .read_sample_entry("avc1")
+        .or_else(|_| stsd.read_sample_entry("avc3"))
+    {
+        let config = AVCSampleEntry::parse(avc_data)?.config;
+        TrackCodec::AVC {
+            sequence_parameter_sets: config.sequence_parameter_sets,
+            picture_parameter_sets: config.picture_parameter_sets,
+            nal_unit_length_size: config.length_size_minus_one as usize + 1,
+        }
+    } else if let Ok(mp4a_data) = stsd.read_sample_entry("mp4a") {
+        let audio_specific_config = match MP4ASampleEntry::parse(mp4a_data)?
+            .es_descriptor
+            .dec_config_descr
+            .audio_sepcific_info
+        {
+            Some(audio_specific_config) => audio_specific_config,
+            None => {
+                println!("remux_mp4_to_ts :: track {} :: MPEG audio in mp4a is not supported", track_id);
+                return Ok(None);
+            }
+        };
+        // The explicit sampling frequency has to map onto an index, since that is all ADTS can signal
+        let sampling_frequency_index = match audio_specific_config.sampling_frequency {
+            Some(sampling_frequency) => (0..ADTS_SAMPLING_FREQUENCY_INDEX_COUNT)
+                .find(|index| map_sample_frequency_index(*index) == sampling_frequency)
+                .ok_or_else(|| {
+                    generate_adts_error(format!(
+                        "Track {} has a sampling frequency of {} Hz, which has no sampling frequency index",
+                        track_id, sampling_frequency
+                    ))
+                })?,
+            None if audio_specific_config.sampling_frequency_index < ADTS_SAMPLING_FREQUENCY_INDEX_COUNT => {
+                audio_specific_config.sampling_frequency_index
+            }
+            None => {
+                return Err(generate_adts_error(format!(
+                    "Track {} has the reserved sampling frequency index {}",
+                    track_id, audio_specific_config.sampling_frequency_index
+                )))
+            }
+        };
+        // ADTS can only carry the profiles of the first 4 audio object types. HE-AAC keeps the core (LC) profile and
+        // core sampling frequency, the SBR and PS data in the raw data blocks signals it implicitly.
+        let profile = match audio_specific_config.audio_object_type {
+            1..=4 => audio_specific_config.audio_object_type - 1,
+            _ => 1,
+        };
+        TrackCodec::AAC {
+            profile,
+            sampling_frequency_index,
+            channel_configuration: audio_specific_config.channel_configuration,
+        }
+    } else {
+        println!("remux_mp4_to_ts :: track {} :: Only AVC and AAC tracks are remuxed", track_id);
+        return Ok(None);
+    };
+
+    Ok(Some(Mp4Track {
+        track_id,
+        timescale: mdhd.get_timescale()?,
+        language: mdhd.get_language()?,
+        codec,
+        defaults: trexes
+            .iter()
+            .find(|trex| trex.track_id == track_id)
+            .cloned()
+            .unwrap_or_default(),
+        samples: vec![],
+        next_dts: 0,
+    }))
+}
+
+fn generate_adts_error(message: String) -> CustomError {
+    construct_error(
+        MajorCode::REMUX,
+        Box::new(RemuxMinorCode::UNSUPPORTED_ADTS_ERROR),
+        message,
+        file!(),
+        line!(),
+    )
+}
+
+/// Gather the samples of every traf of the moof. The sample data is found in the file through the trun data offsets.
+/// ISO/IEC 14496-12; 8.8.7.1
+fn read_fragment(mp4: &[u8], moof_start: usize, moof: &[u8], tracks: &mut [Mp4Track]) -> Result<(), CustomError> {
+    // End of the sample data of the previous traf, which is the base of the next one when neither a base data offset
+    // nor default-base-is-moof is set
+    let mut previous_data_end: Option<usize> = None;
+    for (box_type, _, traf) in read_child_boxes(moof, 8) {
+        if box_type != "traf" {
+            continue;
+        }
+        let tfhd = get_box("tfhd", 8, traf).and_then(TFHD::parse_tfhd)?;
+        let base_offset = match tfhd.get_base_data_offset() {
+            Some(base_data_offset) => base_data_offset as usize,
+            None if tfhd.is_default_base_moof() => moof_start,
+            None => previous_data_end.unwrap_or(moof_start),
+        };
+        // The samples of a track that isn't remuxed are still walked, so the next traf knows where its data starts
+        let mut track = tracks
+            .iter_mut()
+            .find(|track| track.track_id == tfhd.get_track_id());
+        let defaults = track.as_ref().map(|track| track.defaults.clone()).unwrap_or_default();
+        let mut dts = match get_box("tfdt", 8, traf).and_then(TFDT::parse_tfdt) {
+            Ok(tfdt) => tfdt.get_base_media_decode_time(),
+            Err(_) => track.as_ref().map(|track| track.next_dts).unwrap_or_default(),
+        };
+        let mut data_position = base_offset;
+
+        for (box_type, _, trun_data) in read_child_boxes(traf, 8) {
+            if box_type != "trun" {
+                continue;
+            }
+            let trun = TRUN::parse_trun(trun_data)?;
+            // Without a data offset the samples follow the ones of the previous trun
+            if let Some(data_offset) = trun.get_data_offset() {
+                data_position = (base_offset as i64 + data_offset as i64).max(0) as usize;
+            }
+            for (index, sample) in trun.get_samples().iter().enumerate() {
+                let duration = sample
+                    .sample_duration
+                    .or(tfhd.get_default_sample_duration())
+                    .unwrap_or(defaults.default_sample_duration);
+                let size = sample
+                    .sample_size
+                    .or(tfhd.get_default_sample_size())
+                    .unwrap_or(defaults.default_sample_size) as usize;
+                let sample_flags = trun
+                    .first_sample_flags
+                    .filter(|_| index == 0)
+                    .or(sample.sample_flags)
+                    .or(tfhd.get_default_sample_flags())
+                    .unwrap_or(defaults.default_sample_flags);
+                let composition_time_offset = sample.sample_composition_time_offset.unwrap_or(0);
+                if let Some(track) = track.as_mut() {
+                    let data = mp4
+                        .get(data_position..(data_position + size))
+                        .ok_or_else(|| {
+                            construct_error(
+                                MajorCode::ISOBMFF,
+                                Box::new(ISOBMFFMinorCode::PARSE_BOX_ERROR),
+                                format!("Sample data of track {} is outside of the file", track.track_id),
+                                file!(),
+                                line!(),
+                            )
+                        })?;
+                    track.samples.push(SampleInfo {
+                        dts,
+                        pts: (dts as i64 + composition_time_offset as i64).max(0) as u64,
+                        sample_flags: Some(sample_flags),
+                        sample_duration: Some(duration),
+                        data: data.to_vec(),
+                    });
+                }
+                dts += duration as u64;
+                data_position += size;
+            }
+        }
+        if let Some(track) = track {
+            track.next_dts = dts;
+        }
+        previous_data_end = Some(data_position);
+    }
+    Ok(())
+}
+
+/// Replace the NAL unit lengths of an AVC sample with start codes. The access unit starts with an access unit
+/// delimiter, and the parameter sets are put in front of an IDR picture that doesn't carry them, so a decoder can
+/// start from it. Returns whether the access unit has an IDR picture.
+fn convert_to_annex_b(
+    sample: &[u8],
+    nal_unit_length_size: usize,
+    sequence_parameter_sets: &[Vec<u8>],
+    picture_parameter_sets: &[Vec<u8>],
+) -> Result<(Vec<u8>, bool), CustomError> {
+    let mut nal_units: Vec<&[u8]> = vec![];
+    let mut offset = 0usize;
+    while offset + nal_unit_length_size <= sample.len() {
+        let length = sample[offset..(offset + nal_unit_length_size)]
+            .iter()
+            .fold(0usize, |length, byte| (length << 8) | *byte as usize);
+        offset += nal_unit_length_size;
+        let nal_unit = sample.get(offset..(offset + length)).ok_or_else(|| {
+            construct_error(
+                MajorCode::NAL,
+                Box::new(NalMinorCode::UNEXPTED_NAL_UNIT_LENGTH_ERROR),
+                format!("NAL unit of {} bytes exceeds the sample", length),
+                file!(),
+                line!(),
+            )
+        })?;
+        nal_units.push(nal_unit);
+        offset += length;
+    }
+
+    let nal_unit_type = |nal_unit: &[u8]| nal_unit.first().map(|header| header & 0x1F);
+    let is_idr = nal_units
+        .iter()
+        .any(|nal_unit| nal_unit_type(nal_unit) == Some(NALType::IDR_Picture.value()));
+    let has_parameter_sets = nal_units
+        .iter()
+        .any(|nal_unit| nal_unit_type(nal_unit) == Some(NALType::SPS.value()));
+
+    let mut access_unit = ACCESS_UNIT_DELIMITER.to_vec();
+    if is_idr && !has_parameter_sets {
+        for parameter_set in sequence_parameter_sets.iter().chain(picture_parameter_sets.iter()) {
+            access_unit.extend_from_slice(&START_CODE);
+            access_unit.extend_from_slice(parameter_set);
+        }
+    }
+    for nal_unit in nal_units {
+        if nal_unit_type(nal_unit) == Some(NALType::AUD.value()) {
+            continue;
+        }
+        access_unit.extend_from_slice(&START_CODE);
+        access_unit.extend_from_slice(nal_unit);
+    }
+    Ok((access_unit, is_idr))
+}
+
+/// Type, offset and data of every box from the offset to the end of the data
+fn read_child_boxes(data: &[u8], offset: usize) -> Vec<(&str, usize, &[u8])> {
+    let mut boxes: Vec<(&str, usize, &[u8])> = vec![];
+    let mut start = offset;
+    while start + 8 <= data.len() {
+        let size = u32::from_be_bytes([data[start], data[start + 1], data[start + 2], data[start + 3]]) as usize;
+        // A size of 0 means the box goes to the end of the data
+        let end = if size == 0 { data.len() } else { start + size };
+        if size != 0 && size < 8 || end > data.len() {
+            break;
+        }
+        if let Ok(box_type) = str::from_utf8(&data[(start + 4)..(start + 8)]) {
+            boxes.push((box_type, start, &data[start..end]));
+        }
+        start = end;
+    }
+    boxes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::h264::picture_parameter_set::tests::get_pps;
+    use crate::codec::h264::sequence_parameter_set::tests::get_sps_with_pic_order_cnt_lsb;
+    use crate::codec::h264::slice_header::tests::create_slice_with_pic_order_cnt_lsb;
+    use crate::container::remux::{remux_mp4_to_ts, remux_ts_to_muxed_mp4};
+    use crate::container::transport_stream::{pes_packet::PESPacket, test_util::create_annex_b, ts_packet::TransportPacket};
+    use crate::container::writer::ts_writer::PMT_PID;
+
+    fn create_length_prefixed(nal_units: &[Vec<u8>]) -> Vec<u8> {
+        nal_units
+            .iter()
+            .flat_map(|nal_unit| [(nal_unit.len() as u32).to_be_bytes().to_vec(), nal_unit.to_owned()].concat())
+            .collect()
+    }
+
+    // PES packets of every elementary stream PID, in the order they start
+    fn read_pes_packets(ts: &[u8]) -> Vec<(u16, Vec<u8>)> {
+        let mut pes_packets: Vec<(u16, Vec<u8>)> = vec![];
+        for packet in ts.chunks(188) {
+            let packet = TransportPacket::parse(packet).unwrap();
+            if packet.pid < FIRST_ELEMENTARY_STREAM_PID || packet.pid == PMT_PID {
+                continue;
+            }
+            if packet.payload_unit_start_indicator {
+                pes_packets.push((packet.pid, vec![]));
+            }
+            let pes = pes_packets.iter_mut().rev().find(|(pid, _)| *pid == packet.pid).unwrap();
+            pes.1.extend_from_slice(packet.data);
+        }
+        pes_packets
+    }
+
+    #[test]
+    fn test_remux_mp4_to_ts() {
+        let adts_frame = vec![0xFF, 0xF1, 0x4C, 0x80, 0x01, 0x3F, 0xFC, 0x01, 0x02];
+        let mut ts_writer = TSWriter::create_ts_writer()
+            .stream(0x100, ElementaryStreamType::H_264, "und")
+            .stream(0x101, ElementaryStreamType::AAC, "eng");
+        let mut ts_file = ts_writer.write_program_tables();
+        let idr = create_annex_b(&[
+            get_sps_with_pic_order_cnt_lsb(),
+            get_pps(),
+            create_slice_with_pic_order_cnt_lsb(0x65, 7, 0, Some(0)),
+        ]);
+        let non_idr = create_annex_b(&[create_slice_with_pic_order_cnt_lsb(0x41, 5, 1, Some(2))]);
+        for (index, picture) in [&idr, &non_idr, &non_idr].iter().enumerate() {
+            let timestamp = 3000 + index as u64 * 3000;
+            ts_file.append(&mut ts_writer.write_pes(0x100, timestamp, None, picture, index == 0).unwrap());
+            ts_file.append(&mut ts_writer.write_pes(0x101, timestamp, None, &adts_frame, true).unwrap());
+        }
+        let muxed_mp4 = remux_ts_to_muxed_mp4(&ts_file).unwrap();
+
+        let ts = remux_mp4_to_ts(&muxed_mp4.init_segment, &[&muxed_mp4.media_segment]).unwrap();
+        let pes_packets = read_pes_packets(&ts);
+        let pids: Vec<u16> = pes_packets.iter().map(|(pid, _)| *pid).collect();
+        // Interleaved by decode time
+        assert_eq!(pids, vec![0x100, 0x101, 0x100, 0x101, 0x100, 0x101]);
+
+        let first_picture = PESPacket::parse(&pes_packets[0].1).unwrap();
+        assert_eq!(first_picture.pts, Some(3000));
+        // AUD, then the parameter sets in front of the IDR
+        assert_eq!(first_picture.payload_data[0..6], ACCESS_UNIT_DELIMITER);
+        assert_eq!(first_picture.payload_data[10] & 0x1F, NALType::SPS.value());
+        let first_frame = PESPacket::parse(&pes_packets[1].1).unwrap();
+        assert_eq!(first_frame.stream_id, 0xC0);
+        assert_eq!(first_frame.payload_data, adts_frame.as_slice());
+
+        // Remuxing the TS again gives the same samples and timing
+        assert_eq!(remux_ts_to_muxed_mp4(&ts).unwrap().media_segment, muxed_mp4.media_segment);
+    }
+
+    // traf with a tfhd without default-base-is-moof and a trun of one sample (sample-duration and sample-size present)
+    fn create_traf(track_id: u32, data_offset: i32, sample_size: u32) -> Vec<u8> {
+        [
+            vec![0x00, 0x00, 0x00, 0x48, b't', b'r', b'a', b'f'],
+            vec![0x00, 0x00, 0x00, 0x10, b't', b'f', b'h', b'd', 0x00, 0x00, 0x00, 0x00],
+            track_id.to_be_bytes().to_vec(),
+            vec![0x00, 0x00, 0x00, 0x14, b't', b'f', b'd', b't', 0x01, 0x00, 0x00, 0x00],
+            0u64.to_be_bytes().to_vec(),
+            vec![0x00, 0x00, 0x00, 0x1C, b't', b'r', b'u', b'n', 0x00, 0x00, 0x03, 0x01, 0x00, 0x00, 0x00, 0x01],
+            data_offset.to_be_bytes().to_vec(),
+            3000u32.to_be_bytes().to_vec(),
+            sample_size.to_be_bytes().to_vec(),
+        ].concat()
+    }
+
+    // Init segment of an H.264 track (track 1) and an AAC LC, 48 kHz, stereo track (track 2)
+    fn create_init_segment() -> Vec<u8> {
+        let adts_frame = vec![0xFF, 0xF1, 0x4C, 0x80, 0x01, 0x3F, 0xFC, 0x01, 0x02];
+        let mut ts_writer = TSWriter::create_ts_writer()
+            .stream(0x100, ElementaryStreamType::H_264, "und")
+            .stream(0x101, ElementaryStreamType::AAC, "und");
+        let mut ts_file = ts_writer.write_program_tables();
+        let idr = create_annex_b(&[
+            get_sps_with_pic_order_cnt_lsb(),
+            get_pps(),
+            create_slice_with_pic_order_cnt_lsb(0x65, 7, 0, Some(0)),
+        ]);
+        ts_file.append(&mut ts_writer.write_pes(0x100, 3000, None, &idr, true).unwrap());
+        ts_file.append(&mut ts_writer.write_pes(0x101, 3000, None, &adts_frame, true).unwrap());
+        remux_ts_to_muxed_mp4(&ts_file).unwrap().init_segment
+    }
+
+    // Media segment of one picture and one audio frame for the tracks of create_init_segment
+    fn create_media_segment(audio_frame: &[u8]) -> Vec<u8> {
+        // The data offset of the second traf is relative to the end of the data of the first one
+        let picture = create_length_prefixed(&[create_slice_with_pic_order_cnt_lsb(0x65, 7, 0, Some(0))]);
+        let moof_size = 8 + 16 + 72 * 2;
+        [
+            vec![0x00, 0x00, 0x00, moof_size as u8, b'm', b'o', b'o', b'f'],
+            vec![0x00, 0x00, 0x00, 0x10, b'm', b'f', b'h', b'd', 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01],
+            create_traf(1, moof_size + 8, picture.len() as u32),
+            create_traf(2, 0, audio_frame.len() as u32),
+            (8 + picture.len() as u32 + audio_frame.len() as u32).to_be_bytes().to_vec(),
+            b"mdat".to_vec(),
+            picture,
+            audio_frame.to_vec(),
+        ].concat()
+    }
+
+    // Position of the box type in the init segment
+    fn find_box_type(init_segment: &[u8], box_type: &[u8; 4]) -> usize {
+        init_segment.windows(4).position(|window| window == box_type).unwrap()
+    }
+
+    #[test]
+    fn test_remux_mp4_to_ts_traf_base_offset() {
+        let audio_frame = vec![0xA1, 0xA2, 0xA3];
+        let ts = remux_mp4_to_ts(&create_init_segment(), &[&create_media_segment(&audio_frame)]).unwrap();
+        let pes_packets = read_pes_packets(&ts);
+        let frame = PESPacket::parse(&pes_packets[1].1).unwrap();
+        assert_eq!(frame.stream_id, 0xC0);
+        assert_eq!(frame.payload_data[ADTS_HEADER_SIZE..], audio_frame);
+    }
+
+    #[test]
+    fn test_remux_mp4_to_ts_unsupported_adts() {
+        let init_segment = create_init_segment();
+        // An audio frame that doesn't fit in the 13 bit frame_length
+        let err = remux_mp4_to_ts(&init_segment, &[&create_media_segment(&[0xA1; MAX_ADTS_FRAME_LENGTH])]).err().unwrap();
+        assert_eq!(err.major, MajorCode::REMUX);
+        assert_eq!(err.minor, RemuxMinorCode::UNSUPPORTED_ADTS_ERROR as u8);
+
+        // AudioSpecificConfig with the reserved sampling frequency index 0xD instead of 0x3
+        let mut init_segment = init_segment;
+        let esds = find_box_type(&init_segment, b"esds");
+        let audio_specific_config = esds + init_segment[esds..].windows(2).position(|window| window == [0x11, 0x90]).unwrap();
+        init_segment[audio_specific_config] = 0x16;
+        let err = remux_mp4_to_ts(&init_segment, &[]).err().unwrap();
+        assert_eq!(err.major, MajorCode::REMUX);
+        assert_eq!(err.minor, RemuxMinorCode::UNSUPPORTED_ADTS_ERROR as u8);
+    }
+
+    #[test]
+    fn test_remux_mp4_to_ts_invalid_sample_entry() {
+        // A compressorname that isn't ASCII is read lossily
+        let mut init_segment = create_init_segment();
+        let compressor_name = find_box_type(&init_segment, b"avc1") + 46;
+        init_segment[compressor_name..compressor_name + 3].copy_from_slice(&[0x02, 0xFF, 0xFE]);
+        assert!(remux_mp4_to_ts(&init_segment, &[&create_media_segment(&[0xA1])]).is_ok());
+
+        // avc1 without an avcC box
+        let mut init_segment = create_init_segment();
+        let avcc = find_box_type(&init_segment, b"avcC");
+        init_segment[avcc + 3] = b'X';
+        assert_eq!(remux_mp4_to_ts(&init_segment, &[]).err().unwrap().major, MajorCode::ISOBMFF);
+
+        // ES_Descriptor without a DecoderConfigDescriptor
+        let mut init_segment = create_init_segment();
+        let dec_config_descr = find_box_type(&init_segment, b"esds") + 16;
+        assert_eq!(init_segment[dec_config_descr], 0x04);
+        init_segment[dec_config_descr] = 0x7F;
+        assert_eq!(remux_mp4_to_ts(&init_segment, &[]).err().unwrap().major, MajorCode::ISOBMFF);
+    }
+
+    #[test]
+    fn test_convert_to_annex_b() {
+        let sps = get_sps_with_pic_order_cnt_lsb();
+        let pps = get_pps();
+        let idr_slice = vec![0x65, 0x88, 0x84, 0x11];
+        let sample = create_length_prefixed(&[vec![0x09, 0xF0], idr_slice.clone()]);
+        let (access_unit, is_idr) = convert_to_annex_b(&sample, 4, std::slice::from_ref(&sps), std::slice::from_ref(&pps)).unwrap();
+        assert!(is_idr);
+        // The AUD of the sample is replaced and the parameter sets are added
+        assert_eq!(access_unit, [ACCESS_UNIT_DELIMITER.to_vec(), create_annex_b(&[sps.clone(), pps.clone(), idr_slice])].concat());
+
+        let non_idr_slice = vec![0x41, 0x9A, 0x02, 0x33];
+        let (access_unit, is_idr) = convert_to_annex_b(&create_length_prefixed(std::slice::from_ref(&non_idr_slice)), 4, &[sps], &[pps]).unwrap();
+        assert!(!is_idr);
+        assert_eq!(access_unit, [ACCESS_UNIT_DELIMITER.to_vec(), create_annex_b(&[non_idr_slice])].concat());
+
+        let err = convert_to_annex_b(&[0x00, 0x00, 0x00, 0x08, 0x41], 4, &[], &[]).err().unwrap();
+        assert_eq!(err.major, MajorCode::NAL);
+    }
+
+    #[test]
+    fn test_remux_mp4_to_ts_no_supported_track() {
+        let moov = [0x00, 0x00, 0x00, 0x08, 0x6D, 0x6F, 0x6F, 0x76];
+        let err = remux_mp4_to_ts(&moov, &[]).err().unwrap();
+        assert_eq!(err.major, MajorCode::REMUX);
+        assert_eq!(err.minor, RemuxMinorCode::NO_SUPPORTED_TRACK_ERROR as u8);
+    }
+}
@@ -0,0 +1,969 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::container::remux::extractor::{get_ts_extractor, TSExtractor};
+use crate::container::remux::RemuxOptions;
+use crate::container::transport_stream::continuity_counter::{
+    ContinuityCounterTracker, ContinuityIssue, ContinuityReport, ContinuityStatus, CorruptFrame, TimestampIssue,
+};
+use crate::container::transport_stream::elementary_stream_type::ElementaryStreamType;
+use crate::container::transport_stream::packet_format::TSPacketFormat;
+use crate::container::transport_stream::program_map_table::{StreamInfo, PMT_TABLE_ID};
+use crate::container::transport_stream::psi_assembler::PSIAssembler;
+use crate::container::transport_stream::sample_aes::SampleAESDecrypter;
+use crate::container::transport_stream::timestamp_unwrapper::TimestampUnwrapper;
+use crate::container::transport_stream::{
+    pes_assembler::PESAssembler, pes_packet::PESPacket,
+    program_association_table::ProgramAssociationTable, program_map_table::ProgramMapTable,
+    ts_packet::TransportPacket,
+};
+use crate::container::writer::mp4_writer::Mp4Writer;
+use crate::error::error_code::{MajorCode, RemuxMinorCode, TransportStreamMinorCode};
+use crate::error::{construct_error, CustomError};
+
+// Data buffered while looking for the packet size before falling back to 188 byte packets
+static MAX_DETECTION_SIZE: usize = 204 * 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum DemuxEvent {
+    /// The PAT changed. program_number -> program map PID
+    PAT { programs: BTreeMap<u16, u16> },
+    /// The PMT of the selected program changed
+    PMT { program_number: u16, streams: Vec<StreamInfo> },
+    /// Complete PES packet (header included) of a track of the selected program
+    PES { pid: u16, data: Vec<u8> },
+    /// Init segment of a track. Sent once, as soon as the codec configuration of the track is known.
+    INIT_SEGMENT { track_id: usize, pid: u16, data: Vec<u8> },
+    /// Media segment (moof + mdat) of a track
+    MEDIA_SEGMENT { track_id: usize, pid: u16, data: Vec<u8> },
+    /// A continuity problem that was only reported (strict continuity is off)
+    CONTINUITY_ISSUE(ContinuityIssue),
+    /// A gap or overlap in the PES timestamps of a track
+    TIMESTAMP_ISSUE(TimestampIssue),
+    /// A frame left out of a track because its CRC didn't match or it couldn't be parsed
+    CORRUPT_FRAME(CorruptFrame),
+}
+
+/// Elementary stream of the selected program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DemuxedTrack {
+    pub track_id: usize,
+    pub pid: u16,
+    pub stream_type: ElementaryStreamType,
+    /// ISO 639-2 language from the PMT descriptors, "und" when not signaled
+    pub language: String,
+}
+
+/// Track and the extractor remuxing it. The extractor is None when there is no extractor implemented for the
+/// stream type.
+struct ElementaryStreamTrack {
+    track: DemuxedTrack,
+    extractor: Option<Box<dyn TSExtractor>>,
+    // Set for SAMPLE-AES encrypted streams
+    decrypter: Option<SampleAESDecrypter>,
+    init_segment_sent: bool,
+    // mfhd sequence number of the next media segment
+    sequence_number: u32,
+}
+
+/// Push based transport stream demuxer. Feed it chunks of any size (they don't need to be aligned to TS packets)
+/// and get back the events they produced. The packet size (188 byte TS, 192 byte M2TS or 204 byte DVB) is detected
+/// from the first packets, which are buffered until then. After that only a partial TS packet and the PES packets
+/// being assembled are buffered between pushes. When a fragment duration is set, media segments are cut while
+/// pushing, otherwise the media is only returned by finish (or cut_media_segments), so every sample of the stream
+/// is held in memory until then.
+pub struct TSDemuxer {
+    options: RemuxOptions,
+    fragment_duration: Option<u64>,
+    // The media is held for finish_muxed, which cuts the fragments of all the tracks together
+    muxed: bool,
+    // The init segments are only built by finish, once the whole stream has been pushed
+    whole_stream: bool,
+    packet_format: Option<TSPacketFormat>,
+    partial_packet: Vec<u8>,
+    pes_assembler: PESAssembler,
+    psi_assembler: PSIAssembler,
+    continuity_tracker: ContinuityCounterTracker,
+    programs: Option<BTreeMap<u16, u16>>,
+    program_number: u16,
+    program_map_pid: Option<u16>,
+    streams: Option<Vec<StreamInfo>>,
+    es_tracks: Vec<ElementaryStreamTrack>,
+    // random_access_indicator of the PES packet being assembled per PID
+    pes_random_access: HashMap<u16, bool>,
+    fragment_start: Option<u64>,
+    fragment_timestamp_unwrapper: TimestampUnwrapper,
+    // First PES timestamp of the program, every track unwraps its timestamps relative to it
+    timestamp_reference: Option<u64>,
+}
+
+impl TSDemuxer {
+    pub fn create(options: RemuxOptions) -> TSDemuxer {
+        TSDemuxer {
+            options,
+            fragment_duration: None,
+            muxed: false,
+            whole_stream: false,
+            packet_format: None,
+            partial_packet: vec![],
+            pes_assembler: PESAssembler::create(),
+            psi_assembler: PSIAssembler::create(),
+            continuity_tracker: ContinuityCounterTracker::create(),
+            programs: None,
+            program_number: 0,
+            program_map_pid: None,
+            streams: None,
+            es_tracks: vec![],
+            pes_random_access: HashMap::new(),
+            fragment_start: None,
+            fragment_timestamp_unwrapper: TimestampUnwrapper::create(),
+            timestamp_reference: None,
+        }
+    }
+
+    /// Cut a media segment for every track once this much time (90 kHz) has passed on the first video track (or the
+    /// first track when there is no video). Video tracks are only cut on a random access point.
+    pub fn fragment_duration(mut self, fragment_duration: u64) -> TSDemuxer {
+        self.fragment_duration = Some(fragment_duration);
+        self
+    }
+
+    /// Hold the media of every track for finish_muxed instead of sending media segments per track while pushing. The
+    /// fragment duration then cuts the muxed media segments.
+    pub fn muxed(mut self, muxed: bool) -> TSDemuxer {
+        self.muxed = muxed;
+        self
+    }
+
+    /// Only build the init segments in finish, after the whole stream has been pushed (no fragment duration and no
+    /// cut_media_segments). By then every AVC and HEVC parameter set of the stream is known, so they can stay out of
+    /// the samples (avc1, hvc1) unless they change. Otherwise the init segment goes out with the first parameter sets
+    /// found and a later one can only be carried in band, so the parameter sets are kept in the samples (avc3, hev1).
+    pub fn whole_stream(mut self, whole_stream: bool) -> TSDemuxer {
+        self.whole_stream = whole_stream;
+        self
+    }
+
+    pub fn get_tracks(&self) -> Vec<DemuxedTrack> {
+        self.es_tracks
+            .iter()
+            .map(|es_track| es_track.track.clone())
+            .collect()
+    }
+
+    pub fn get_continuity_report(&self) -> &ContinuityReport {
+        self.continuity_tracker.get_report()
+    }
+
+    pub fn take_continuity_report(&mut self) -> ContinuityReport {
+        self.continuity_tracker.take_report()
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<DemuxEvent>, CustomError> {
+        let mut events: Vec<DemuxEvent> = vec![];
+        let mut data = std::mem::take(&mut self.partial_packet);
+        data.extend_from_slice(chunk);
+
+        let offset = match self.packet_format {
+            Some(_) => 0,
+            None => match TSPacketFormat::detect(&data, false) {
+                Some((packet_format, offset)) => {
+                    self.packet_format = Some(packet_format);
+                    offset
+                }
+                None if data.len() < MAX_DETECTION_SIZE => {
+                    // Wait for enough packets to tell the packet size
+                    self.partial_packet = data;
+                    return Ok(events);
+                }
+                None => {
+                    println!("TSDemuxer :: push :: Unable to detect the packet size. Reading 188 byte packets");
+                    self.packet_format = Some(TSPacketFormat::TS);
+                    0
+                }
+            },
+        };
+        self.handle_packets(data, offset, &mut events)?;
+
+        Ok(events)
+    }
+
+    /// The packet size detected from the first packets pushed
+    pub fn get_packet_format(&self) -> Option<TSPacketFormat> {
+        self.packet_format
+    }
+
+    /// Send a media segment for every track with the media gathered so far, without ending the stream. The PES
+    /// packets still being assembled are treated as complete, so this should only be called on a PES boundary (ex.
+    /// at the end of an HLS segment). The last sample of a track is held back until the duration of it is known, so it
+    /// ends up in the next media segment.
+    pub fn cut_media_segments(&mut self) -> Result<Vec<DemuxEvent>, CustomError> {
+        let mut events: Vec<DemuxEvent> = vec![];
+        self.handle_undetected_packets(&mut events)?;
+        for es_track in self.es_tracks.iter_mut() {
+            if let Some(pes_data) = self.pes_assembler.flush(es_track.track.pid) {
+                accumulate_pes(
+                    es_track,
+                    pes_data,
+                    &mut self.timestamp_reference,
+                    &mut self.continuity_tracker,
+                    &mut events,
+                );
+            }
+            push_init_segment_if_ready(es_track, &mut events);
+            push_media_segment(es_track, &mut events);
+        }
+        Ok(events)
+    }
+
+    /// Flush the PES packets still being assembled and the remaining media of every track
+    pub fn finish(&mut self) -> Result<Vec<DemuxEvent>, CustomError> {
+        let mut events: Vec<DemuxEvent> = vec![];
+        self.handle_undetected_packets(&mut events)?;
+        for es_track in self.es_tracks.iter_mut() {
+            if let Some(pes_data) = self.pes_assembler.flush(es_track.track.pid) {
+                accumulate_pes(
+                    es_track,
+                    pes_data,
+                    &mut self.timestamp_reference,
+                    &mut self.continuity_tracker,
+                    &mut events,
+                );
+            }
+            if let Some(extractor) = es_track.extractor.as_mut() {
+                extractor.flush_final_media().ok();
+            }
+            report_extractor_issues(es_track, &mut self.continuity_tracker, &mut events);
+            // Whatever was gathered goes into the init segment, even if the codec configuration was never found
+            if !es_track.init_segment_sent {
+                if let Some(data) = es_track
+                    .extractor
+                    .as_mut()
+                    .and_then(|tse| tse.get_init_segment().ok())
+                {
+                    es_track.init_segment_sent = true;
+                    events.push(DemuxEvent::INIT_SEGMENT {
+                        track_id: es_track.track.track_id,
+                        pid: es_track.track.pid,
+                        data,
+                    });
+                }
+            }
+            push_media_segment(es_track, &mut events);
+        }
+        Ok(events)
+    }
+
+    /// Flush the PES packets still being assembled and the remaining media of every track into one muxed init
+    /// segment (a trak per track) and muxed media segments (a traf per track), instead of segments per track. With a
+    /// fragment duration (on a muxed demuxer) the media is cut into a media segment per fragment, on the random
+    /// access points of the first video track (or the first track when there is no video). Tracks the init segment
+    /// can't be built for are left out.
+    pub fn finish_muxed(&mut self) -> Result<(Vec<u8>, Vec<u8>), CustomError> {
+        let mut init_writers: Vec<(Mp4Writer, Vec<u8>)> = vec![];
+        let mut media_writers: Vec<Mp4Writer> = vec![];
+        let mut reference_track: Option<usize> = None;
+        self.handle_undetected_packets(&mut vec![])?;
+        for es_track in self.es_tracks.iter_mut() {
+            if let Some(pes_data) = self.pes_assembler.flush(es_track.track.pid) {
+                accumulate_pes(
+                    es_track,
+                    pes_data,
+                    &mut self.timestamp_reference,
+                    &mut self.continuity_tracker,
+                    &mut vec![],
+                );
+            }
+            if let Some(extractor) = es_track.extractor.as_mut() {
+                extractor.flush_final_media().ok();
+            }
+            report_extractor_issues(es_track, &mut self.continuity_tracker, &mut vec![]);
+            let extractor = match es_track.extractor.as_mut() {
+                Some(extractor) => extractor,
+                None => continue,
+            };
+            let init_writer = match extractor.get_init_writer() {
+                Ok(init_writer) => init_writer,
+                Err(err) => {
+                    println!("remux :: pid {} :: {:?}", es_track.track.pid, err);
+                    continue;
+                }
+            };
+            init_writers.push(init_writer);
+            media_writers.push(extractor.get_media_writer()?);
+            if reference_track.is_none() && es_track.track.stream_type.is_video() {
+                reference_track = Some(media_writers.len() - 1);
+            }
+        }
+
+        let init_segment = Mp4Writer::build_muxed_init_segment(init_writers)?;
+        let media_segment = match self.fragment_duration {
+            Some(fragment_duration) => Mp4Writer::build_fragmented_muxed_media_segments(
+                media_writers,
+                reference_track.unwrap_or(0),
+                fragment_duration,
+                1,
+            )?,
+            None => Mp4Writer::build_muxed_media_segment(media_writers, 1)?,
+        };
+        Ok((init_segment, media_segment))
+    }
+
+    fn handle_packets(
+        &mut self,
+        data: Vec<u8>,
+        offset: usize,
+        events: &mut Vec<DemuxEvent>,
+    ) -> Result<(), CustomError> {
+        let packet_format = self.packet_format.unwrap_or(TSPacketFormat::TS);
+        let mut index = offset;
+        loop {
+            let (packet_data, next_index) = packet_format.read_packet(&data, index);
+            index = next_index;
+            let packet_data = match packet_data {
+                Some(packet_data) => packet_data,
+                None => break,
+            };
+            if let Err(err) = self.handle_packet(packet_data, events) {
+                // Keep the packets after the one that failed, so the next push carries on from there
+                self.partial_packet = data[index..].to_vec();
+                return Err(err);
+            }
+        }
+        self.partial_packet = data[index..].to_vec();
+        Ok(())
+    }
+
+    // The stream ended (or the segment did) with too few packets to be sure of the packet size, so go with the whole
+    // packets there are
+    fn handle_undetected_packets(&mut self, events: &mut Vec<DemuxEvent>) -> Result<(), CustomError> {
+        if self.packet_format.is_some() {
+            return Ok(());
+        }
+        let data = std::mem::take(&mut self.partial_packet);
+        if let Some((packet_format, offset)) = TSPacketFormat::detect(&data, true) {
+            self.packet_format = Some(packet_format);
+            self.handle_packets(data, offset, events)?;
+        }
+        Ok(())
+    }
+
+    fn handle_packet(
+        &mut self,
+        packet_data: &[u8],
+        events: &mut Vec<DemuxEvent>,
+    ) -> Result<(), CustomError> {
+        let packet = match TransportPacket::parse(packet_data) {
+            Ok(packet) => packet,
+            Err(_) if !self.is_tracked_pid(TransportPacket::read_pid(packet_data)) => {
+                self.continuity_tracker.skip_packet();
+                return Ok(());
+            }
+            Err(_) => {
+                let issue = self.continuity_tracker.report_malformed_packet(packet_data);
+                if self.options.strict_continuity {
+                    return Err(generate_continuity_error(issue));
+                }
+                // The PES packet (or PSI section) in progress is missing data, so don't hand it on
+                self.pes_assembler.discard(issue.pid);
+                self.psi_assembler.discard(issue.pid);
+                events.push(DemuxEvent::CONTINUITY_ISSUE(issue));
+                return Ok(());
+            }
+        };
+        if !self.is_tracked_pid(packet.pid) {
+            self.continuity_tracker.skip_packet();
+            return Ok(());
+        }
+        match self.continuity_tracker.check(&packet) {
+            ContinuityStatus::OK => {}
+            ContinuityStatus::DISCONTINUITY(issue) | ContinuityStatus::DROP(issue)
+                if self.options.strict_continuity =>
+            {
+                return Err(generate_continuity_error(issue));
+            }
+            ContinuityStatus::DISCONTINUITY(issue) => {
+                // The PES packet (or PSI section) in progress is missing data, so don't hand it on
+                self.pes_assembler.discard(packet.pid);
+                self.psi_assembler.discard(packet.pid);
+                events.push(DemuxEvent::CONTINUITY_ISSUE(issue));
+            }
+            ContinuityStatus::DROP(issue) => {
+                if packet.transport_error_indicator {
+                    self.pes_assembler.discard(issue.pid);
+                    self.psi_assembler.discard(issue.pid);
+                }
+                events.push(DemuxEvent::CONTINUITY_ISSUE(issue));
+                return Ok(());
+            }
+            ContinuityStatus::DUPLICATE => return Ok(()),
+        }
+
+        // ProgramAssociationTable
+        if packet.pid == 0 {
+            for section in self.psi_assembler.push(packet.pid, packet.payload_unit_start_indicator, packet.data)? {
+                self.handle_pat(&section, events)?;
+            }
+            return Ok(());
+        }
+
+        // ProgramMapTable
+        if Some(packet.pid) == self.program_map_pid {
+            for section in self.psi_assembler.push(packet.pid, packet.payload_unit_start_indicator, packet.data)? {
+                // Other tables can be carried on the PID of the PMT
+                if section.first() == Some(&PMT_TABLE_ID) {
+                    self.handle_pmt(&section, events)?;
+                }
+            }
+            return Ok(());
+        }
+
+        // Elementary stream PES
+        self.handle_pes(&packet, events);
+        Ok(())
+    }
+
+    // Only the PAT, the PMT of the selected program and the elementary streams of its tracks are demuxed, so the
+    // continuity of every other PID (ex. other programs, SI tables) is neither tracked nor reported
+    fn is_tracked_pid(&self, pid: u16) -> bool {
+        pid == 0
+            || Some(pid) == self.program_map_pid
+            || self.es_tracks.iter().any(|es_track| es_track.track.pid == pid)
+    }
+
+    fn handle_pat(
+        &mut self,
+        section: &[u8],
+        events: &mut Vec<DemuxEvent>,
+    ) -> Result<(), CustomError> {
+        let pat = ProgramAssociationTable::parse(section, false)?;
+        let (program_number, program_map_pid) =
+            self.options.program_selector.select(&pat).ok_or_else(|| {
+                construct_error(
+                    MajorCode::REMUX,
+                    Box::new(RemuxMinorCode::PROGRAM_NOT_FOUND_ERROR),
+                    format!("Program not found in PAT: {:?}", self.options.program_selector),
+                    file!(),
+                    line!(),
+                )
+            })?;
+        self.program_number = program_number;
+        self.program_map_pid = Some(program_map_pid);
+        if self.programs.as_ref() != Some(&pat.programs) {
+            self.programs = Some(pat.programs.clone());
+            events.push(DemuxEvent::PAT {
+                programs: pat.programs,
+            });
+        }
+        Ok(())
+    }
+
+    fn handle_pmt(
+        &mut self,
+        section: &[u8],
+        events: &mut Vec<DemuxEvent>,
+    ) -> Result<(), CustomError> {
+        let pmt = ProgramMapTable::parse(section, false)?;
+        // Multiple programs are allowed to share the same PMT PID
+        if pmt.program_number != self.program_number {
+            return Ok(());
+        }
+        let options = self.get_extractor_options();
+        register_es_tracks(&pmt, &mut self.es_tracks, &options);
+        if self.streams.as_ref() != Some(&pmt.streams) {
+            self.streams = Some(pmt.streams.clone());
+            events.push(DemuxEvent::PMT {
+                program_number: pmt.program_number,
+                streams: pmt.streams,
+            });
+        }
+        Ok(())
+    }
+
+    /// Options the extractors get created with. The parameter sets are kept in band unless the init segments wait for
+    /// the end of the stream (see whole_stream), as a parameter set that comes after the init segment went out can't
+    /// be added to its avcC or hvcC.
+    fn get_extractor_options(&self) -> RemuxOptions {
+        RemuxOptions {
+            in_band_parameter_sets: self.options.in_band_parameter_sets || !(self.whole_stream || self.muxed),
+            ..self.options
+        }
+    }
+
+    fn handle_pes(&mut self, packet: &TransportPacket, events: &mut Vec<DemuxEvent>) {
+        let pid = packet.pid;
+        let previous_random_access = self.pes_random_access.get(&pid).copied().unwrap_or(false);
+        if packet.payload_unit_start_indicator {
+            self.pes_random_access
+                .insert(pid, packet.get_random_access_indicator());
+        }
+        let current_random_access = self.pes_random_access.get(&pid).copied().unwrap_or(false);
+
+        let complete_pes =
+            self.pes_assembler
+                .push(pid, packet.payload_unit_start_indicator, packet.data);
+        let complete_pes_count = complete_pes.len();
+        let is_assembling = self.pes_assembler.is_assembling(pid);
+        for (pes_index, pes_data) in complete_pes.into_iter().enumerate() {
+            // A packet starting a new PES packet completes the previous one. The new one is only complete as well
+            // when it fit in this packet, in which case it's the last one.
+            let is_previous_pes = packet.payload_unit_start_indicator
+                && (is_assembling || pes_index + 1 < complete_pes_count);
+            let random_access = if is_previous_pes {
+                previous_random_access
+            } else {
+                current_random_access
+            };
+            self.cut_fragment_if_needed(pid, &pes_data, random_access, events);
+            if let Some(es_track) = self
+                .es_tracks
+                .iter_mut()
+                .find(|es_track| es_track.track.pid == pid)
+            {
+                accumulate_pes(es_track, pes_data, &mut self.timestamp_reference, &mut self.continuity_tracker, events);
+            }
+        }
+    }
+
+    /// Send a media segment for every track when the PES packet of the reference track starts a new fragment
+    fn cut_fragment_if_needed(
+        &mut self,
+        pid: u16,
+        pes_data: &[u8],
+        random_access: bool,
+        events: &mut Vec<DemuxEvent>,
+    ) {
+        let fragment_duration = match self.fragment_duration {
+            Some(fragment_duration) if !self.muxed => fragment_duration,
+            _ => return,
+        };
+        let reference_track = self
+            .es_tracks
+            .iter()
+            .find(|es_track| es_track.track.stream_type.is_video())
+            .or_else(|| self.es_tracks.first())
+            .map(|es_track| es_track.track.clone());
+        let reference_track = match reference_track {
+            Some(track) if track.pid == pid => track,
+            _ => return,
+        };
+        let dts = match PESPacket::parse(pes_data).ok().and_then(|pes| pes.dts) {
+            Some(dts) => self.fragment_timestamp_unwrapper.unwrap_timestamp(dts),
+            None => return,
+        };
+        let fragment_start = *self.fragment_start.get_or_insert(dts);
+        if dts.saturating_sub(fragment_start) < fragment_duration {
+            return;
+        }
+        if reference_track.stream_type.is_video() && !random_access {
+            return;
+        }
+
+        self.fragment_start = Some(dts);
+        for es_track in self.es_tracks.iter_mut() {
+            push_init_segment_if_ready(es_track, events);
+            push_media_segment(es_track, events);
+        }
+    }
+}
+
+/// Register a track for every elementary stream in the PMT that hasn't been seen yet. Track ids are assigned
+/// in the order the streams are listed in the PMT, so they stay the same for every remux of the same program.
+fn register_es_tracks(
+    pmt: &ProgramMapTable,
+    es_tracks: &mut Vec<ElementaryStreamTrack>,
+    options: &RemuxOptions,
+) {
+    for stream_info in pmt.streams.iter() {
+        if stream_info.stream_type == ElementaryStreamType::UNKNOWN {
+            continue;
+        }
+        if es_tracks
+            .iter()
+            .any(|es_track| es_track.track.pid == stream_info.pid)
+        {
+            continue;
+        }
+        let track_id = es_tracks.len() + 1;
+        let language = stream_info
+            .get_language()
+            .unwrap_or_else(|| "und".to_string());
+        let decrypter = match (stream_info.sample_aes, options.sample_aes_key.as_ref()) {
+            (true, Some(sample_aes_key)) => Some(SampleAESDecrypter::create(sample_aes_key)),
+            _ => None,
+        };
+        let extractor = match get_ts_extractor(stream_info.stream_type, track_id, language.clone(), options) {
+            // Without a key the samples can't be remuxed
+            Ok(_) if stream_info.sample_aes && decrypter.is_none() => {
+                println!("remux :: pid {} :: SAMPLE-AES encrypted stream without a key", stream_info.pid);
+                None
+            }
+            Ok(extractor) => Some(extractor),
+            Err(err) => {
+                println!("remux :: pid {} :: {:?}", stream_info.pid, err);
+                None
+            }
+        };
+        es_tracks.push(ElementaryStreamTrack {
+            track: DemuxedTrack {
+                track_id,
+                pid: stream_info.pid,
+                stream_type: stream_info.stream_type,
+                language,
+            },
+            extractor,
+            decrypter,
+            init_segment_sent: false,
+            sequence_number: 1,
+        });
+    }
+}
+
+/// Hand a complete PES packet to the track's extractor, decrypted when the track is SAMPLE-AES encrypted
+fn accumulate_pes(
+    es_track: &mut ElementaryStreamTrack,
+    pes_data: Vec<u8>,
+    timestamp_reference: &mut Option<u64>,
+    continuity_tracker: &mut ContinuityCounterTracker,
+    events: &mut Vec<DemuxEvent>,
+) {
+    let pes_data = match es_track.decrypter.as_ref() {
+        Some(decrypter) => match decrypter.decrypt_pes(&pes_data, es_track.track.stream_type) {
+            Ok(decrypted_pes_data) => decrypted_pes_data,
+            Err(err) => {
+                println!("remux :: pid {} :: {:?}", es_track.track.pid, err);
+                return;
+            }
+        },
+        None => pes_data,
+    };
+    match PESPacket::parse(&pes_data) {
+        Ok(pes) => {
+            if let Some(timestamp) = pes.dts.or(pes.pts) {
+                timestamp_reference.get_or_insert(timestamp);
+            }
+            if let Some(extractor) = es_track.extractor.as_mut() {
+                if let Some(reference) = *timestamp_reference {
+                    extractor.seed_timestamps(reference);
+                }
+                extractor.accumulate_pes_payload(pes).ok();
+            }
+        }
+        Err(err) => {
+            println!("remux :: pid {} :: {:?}", es_track.track.pid, err);
+            return;
+        }
+    };
+    events.push(DemuxEvent::PES {
+        pid: es_track.track.pid,
+        data: pes_data,
+    });
+    report_extractor_issues(es_track, continuity_tracker, events);
+}
+
+/// Add the timestamp issues and the corrupt frames the extractor of the track found to the continuity report
+fn report_extractor_issues(
+    es_track: &mut ElementaryStreamTrack,
+    continuity_tracker: &mut ContinuityCounterTracker,
+    events: &mut Vec<DemuxEvent>,
+) {
+    let pid = es_track.track.pid;
+    let extractor = match es_track.extractor.as_mut() {
+        Some(extractor) => extractor,
+        None => return,
+    };
+    for issue in extractor.take_timestamp_issues(pid) {
+        continuity_tracker.report_timestamp_issue(issue);
+        events.push(DemuxEvent::TIMESTAMP_ISSUE(issue));
+    }
+    for corrupt_frame in extractor.take_corrupt_frames(pid) {
+        continuity_tracker.report_corrupt_frame(corrupt_frame);
+        events.push(DemuxEvent::CORRUPT_FRAME(corrupt_frame));
+    }
+    continuity_tracker.report_unverified_frames(extractor.take_unverified_frame_count());
+}
+
+fn push_init_segment_if_ready(es_track: &mut ElementaryStreamTrack, events: &mut Vec<DemuxEvent>) {
+    if es_track.init_segment_sent {
+        return;
+    }
+    let extractor = match es_track.extractor.as_mut() {
+        Some(extractor) if extractor.has_codec_config() => extractor,
+        _ => return,
+    };
+    if let Ok(data) = extractor.get_init_segment() {
+        es_track.init_segment_sent = true;
+        events.push(DemuxEvent::INIT_SEGMENT {
+            track_id: es_track.track.track_id,
+            pid: es_track.track.pid,
+            data,
+        });
+    }
+}
+
+fn push_media_segment(es_track: &mut ElementaryStreamTrack, events: &mut Vec<DemuxEvent>) {
+    // Hold on to the media until the init segment is out, so the media segments always follow it
+    if !es_track.init_segment_sent {
+        return;
+    }
+    let sequence_number = es_track.sequence_number;
+    if let Some(data) = es_track
+        .extractor
+        .as_mut()
+        .and_then(|tse| tse.get_media_segment(sequence_number).ok())
+    {
+        es_track.sequence_number += 1;
+        events.push(DemuxEvent::MEDIA_SEGMENT {
+            track_id: es_track.track.track_id,
+            pid: es_track.track.pid,
+            data,
+        });
+    }
+}
+
+fn generate_continuity_error(issue: ContinuityIssue) -> CustomError {
+    construct_error(
+        MajorCode::TRANSPORT_STREAM,
+        Box::new(TransportStreamMinorCode::CONTINUITY_ERROR),
+        format!(
+            "{:?} on pid {} at packet {} (continuity_counter {}, expected {:?})",
+            issue.kind, issue.pid, issue.packet_index, issue.continuity_counter, issue.expected_counter
+        ),
+        file!(),
+        line!(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::remux::tests::{
+        create_aac_pes, create_es_packet, create_pat_packet, create_pmt_packet,
+    };
+    use crate::container::transport_stream::continuity_counter::ContinuityIssueKind;
+    use crate::container::transport_stream::packet_format::TS_PACKET_SIZE;
+    use crate::util;
+
+    fn create_aac_ts() -> Vec<u8> {
+        let mut ts_file = [
+            create_pat_packet(&[(1, 0x1000)]),
+            create_pmt_packet(0x1000, 1, &[(0x0F, 0x101)]),
+        ]
+        .concat();
+        for index in 0..5u64 {
+            ts_file.append(&mut create_es_packet(0x101, index as u8, &create_aac_pes(index * 1920)));
+        }
+        ts_file
+    }
+
+    #[test]
+    fn test_ts_demuxer_unaligned_chunks() {
+        let ts_file = [create_aac_ts(), create_pmt_packet(0x1000, 1, &[(0x0F, 0x101)])].concat();
+        let mut demuxer = TSDemuxer::create(RemuxOptions::default());
+        let mut events: Vec<DemuxEvent> = vec![];
+        for chunk in ts_file.chunks(100) {
+            events.append(&mut demuxer.push(chunk).unwrap());
+        }
+
+        // The repeated PMT (with a new continuity counter) is unchanged so it doesn't send another event
+        assert_eq!(events.len(), 7);
+        assert_eq!(events[0], DemuxEvent::PAT { programs: [(1, 0x1000)].iter().cloned().collect() });
+        match &events[1] {
+            DemuxEvent::PMT { program_number, streams } => {
+                assert_eq!(*program_number, 1);
+                assert_eq!(streams.len(), 1);
+                assert_eq!(streams[0].stream_type, ElementaryStreamType::AAC);
+            }
+            _ => panic!("Expected PMT event"),
+        }
+        assert_eq!(events[2], DemuxEvent::PES { pid: 0x101, data: create_aac_pes(0) });
+        assert!(events[3..].iter().all(|event| matches!(event, DemuxEvent::PES { pid: 0x101, .. })));
+        assert_eq!(demuxer.get_tracks().len(), 1);
+        assert_eq!(demuxer.get_tracks()[0].stream_type, ElementaryStreamType::AAC);
+    }
+
+    #[test]
+    fn test_ts_demuxer_fragments() {
+        let ts_file = create_aac_ts();
+        let mut demuxer = TSDemuxer::create(RemuxOptions::default()).fragment_duration(3840);
+        let mut events = demuxer.push(&ts_file).unwrap();
+        events.append(&mut demuxer.finish().unwrap());
+
+        let segments: Vec<&DemuxEvent> = events
+            .iter()
+            .filter(|event| !matches!(event, DemuxEvent::PAT { .. } | DemuxEvent::PMT { .. } | DemuxEvent::PES { .. }))
+            .collect();
+        assert_eq!(segments.len(), 4);
+        assert!(matches!(segments[0], DemuxEvent::INIT_SEGMENT { track_id: 1, pid: 0x101, .. }));
+        assert!(segments[1..]
+            .iter()
+            .all(|event| matches!(event, DemuxEvent::MEDIA_SEGMENT { track_id: 1, pid: 0x101, .. })));
+        assert!(demuxer.get_continuity_report().is_clean());
+    }
+
+    #[test]
+    fn test_ts_demuxer_muxed_fragments() {
+        let ts_file = create_aac_ts();
+        let mut demuxer = TSDemuxer::create(RemuxOptions::default()).fragment_duration(3840).muxed(true);
+        let events = demuxer.push(&ts_file).unwrap();
+        // The media is held for the muxed segments
+        assert!(!events.iter().any(|event| matches!(event, DemuxEvent::MEDIA_SEGMENT { .. })));
+
+        let (_, media_segments) = demuxer.finish_muxed().unwrap();
+        let mut sequence_numbers: Vec<u32> = vec![];
+        let mut offset = 0usize;
+        while offset < media_segments.len() {
+            if &media_segments[(offset + 4)..(offset + 8)] == b"moof" {
+                sequence_numbers.push(util::get_u32(&media_segments, offset + 20).unwrap());
+            }
+            offset += util::get_u32(&media_segments, offset).unwrap() as usize;
+        }
+        // 5 frames of 1920, cut every 3840
+        assert_eq!(sequence_numbers, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_ts_demuxer_malformed_packet() {
+        let ts_file = create_aac_ts();
+        // adaptation_field_length runs past the end of the packet
+        let mut malformed_packet = create_es_packet(0x101, 2, &[]);
+        malformed_packet[3] = 0x32;
+        malformed_packet[4] = 0xC0;
+        let ts_file = [&ts_file[..(TS_PACKET_SIZE * 4)], &malformed_packet, &ts_file[(TS_PACKET_SIZE * 4)..]].concat();
+
+        let mut demuxer = TSDemuxer::create(RemuxOptions::default());
+        let events = demuxer.push(&ts_file).unwrap();
+        // The packet is skipped and the packets after it are still demuxed
+        let pes_count = events.iter().filter(|event| matches!(event, DemuxEvent::PES { .. })).count();
+        assert_eq!(pes_count, 5);
+        let report = demuxer.get_continuity_report();
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].kind, ContinuityIssueKind::MALFORMED_PACKET);
+        assert_eq!(report.issues[0].pid, 0x101);
+        assert_eq!(report.issues[0].packet_index, 4);
+
+        let options = RemuxOptions {
+            strict_continuity: true,
+            ..RemuxOptions::default()
+        };
+        let err = TSDemuxer::create(options).push(&ts_file).err().unwrap();
+        assert_eq!(err.minor, TransportStreamMinorCode::CONTINUITY_ERROR as u8);
+    }
+
+    #[test]
+    fn test_ts_demuxer_untracked_pids() {
+        let ts_file = create_aac_ts();
+        // Packets of a PID outside the program with the continuity_counter jumping around, and a malformed one
+        let mut malformed_packet = create_es_packet(0x200, 9, &[]);
+        malformed_packet[3] = 0x39;
+        malformed_packet[4] = 0xC0;
+        let ts_file = [
+            &ts_file[..(TS_PACKET_SIZE * 3)],
+            &create_es_packet(0x200, 0, &[]),
+            &create_es_packet(0x200, 5, &[]),
+            &malformed_packet,
+            &create_es_packet(0x200, 5, &[]),
+            &create_es_packet(0x200, 5, &[]),
+            &ts_file[(TS_PACKET_SIZE * 3)..(TS_PACKET_SIZE * 4)],
+            // The packet with continuity_counter 2 of the tracked PID is lost
+            &ts_file[(TS_PACKET_SIZE * 5)..],
+        ]
+        .concat();
+
+        let options = RemuxOptions {
+            strict_continuity: true,
+            ..RemuxOptions::default()
+        };
+        let mut demuxer = TSDemuxer::create(options);
+        let err = demuxer.push(&ts_file).err().unwrap();
+        assert_eq!(err.minor, TransportStreamMinorCode::CONTINUITY_ERROR as u8);
+        // Enforced on the tracked PID only
+        assert!(err.debug_message.contains("on pid 257 at packet 9"));
+
+        let mut demuxer = TSDemuxer::create(RemuxOptions::default());
+        demuxer.push(&ts_file).unwrap();
+        let report = demuxer.get_continuity_report();
+        // Only the tracked PID is reported, at its index in the stream
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].pid, 0x101);
+        assert_eq!(report.issues[0].kind, ContinuityIssueKind::LOST_PACKETS(1));
+        assert_eq!(report.issues[0].packet_index, 9);
+        assert_eq!(report.dropped_packets, 0);
+    }
+
+    #[test]
+    fn test_ts_demuxer_pmt_across_packets() {
+        let pmt_packet = create_pmt_packet(0x1000, 1, &[(0x0F, 0x101), (0x1B, 0x100)]);
+        let section_end = 5 + 3 + pmt_packet[7] as usize;
+        let section = &pmt_packet[5..section_end];
+        // The first 10 bytes of the section after the pointer_field, behind adaptation field stuffing
+        let mut first_packet = vec![0x47, 0x50, 0x00, 0x31, 172, 0x00];
+        first_packet.resize(TS_PACKET_SIZE - 11, 0xFF);
+        first_packet.push(0x00);
+        first_packet.extend_from_slice(&section[..10]);
+        let mut second_packet = vec![0x47, 0x10, 0x00, 0x12];
+        second_packet.extend_from_slice(&section[10..]);
+        second_packet.resize(TS_PACKET_SIZE, 0xFF);
+        // A continuation without a section in progress is ignored
+        let mut stray_packet = second_packet.clone();
+        stray_packet[3] = 0x10;
+        let ts_file = [create_pat_packet(&[(1, 0x1000)]), stray_packet, first_packet, second_packet].concat();
+
+        let mut demuxer = TSDemuxer::create(RemuxOptions::default());
+        let mut events = demuxer.push(&ts_file).unwrap();
+        // Too few packets to detect the packet size before finish
+        events.append(&mut demuxer.finish().unwrap());
+        assert!(matches!(events[0], DemuxEvent::PAT { .. }));
+        assert!(matches!(&events[1], DemuxEvent::PMT { streams, .. } if streams.len() == 2));
+        assert_eq!(demuxer.get_tracks().len(), 2);
+
+        // A PMT whose CRC_32 doesn't match
+        let mut corrupt_pmt_packet = create_pmt_packet(0x1000, 1, &[(0x0F, 0x101)]);
+        corrupt_pmt_packet[3] = 0x13;
+        corrupt_pmt_packet[13] = 0x1F;
+        assert!(demuxer.push(&corrupt_pmt_packet).is_err());
+    }
+
+    #[test]
+    fn test_ts_demuxer_error_keeps_remaining_packets() {
+        let ts_file = create_aac_ts();
+        // pointer_field points past the end of the packet
+        let mut bad_pat_packet = create_pat_packet(&[(1, 0x1000)]);
+        bad_pat_packet[3] = 0x11;
+        bad_pat_packet[4] = 0xC0;
+        let ts_file = [&ts_file[..(TS_PACKET_SIZE * 4)], &bad_pat_packet, &ts_file[(TS_PACKET_SIZE * 4)..]].concat();
+
+        let mut demuxer = TSDemuxer::create(RemuxOptions::default());
+        assert!(demuxer.push(&ts_file).is_err());
+        // The packets after the PAT are still there for the next push
+        let events = demuxer.push(&[]).unwrap();
+        let pes_count = events.iter().filter(|event| matches!(event, DemuxEvent::PES { .. })).count();
+        assert_eq!(pes_count, 3);
+    }
+
+    #[test]
+    fn test_ts_demuxer_packet_format_detection() {
+        // 192 byte M2TS packets pushed a few bytes at a time
+        let m2ts_file: Vec<u8> = create_aac_ts()
+            .chunks(TS_PACKET_SIZE)
+            .flat_map(|packet| [vec![0x00, 0x00, 0x00, 0x00], packet.to_vec()].concat())
+            .collect();
+        let mut demuxer = TSDemuxer::create(RemuxOptions::default());
+        let mut events: Vec<DemuxEvent> = vec![];
+        for chunk in m2ts_file.chunks(100) {
+            events.append(&mut demuxer.push(chunk).unwrap());
+        }
+        assert_eq!(demuxer.get_packet_format(), Some(TSPacketFormat::M2TS));
+        let pes_count = events.iter().filter(|event| matches!(event, DemuxEvent::PES { .. })).count();
+        assert_eq!(pes_count, 5);
+
+        // Too few packets to detect the packet size until the stream ends
+        let mut demuxer = TSDemuxer::create(RemuxOptions::default());
+        let ts_file = create_aac_ts();
+        assert!(demuxer.push(&ts_file[..(TS_PACKET_SIZE * 3)]).unwrap().is_empty());
+        assert_eq!(demuxer.get_packet_format(), None);
+        let events = demuxer.finish().unwrap();
+        assert_eq!(demuxer.get_packet_format(), Some(TSPacketFormat::TS));
+        assert!(matches!(events[0], DemuxEvent::PAT { .. }));
+    }
+}
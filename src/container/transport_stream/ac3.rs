@@ -0,0 +1,258 @@
+use crate::error::{CustomError, construct_error, error_code::{MajorCode, TransportStreamMinorCode}};
+use crate::util::bit_reader::BitReader;
+
+static SYNC_WORD: u16 = 0x0B77;
+// Bytes needed to read the syncinfo and the start of the bsi up to lfeon
+static HEADER_SIZE: usize = 8;
+/// Every AC-3 sync frame holds 6 audio blocks of 256 samples
+pub static AC3_SAMPLES_PER_FRAME: u32 = 1536;
+// Nominal bit rate (kbps) per frame_size_code / 2. ETSI TS 102 366; 4.4.1.4 Table 4.13
+static BIT_RATES: [u32; 19] = [
+  32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 448, 512, 576, 640
+];
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AC3SyncFrameHeader {
+  pub fscod: u8,                // 2 bit
+  pub frmsizecod: u8,           // 6 bit
+  pub bsid: u8,                 // 5 bit
+  pub bsmod: u8,                // 3 bit
+  pub acmod: u8,                // 3 bit
+  pub lfeon: bool,
+  /// Size of the whole sync frame in bytes
+  pub frame_size: usize,
+}
+
+impl AC3SyncFrameHeader {
+  pub fn get_sample_rate(&self) -> u32 {
+    map_fscod(self.fscod)
+  }
+
+  /// Number of full bandwidth channels (from acmod) plus the LFE channel
+  pub fn get_channel_count(&self) -> u32 {
+    let channel_count = match self.acmod {
+      0 => 2, // 1+1 dual mono
+      1 => 1,
+      2 => 2,
+      3 => 3,
+      4 => 3,
+      5 => 4,
+      6 => 4,
+      _ => 5,
+    };
+    channel_count + self.lfeon as u32
+  }
+
+  /// bit_rate_code of the dac3 box
+  pub fn get_bit_rate_code(&self) -> u8 {
+    self.frmsizecod >> 1
+  }
+}
+
+/// An AC-3 sync frame. The data is the whole sync frame (syncinfo included), which is what an ac-3 sample holds.
+#[derive(Debug, Default)]
+pub struct AC3SyncFrame {
+  pub header: AC3SyncFrameHeader,
+  pub data: Vec<u8>,
+  /// Where the sync frame starts in the parsed data
+  pub offset: usize,
+  pub pts: u64,
+  pub dts: u64,
+}
+
+impl AC3SyncFrame {
+  pub fn set_pts(&mut self, pts: u64) {
+    self.pts = pts;
+  }
+
+  pub fn set_dts(&mut self, dts: u64) {
+    self.dts = dts;
+  }
+}
+
+pub fn map_fscod(fscod: u8) -> u32 {
+  match fscod {
+    0 => 48000,
+    1 => 44100,
+    2 => 32000,
+    _ => 0,
+  }
+}
+
+/*
+ETSI TS 102 366 (A/52); 4.3 Bit stream syntax
+*/
+#[derive(Debug)]
+pub struct AC3 {}
+
+impl AC3 {
+  /// Parse every complete sync frame in the data. Returns the sync frames and the number of bytes they took up. The
+  /// bytes after that are the start of a sync frame that continues in the next PES packet.
+  pub fn parse(data: &[u8]) -> Result<(Vec<AC3SyncFrame>, usize), CustomError> {
+    let mut index = 0usize;
+    let mut sync_frames: Vec<AC3SyncFrame> = vec![];
+    while index + HEADER_SIZE <= data.len() {
+      if !AC3::is_sync_word(&data[index..]) {
+        // Skip anything that isn't a sync frame until the next sync word
+        index += 1;
+        continue;
+      }
+      let header = match AC3::parse_sync_frame_header(&data[index..]) {
+        Ok(header) => header,
+        // The sync word showed up in the middle of other data
+        Err(_) => {
+          index += 1;
+          continue;
+        }
+      };
+      let end = index + header.frame_size;
+      if end > data.len() {
+        break;
+      }
+      sync_frames.push(AC3SyncFrame {
+        data: data[index..end].to_vec(),
+        header,
+        offset: index,
+        pts: 0,
+        dts: 0,
+      });
+      index = end;
+    }
+    Ok((sync_frames, index))
+  }
+
+  fn is_sync_word(data: &[u8]) -> bool {
+    data.len() >= 2 && ((data[0] as u16) << 8 | data[1] as u16) == SYNC_WORD
+  }
+
+  fn parse_sync_frame_header(data: &[u8]) -> Result<AC3SyncFrameHeader, CustomError> {
+    let mut bit_reader = BitReader::create_bit_reader(&data[0..HEADER_SIZE]);
+    // syncword
+    bit_reader.read_bits(16)?;
+    // crc1
+    bit_reader.read_bits(16)?;
+    let fscod = bit_reader.read_bits(2)? as u8;
+    let frmsizecod = bit_reader.read_bits(6)? as u8;
+    let bsid = bit_reader.read_bits(5)? as u8;
+    let bsmod = bit_reader.read_bits(3)? as u8;
+    let acmod = bit_reader.read_bits(3)? as u8;
+
+    if bsid > 10 {
+      // bsid of 16 is E-AC-3, which has a different syntax
+      return Err(AC3::generate_error(format!("Unsupported AC-3 bsid: {}", bsid)));
+    }
+    let frame_size = AC3::get_frame_size(fscod, frmsizecod)
+      .ok_or_else(||AC3::generate_error(format!("Invalid AC-3 fscod ({}) or frmsizecod ({})", fscod, frmsizecod)))?;
+
+    if (acmod & 0x1) != 0 && acmod != 0x1 {
+      // cmixlev
+      bit_reader.read_bits(2)?;
+    }
+    if (acmod & 0x4) != 0 {
+      // surmixlev
+      bit_reader.read_bits(2)?;
+    }
+    if acmod == 0x2 {
+      // dsurmod
+      bit_reader.read_bits(2)?;
+    }
+    let lfeon = bit_reader.read_bits(1)? == 1;
+
+    Ok(AC3SyncFrameHeader {
+      fscod,
+      frmsizecod,
+      bsid,
+      bsmod,
+      acmod,
+      lfeon,
+      frame_size,
+    })
+  }
+
+  // Sync frame size in bytes. ETSI TS 102 366; 4.4.1.4 Table 4.13
+  fn get_frame_size(fscod: u8, frmsizecod: u8) -> Option<usize> {
+    let bit_rate = *BIT_RATES.get((frmsizecod >> 1) as usize)?;
+    let words = match fscod {
+      0 => bit_rate * 2,
+      // 44.1 kHz frames don't divide evenly, so every other frame size code adds a word
+      1 => (bit_rate * 1000 * AC3_SAMPLES_PER_FRAME) / (44100 * 16) + (frmsizecod & 0x1) as u32,
+      2 => bit_rate * 3,
+      _ => return None,
+    };
+    Some(words as usize * 2)
+  }
+
+  fn generate_error(message: String) -> CustomError {
+    construct_error(
+      MajorCode::TRANSPORT_STREAM,
+      Box::new(TransportStreamMinorCode::PARSE_TS_ERROR),
+      message,
+      file!(),
+      line!())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // 48 kHz, 192 kbps (768 bytes), bsid 8, 3/2 (acmod 7) with LFE
+  fn create_sync_frame() -> Vec<u8> {
+    let mut sync_frame = vec![
+      // syncword
+      0x0B, 0x77,
+      // crc1
+      0x00, 0x00,
+      // fscod + frmsizecod
+      0x14,
+      // bsid + bsmod
+      0x40,
+      // acmod, cmixlev, surmixlev, lfeon
+      0xE5, 0x00,
+    ];
+    sync_frame.resize(768, 0x00);
+    sync_frame
+  }
+
+  #[test]
+  fn test_parse_ac3_sync_frames() {
+    let data = [create_sync_frame(), create_sync_frame(), create_sync_frame()[0..100].to_vec()].concat();
+    let (sync_frames, size) = AC3::parse(&data).unwrap();
+
+    assert_eq!(sync_frames.len(), 2);
+    assert_eq!(size, 768 * 2);
+    let header = &sync_frames[0].header;
+    assert_eq!(header.bsid, 8);
+    assert_eq!(header.acmod, 7);
+    assert!(header.lfeon);
+    assert_eq!(header.frame_size, 768);
+    assert_eq!(header.get_sample_rate(), 48000);
+    assert_eq!(header.get_channel_count(), 6);
+    assert_eq!(header.get_bit_rate_code(), 10);
+    assert_eq!(sync_frames[1].data.len(), 768);
+  }
+
+  #[test]
+  fn test_parse_ac3_skips_false_sync_word() {
+    // A sync word followed by a reserved fscod and frmsizecod
+    let junk = vec![0x0B, 0x77, 0x00, 0x00, 0xFF, 0x40, 0x00, 0x00, 0x12];
+    let data = [junk.clone(), create_sync_frame(), create_sync_frame()].concat();
+    let (sync_frames, size) = AC3::parse(&data).unwrap();
+
+    assert_eq!(sync_frames.len(), 2);
+    assert_eq!(size, junk.len() + 768 * 2);
+    assert_eq!(sync_frames[0].offset, junk.len());
+    assert_eq!(sync_frames[1].offset, junk.len() + 768);
+  }
+
+  #[test]
+  fn test_ac3_frame_size() {
+    assert_eq!(AC3::get_frame_size(0, 0), Some(128));
+    assert_eq!(AC3::get_frame_size(1, 0), Some(138));
+    assert_eq!(AC3::get_frame_size(1, 1), Some(140));
+    assert_eq!(AC3::get_frame_size(1, 37), Some(2788));
+    assert_eq!(AC3::get_frame_size(2, 37), Some(3840));
+    assert_eq!(AC3::get_frame_size(3, 0), None);
+    assert_eq!(AC3::get_frame_size(0, 38), None);
+  }
+}
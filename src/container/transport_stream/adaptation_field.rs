@@ -0,0 +1,165 @@
+use crate::error::{construct_error, CustomError};
+use crate::error::error_code::{MajorCode, TransportStreamMinorCode};
+use crate::util;
+
+/// Program clock reference. 33 bit base in 90 kHz and 9 bit extension in 27 MHz
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramClockReference {
+  pub base: u64,                          // 33 bit
+  pub extension: u16,                     // 9 bit
+}
+
+impl ProgramClockReference {
+  fn parse(data: &[u8], start: usize) -> Result<ProgramClockReference, CustomError> {
+    let high = util::get_u32(data, start)? as u64;
+    let low = util::get_u16(data, start + 4)? as u64;
+    let pcr = (high << 16) | low;
+    Ok(ProgramClockReference {
+      base: pcr >> 15,
+      extension: (pcr & 0x1FF) as u16,
+    })
+  }
+
+  /// PCR in the 27 MHz system clock
+  pub fn get_value(&self) -> u64 {
+    self.base * 300 + self.extension as u64
+  }
+}
+
+/*
+ISO/IEC 13818-1; 2.4.3.4 Adaptation field
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdaptationField<'a> {
+  pub adaptation_field_length: u8,
+  pub discontinuity_indicator: bool,
+  pub random_access_indicator: bool,
+  pub elementary_stream_priority_indicator: bool,
+  pub pcr: Option<ProgramClockReference>,
+  pub opcr: Option<ProgramClockReference>,
+  pub splice_countdown: Option<i8>,
+  pub transport_private_data: Option<&'a [u8]>,
+  pub adaptation_field_extension: Option<&'a [u8]>,
+}
+
+impl<'a> AdaptationField<'a> {
+  /// Parse the adaptation field. The data starts at the adaptation_field_length byte.
+  pub fn parse(data: &'a [u8]) -> Result<AdaptationField<'a>, CustomError> {
+    let adaptation_field_length = util::get_u8(data, 0)?;
+    let end = 1 + adaptation_field_length as usize;
+    if end > data.len() {
+      return Err(AdaptationField::generate_error("Adaptation field length is longer than the packet"));
+    }
+
+    let mut adaptation_field = AdaptationField {
+      adaptation_field_length,
+      discontinuity_indicator: false,
+      random_access_indicator: false,
+      elementary_stream_priority_indicator: false,
+      pcr: None,
+      opcr: None,
+      splice_countdown: None,
+      transport_private_data: None,
+      adaptation_field_extension: None,
+    };
+    if adaptation_field_length == 0 {
+      // Single stuffing byte
+      return Ok(adaptation_field);
+    }
+
+    let data = &data[0..end];
+    let flags = util::get_u8(data, 1)?;
+    adaptation_field.discontinuity_indicator = (flags & 0x80) != 0;
+    adaptation_field.random_access_indicator = (flags & 0x40) != 0;
+    adaptation_field.elementary_stream_priority_indicator = (flags & 0x20) != 0;
+    let pcr_flag = (flags & 0x10) != 0;
+    let opcr_flag = (flags & 0x08) != 0;
+    let splicing_point_flag = (flags & 0x04) != 0;
+    let transport_private_data_flag = (flags & 0x02) != 0;
+    let adaptation_field_extension_flag = (flags & 0x01) != 0;
+
+    let mut start = 2usize;
+    if pcr_flag {
+      adaptation_field.pcr = Some(ProgramClockReference::parse(data, start)?);
+      start += 6;
+    }
+    if opcr_flag {
+      adaptation_field.opcr = Some(ProgramClockReference::parse(data, start)?);
+      start += 6;
+    }
+    if splicing_point_flag {
+      adaptation_field.splice_countdown = Some(util::get_u8(data, start)? as i8);
+      start += 1;
+    }
+    if transport_private_data_flag {
+      let transport_private_data_length = util::get_u8(data, start)? as usize;
+      start += 1;
+      let private_data_end = start + transport_private_data_length;
+      adaptation_field.transport_private_data = Some(
+        data.get(start..private_data_end)
+          .ok_or_else(||AdaptationField::generate_error("Transport private data is longer than the adaptation field"))?
+      );
+      start = private_data_end;
+    }
+    if adaptation_field_extension_flag {
+      let adaptation_field_extension_length = util::get_u8(data, start)? as usize;
+      start += 1;
+      adaptation_field.adaptation_field_extension = Some(
+        data.get(start..(start + adaptation_field_extension_length))
+          .ok_or_else(||AdaptationField::generate_error("Adaptation field extension is longer than the adaptation field"))?
+      );
+    }
+
+    Ok(adaptation_field)
+  }
+
+  fn generate_error(message: &str) -> CustomError {
+    construct_error(
+      MajorCode::TRANSPORT_STREAM,
+      Box::new(TransportStreamMinorCode::PARSE_TS_ERROR),
+      message.to_string(),
+      file!(),
+      line!())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_adaptation_field() {
+    let adaptation_field: [u8; 20] = [
+      // adaptation_field_length
+      0x13,
+      // discontinuity_indicator, random_access_indicator, PCR_flag, splicing_point_flag, transport_private_data_flag
+      0xD6,
+      // PCR (base: 900000, extension: 12)
+      0x00, 0x06, 0xDD, 0xD0, 0x7E, 0x0C,
+      // splice_countdown
+      0xFE,
+      // transport_private_data_length
+      0x03,
+      0x01, 0x02, 0x03,
+      // stuffing
+      0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    ];
+
+    let adaptation_field = AdaptationField::parse(&adaptation_field).unwrap();
+    assert!(adaptation_field.discontinuity_indicator);
+    assert!(adaptation_field.random_access_indicator);
+    assert!(!adaptation_field.elementary_stream_priority_indicator);
+    assert_eq!(adaptation_field.pcr, Some(ProgramClockReference{base: 900000, extension: 12}));
+    assert_eq!(adaptation_field.pcr.unwrap().get_value(), 270000012);
+    assert_eq!(adaptation_field.opcr, None);
+    assert_eq!(adaptation_field.splice_countdown, Some(-2));
+    assert_eq!(adaptation_field.transport_private_data, Some([0x01u8, 0x02, 0x03].as_ref()));
+    assert_eq!(adaptation_field.adaptation_field_extension, None);
+  }
+
+  #[test]
+  fn test_parse_adaptation_field_invalid_length() {
+    let adaptation_field: [u8; 4] = [0x07, 0x10, 0x00, 0x01];
+    assert!(AdaptationField::parse(&adaptation_field).is_err());
+  }
+}
@@ -1,7 +1,9 @@
 use crate::error::{CustomError, construct_error, error_code::{MajorCode, TransportStreamMinorCode}};
+use crate::util;
 use crate::util::bit_reader::BitReader;
+use crate::util::bit_writer::BitWriter;
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct ADTSHeader {
   pub id_version: u8,
   pub profile: u8,
@@ -11,6 +13,36 @@ pub struct ADTSHeader {
   pub crc: Option<u16>
 }
 
+impl ADTSHeader {
+  /// Write the header in front of a single raw data block. frame_length includes the header.
+  /// ISO/IEC 13818-7; 6.2.1
+  pub fn build(&self) -> Vec<u8> {
+    let mut bit_writer = BitWriter::create_bit_writer();
+    bit_writer
+      .write_bits(0xFFF, 12)
+      .write_bits((self.id_version != 0) as usize, 1)
+      // layer
+      .write_bits(0, 2)
+      .write_bits(self.crc.is_none() as usize, 1)
+      .write_bits(self.profile as usize, 2)
+      .write_bits(self.sampling_frequency_index as usize, 4)
+      // private_bit
+      .write_bits(0, 1)
+      .write_bits(self.channel_configuration as usize, 3)
+      // original_copy, home, copyright_identification_bit and copyright_identification_start
+      .write_bits(0, 4)
+      .write_bits(self.frame_length as usize, 13)
+      // adts_buffer_fullness of 0x7FF signals a variable bitrate
+      .write_bits(0x7FF, 11)
+      // number_of_raw_data_blocks_in_frame
+      .write_bits(0, 2);
+    if let Some(crc) = self.crc {
+      bit_writer.write_bits(crc as usize, 16);
+    }
+    bit_writer.finish()
+  }
+}
+
 #[derive(Debug, Default)]
 pub struct ADTSFrame {
   pub header: ADTSHeader,
@@ -27,42 +59,250 @@ impl ADTSFrame {
   pub fn set_dts(&mut self, dts: u64) {
     self.dts = dts;
   }
+
+  /// Whether the raw data block carries SBR data (HE-AAC), which implicit signaling leaves for the decoder to find.
+  /// The elements in front of it can't be skipped without decoding them, so the fill elements are walked back from the
+  /// ID_END at the end of the block, taking the one whose count lines up with the bits in front of it.
+  /// ISO/IEC 14496-3; 4.4.2.1 Table 4.3 and 4.4.2.7 Table 4.10
+  pub fn has_sbr_payload(&self) -> bool {
+    self.find_sbr_payload().is_some()
+  }
+
+  /// Whether the SBR data carries parametric stereo (HE-AAC v2), which only goes with a mono core.
+  /// The ps_data is an sbr_extension behind the Huffman coded envelope and noise floor data, so the SBR extension is
+  /// read from the end of the fill element: past the zero bits aligning it to the fill element, the last
+  /// bs_extension_size bytes of the sbr_single_channel_element are its extensions, right behind bs_extended_data and
+  /// bs_extension_size, and the first one has to be EXTENSION_ID_PS.
+  /// ISO/IEC 14496-3; 4.4.2.8
+  pub fn has_ps_payload(&self) -> bool {
+    if self.header.channel_configuration != 1 {
+      return false;
+    }
+    let (payload_start, payload_end) = match self.find_sbr_payload() {
+      Some(payload) => payload,
+      None => return false,
+    };
+    (0..8)
+      .filter(|align_bits| payload_end >= payload_start + align_bits && get_bits(&self.data, payload_end - align_bits, *align_bits) == 0)
+      .any(|align_bits| ADTSFrame::has_ps_extension(&self.data, payload_start, payload_end - align_bits))
+  }
+
+  // bs_extended_data set, then bs_extension_size (with bs_esc_count when it is 15) counting the bytes up to sbr_end,
+  // which start with the 2 bit bs_extension_id
+  fn has_ps_extension(data: &[u8], payload_start: usize, sbr_end: usize) -> bool {
+    (1..=MAX_SBR_EXTENSION_SIZE).any(|size| {
+      let extension_start = match sbr_end.checked_sub(size * 8) {
+        Some(extension_start) => extension_start,
+        None => return false,
+      };
+      let size_bits = if size < 15 { 4 } else { 12 };
+      // extension_type, bs_header_flag and bs_extended_data are in front of it at least
+      if extension_start < payload_start + 6 + size_bits {
+        return false;
+      }
+      let size_start = extension_start - size_bits;
+      let signaled_size = match get_bits(data, size_start, 4) {
+        15 if size_bits == 12 => 15 + get_bits(data, size_start + 4, 8),
+        signaled_size if size_bits == 4 => signaled_size,
+        _ => return false,
+      };
+      signaled_size == size
+        && get_bits(data, size_start - 1, 1) == 1
+        && get_bits(data, extension_start, 2) == EXTENSION_ID_PS
+    })
+  }
+
+  // Start of the extension payload, with the extension_type, and end of the fill element carrying the SBR data
+  fn find_sbr_payload(&self) -> Option<(usize, usize)> {
+    let last_one_bit = match self.data.iter().rposition(|byte|*byte != 0) {
+      Some(index) => index * 8 + 7 - self.data[index].trailing_zeros() as usize,
+      None => return None,
+    };
+    // The last bit set is the end of ID_END, every element before it ends where the next one starts
+    if last_one_bit < 2 || get_bits(&self.data, last_one_bit - 2, 3) != ID_END {
+      return None;
+    }
+    let mut element_end = last_one_bit - 2;
+    while let Some((element_start, payload_start)) = ADTSFrame::find_fill_element(&self.data, element_end) {
+      let extension_type = get_bits(&self.data, payload_start, 4);
+      if extension_type == EXT_SBR_DATA || extension_type == EXT_SBR_DATA_CRC {
+        return Some((payload_start, element_end));
+      }
+      element_end = element_start;
+    }
+    None
+  }
+
+  // A fill_element ending at element_end: ID_FIL, a 4 bit count (with an 8 bit esc_count when it is 15) and count
+  // bytes of extension payload, starting with the extension_type. Returns where the element and the payload start.
+  fn find_fill_element(data: &[u8], element_end: usize) -> Option<(usize, usize)> {
+    for count in 1..=MAX_FILL_COUNT {
+      let payload_start = element_end.checked_sub(count * 8)?;
+      let count_bits = if count < 15 { 4 } else { 12 };
+      let element_start = payload_start.checked_sub(3 + count_bits)?;
+      if get_bits(data, element_start, 3) != ID_FIL {
+        continue;
+      }
+      let signaled_count = match get_bits(data, element_start + 3, 4) {
+        15 if count_bits == 12 => 15 + get_bits(data, element_start + 7, 8) - 1,
+        signaled_count if count_bits == 4 => signaled_count,
+        _ => continue,
+      };
+      if signaled_count == count {
+        return Some((element_start, payload_start));
+      }
+    }
+    None
+  }
 }
 
+fn get_bits(data: &[u8], bit_offset: usize, count: usize) -> usize {
+  (bit_offset..bit_offset + count).fold(0, |value, bit_index| {
+    let bit = data.get(bit_index / 8).map(|byte|(byte >> (7 - bit_index % 8)) & 0x1).unwrap_or(0);
+    (value << 1) | bit as usize
+  })
+}
+
+
+// Size of the adts_fixed_header and adts_variable_header
+static ADTS_HEADER_SIZE: usize = 7;
+// Size of a crc_check or a raw_data_block_position
+static ADTS_FIELD_SIZE: usize = 2;
+
+// Syntactic element ids and extension types. ISO/IEC 14496-3; 4.5.2.1 Table 4.85 and 4.5.2.8 Table 4.121
+static ID_FIL: usize = 0x6;
+static ID_END: usize = 0x7;
+static EXT_SBR_DATA: usize = 0xD;
+static EXT_SBR_DATA_CRC: usize = 0xE;
+// Largest count of a fill_element, 15 + esc_count - 1
+static MAX_FILL_COUNT: usize = 269;
+// bs_extension_id of ps_data. ISO/IEC 14496-3; 4.4.2.8
+static EXTENSION_ID_PS: usize = 0x2;
+// Largest bs_extension_size, 15 + bs_esc_count
+static MAX_SBR_EXTENSION_SIZE: usize = 270;
+
+/// Outcome of the crc_checks of the ADTS frames parsed by ADTS::parse_with_crc_check
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ADTSCrcCheck {
+  /// ADTS frames left out because their crc_check didn't match
+  pub dropped_frames: usize,
+  /// ADTS frames kept as is because their crc_check can't be verified without decoding them
+  pub unverified_frames: usize,
+}
 
 #[derive(Debug)]
 pub struct ADTS {}
 
 impl ADTS {
+  /// Split the ADTS frames into their raw data blocks, one ADTSFrame (AAC access unit) per block
   pub fn parse(data: &[u8]) -> Result<Vec<ADTSFrame>, CustomError> {
+    ADTS::parse_adts_frames(data, false).map(|(adts_frames, _)|adts_frames)
+  }
+
+  /// Same as parse, but ADTS frames with a header crc_check that doesn't match are left out. Only frames with several
+  /// raw data blocks have a crc_check that can be verified (see is_header_crc_valid), the ones with a single block and
+  /// a crc_check are counted as unverified.
+  pub fn parse_with_crc_check(data: &[u8]) -> Result<(Vec<ADTSFrame>, ADTSCrcCheck), CustomError> {
+    ADTS::parse_adts_frames(data, true)
+  }
+
+  fn parse_adts_frames(data: &[u8], verify_crc: bool) -> Result<(Vec<ADTSFrame>, ADTSCrcCheck), CustomError> {
     let data_read = data;
     let mut index = 0usize;
     let mut adts_frames: Vec<ADTSFrame> = vec![];
+    let mut crc_check = ADTSCrcCheck::default();
     while index < data_read.len() {
-      let adts_header = ADTS::parse_adts_header(data_read[index..].as_ref())?;
-      let mut offset = 7usize;
-      if adts_header.crc.is_some() {
-        offset = 9;
-      }
-      let start = index + offset;
+      let (adts_header, raw_data_block_positions) = ADTS::parse_adts_header(data_read[index..].as_ref())?;
       let end = index + adts_header.frame_length as usize;
-      let frame_data = data_read[start..end].to_vec();
-      let adts_frame = ADTSFrame{
-        header: adts_header,
-        data: frame_data,
-        pts: 0,
-        dts: 0,
-      };
-
-      adts_frames.push(adts_frame);
+      let header_size = ADTS::get_header_size(&adts_header, raw_data_block_positions.len());
+      if adts_header.frame_length as usize <= header_size || end > data_read.len() {
+        return Err(
+          construct_error(
+            MajorCode::TRANSPORT_STREAM,
+            Box::new(TransportStreamMinorCode::PARSE_TS_ERROR),
+            format!("ADTS frame_length of {} doesn't fit the {} bytes left", adts_header.frame_length, data_read.len() - index),
+            file!(),
+            line!()
+          )
+        );
+      }
+      let frame = &data_read[index..end];
       index = end;
+
+      if verify_crc && adts_header.crc.is_some() && raw_data_block_positions.is_empty() {
+        crc_check.unverified_frames += 1;
+      } else if verify_crc && !ADTS::is_header_crc_valid(frame, &adts_header, raw_data_block_positions.len()) {
+        crc_check.dropped_frames += 1;
+        continue;
+      }
+      for raw_data_block in ADTS::split_raw_data_blocks(frame, &adts_header, &raw_data_block_positions)? {
+        adts_frames.push(ADTSFrame{
+          header: adts_header.clone(),
+          data: raw_data_block.to_vec(),
+          pts: 0,
+          dts: 0,
+        });
+      }
     }
     Ok(
-      adts_frames
+      (adts_frames, crc_check)
     )
   }
 
-  fn parse_adts_header(data: &[u8]) -> Result<ADTSHeader, CustomError> {
+  fn get_header_size(adts_header: &ADTSHeader, number_of_raw_data_block_positions: usize) -> usize {
+    match adts_header.crc {
+      Some(_) => ADTS_HEADER_SIZE + number_of_raw_data_block_positions * ADTS_FIELD_SIZE + ADTS_FIELD_SIZE,
+      None => ADTS_HEADER_SIZE,
+    }
+  }
+
+  // ISO/IEC 13818-7; 6.2 Table 5 — Syntax of adts_frame(). With more than one raw data block, every block is
+  // followed by its own crc_check, and the raw_data_block_position of a block is its offset from the first one.
+  fn split_raw_data_blocks<'a>(frame: &'a [u8], adts_header: &ADTSHeader, raw_data_block_positions: &[u16]) -> Result<Vec<&'a [u8]>, CustomError> {
+    let header_size = ADTS::get_header_size(adts_header, raw_data_block_positions.len());
+    if raw_data_block_positions.is_empty() {
+      return Ok(vec![&frame[header_size..]]);
+    }
+
+    let raw_data_size = frame.len() - header_size;
+    let mut block_starts: Vec<usize> = vec![0];
+    block_starts.extend(raw_data_block_positions.iter().map(|position|*position as usize));
+    let mut raw_data_blocks: Vec<&[u8]> = vec![];
+    for (i, block_start) in block_starts.iter().enumerate() {
+      let block_end = block_starts.get(i + 1).copied().unwrap_or(raw_data_size);
+      if block_end > raw_data_size || block_end < block_start + ADTS_FIELD_SIZE {
+        return Err(
+          construct_error(
+            MajorCode::TRANSPORT_STREAM,
+            Box::new(TransportStreamMinorCode::PARSE_TS_ERROR),
+            format!("ADTS raw_data_block_position {} is out of order or outside of the frame", block_end),
+            file!(),
+            line!()
+          )
+        );
+      }
+      // Leave out the crc_check of the block
+      raw_data_blocks.push(&frame[(header_size + block_start)..(header_size + block_end - ADTS_FIELD_SIZE)]);
+    }
+    Ok(raw_data_blocks)
+  }
+
+  // Only the crc_check of adts_header_error_check() is verified, it covers the headers and the
+  // raw_data_block_positions. The other crc_checks also cover part of the channel elements, which would need the
+  // raw data blocks to be decoded. That includes the crc_check of adts_error_check() in a frame with a single raw data
+  // block (the first 192 bits of every channel element and the first 128 of the second channel of a pair, ISO/IEC
+  // 13818-7; 6.2.3), so those frames are never left out and only counted as unverified.
+  fn is_header_crc_valid(frame: &[u8], adts_header: &ADTSHeader, number_of_raw_data_block_positions: usize) -> bool {
+    match adts_header.crc {
+      Some(crc) if number_of_raw_data_block_positions > 0 => {
+        let protected_size = ADTS_HEADER_SIZE + number_of_raw_data_block_positions * ADTS_FIELD_SIZE;
+        util::crc_16_mpeg(&frame[..protected_size]) == crc
+      }
+      _ => true,
+    }
+  }
+
+  fn parse_adts_header(data: &[u8]) -> Result<(ADTSHeader, Vec<u16>), CustomError> {
     let mut bit_reader = BitReader::create_bit_reader(data);
     let starting_marker = bit_reader.read_bits(16)?;
 
@@ -80,7 +320,8 @@ impl ADTS {
     }
     let temp = starting_marker & 0xF;
     let id_version = (temp & 0x8) as u8;
-    let protection_absent = temp & 0x1 == 0;
+    // protection_absent is 0 when there is a CRC
+    let has_crc = temp & 0x1 == 0;
     let profile = bit_reader.read_bits(2)? as u8;
     let sampling_frequency_index = bit_reader.read_bits(4)? as u8;
     // Skip private bit
@@ -94,33 +335,40 @@ impl ADTS {
     let number_of_raw_data_blocks_in_frame = bit_reader.read_bits(2)?;
 
     // Checkout ISO/IEC 13818-7: 6.2 Audio Data Transport Stream, ADTS :: Table 5 — Syntax of adts_frame()
-    // If there are more than 1 frame per data block, we need to handle it. I think todays expectation is to
-    // only have 1 aac frame per adts frame
-    if number_of_raw_data_blocks_in_frame > 0 {
+    // Without the CRC there are no raw_data_block_positions, so the raw data blocks can't be told apart without
+    // decoding them
+    if number_of_raw_data_blocks_in_frame > 0 && !has_crc {
       return Err(
         construct_error(
           MajorCode::TRANSPORT_STREAM,
           Box::new(TransportStreamMinorCode::UNSUPPORTED_ADTS_PARSING),
-          format!("Could not parse because there is more than 1 aac frame in adtc frame. This is not supported in the current implementation."),
+          "Could not parse because there is more than 1 aac frame in an adts frame without a CRC. This is not supported in the current implementation.".to_string(),
           file!(),
           line!()
         )
       );
     }
+    let mut raw_data_block_positions: Vec<u16> = vec![];
     let mut crc: Option<u16> = None;
-    if protection_absent {
+    if has_crc {
+      for _ in 0..number_of_raw_data_blocks_in_frame {
+        raw_data_block_positions.push(bit_reader.read_bits(16)? as u16);
+      }
       crc = Some(bit_reader.read_bits(16)? as u16);
     }
 
     Ok(
-      ADTSHeader{
-        id_version,
-        profile,
-        sampling_frequency_index,
-        channel_configuration,
-        frame_length,
-        crc,
-      }
+      (
+        ADTSHeader{
+          id_version,
+          profile,
+          sampling_frequency_index,
+          channel_configuration,
+          frame_length,
+          crc,
+        },
+        raw_data_block_positions
+      )
     )
   }
 }
@@ -175,6 +423,130 @@ mod tests {
     let adts_frames = ADTS::parse(&adts_sequence).unwrap();
     assert_eq!(adts_frames[0].header, expected_header);
     assert_eq!(adts_frames[0].data.len(), 401);
+    assert_eq!(expected_header.build(), adts_sequence[0..7].to_vec());
+  }
+
+  #[test]
+  fn test_parse_adts_multiple_raw_data_blocks() {
+    let adts_sequence: [u8; 28] = [
+      // adts header with 3 raw data blocks and a CRC
+      0xFF, 0xF0, 0x4C, 0x80, 0x03, 0x9F, 0xFE,
+      // raw_data_block_position of the second and third block
+      0x00, 0x05, 0x00, 0x09,
+      // crc_check of the header
+      0x12, 0xF1,
+      // raw data blocks, each followed by a crc_check
+      0x11, 0x12, 0x13, 0xAB, 0xCD,
+      0x21, 0x22, 0xAB, 0xCD,
+      0x31, 0x32, 0x33, 0x34, 0xAB, 0xCD,
+    ];
+
+    let (adts_frames, crc_check) = ADTS::parse_with_crc_check(&adts_sequence).unwrap();
+    assert_eq!(crc_check, ADTSCrcCheck::default());
+    let raw_data_blocks: Vec<Vec<u8>> = adts_frames.iter().map(|frame|frame.data.to_owned()).collect();
+    assert_eq!(raw_data_blocks, vec![vec![0x11, 0x12, 0x13], vec![0x21, 0x22], vec![0x31, 0x32, 0x33, 0x34]]);
+    assert_eq!(adts_frames[2].header.crc, Some(0x12F1));
+    assert_eq!(adts_frames[2].header.channel_configuration, 2);
+
+    // A corrupt header is only left out when the CRC gets checked
+    let mut corrupt_sequence = adts_sequence;
+    corrupt_sequence[8] = 0x04;
+    let (adts_frames, crc_check) = ADTS::parse_with_crc_check(&corrupt_sequence).unwrap();
+    assert!(adts_frames.is_empty());
+    assert_eq!(crc_check, ADTSCrcCheck { dropped_frames: 1, unverified_frames: 0 });
+    assert_eq!(ADTS::parse(&corrupt_sequence).unwrap()[0].data, vec![0x11, 0x12]);
+
+    // The crc_check of a single raw data block can't be verified
+    let single_block_header = ADTSHeader { profile: 1, sampling_frequency_index: 3, frame_length: 12, crc: Some(0xABCD), ..Default::default() };
+    let single_block_frame = [single_block_header.build(), vec![0x11, 0x12, 0x13]].concat();
+    let (adts_frames, crc_check) = ADTS::parse_with_crc_check(&single_block_frame).unwrap();
+    assert_eq!(adts_frames.len(), 1);
+    assert_eq!(crc_check, ADTSCrcCheck { dropped_frames: 0, unverified_frames: 1 });
+
+    // raw_data_block_position past the end of the frame
+    corrupt_sequence[8] = 0x20;
+    let adts_frames_error = ADTS::parse(&corrupt_sequence).unwrap_err();
+    assert_eq!(adts_frames_error.minor, TransportStreamMinorCode::PARSE_TS_ERROR as u8);
+  }
+
+  #[test]
+  fn test_adts_frame_has_sbr_payload() {
+    let mut bit_writer = BitWriter::create_bit_writer();
+    bit_writer
+      // ID_SCE with element_instance_tag and some channel data
+      .write_bits(0x0, 3)
+      .write_bits(0x0, 4)
+      .write_bits(0x2A5, 10)
+      // ID_FIL with a count of 3 and EXT_SBR_DATA
+      .write_bits(0x6, 3)
+      .write_bits(0x3, 4)
+      .write_bits(0xD, 4)
+      .write_bits(0xB6F1A, 20)
+      // ID_FIL with a count of 1 and EXT_FILL
+      .write_bits(0x6, 3)
+      .write_bits(0x1, 4)
+      .write_bits(0x0, 4)
+      .write_bits(0x0, 4)
+      // ID_END
+      .write_bits(0x7, 3);
+    let sbr_frame = ADTSFrame { data: bit_writer.finish(), ..Default::default() };
+    assert!(sbr_frame.has_sbr_payload());
+
+    let mut bit_writer = BitWriter::create_bit_writer();
+    bit_writer
+      .write_bits(0x0, 3)
+      .write_bits(0x0, 4)
+      .write_bits(0x2A5, 10)
+      .write_bits(0x7, 3);
+    let lc_frame = ADTSFrame { data: bit_writer.finish(), ..Default::default() };
+    assert!(!lc_frame.has_sbr_payload());
+    assert!(!ADTSFrame::default().has_sbr_payload());
+  }
+
+  fn create_ps_raw_data_block(extension_id: usize) -> Vec<u8> {
+    let mut bit_writer = BitWriter::create_bit_writer();
+    bit_writer
+      // ID_SCE with element_instance_tag and some channel data
+      .write_bits(0x0, 3)
+      .write_bits(0x0, 4)
+      .write_bits(0x2A5, 10)
+      // ID_FIL with a count of 4 and EXT_SBR_DATA without bs_header_flag
+      .write_bits(0x6, 3)
+      .write_bits(0x4, 4)
+      .write_bits(0xD, 4)
+      .write_bits(0x0, 1)
+      // Some SBR data
+      .write_bits(0x2A5, 10)
+      // bs_extended_data with a bs_extension_size of 1
+      .write_bits(0x1, 1)
+      .write_bits(0x1, 4)
+      // bs_extension_id and the extension data
+      .write_bits(extension_id, 2)
+      .write_bits(0x2D, 6)
+      // Fill bits up to the count of the fill element
+      .write_bits(0x0, 4)
+      // ID_END
+      .write_bits(0x7, 3);
+    bit_writer.finish()
+  }
+
+  #[test]
+  fn test_adts_frame_has_ps_payload() {
+    let mono_header = ADTSHeader { channel_configuration: 1, ..Default::default() };
+    let ps_frame = ADTSFrame { header: mono_header.clone(), data: create_ps_raw_data_block(0x2), ..Default::default() };
+    assert!(ps_frame.has_sbr_payload());
+    assert!(ps_frame.has_ps_payload());
+
+    // Another SBR extension
+    let sbr_frame = ADTSFrame { header: mono_header, data: create_ps_raw_data_block(0x0), ..Default::default() };
+    assert!(sbr_frame.has_sbr_payload());
+    assert!(!sbr_frame.has_ps_payload());
+
+    // PS only goes with a mono core
+    let stereo_header = ADTSHeader { channel_configuration: 2, ..Default::default() };
+    let stereo_frame = ADTSFrame { header: stereo_header, data: create_ps_raw_data_block(0x2), ..Default::default() };
+    assert!(!stereo_frame.has_ps_payload());
+    assert!(!ADTSFrame::default().has_ps_payload());
   }
 
   #[test]
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+
+use super::ts_packet::TransportPacket;
+
+static NULL_PACKET_PID: u16 = 0x1FFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum ContinuityIssueKind {
+  /// Packets are missing between the previous and the current packet
+  LOST_PACKETS(u8),
+  /// The packet was sent more than the one allowed duplicate
+  DUPLICATE_PACKET,
+  /// The continuity counter went backwards
+  OUT_OF_ORDER,
+  /// The transport_error_indicator is set, meaning the packet is corrupt
+  TRANSPORT_ERROR,
+  /// The packet couldn't be parsed (ex. an adaptation field running past the end of the packet)
+  MALFORMED_PACKET,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContinuityIssue {
+  pub pid: u16,
+  /// Index of the TS packet in the stream (not the byte offset)
+  pub packet_index: usize,
+  pub expected_counter: Option<u8>,
+  pub continuity_counter: u8,
+  pub kind: ContinuityIssueKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum TimestampIssueKind {
+  /// The PES timestamp is after the end of the frames before it, so there is media missing
+  GAP,
+  /// The PES timestamp is before the end of the frames before it
+  OVERLAP,
+}
+
+/// PES timestamp that doesn't follow on from the frames before it in the elementary stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampIssue {
+  pub pid: u16,
+  /// Where the PES packet should have started given the duration of the frames before it (90 kHz)
+  pub expected_timestamp: u64,
+  /// Unwrapped PTS of the PES packet (90 kHz)
+  pub timestamp: u64,
+  pub kind: TimestampIssueKind,
+}
+
+/// Frame of an elementary stream left out of the remux because its CRC didn't match or it couldn't be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CorruptFrame {
+  pub pid: u16,
+  /// Unwrapped PTS of the PES packet the frame was in (90 kHz)
+  pub timestamp: u64,
+}
+
+/// Every continuity problem found while demuxing a transport stream
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContinuityReport {
+  pub issues: Vec<ContinuityIssue>,
+  /// Number of packets dropped because they were an allowed duplicate or corrupt
+  pub dropped_packets: usize,
+  pub timestamp_issues: Vec<TimestampIssue>,
+  pub corrupt_frames: Vec<CorruptFrame>,
+  /// Number of frames kept although their CRC couldn't be verified (ADTS frames with a single raw data block)
+  pub unverified_frames: usize,
+}
+
+impl ContinuityReport {
+  pub fn is_clean(&self) -> bool {
+    self.issues.is_empty() && self.timestamp_issues.is_empty() && self.corrupt_frames.is_empty()
+  }
+
+  pub fn get_issues_for_pid(&self, pid: u16) -> Vec<&ContinuityIssue> {
+    self.issues
+      .iter()
+      .filter(|issue|issue.pid == pid)
+      .collect()
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum ContinuityStatus {
+  /// Packet is in order and should be used
+  OK,
+  /// Packet is the one duplicate allowed by ISO/IEC 13818-1 and should be dropped
+  DUPLICATE,
+  /// Packet should be used, but the data before it was lost or arrived out of order
+  DISCONTINUITY(ContinuityIssue),
+  /// Packet is corrupt or a repeated duplicate and should be dropped
+  DROP(ContinuityIssue),
+}
+
+struct PidState {
+  last_counter: u8,
+  duplicate_count: u8,
+}
+
+/*
+ISO/IEC 13818-1; 2.4.3.3 continuity_counter
+The continuity_counter increments with each packet of a PID that has a payload. It doesn't increment for packets
+without a payload, may be sent twice in a row (duplicate) and may jump when the discontinuity_indicator is set.
+*/
+/// Tracks the continuity_counter of every PID and builds a report of the continuity problems
+#[derive(Default)]
+pub struct ContinuityCounterTracker {
+  pid_states: HashMap<u16, PidState>,
+  packet_index: usize,
+  report: ContinuityReport,
+}
+
+impl ContinuityCounterTracker {
+  pub fn create() -> ContinuityCounterTracker {
+    ContinuityCounterTracker::default()
+  }
+
+  pub fn check(&mut self, packet: &TransportPacket) -> ContinuityStatus {
+    let packet_index = self.packet_index;
+    self.packet_index += 1;
+    let pid = packet.pid;
+    let continuity_counter = packet.continuity_counter;
+
+    if pid == NULL_PACKET_PID {
+      return ContinuityStatus::OK;
+    }
+
+    if packet.transport_error_indicator {
+      let issue = ContinuityIssue{
+        pid,
+        packet_index,
+        expected_counter: None,
+        continuity_counter,
+        kind: ContinuityIssueKind::TRANSPORT_ERROR,
+      };
+      self.report.issues.push(issue);
+      self.report.dropped_packets += 1;
+      return ContinuityStatus::DROP(issue);
+    }
+
+    let previous_state = self.pid_states.get(&pid).map(|state|(state.last_counter, state.duplicate_count));
+    let (last_counter, duplicate_count) = match previous_state {
+      Some(state) if !packet.get_discontinuity_indicator() => state,
+      _ => {
+        // First packet of the PID or a signaled discontinuity. Either way there is nothing to compare against.
+        self.pid_states.insert(pid, PidState{last_counter: continuity_counter, duplicate_count: 0});
+        return ContinuityStatus::OK;
+      }
+    };
+
+    if !packet.has_payload() {
+      // The counter doesn't increment for packets without a payload
+      return ContinuityStatus::OK;
+    }
+
+    let expected_counter = (last_counter + 1) & 0xF;
+    let delta = continuity_counter.wrapping_sub(last_counter) & 0xF;
+    let status = match delta {
+      1 => ContinuityStatus::OK,
+      0 if duplicate_count == 0 => {
+        self.report.dropped_packets += 1;
+        ContinuityStatus::DUPLICATE
+      }
+      0 => {
+        self.report.dropped_packets += 1;
+        ContinuityStatus::DROP(ContinuityIssue{
+          pid,
+          packet_index,
+          expected_counter: Some(expected_counter),
+          continuity_counter,
+          kind: ContinuityIssueKind::DUPLICATE_PACKET,
+        })
+      }
+      // Anything more than half of the counter range away is treated as the counter going backwards
+      _ if delta > 8 => ContinuityStatus::DISCONTINUITY(ContinuityIssue{
+        pid,
+        packet_index,
+        expected_counter: Some(expected_counter),
+        continuity_counter,
+        kind: ContinuityIssueKind::OUT_OF_ORDER,
+      }),
+      _ => ContinuityStatus::DISCONTINUITY(ContinuityIssue{
+        pid,
+        packet_index,
+        expected_counter: Some(expected_counter),
+        continuity_counter,
+        kind: ContinuityIssueKind::LOST_PACKETS(delta - 1),
+      }),
+    };
+
+    if let ContinuityStatus::DISCONTINUITY(issue) | ContinuityStatus::DROP(issue) = status {
+      self.report.issues.push(issue);
+    }
+    let state = self.pid_states.entry(pid).or_insert(PidState{last_counter, duplicate_count});
+    state.duplicate_count = if delta == 0 { duplicate_count + 1 } else { 0 };
+    state.last_counter = continuity_counter;
+    status
+  }
+
+  /// Count a packet of a PID that isn't tracked (ex. a PID of another program), so the packet_index of the issues
+  /// stays the index of the packet in the stream
+  pub fn skip_packet(&mut self) {
+    self.packet_index += 1;
+  }
+
+  /// Record a packet that couldn't be parsed and is dropped. Only the fixed 4 byte header is read from it, so the
+  /// continuity_counter of the PID isn't updated.
+  pub fn report_malformed_packet(&mut self, packet_data: &[u8]) -> ContinuityIssue {
+    let packet_index = self.packet_index;
+    self.packet_index += 1;
+    let header = |index: usize|packet_data.get(index).copied().unwrap_or(0);
+    let kind = if (header(1) & 0x80) != 0 {
+      ContinuityIssueKind::TRANSPORT_ERROR
+    } else {
+      ContinuityIssueKind::MALFORMED_PACKET
+    };
+    let issue = ContinuityIssue{
+      pid: ((header(1) as u16 & 0x1F) << 8) | header(2) as u16,
+      packet_index,
+      expected_counter: None,
+      continuity_counter: header(3) & 0xF,
+      kind,
+    };
+    self.report.issues.push(issue);
+    self.report.dropped_packets += 1;
+    issue
+  }
+
+  pub fn report_timestamp_issue(&mut self, issue: TimestampIssue) {
+    self.report.timestamp_issues.push(issue);
+  }
+
+  pub fn report_corrupt_frame(&mut self, corrupt_frame: CorruptFrame) {
+    self.report.corrupt_frames.push(corrupt_frame);
+  }
+
+  pub fn report_unverified_frames(&mut self, count: usize) {
+    self.report.unverified_frames += count;
+  }
+
+  pub fn get_report(&self) -> &ContinuityReport {
+    &self.report
+  }
+
+  pub fn take_report(&mut self) -> ContinuityReport {
+    std::mem::take(&mut self.report)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn create_packet(pid: u16, continuity_counter: u8, adaptation_field_control: u8, discontinuity_indicator: bool) -> [u8; 188] {
+    let mut packet = [0xFFu8; 188];
+    packet[0] = 0x47;
+    packet[1] = ((pid >> 8) & 0x1F) as u8;
+    packet[2] = (pid & 0xFF) as u8;
+    packet[3] = (adaptation_field_control << 4) | continuity_counter;
+    if adaptation_field_control > 1 {
+      packet[4] = 1;
+      packet[5] = if discontinuity_indicator { 0x80 } else { 0x00 };
+    }
+    packet
+  }
+
+  fn check(tracker: &mut ContinuityCounterTracker, packet: &[u8; 188]) -> ContinuityStatus {
+    let packet = TransportPacket::parse(packet).unwrap();
+    tracker.check(&packet)
+  }
+
+  #[test]
+  fn test_continuity_counter_in_order() {
+    let mut tracker = ContinuityCounterTracker::create();
+    for counter in 0..20u8 {
+      assert_eq!(check(&mut tracker, &create_packet(0x100, counter & 0xF, 1, false)), ContinuityStatus::OK);
+    }
+    // Adaptation field only packets don't increment the counter
+    assert_eq!(check(&mut tracker, &create_packet(0x100, 3, 2, false)), ContinuityStatus::OK);
+    assert_eq!(check(&mut tracker, &create_packet(0x100, 4, 1, false)), ContinuityStatus::OK);
+    assert!(tracker.get_report().is_clean());
+  }
+
+  #[test]
+  fn test_continuity_counter_lost_duplicate_and_out_of_order() {
+    let mut tracker = ContinuityCounterTracker::create();
+    check(&mut tracker, &create_packet(0x100, 14, 1, false));
+    // One duplicate is allowed, but is dropped
+    assert_eq!(check(&mut tracker, &create_packet(0x100, 14, 1, false)), ContinuityStatus::DUPLICATE);
+    // A second duplicate is an error
+    assert!(matches!(check(&mut tracker, &create_packet(0x100, 14, 1, false)), ContinuityStatus::DROP(_)));
+    // Counter wraps from 15 to 0, so 1 means packets 15 and 0 were lost
+    assert!(matches!(check(&mut tracker, &create_packet(0x100, 1, 1, false)), ContinuityStatus::DISCONTINUITY(_)));
+    assert!(matches!(check(&mut tracker, &create_packet(0x100, 0, 1, false)), ContinuityStatus::DISCONTINUITY(_)));
+
+    let report = tracker.get_report();
+    assert_eq!(report.dropped_packets, 2);
+    let kinds: Vec<ContinuityIssueKind> = report.issues.iter().map(|issue|issue.kind).collect();
+    assert_eq!(kinds, vec![
+      ContinuityIssueKind::DUPLICATE_PACKET,
+      ContinuityIssueKind::LOST_PACKETS(2),
+      ContinuityIssueKind::OUT_OF_ORDER,
+    ]);
+    assert_eq!(report.issues[1].packet_index, 3);
+    assert_eq!(report.issues[1].expected_counter, Some(15));
+  }
+
+  #[test]
+  fn test_continuity_counter_discontinuity_indicator() {
+    let mut tracker = ContinuityCounterTracker::create();
+    check(&mut tracker, &create_packet(0x100, 5, 1, false));
+    check(&mut tracker, &create_packet(0x101, 9, 1, false));
+    // Signaled discontinuity, so the jump is expected
+    assert_eq!(check(&mut tracker, &create_packet(0x100, 12, 3, true)), ContinuityStatus::OK);
+    assert_eq!(check(&mut tracker, &create_packet(0x100, 13, 1, false)), ContinuityStatus::OK);
+    assert_eq!(check(&mut tracker, &create_packet(0x101, 10, 1, false)), ContinuityStatus::OK);
+    assert!(tracker.get_report().is_clean());
+  }
+
+  #[test]
+  fn test_continuity_counter_malformed_packet() {
+    let mut tracker = ContinuityCounterTracker::create();
+    check(&mut tracker, &create_packet(0x100, 5, 1, false));
+    // adaptation_field_length runs past the end of the packet
+    let mut packet = create_packet(0x100, 6, 3, false);
+    packet[4] = 0xC0;
+    let issue = tracker.report_malformed_packet(&packet);
+    assert_eq!(issue.pid, 0x100);
+    assert_eq!(issue.packet_index, 1);
+    assert_eq!(issue.continuity_counter, 6);
+    assert_eq!(issue.kind, ContinuityIssueKind::MALFORMED_PACKET);
+    packet[1] |= 0x80;
+    assert_eq!(tracker.report_malformed_packet(&packet).kind, ContinuityIssueKind::TRANSPORT_ERROR);
+    assert_eq!(tracker.get_report().dropped_packets, 2);
+  }
+}
@@ -0,0 +1,425 @@
+use crate::error::CustomError;
+use crate::util;
+
+/*
+ISO/IEC 13818-1; 2.6 Program and program element descriptors
+ETSI EN 300 468; Annex D (DVB AC-3 and Enhanced AC-3 descriptors)
+ATSC A/52; Annex A (ATSC AC-3 audio descriptor)
+*/
+static REGISTRATION_DESCRIPTOR_TAG: u8 = 0x05;
+static ISO_639_LANGUAGE_DESCRIPTOR_TAG: u8 = 0x0A;
+static HEVC_VIDEO_DESCRIPTOR_TAG: u8 = 0x38;
+static STREAM_IDENTIFIER_DESCRIPTOR_TAG: u8 = 0x52;
+static DVB_AC3_DESCRIPTOR_TAG: u8 = 0x6A;
+static DVB_ENHANCED_AC3_DESCRIPTOR_TAG: u8 = 0x7A;
+static ATSC_AC3_DESCRIPTOR_TAG: u8 = 0x81;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistrationDescriptor {
+  pub format_identifier: u32,
+  pub additional_identification_info: Vec<u8>,
+}
+
+impl RegistrationDescriptor {
+  /// The format_identifier as a 4 character code (ex. "AC-3", "EAC3", "HEVC")
+  pub fn get_format_identifier_string(&self) -> String {
+    self.format_identifier
+      .to_be_bytes()
+      .iter()
+      .map(|byte| *byte as char)
+      .collect()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ISO639Language {
+  pub language_code: String,          // 24 bit
+  pub audio_type: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AC3Descriptor {
+  pub component_type: Option<u8>,
+  pub bsid: Option<u8>,
+  pub mainid: Option<u8>,
+  pub asvc: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnhancedAC3Descriptor {
+  pub component_type: Option<u8>,
+  pub bsid: Option<u8>,
+  pub mainid: Option<u8>,
+  pub asvc: Option<u8>,
+  pub mixinfoexists: bool,
+  pub substream1: Option<u8>,
+  pub substream2: Option<u8>,
+  pub substream3: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ATSCAC3Descriptor {
+  pub sample_rate_code: u8,           // 3 bit
+  pub bsid: u8,                       // 5 bit
+  pub bit_rate_code: u8,              // 6 bit
+  pub surround_mode: u8,              // 2 bit
+  pub bsmod: u8,                      // 3 bit
+  pub num_channels: u8,               // 4 bit
+  pub full_svc: bool,
+  pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HEVCVideoDescriptor {
+  pub profile_space: u8,              // 2 bit
+  pub tier_flag: bool,
+  pub profile_idc: u8,                // 5 bit
+  pub profile_compatibility_indication: u32,
+  pub progressive_source_flag: bool,
+  pub interlaced_source_flag: bool,
+  pub non_packed_constraint_flag: bool,
+  pub frame_only_constraint_flag: bool,
+  pub copied_44bits: u64,             // 44 bit
+  pub level_idc: u8,
+  pub hevc_still_present_flag: bool,
+  pub hevc_24hr_picture_present_flag: bool,
+  pub temporal_id_min: Option<u8>,    // 3 bit
+  pub temporal_id_max: Option<u8>,    // 3 bit
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum Descriptor {
+  REGISTRATION(RegistrationDescriptor),
+  ISO_639_LANGUAGE(Vec<ISO639Language>),
+  HEVC_VIDEO(HEVCVideoDescriptor),
+  STREAM_IDENTIFIER(u8),
+  AC3(AC3Descriptor),
+  ENHANCED_AC3(EnhancedAC3Descriptor),
+  ATSC_AC3(ATSCAC3Descriptor),
+  UNKNOWN{tag: u8, data: Vec<u8>},
+}
+
+impl Descriptor {
+  /// Parse a descriptor loop (program_info or ES_info) into descriptors
+  pub fn parse_descriptors(data: &[u8]) -> Result<Vec<Descriptor>, CustomError> {
+    let mut start = 0usize;
+    let mut descriptors: Vec<Descriptor> = vec![];
+    while start + 2 <= data.len() {
+      let tag = util::get_u8(data, start)?;
+      let length = util::get_u8(data, start + 1)? as usize;
+      start += 2;
+      let end = usize::min(start + length, data.len());
+      descriptors.push(Descriptor::parse(tag, &data[start..end])?);
+      start = end;
+    }
+    Ok(descriptors)
+  }
+
+  fn parse(tag: u8, data: &[u8]) -> Result<Descriptor, CustomError> {
+    // Malformed descriptors are kept as unknown so one bad descriptor doesn't throw away the whole PMT
+    let descriptor = match tag {
+      _ if tag == REGISTRATION_DESCRIPTOR_TAG => Descriptor::parse_registration(data),
+      _ if tag == ISO_639_LANGUAGE_DESCRIPTOR_TAG => Descriptor::parse_iso_639_language(data),
+      _ if tag == HEVC_VIDEO_DESCRIPTOR_TAG => Descriptor::parse_hevc_video(data),
+      _ if tag == STREAM_IDENTIFIER_DESCRIPTOR_TAG => util::get_u8(data, 0).map(Descriptor::STREAM_IDENTIFIER),
+      _ if tag == DVB_AC3_DESCRIPTOR_TAG => Descriptor::parse_ac3(data),
+      _ if tag == DVB_ENHANCED_AC3_DESCRIPTOR_TAG => Descriptor::parse_enhanced_ac3(data),
+      _ if tag == ATSC_AC3_DESCRIPTOR_TAG => Descriptor::parse_atsc_ac3(data),
+      _ => Ok(Descriptor::UNKNOWN{tag, data: data.to_vec()}),
+    };
+    Ok(descriptor.unwrap_or_else(|_|Descriptor::UNKNOWN{tag, data: data.to_vec()}))
+  }
+
+  fn parse_registration(data: &[u8]) -> Result<Descriptor, CustomError> {
+    let format_identifier = util::get_u32(data, 0)?;
+    Ok(Descriptor::REGISTRATION(RegistrationDescriptor{
+      format_identifier,
+      additional_identification_info: data[4..].to_vec(),
+    }))
+  }
+
+  fn parse_iso_639_language(data: &[u8]) -> Result<Descriptor, CustomError> {
+    let languages = data
+      .chunks_exact(4)
+      .map(|entry| ISO639Language{
+        language_code: Descriptor::parse_language_code(&entry[0..3]),
+        audio_type: entry[3],
+      })
+      .collect();
+    Ok(Descriptor::ISO_639_LANGUAGE(languages))
+  }
+
+  fn parse_hevc_video(data: &[u8]) -> Result<Descriptor, CustomError> {
+    let buffer_8 = util::get_u8(data, 0)?;
+    let profile_compatibility_indication = util::get_u32(data, 1)?;
+    // progressive_source_flag, interlaced_source_flag, non_packed_constraint_flag, frame_only_constraint_flag and copied_44bits
+    let constraint_data = util::get_u64(data, 5)? >> 16;
+    let level_idc = util::get_u8(data, 11)?;
+    let flags = util::get_u8(data, 12)?;
+    let temporal_layer_subset_flag = (flags & 0x80) != 0;
+    let mut temporal_id_min = None;
+    let mut temporal_id_max = None;
+    if temporal_layer_subset_flag {
+      temporal_id_min = Some(util::get_u8(data, 13)? & 0x7);
+      temporal_id_max = Some(util::get_u8(data, 14)? & 0x7);
+    }
+
+    Ok(Descriptor::HEVC_VIDEO(HEVCVideoDescriptor{
+      profile_space: (buffer_8 & 0xC0) >> 6,
+      tier_flag: (buffer_8 & 0x20) != 0,
+      profile_idc: buffer_8 & 0x1F,
+      profile_compatibility_indication,
+      progressive_source_flag: (constraint_data & 0x800000000000) != 0,
+      interlaced_source_flag: (constraint_data & 0x400000000000) != 0,
+      non_packed_constraint_flag: (constraint_data & 0x200000000000) != 0,
+      frame_only_constraint_flag: (constraint_data & 0x100000000000) != 0,
+      copied_44bits: constraint_data & 0xFFFFFFFFFFF,
+      level_idc,
+      hevc_still_present_flag: (flags & 0x40) != 0,
+      hevc_24hr_picture_present_flag: (flags & 0x20) != 0,
+      temporal_id_min,
+      temporal_id_max,
+    }))
+  }
+
+  fn parse_ac3(data: &[u8]) -> Result<Descriptor, CustomError> {
+    let flags = util::get_u8(data, 0)?;
+    let mut start = 1usize;
+    let mut read_optional = |flag: u8| -> Result<Option<u8>, CustomError> {
+      if (flags & flag) == 0 {
+        return Ok(None);
+      }
+      let value = util::get_u8(data, start)?;
+      start += 1;
+      Ok(Some(value))
+    };
+    Ok(Descriptor::AC3(AC3Descriptor{
+      component_type: read_optional(0x80)?,
+      bsid: read_optional(0x40)?,
+      mainid: read_optional(0x20)?,
+      asvc: read_optional(0x10)?,
+    }))
+  }
+
+  fn parse_enhanced_ac3(data: &[u8]) -> Result<Descriptor, CustomError> {
+    let flags = util::get_u8(data, 0)?;
+    let mut start = 1usize;
+    let mut read_optional = |flag: u8| -> Result<Option<u8>, CustomError> {
+      if (flags & flag) == 0 {
+        return Ok(None);
+      }
+      let value = util::get_u8(data, start)?;
+      start += 1;
+      Ok(Some(value))
+    };
+    Ok(Descriptor::ENHANCED_AC3(EnhancedAC3Descriptor{
+      component_type: read_optional(0x80)?,
+      bsid: read_optional(0x40)?,
+      mainid: read_optional(0x20)?,
+      asvc: read_optional(0x10)?,
+      mixinfoexists: (flags & 0x08) != 0,
+      substream1: read_optional(0x04)?,
+      substream2: read_optional(0x02)?,
+      substream3: read_optional(0x01)?,
+    }))
+  }
+
+  fn parse_atsc_ac3(data: &[u8]) -> Result<Descriptor, CustomError> {
+    let buffer_8 = util::get_u8(data, 0)?;
+    let sample_rate_code = (buffer_8 & 0xE0) >> 5;
+    let bsid = buffer_8 & 0x1F;
+    let buffer_8 = util::get_u8(data, 1)?;
+    let bit_rate_code = (buffer_8 & 0xFC) >> 2;
+    let surround_mode = buffer_8 & 0x3;
+    let buffer_8 = util::get_u8(data, 2)?;
+    let bsmod = (buffer_8 & 0xE0) >> 5;
+    let num_channels = (buffer_8 & 0x1E) >> 1;
+    let full_svc = (buffer_8 & 0x1) != 0;
+
+    // The rest of the descriptor is optional. Only the trailing ISO 639 language field is of interest.
+    let mut language = None;
+    let mut start = 3usize;
+    // langcod (deprecated)
+    start += 1;
+    if num_channels == 0 {
+      // langcod2 (deprecated)
+      start += 1;
+    }
+    // mainid + priority or asvcflags
+    start += 1;
+    if let Ok(text_data) = util::get_u8(data, start) {
+      let textlen = ((text_data & 0xFE) >> 1) as usize;
+      start += 1 + textlen;
+      if let Ok(language_flags) = util::get_u8(data, start) {
+        start += 1;
+        if (language_flags & 0x80) != 0 && start + 3 <= data.len() {
+          language = Some(Descriptor::parse_language_code(&data[start..(start + 3)]));
+        }
+      }
+    }
+
+    Ok(Descriptor::ATSC_AC3(ATSCAC3Descriptor{
+      sample_rate_code,
+      bsid,
+      bit_rate_code,
+      surround_mode,
+      bsmod,
+      num_channels,
+      full_svc,
+      language,
+    }))
+  }
+
+  /// ISO 639-2 language codes are lower case 3 letter codes. Anything else is treated as undetermined.
+  fn parse_language_code(data: &[u8]) -> String {
+    let language_code: String = data
+      .iter()
+      .map(|byte| (*byte as char).to_ascii_lowercase())
+      .collect();
+    if language_code.len() == 3 && language_code.chars().all(|c| c.is_ascii_lowercase()) {
+      language_code
+    } else {
+      "und".to_string()
+    }
+  }
+
+  /// Find the first ISO 639 language in the descriptors
+  pub fn find_language(descriptors: &[Descriptor]) -> Option<String> {
+    descriptors
+      .iter()
+      .find_map(|descriptor| match descriptor {
+        Descriptor::ISO_639_LANGUAGE(languages) => languages.first().map(|lang| lang.language_code.clone()),
+        Descriptor::ATSC_AC3(ac3) => ac3.language.clone(),
+        _ => None,
+      })
+  }
+
+  /// Find the format_identifier of the first registration descriptor
+  pub fn find_registration(descriptors: &[Descriptor]) -> Option<String> {
+    descriptors
+      .iter()
+      .find_map(|descriptor| match descriptor {
+        Descriptor::REGISTRATION(registration) => Some(registration.get_format_identifier_string()),
+        _ => None,
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_descriptors() {
+    let descriptors: [u8; 31] = [
+      // registration descriptor ("AC-3")
+      0x05, 0x04, 0x41, 0x43, 0x2D, 0x33,
+      // ISO 639 language descriptor ("spa", "eng")
+      0x0A, 0x08, 0x73, 0x70, 0x61, 0x00, 0x45, 0x4E, 0x47, 0x03,
+      // stream identifier descriptor
+      0x52, 0x01, 0x11,
+      // DVB AC-3 descriptor (component_type and bsid)
+      0x6A, 0x03, 0xC0, 0x44, 0x08,
+      // unknown descriptor
+      0xFE, 0x02, 0x01, 0x02,
+      // DVB Enhanced AC-3 descriptor (mixinfoexists only)
+      0x7A, 0x01, 0x08,
+    ];
+
+    let parsed = Descriptor::parse_descriptors(&descriptors).unwrap();
+    assert_eq!(parsed.len(), 6);
+    assert_eq!(Descriptor::find_registration(&parsed), Some("AC-3".to_string()));
+    assert_eq!(Descriptor::find_language(&parsed), Some("spa".to_string()));
+    assert_eq!(parsed[1], Descriptor::ISO_639_LANGUAGE(vec![
+      ISO639Language{language_code: "spa".to_string(), audio_type: 0},
+      ISO639Language{language_code: "eng".to_string(), audio_type: 3},
+    ]));
+    assert_eq!(parsed[2], Descriptor::STREAM_IDENTIFIER(0x11));
+    assert_eq!(parsed[3], Descriptor::AC3(AC3Descriptor{
+      component_type: Some(0x44),
+      bsid: Some(0x08),
+      mainid: None,
+      asvc: None,
+    }));
+    assert_eq!(parsed[4], Descriptor::UNKNOWN{tag: 0xFE, data: vec![0x01, 0x02]});
+    assert_eq!(parsed[5], Descriptor::ENHANCED_AC3(EnhancedAC3Descriptor{
+      component_type: None,
+      bsid: None,
+      mainid: None,
+      asvc: None,
+      mixinfoexists: true,
+      substream1: None,
+      substream2: None,
+      substream3: None,
+    }));
+  }
+
+  #[test]
+  fn test_parse_hevc_video_descriptor() {
+    let descriptor: [u8; 15] = [
+      0x38, 0x0D,
+      // profile_space, tier_flag, profile_idc (Main 10)
+      0x02,
+      // profile_compatibility_indication
+      0x20, 0x00, 0x00, 0x00,
+      // progressive_source_flag + copied_44bits
+      0x90, 0x00, 0x00, 0x00, 0x00, 0x00,
+      // level_idc (5.1)
+      0x99,
+      // temporal_layer_subset_flag, HEVC_still_present_flag, HEVC_24hr_picture_present_flag
+      0x00,
+    ];
+    let parsed = Descriptor::parse_descriptors(&descriptor).unwrap();
+    match &parsed[0] {
+      Descriptor::HEVC_VIDEO(hevc) => {
+        assert_eq!(hevc.profile_space, 0);
+        assert!(!hevc.tier_flag);
+        assert_eq!(hevc.profile_idc, 2);
+        assert_eq!(hevc.profile_compatibility_indication, 0x20000000);
+        assert!(hevc.progressive_source_flag);
+        assert!(!hevc.interlaced_source_flag);
+        assert!(hevc.frame_only_constraint_flag);
+        assert_eq!(hevc.level_idc, 153);
+        assert_eq!(hevc.temporal_id_min, None);
+      }
+      _ => panic!("Expected HEVC video descriptor")
+    }
+  }
+
+  #[test]
+  fn test_parse_atsc_ac3_descriptor() {
+    let descriptor: [u8; 13] = [
+      0x81, 0x0B,
+      // sample_rate_code (48 kHz), bsid (8)
+      0x08,
+      // bit_rate_code (384 kbps), surround_mode
+      0x1C,
+      // bsmod, num_channels (3/2), full_svc
+      0x0F,
+      // langcod
+      0xFF,
+      // mainid, priority
+      0x00,
+      // textlen, text_code
+      0x00,
+      // language_flag, language_flag_2
+      0xBF,
+      // language
+      0x66, 0x72, 0x61,
+      0x00,
+    ];
+    let parsed = Descriptor::parse_descriptors(&descriptor).unwrap();
+    assert_eq!(parsed[0], Descriptor::ATSC_AC3(ATSCAC3Descriptor{
+      sample_rate_code: 0,
+      bsid: 8,
+      bit_rate_code: 7,
+      surround_mode: 0,
+      bsmod: 0,
+      num_channels: 7,
+      full_svc: true,
+      language: Some("fra".to_string()),
+    }));
+    assert_eq!(Descriptor::find_language(&parsed), Some("fra".to_string()));
+  }
+}
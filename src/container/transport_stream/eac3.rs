@@ -0,0 +1,478 @@
+use crate::error::{CustomError, construct_error, error_code::{MajorCode, TransportStreamMinorCode}};
+use crate::util::bit_reader::BitReader;
+
+static SYNC_WORD: u16 = 0x0B77;
+// Bytes needed to read the syncword, strmtyp, substreamid and frmsiz
+static SYNC_INFO_SIZE: usize = 5;
+/// An E-AC-3 sample always holds 6 audio blocks of 256 samples, same as an AC-3 sync frame
+pub static EAC3_SAMPLES_PER_SAMPLE: u32 = 1536;
+static BLOCKS_PER_SAMPLE: u8 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum SubstreamType {
+  INDEPENDENT,
+  DEPENDENT,
+  /// Independent substream converted from an AC-3 stream
+  AC3_CONVERTED,
+  RESERVED,
+}
+
+impl From<u8> for SubstreamType {
+  fn from(strmtyp: u8) -> Self {
+    match strmtyp {
+      0 => SubstreamType::INDEPENDENT,
+      1 => SubstreamType::DEPENDENT,
+      2 => SubstreamType::AC3_CONVERTED,
+      _ => SubstreamType::RESERVED,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EAC3SyncFrameHeader {
+  pub strmtyp: SubstreamType,
+  pub substreamid: u8,                // 3 bit
+  /// Size of the whole sync frame in bytes
+  pub frame_size: usize,
+  pub fscod: u8,                      // 2 bit
+  pub fscod2: Option<u8>,             // 2 bit
+  pub numblkscod: u8,                 // 2 bit
+  pub acmod: u8,                      // 3 bit
+  pub lfeon: bool,
+  pub bsid: u8,                       // 5 bit
+  pub bsmod: u8,                      // 3 bit
+  /// Custom channel map of a dependent substream
+  pub chanmap: Option<u16>,
+  /// complexity_index_type_a of the Joint Object Coding (Dolby Atmos) extension. ETSI TS 103 420
+  pub joc_complexity_index: Option<u8>,
+}
+
+impl EAC3SyncFrameHeader {
+  pub fn get_sample_rate(&self) -> u32 {
+    match (self.fscod, self.fscod2) {
+      (0, _) => 48000,
+      (1, _) => 44100,
+      (2, _) => 32000,
+      // Reduced sample rates
+      (3, Some(0)) => 24000,
+      (3, Some(1)) => 22050,
+      (3, Some(2)) => 16000,
+      _ => 0,
+    }
+  }
+
+  pub fn get_number_of_blocks(&self) -> u8 {
+    match self.numblkscod {
+      0 => 1,
+      1 => 2,
+      2 => 3,
+      _ => 6,
+    }
+  }
+
+  /// Number of full bandwidth channels (from acmod) plus the LFE channel
+  pub fn get_channel_count(&self) -> u32 {
+    let channel_count = match self.acmod {
+      0 => 2, // 1+1 dual mono
+      1 => 1,
+      2 => 2,
+      3 => 3,
+      4 => 3,
+      5 => 4,
+      6 => 4,
+      _ => 5,
+    };
+    channel_count + self.lfeon as u32
+  }
+
+  /// Independent substreams (and AC-3 converted ones) start a new program, which dependent substreams extend
+  pub fn is_independent(&self) -> bool {
+    self.strmtyp != SubstreamType::DEPENDENT
+  }
+
+  /*
+  ETSI TS 102 366; F.6.2.14 chan_loc
+  chan_loc is the chanmap of the dependent substream (Table E.1.4) without the channels an independent substream
+  can carry. Bit 0 is the most significant bit in both.
+  chanmap:  L C R Ls Rs | Lc/Rc Lrs/Rrs Cs Ts Lsd/Rsd Lw/Rw Vhl/Vhr Vhc | Lts/Rts | LFE2 | reserved
+  chan_loc:               Lc/Rc Lrs/Rrs Cs Ts Lsd/Rsd Lw/Rw Vhl/Vhr Vhc |           LFE2
+  */
+  pub fn get_chan_loc(&self) -> u16 {
+    self.chanmap
+      .map(|chanmap|((chanmap >> 2) & 0x1FE) | ((chanmap >> 1) & 0x1))
+      .unwrap_or(0)
+  }
+}
+
+/// Number of channels the chan_loc of a dependent substream adds to the independent substream
+pub fn get_chan_loc_channel_count(chan_loc: u16) -> u32 {
+  // Locations that are channel pairs. Bit 0 of chan_loc is the most significant of the 9 bits.
+  let pairs = [true, true, false, false, true, true, true, false, false];
+  pairs
+    .iter()
+    .enumerate()
+    .filter(|(index, _)|(chan_loc >> (8 - index)) & 0x1 == 1)
+    .map(|(_, is_pair)|if *is_pair { 2 } else { 1 })
+    .sum()
+}
+
+#[derive(Debug)]
+pub struct EAC3SyncFrame {
+  pub header: EAC3SyncFrameHeader,
+  pub data: Vec<u8>,
+  /// Where the sync frame starts in the parsed data
+  pub offset: usize,
+}
+
+/// An E-AC-3 sample. ETSI TS 102 366; F.6 Every sync frame (independent and dependent substreams) that make up
+/// 6 audio blocks of the stream, one after the other.
+#[derive(Debug, Default)]
+pub struct EAC3Sample {
+  pub sync_frames: Vec<EAC3SyncFrame>,
+  pub pts: u64,
+  pub dts: u64,
+}
+
+impl EAC3Sample {
+  pub fn set_pts(&mut self, pts: u64) {
+    self.pts = pts;
+  }
+
+  pub fn set_dts(&mut self, dts: u64) {
+    self.dts = dts;
+  }
+
+  pub fn get_data(&self) -> Vec<u8> {
+    self.sync_frames
+      .iter()
+      .flat_map(|sync_frame|sync_frame.data.iter().copied())
+      .collect()
+  }
+
+  /// Number of audio blocks of the first independent substream in the sample
+  pub fn get_number_of_blocks(&self) -> u8 {
+    self.sync_frames
+      .iter()
+      .filter(|sync_frame|sync_frame.header.is_independent() && sync_frame.header.substreamid == 0)
+      .map(|sync_frame|sync_frame.header.get_number_of_blocks())
+      .sum()
+  }
+
+  pub fn is_complete(&self) -> bool {
+    self.get_number_of_blocks() >= BLOCKS_PER_SAMPLE
+  }
+}
+
+/*
+ETSI TS 102 366; Annex E Enhanced AC-3
+*/
+#[derive(Debug)]
+pub struct EAC3 {}
+
+impl EAC3 {
+  /// Parse every complete sync frame in the data. Returns the sync frames and the number of bytes they took up. The
+  /// bytes after that are the start of a sync frame that continues in the next PES packet.
+  pub fn parse(data: &[u8]) -> Result<(Vec<EAC3SyncFrame>, usize), CustomError> {
+    let mut index = 0usize;
+    let mut sync_frames: Vec<EAC3SyncFrame> = vec![];
+    while index + SYNC_INFO_SIZE <= data.len() {
+      if !EAC3::is_sync_word(&data[index..]) {
+        // Skip anything that isn't a sync frame until the next sync word
+        index += 1;
+        continue;
+      }
+      // frmsiz is the number of 16 bit words minus one
+      let frame_size = ((((data[index + 2] & 0x07) as usize) << 8) | data[index + 3] as usize) * 2 + 2;
+      let end = index + frame_size;
+      if end > data.len() {
+        break;
+      }
+      let header = match EAC3::parse_sync_frame_header(&data[index..end]) {
+        Ok(header) => header,
+        // The sync word showed up in the middle of other data
+        Err(_) => {
+          index += 1;
+          continue;
+        }
+      };
+      sync_frames.push(EAC3SyncFrame {
+        header,
+        data: data[index..end].to_vec(),
+        offset: index,
+      });
+      index = end;
+    }
+    Ok((sync_frames, index))
+  }
+
+  fn is_sync_word(data: &[u8]) -> bool {
+    data.len() >= 2 && ((data[0] as u16) << 8 | data[1] as u16) == SYNC_WORD
+  }
+
+  // ETSI TS 102 366; E.1.2.2 bsi
+  fn parse_sync_frame_header(data: &[u8]) -> Result<EAC3SyncFrameHeader, CustomError> {
+    let mut bit_reader = BitReader::create_bit_reader(data);
+    // syncword
+    bit_reader.read_bits(16)?;
+    let strmtyp = bit_reader.read_bits(2)? as u8;
+    let substreamid = bit_reader.read_bits(3)? as u8;
+    let frame_size = (bit_reader.read_bits(11)? + 1) * 2;
+    let fscod = bit_reader.read_bits(2)? as u8;
+    let mut fscod2 = None;
+    let numblkscod = if fscod == 0x3 {
+      fscod2 = Some(bit_reader.read_bits(2)? as u8);
+      0x3
+    } else {
+      bit_reader.read_bits(2)? as u8
+    };
+    let number_of_blocks = match numblkscod { 0 => 1, 1 => 2, 2 => 3, _ => 6 };
+    let acmod = bit_reader.read_bits(3)? as u8;
+    let lfeon = bit_reader.read_bits(1)? == 1;
+    let bsid = bit_reader.read_bits(5)? as u8;
+    if bsid <= 10 || bsid > 16 {
+      return Err(EAC3::generate_error(format!("Unsupported E-AC-3 bsid: {}", bsid)));
+    }
+    // dialnorm
+    bit_reader.read_bits(5)?;
+    EAC3::skip_optional(&mut bit_reader, 8)?; // compr
+    if acmod == 0x0 {
+      // dialnorm2
+      bit_reader.read_bits(5)?;
+      EAC3::skip_optional(&mut bit_reader, 8)?; // compr2
+    }
+    let mut chanmap = None;
+    if strmtyp == 0x1 && bit_reader.read_bits(1)? == 1 {
+      chanmap = Some(bit_reader.read_bits(16)? as u16);
+    }
+
+    // mixmdate
+    if bit_reader.read_bits(1)? == 1 {
+      if acmod > 0x2 {
+        // dmixmod
+        bit_reader.read_bits(2)?;
+      }
+      if (acmod & 0x1) != 0 && acmod > 0x2 {
+        // ltrtcmixlev, lorocmixlev
+        bit_reader.read_bits(6)?;
+      }
+      if (acmod & 0x4) != 0 {
+        // ltrtsurmixlev, lorosurmixlev
+        bit_reader.read_bits(6)?;
+      }
+      if lfeon {
+        EAC3::skip_optional(&mut bit_reader, 5)?; // lfemixlevcod
+      }
+      if strmtyp == 0x0 {
+        EAC3::skip_optional(&mut bit_reader, 6)?; // pgmscl
+        if acmod == 0x0 {
+          EAC3::skip_optional(&mut bit_reader, 6)?; // pgmscl2
+        }
+        EAC3::skip_optional(&mut bit_reader, 6)?; // extpgmscl
+        match bit_reader.read_bits(2)? {
+          // premixcmpsel, drcsrc, premixcmpscl
+          0x1 => { bit_reader.read_bits(5)?; }
+          // mixdata
+          0x2 => { bit_reader.read_bits(12)?; }
+          0x3 => {
+            let mixdeflen = bit_reader.read_bits(5)?;
+            EAC3::skip_bits(&mut bit_reader, 8 * (mixdeflen + 2))?;
+          }
+          _ => {}
+        }
+        if acmod < 0x2 {
+          EAC3::skip_optional(&mut bit_reader, 14)?; // panmean, paninfo
+          if acmod == 0x0 {
+            EAC3::skip_optional(&mut bit_reader, 14)?; // panmean2, paninfo2
+          }
+        }
+        // frmmixcfginfoe
+        if bit_reader.read_bits(1)? == 1 {
+          if numblkscod == 0x0 {
+            // blkmixcfginfo[0]
+            bit_reader.read_bits(5)?;
+          } else {
+            for _ in 0..number_of_blocks {
+              EAC3::skip_optional(&mut bit_reader, 5)?; // blkmixcfginfo[blk]
+            }
+          }
+        }
+      }
+    }
+
+    let mut bsmod = 0u8;
+    // infomdate
+    if bit_reader.read_bits(1)? == 1 {
+      bsmod = bit_reader.read_bits(3)? as u8;
+      // copyrightb, origbs
+      bit_reader.read_bits(2)?;
+      if acmod == 0x2 {
+        // dsurmod, dheadphonmod
+        bit_reader.read_bits(4)?;
+      }
+      if acmod >= 0x6 {
+        // dsurexmod
+        bit_reader.read_bits(2)?;
+      }
+      EAC3::skip_optional(&mut bit_reader, 8)?; // mixlevel, roomtyp, adconvtyp
+      if acmod == 0x0 {
+        EAC3::skip_optional(&mut bit_reader, 8)?; // mixlevel2, roomtyp2, adconvtyp2
+      }
+      if fscod < 0x3 {
+        // sourcefscod
+        bit_reader.read_bits(1)?;
+      }
+    }
+    if strmtyp == 0x0 && numblkscod != 0x3 {
+      // convsync
+      bit_reader.read_bits(1)?;
+    }
+    if strmtyp == 0x2 {
+      let blkid = if numblkscod == 0x3 { 1 } else { bit_reader.read_bits(1)? };
+      if blkid == 1 {
+        // frmsizecod
+        bit_reader.read_bits(6)?;
+      }
+    }
+
+    let mut joc_complexity_index = None;
+    // addbsie
+    if bit_reader.read_bits(1)? == 1 {
+      let addbsil = bit_reader.read_bits(6)?;
+      // ETSI TS 103 420; 8.3 The JOC extension is signaled in the first two bytes of addbsi
+      let flag_ec3_extension_type_a = bit_reader.read_bits(1)? == 1;
+      if flag_ec3_extension_type_a && addbsil >= 1 {
+        joc_complexity_index = Some(bit_reader.read_bits(8)? as u8);
+      }
+    }
+
+    Ok(EAC3SyncFrameHeader {
+      strmtyp: SubstreamType::from(strmtyp),
+      substreamid,
+      frame_size,
+      fscod,
+      fscod2,
+      numblkscod,
+      acmod,
+      lfeon,
+      bsid,
+      bsmod,
+      chanmap,
+      joc_complexity_index,
+    })
+  }
+
+  // Read the 1 bit flag and skip the field it signals
+  fn skip_optional(bit_reader: &mut BitReader, count: usize) -> Result<(), CustomError> {
+    if bit_reader.read_bits(1)? == 1 {
+      EAC3::skip_bits(bit_reader, count)?;
+    }
+    Ok(())
+  }
+
+  fn skip_bits(bit_reader: &mut BitReader, count: usize) -> Result<(), CustomError> {
+    let mut remaining = count;
+    while remaining > 0 {
+      let bits = usize::min(remaining, 32);
+      bit_reader.read_bits(bits)?;
+      remaining -= bits;
+    }
+    Ok(())
+  }
+
+  fn generate_error(message: String) -> CustomError {
+    construct_error(
+      MajorCode::TRANSPORT_STREAM,
+      Box::new(TransportStreamMinorCode::PARSE_TS_ERROR),
+      message,
+      file!(),
+      line!())
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  /*
+  48 kHz, 6 blocks, 5.1 independent substream with the JOC extension (complexity index 16) followed by a dependent
+  substream with a chanmap of Lrs/Rrs (7.1). Both are 256 bytes.
+  */
+  pub fn create_joc_sample() -> Vec<u8> {
+    let mut independent = vec![
+      // syncword
+      0x0B, 0x77,
+      // strmtyp (0), substreamid (0), frmsiz (127)
+      0x00, 0x7F,
+      // fscod (0), numblkscod (3), acmod (7), lfeon (1)
+      0x3F,
+      // bsid (16), dialnorm (0), compre (0), mixmdate (0), infomdate (0), addbsie (1), addbsil (1),
+      // flag_ec3_extension_type_a (1), complexity_index_type_a (16)
+      0x80, 0x04, 0x18, 0x80,
+    ];
+    independent.resize(256, 0x00);
+    let mut dependent = vec![
+      // syncword
+      0x0B, 0x77,
+      // strmtyp (1), substreamid (0), frmsiz (127)
+      0x40, 0x7F,
+      // fscod (0), numblkscod (3), acmod (2), lfeon (0)
+      0x34,
+      // bsid (16), dialnorm (0), compre (0), chanmape (1), chanmap (Lrs/Rrs), mixmdate (0), infomdate (0),
+      // addbsie (0)
+      0x80, 0x10, 0x20, 0x00,
+    ];
+    dependent.resize(256, 0x00);
+    [independent, dependent].concat()
+  }
+
+  #[test]
+  fn test_parse_eac3_sync_frames() {
+    let data = create_joc_sample();
+    let (sync_frames, size) = EAC3::parse(&data).unwrap();
+    assert_eq!(sync_frames.len(), 2);
+    assert_eq!(size, 512);
+
+    let independent = &sync_frames[0].header;
+    assert_eq!(independent.strmtyp, SubstreamType::INDEPENDENT);
+    assert_eq!(independent.frame_size, 256);
+    assert_eq!(independent.get_sample_rate(), 48000);
+    assert_eq!(independent.get_number_of_blocks(), 6);
+    assert_eq!(independent.acmod, 7);
+    assert!(independent.lfeon);
+    assert_eq!(independent.bsid, 16);
+    assert_eq!(independent.get_channel_count(), 6);
+    assert_eq!(independent.joc_complexity_index, Some(16));
+
+    let dependent = &sync_frames[1].header;
+    assert_eq!(dependent.strmtyp, SubstreamType::DEPENDENT);
+    assert_eq!(dependent.chanmap, Some(0x0200));
+    assert_eq!(dependent.get_chan_loc(), 0x80);
+    assert_eq!(get_chan_loc_channel_count(dependent.get_chan_loc()), 2);
+    assert_eq!(dependent.joc_complexity_index, None);
+  }
+
+  // A sync word with a frmsiz of 3 (8 bytes) followed by an AC-3 bsid
+  pub fn create_false_sync_frame() -> Vec<u8> {
+    vec![0x0B, 0x77, 0x00, 0x03, 0x3F, 0x00, 0x00, 0x00]
+  }
+
+  #[test]
+  fn test_parse_eac3_skips_false_sync_word() {
+    let junk = create_false_sync_frame();
+    let data = [junk.clone(), create_joc_sample()].concat();
+    let (sync_frames, size) = EAC3::parse(&data).unwrap();
+    assert_eq!(sync_frames.len(), 2);
+    assert_eq!(size, junk.len() + 512);
+    assert_eq!(sync_frames[0].offset, junk.len());
+    assert_eq!(sync_frames[1].offset, junk.len() + 256);
+  }
+
+  #[test]
+  fn test_parse_eac3_incomplete_sync_frame() {
+    let data = create_joc_sample();
+    let (sync_frames, size) = EAC3::parse(&data[0..300]).unwrap();
+    assert_eq!(sync_frames.len(), 1);
+    assert_eq!(size, 256);
+  }
+}
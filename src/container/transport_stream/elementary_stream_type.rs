@@ -4,9 +4,11 @@ use std::fmt::Display;
 /*
 https://en.wikipedia.org/wiki/Program-specific_information#Elementary_stream_types
 */
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 pub enum ElementaryStreamType {
+  MPEG1_AUDIO,
+  MPEG2_AUDIO,
   AAC,
   AC3,
   E_AC3,
@@ -18,10 +20,15 @@ pub enum ElementaryStreamType {
 impl ElementaryStreamType {
   pub fn get_type(value: u8) -> ElementaryStreamType {
     match value {
+      0x3 => {ElementaryStreamType::MPEG1_AUDIO}
+      0x4 => {ElementaryStreamType::MPEG2_AUDIO}
       0xF => {ElementaryStreamType::AAC}
       0x81 => {ElementaryStreamType::AC3}
       0x87 => {ElementaryStreamType::E_AC3}
       0x1B => {ElementaryStreamType::H_264}
+      // SAMPLE-AES encrypted H.264 and ADTS AAC
+      0xDB => {ElementaryStreamType::H_264}
+      0xCF => {ElementaryStreamType::AAC}
       0x24 => {ElementaryStreamType::H_265}
       _ => {ElementaryStreamType::UNKNOWN}
     }
@@ -29,6 +36,8 @@ impl ElementaryStreamType {
 
   pub fn get_value(&self) -> u8 {
     match self {
+        ElementaryStreamType::MPEG1_AUDIO => {0x3}
+        ElementaryStreamType::MPEG2_AUDIO => {0x4}
         ElementaryStreamType::AAC => {0xF}
         ElementaryStreamType::AC3 => {0x81}
         ElementaryStreamType::E_AC3 => {0x87}
@@ -38,8 +47,23 @@ impl ElementaryStreamType {
     }
   }
 
+  pub fn is_audio(&self) -> bool {
+    matches!(self,
+      ElementaryStreamType::MPEG1_AUDIO |
+      ElementaryStreamType::MPEG2_AUDIO |
+      ElementaryStreamType::AAC |
+      ElementaryStreamType::AC3 |
+      ElementaryStreamType::E_AC3)
+  }
+
+  pub fn is_video(&self) -> bool {
+    matches!(self, ElementaryStreamType::H_264 | ElementaryStreamType::H_265)
+  }
+
   pub fn get_description(&self) -> String {
     match self {
+        ElementaryStreamType::MPEG1_AUDIO => {"ISO/IEC 11172-3 MPEG-1 audio (MP2/MP3)".to_string()}
+        ElementaryStreamType::MPEG2_AUDIO => {"ISO/IEC 13818-3 MPEG-2 halved sample rate audio (MP2/MP3)".to_string()}
         ElementaryStreamType::AAC => {"ISO/IEC 13818-7 ADTS AAC (MPEG-2 lower bit-rate audio)".to_string()}
         ElementaryStreamType::AC3 => {"ATSC Dolby Digital; AC-3".to_string()}
         ElementaryStreamType::E_AC3 => {"ATSC Dolby Digital Plus; E-AC-3".to_string()}
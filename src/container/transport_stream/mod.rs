@@ -1,6 +1,19 @@
 pub mod program_association_table;
 pub mod program_map_table;
+pub mod descriptor;
 pub mod ts_packet;
+pub mod packet_format;
+pub mod adaptation_field;
 pub mod pes_packet;
+pub mod pes_assembler;
+pub mod psi_assembler;
+pub mod timestamp_unwrapper;
+pub mod continuity_counter;
 pub mod elementary_stream_type;
-pub mod adts;
\ No newline at end of file
+pub mod adts;
+pub mod sample_aes;
+pub mod ac3;
+pub mod eac3;
+pub mod mpeg_audio;
+#[cfg(test)]
+pub mod test_util;
@@ -0,0 +1,285 @@
+use crate::error::{CustomError, construct_error, error_code::{MajorCode, TransportStreamMinorCode}};
+use crate::util::bit_reader::BitReader;
+
+static HEADER_SIZE: usize = 4;
+// Bit rates (kbps) per bitrate_index. ISO/IEC 11172-3; 2.4.2.3 and ISO/IEC 13818-3; 2.4.2.3
+static MPEG1_LAYER1_BIT_RATES: [u32; 15] = [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448];
+static MPEG1_LAYER2_BIT_RATES: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384];
+static MPEG1_LAYER3_BIT_RATES: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+static MPEG2_LAYER1_BIT_RATES: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256];
+static MPEG2_LAYER2_LAYER3_BIT_RATES: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+/// objectTypeIndication of MPEG-1 audio (ISO/IEC 11172-3). 14496-1; Table 5
+pub static MPEG1_AUDIO_OBJECT_TYPE_INDICATION: u8 = 0x6B;
+/// objectTypeIndication of MPEG-2 audio (ISO/IEC 13818-3). 14496-1; Table 5
+pub static MPEG2_AUDIO_OBJECT_TYPE_INDICATION: u8 = 0x69;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum MPEGAudioVersion {
+  MPEG_1,
+  MPEG_2,
+  /// Unofficial extension of MPEG-2 to even lower sample rates
+  MPEG_2_5,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MPEGAudioFrameHeader {
+  pub version: MPEGAudioVersion,
+  pub layer: u8,                      // 1, 2 or 3
+  pub protection_absent: bool,
+  pub bitrate_index: u8,              // 4 bit
+  pub sampling_frequency: u8,         // 2 bit
+  pub padding: bool,
+  pub mode: u8,                       // 2 bit
+  /// Size of the whole frame in bytes
+  pub frame_size: usize,
+}
+
+impl MPEGAudioFrameHeader {
+  pub fn get_sample_rate(&self) -> u32 {
+    let sample_rate = match self.sampling_frequency {
+      0 => 44100,
+      1 => 48000,
+      2 => 32000,
+      _ => 0,
+    };
+    match self.version {
+      MPEGAudioVersion::MPEG_1 => sample_rate,
+      MPEGAudioVersion::MPEG_2 => sample_rate / 2,
+      MPEGAudioVersion::MPEG_2_5 => sample_rate / 4,
+    }
+  }
+
+  /// Single channel mode is mono. Stereo, joint stereo and dual channel are 2 channels.
+  pub fn get_channel_count(&self) -> u32 {
+    if self.mode == 0x3 { 1 } else { 2 }
+  }
+
+  /// Bit rate in kbps
+  pub fn get_bit_rate(&self) -> u32 {
+    let bit_rates = match (self.version, self.layer) {
+      (MPEGAudioVersion::MPEG_1, 1) => &MPEG1_LAYER1_BIT_RATES,
+      (MPEGAudioVersion::MPEG_1, 2) => &MPEG1_LAYER2_BIT_RATES,
+      (MPEGAudioVersion::MPEG_1, _) => &MPEG1_LAYER3_BIT_RATES,
+      (_, 1) => &MPEG2_LAYER1_BIT_RATES,
+      (_, _) => &MPEG2_LAYER2_LAYER3_BIT_RATES,
+    };
+    bit_rates.get(self.bitrate_index as usize).copied().unwrap_or_default()
+  }
+
+  pub fn get_samples_per_frame(&self) -> u32 {
+    match (self.version, self.layer) {
+      (_, 1) => 384,
+      (MPEGAudioVersion::MPEG_1, _) | (_, 2) => 1152,
+      (_, _) => 576,
+    }
+  }
+
+  /// objectTypeIndication of the esds. MPEG-2.5 has no code of its own, so it's signaled as MPEG-2 audio.
+  pub fn get_object_type_indication(&self) -> u8 {
+    if self.version == MPEGAudioVersion::MPEG_1 {
+      MPEG1_AUDIO_OBJECT_TYPE_INDICATION
+    } else {
+      MPEG2_AUDIO_OBJECT_TYPE_INDICATION
+    }
+  }
+}
+
+/// An MPEG audio frame. The data is the whole frame (header included), which is what an mp4a sample holds.
+#[derive(Debug)]
+pub struct MPEGAudioFrame {
+  pub header: MPEGAudioFrameHeader,
+  pub data: Vec<u8>,
+  /// Where the frame starts in the parsed data
+  pub offset: usize,
+  pub pts: u64,
+  pub dts: u64,
+}
+
+impl MPEGAudioFrame {
+  pub fn set_pts(&mut self, pts: u64) {
+    self.pts = pts;
+  }
+
+  pub fn set_dts(&mut self, dts: u64) {
+    self.dts = dts;
+  }
+}
+
+/*
+ISO/IEC 11172-3; 2.4.1.3 Audio frame header and ISO/IEC 13818-3; 2.4.1.3 (Layer I, II and III)
+*/
+#[derive(Debug)]
+pub struct MPEGAudio {}
+
+impl MPEGAudio {
+  /// Parse every complete frame in the data. Returns the frames and the number of bytes they took up. The bytes
+  /// after that are the start of a frame that continues in the next PES packet.
+  pub fn parse(data: &[u8]) -> Result<(Vec<MPEGAudioFrame>, usize), CustomError> {
+    let mut index = 0usize;
+    let mut frames: Vec<MPEGAudioFrame> = vec![];
+    while index + HEADER_SIZE <= data.len() {
+      if !MPEGAudio::is_sync_word(&data[index..]) {
+        // Skip anything that isn't a frame until the next sync word
+        index += 1;
+        continue;
+      }
+      let header = match MPEGAudio::parse_frame_header(&data[index..]) {
+        Ok(header) => header,
+        // The sync word showed up in the middle of other data
+        Err(_) => {
+          index += 1;
+          continue;
+        }
+      };
+      let end = index + header.frame_size;
+      if end > data.len() {
+        break;
+      }
+      frames.push(MPEGAudioFrame {
+        data: data[index..end].to_vec(),
+        header,
+        offset: index,
+        pts: 0,
+        dts: 0,
+      });
+      index = end;
+    }
+    Ok((frames, index))
+  }
+
+  fn is_sync_word(data: &[u8]) -> bool {
+    data.len() >= 2 && data[0] == 0xFF && (data[1] & 0xE0) == 0xE0
+  }
+
+  fn parse_frame_header(data: &[u8]) -> Result<MPEGAudioFrameHeader, CustomError> {
+    let mut bit_reader = BitReader::create_bit_reader(&data[0..HEADER_SIZE]);
+    // syncword
+    bit_reader.read_bits(11)?;
+    let version = match bit_reader.read_bits(2)? {
+      0 => MPEGAudioVersion::MPEG_2_5,
+      2 => MPEGAudioVersion::MPEG_2,
+      3 => MPEGAudioVersion::MPEG_1,
+      _ => return Err(MPEGAudio::generate_error(String::from("Reserved MPEG audio version"))),
+    };
+    let layer = match bit_reader.read_bits(2)? {
+      1 => 3,
+      2 => 2,
+      3 => 1,
+      _ => return Err(MPEGAudio::generate_error(String::from("Reserved MPEG audio layer"))),
+    };
+    let protection_absent = bit_reader.read_bits(1)? == 1;
+    let bitrate_index = bit_reader.read_bits(4)? as u8;
+    let sampling_frequency = bit_reader.read_bits(2)? as u8;
+    let padding = bit_reader.read_bits(1)? == 1;
+    // private_bit
+    bit_reader.read_bits(1)?;
+    let mode = bit_reader.read_bits(2)? as u8;
+
+    if bitrate_index == 0 || bitrate_index == 0xF {
+      // Free format frames don't signal their size
+      return Err(MPEGAudio::generate_error(format!("Unsupported MPEG audio bitrate_index: {}", bitrate_index)));
+    }
+    if sampling_frequency == 0x3 {
+      return Err(MPEGAudio::generate_error(String::from("Reserved MPEG audio sampling_frequency")));
+    }
+
+    let mut header = MPEGAudioFrameHeader {
+      version,
+      layer,
+      protection_absent,
+      bitrate_index,
+      sampling_frequency,
+      padding,
+      mode,
+      frame_size: 0,
+    };
+    header.frame_size = MPEGAudio::get_frame_size(&header);
+    Ok(header)
+  }
+
+  // Frame size in bytes. Layer I frames are counted in 4 byte slots.
+  fn get_frame_size(header: &MPEGAudioFrameHeader) -> usize {
+    let bit_rate = header.get_bit_rate() as usize * 1000;
+    let sample_rate = header.get_sample_rate() as usize;
+    let padding = header.padding as usize;
+    if header.layer == 1 {
+      return (12 * bit_rate / sample_rate + padding) * 4;
+    }
+    let bytes_per_sample = header.get_samples_per_frame() as usize / 8;
+    bytes_per_sample * bit_rate / sample_rate + padding
+  }
+
+  fn generate_error(message: String) -> CustomError {
+    construct_error(
+      MajorCode::TRANSPORT_STREAM,
+      Box::new(TransportStreamMinorCode::PARSE_TS_ERROR),
+      message,
+      file!(),
+      line!())
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  /// MPEG-1 Layer II, 48 kHz, 192 kbps (576 bytes), stereo
+  pub fn create_mp2_frame() -> Vec<u8> {
+    let mut frame = vec![0xFF, 0xFD, 0xA4, 0x00];
+    frame.resize(576, 0x00);
+    frame
+  }
+
+  #[test]
+  fn test_parse_mpeg_audio_frames() {
+    let data = [vec![0x00, 0x11], create_mp2_frame(), create_mp2_frame(), create_mp2_frame()[0..10].to_vec()].concat();
+    let (frames, size) = MPEGAudio::parse(&data).unwrap();
+
+    assert_eq!(frames.len(), 2);
+    assert_eq!(size, 2 + 576 * 2);
+    let header = &frames[0].header;
+    assert_eq!(header.version, MPEGAudioVersion::MPEG_1);
+    assert_eq!(header.layer, 2);
+    assert!(header.protection_absent);
+    assert_eq!(header.get_bit_rate(), 192);
+    assert_eq!(header.get_sample_rate(), 48000);
+    assert_eq!(header.get_channel_count(), 2);
+    assert_eq!(header.get_samples_per_frame(), 1152);
+    assert_eq!(header.get_object_type_indication(), 0x6B);
+    assert_eq!(frames[1].data.len(), 576);
+  }
+
+  // A sync word followed by a reserved layer
+  pub fn create_false_sync_frame() -> Vec<u8> {
+    vec![0xFF, 0xE1, 0x00, 0x00]
+  }
+
+  #[test]
+  fn test_parse_mpeg_audio_skips_false_sync_word() {
+    let junk = create_false_sync_frame();
+    let data = [junk.clone(), create_mp2_frame(), create_mp2_frame()].concat();
+    let (frames, size) = MPEGAudio::parse(&data).unwrap();
+
+    assert_eq!(frames.len(), 2);
+    assert_eq!(size, junk.len() + 576 * 2);
+    assert_eq!(frames[0].offset, junk.len());
+    assert_eq!(frames[1].offset, junk.len() + 576);
+  }
+
+  #[test]
+  fn test_mpeg_audio_frame_size() {
+    // MPEG-1 Layer III, 44.1 kHz, 128 kbps with padding
+    let mp3 = MPEGAudio::parse_frame_header(&[0xFF, 0xFB, 0x92, 0x00]).unwrap();
+    assert_eq!(mp3.frame_size, 418);
+    // MPEG-2 Layer III, 24 kHz, 64 kbps, mono
+    let mpeg2 = MPEGAudio::parse_frame_header(&[0xFF, 0xF3, 0x84, 0xC0]).unwrap();
+    assert_eq!(mpeg2.get_sample_rate(), 24000);
+    assert_eq!(mpeg2.get_samples_per_frame(), 576);
+    assert_eq!(mpeg2.get_channel_count(), 1);
+    assert_eq!(mpeg2.frame_size, 192);
+    assert_eq!(mpeg2.get_object_type_indication(), 0x69);
+    // Free format
+    assert!(MPEGAudio::parse_frame_header(&[0xFF, 0xFD, 0x04, 0x00]).is_err());
+  }
+}
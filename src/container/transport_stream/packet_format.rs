@@ -0,0 +1,171 @@
+pub static SYNC_BYTE: u8 = 0x47;
+pub static TS_PACKET_SIZE: usize = 188;
+// Packets in a row that have to start with a sync byte before a packet size is taken
+static DETECTION_PACKETS: usize = 5;
+
+/// How the 188 byte transport packets are laid out in the stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum TSPacketFormat {
+  /// Plain 188 byte packets
+  TS,
+  /// 192 byte packets of Blu-ray and AVCHD (.m2ts), starting with a 4 byte TP_extra_header (copy permission and
+  /// arrival time stamp)
+  M2TS,
+  /// 204 byte packets of DVB captures, ending with 16 bytes of Reed-Solomon parity
+  DVB_RS,
+}
+
+impl TSPacketFormat {
+  pub fn get_packet_size(&self) -> usize {
+    match self {
+      TSPacketFormat::TS => TS_PACKET_SIZE,
+      TSPacketFormat::M2TS => TS_PACKET_SIZE + 4,
+      TSPacketFormat::DVB_RS => TS_PACKET_SIZE + 16,
+    }
+  }
+
+  /// Bytes in front of the transport packet
+  pub fn get_prefix_size(&self) -> usize {
+    match self {
+      TSPacketFormat::M2TS => 4,
+      _ => 0,
+    }
+  }
+
+  /// Find the packet format from the first sync byte that repeats at the packet size, along with the offset of the
+  /// packet it starts. The sync byte has to repeat for a few packets in a row, unless the data ends before then
+  /// (is_end), in which case all the whole packets left are enough.
+  pub fn detect(data: &[u8], is_end: bool) -> Option<(TSPacketFormat, usize)> {
+    let formats = [TSPacketFormat::TS, TSPacketFormat::M2TS, TSPacketFormat::DVB_RS];
+    for (sync_position, _) in data.iter().enumerate().filter(|(_, byte)| **byte == SYNC_BYTE) {
+      for format in formats.iter() {
+        let packet_size = format.get_packet_size();
+        let start = match sync_position.checked_sub(format.get_prefix_size()) {
+          Some(start) => start,
+          None => continue,
+        };
+        let whole_packets = (data.len() - start) / packet_size;
+        let packet_count = if is_end { whole_packets.min(DETECTION_PACKETS) } else { DETECTION_PACKETS };
+        if packet_count == 0 || packet_count > whole_packets {
+          continue;
+        }
+        if (0..packet_count).all(|index| data[sync_position + index * packet_size] == SYNC_BYTE) {
+          return Some((*format, start));
+        }
+      }
+    }
+    None
+  }
+
+  /// Split the data from offset into 188 byte transport packets, without the M2TS header or the DVB parity. Sync
+  /// loss is recovered by jumping to the next sync byte that lines up with the one of the packet after it. Also
+  /// returns the offset of the data that is left, the start of a packet that doesn't fit.
+  pub fn read_packets<'a>(&self, data: &'a [u8], offset: usize) -> (Vec<&'a [u8]>, usize) {
+    let mut packets: Vec<&[u8]> = vec![];
+    let mut index = offset;
+    loop {
+      let (packet, next_index) = self.read_packet(data, index);
+      index = next_index;
+      match packet {
+        Some(packet) => packets.push(packet),
+        None => return (packets, index),
+      }
+    }
+  }
+
+  /// The next 188 byte transport packet from offset and the offset after it. When there is no whole packet left,
+  /// None and the offset of the data that is left.
+  pub fn read_packet<'a>(&self, data: &'a [u8], offset: usize) -> (Option<&'a [u8]>, usize) {
+    let packet_size = self.get_packet_size();
+    let prefix_size = self.get_prefix_size();
+    let mut index = offset;
+    while index + packet_size <= data.len() {
+      if data[index + prefix_size] != SYNC_BYTE {
+        index = self.find_sync(data, index + 1);
+        continue;
+      }
+      let packet = &data[(index + prefix_size)..(index + prefix_size + TS_PACKET_SIZE)];
+      return (Some(packet), index + packet_size);
+    }
+    (None, index.min(data.len()))
+  }
+
+  // Start of the next packet from offset. When the packet after it isn't in the data yet, it can't be confirmed and
+  // is taken as is.
+  fn find_sync(&self, data: &[u8], offset: usize) -> usize {
+    let packet_size = self.get_packet_size();
+    let prefix_size = self.get_prefix_size();
+    let mut index = offset;
+    loop {
+      let sync_position = data
+        .get((index + prefix_size)..)
+        .and_then(|rest| rest.iter().position(|byte| *byte == SYNC_BYTE));
+      let start = match sync_position {
+        Some(position) => index + position,
+        // Keep what could still be the prefix of a packet
+        None => return data.len().saturating_sub(prefix_size).max(offset),
+      };
+      match data.get(start + packet_size + prefix_size) {
+        Some(byte) if *byte != SYNC_BYTE => index = start + 1,
+        _ => return start,
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn create_packets(format: TSPacketFormat, count: usize) -> Vec<u8> {
+    let mut data: Vec<u8> = vec![];
+    for index in 0..count {
+      // TP_extra_header
+      data.resize(data.len() + format.get_prefix_size(), 0x00);
+      let mut packet = vec![SYNC_BYTE, 0x01, 0x00, 0x10 | index as u8];
+      packet.resize(TS_PACKET_SIZE, index as u8);
+      data.append(&mut packet);
+      // Reed-Solomon parity
+      data.resize(data.len() + format.get_packet_size() - format.get_prefix_size() - TS_PACKET_SIZE, 0x00);
+    }
+    data
+  }
+
+  #[test]
+  fn test_detect_packet_format() {
+    for format in [TSPacketFormat::TS, TSPacketFormat::M2TS, TSPacketFormat::DVB_RS] {
+      let data = [vec![0x00, 0x47, 0x12], create_packets(format, 6)].concat();
+      assert_eq!(TSPacketFormat::detect(&data, false), Some((format, 3)));
+
+      // Too few packets to tell, unless the data ends there
+      let data = create_packets(format, 2);
+      assert_eq!(TSPacketFormat::detect(&data, false), None);
+      assert!(TSPacketFormat::detect(&data, true).is_some());
+    }
+    assert_eq!(TSPacketFormat::detect(&[0x47, 0x00, 0x00], true), None);
+  }
+
+  #[test]
+  fn test_read_packets() {
+    let format = TSPacketFormat::M2TS;
+    let mut data = create_packets(format, 4);
+    // Lose the sync of the second packet
+    data[192 + 4] = 0x00;
+    // Truncated final packet
+    data.truncate(data.len() - 10);
+
+    let (packets, end) = format.read_packets(&data, 0);
+    assert_eq!(packets.len(), 2);
+    assert_eq!(packets[0].len(), TS_PACKET_SIZE);
+    assert_eq!(packets[0][3], 0x10);
+    assert_eq!(packets[1][3], 0x12);
+    assert_eq!(end, 192 * 3);
+
+    // No sync byte left keeps nothing but what could be a TP_extra_header
+    let data = [vec![0x47, 0x00], vec![0x00; 400]].concat();
+    let (packets, end) = format.read_packets(&data, 0);
+    assert!(packets.is_empty());
+    assert_eq!(end, data.len() - 4);
+  }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+// packet_start_code_prefix (24 bit) + stream_id (8 bit) + PES_packet_length (16 bit)
+static PES_PACKET_LENGTH_END: usize = 6;
+
+/// PES packet being reassembled for a single PID
+struct PESBuffer {
+  data: Vec<u8>,
+  // Total size of the PES packet (header included). None when the PES_packet_length is 0 (unbounded) or not read yet
+  expected_length: Option<usize>,
+}
+
+impl PESBuffer {
+  fn create(data: &[u8]) -> PESBuffer {
+    let mut buffer = PESBuffer {
+      data: vec![],
+      expected_length: None,
+    };
+    buffer.append(data);
+    buffer
+  }
+
+  fn append(&mut self, data: &[u8]) {
+    self.data.extend_from_slice(data);
+    if self.expected_length.is_none() && self.data.len() >= PES_PACKET_LENGTH_END {
+      let pes_packet_length = ((self.data[4] as usize) << 8) | self.data[5] as usize;
+      if pes_packet_length > 0 {
+        self.expected_length = Some(PES_PACKET_LENGTH_END + pes_packet_length);
+      }
+    }
+  }
+
+  fn is_complete(&self) -> bool {
+    self.expected_length
+      .map(|expected_length|self.data.len() >= expected_length)
+      .unwrap_or(false)
+  }
+
+  fn take_data(mut self) -> Vec<u8> {
+    if let Some(expected_length) = self.expected_length {
+      self.data.truncate(expected_length);
+    }
+    self.data
+  }
+}
+
+/// Reassembles PES packets that span multiple TS packets. A PES packet starts on a TS packet with the
+/// payload_unit_start_indicator set and is complete once PES_packet_length bytes have been received. When the
+/// PES_packet_length is 0 (unbounded, only allowed for video) the PES packet is complete when the next one starts.
+pub struct PESAssembler {
+  buffers: HashMap<u16, PESBuffer>,
+}
+
+impl PESAssembler {
+  pub fn create() -> PESAssembler {
+    PESAssembler {
+      buffers: HashMap::new(),
+    }
+  }
+
+  /// Push the payload of a TS packet and get back every PES packet that was completed by it
+  pub fn push(&mut self, pid: u16, payload_unit_start_indicator: bool, payload: &[u8]) -> Vec<Vec<u8>> {
+    let mut complete_pes: Vec<Vec<u8>> = vec![];
+    if payload_unit_start_indicator {
+      // The start of a new PES packet ends the previous one
+      if let Some(buffer) = self.buffers.remove(&pid) {
+        complete_pes.push(buffer.take_data());
+      }
+      self.buffers.insert(pid, PESBuffer::create(payload));
+    } else if let Some(buffer) = self.buffers.get_mut(&pid) {
+      buffer.append(payload);
+    } else {
+      // Continuation of a PES packet whose start we never saw (ex. joined mid stream). Nothing to do with it.
+      return complete_pes;
+    }
+
+    if self.buffers.get(&pid).map(|buffer|buffer.is_complete()).unwrap_or(false) {
+      if let Some(buffer) = self.buffers.remove(&pid) {
+        complete_pes.push(buffer.take_data());
+      }
+    }
+    complete_pes
+  }
+
+  pub fn is_assembling(&self, pid: u16) -> bool {
+    self.buffers.contains_key(&pid)
+  }
+
+  /// Throw away the PES packet being assembled for the PID (ex. packets of it were lost). Continuation packets are
+  /// ignored until the next PES packet starts.
+  pub fn discard(&mut self, pid: u16) {
+    self.buffers.remove(&pid);
+  }
+
+  /// Get the PES packet still being assembled for the PID. Used at the end of the stream for unbounded PES packets.
+  pub fn flush(&mut self, pid: u16) -> Option<Vec<u8>> {
+    self.buffers
+      .remove(&pid)
+      .map(|buffer|buffer.take_data())
+      .filter(|data|!data.is_empty())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_pes_assembler_bounded_pes_packet() {
+    let mut assembler = PESAssembler::create();
+    // PES packet with a PES_packet_length of 8 split across 3 TS payloads
+    let first: [u8; 8] = [0x00, 0x00, 0x01, 0xC0, 0x00, 0x08, 0x80, 0x00];
+    let second: [u8; 4] = [0x00, 0x01, 0x02, 0x03];
+    let third: [u8; 6] = [0x04, 0x05, 0xFF, 0xFF, 0xFF, 0xFF];
+
+    assert!(assembler.push(0x101, true, &first).is_empty());
+    assert!(assembler.push(0x101, false, &second).is_empty());
+    let complete = assembler.push(0x101, false, &third);
+    assert_eq!(complete.len(), 1);
+    assert_eq!(complete[0], vec![0x00, 0x00, 0x01, 0xC0, 0x00, 0x08, 0x80, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05]);
+    assert_eq!(assembler.flush(0x101), None);
+  }
+
+  #[test]
+  fn test_pes_assembler_unbounded_pes_packet() {
+    let mut assembler = PESAssembler::create();
+    let first: [u8; 9] = [0x00, 0x00, 0x01, 0xE0, 0x00, 0x00, 0x80, 0x00, 0x00];
+    let second: [u8; 2] = [0x01, 0x02];
+    let next: [u8; 9] = [0x00, 0x00, 0x01, 0xE0, 0x00, 0x00, 0x80, 0x00, 0x00];
+
+    assert!(assembler.push(0x100, true, &first).is_empty());
+    assert!(assembler.push(0x100, false, &second).is_empty());
+    // Only the start of the next PES packet completes the unbounded one
+    let complete = assembler.push(0x100, true, &next);
+    assert_eq!(complete.len(), 1);
+    assert_eq!(complete[0], [first.to_vec(), second.to_vec()].concat());
+    assert_eq!(assembler.flush(0x100), Some(next.to_vec()));
+  }
+
+  #[test]
+  fn test_pes_assembler_keyed_by_pid() {
+    let mut assembler = PESAssembler::create();
+    let video: [u8; 9] = [0x00, 0x00, 0x01, 0xE0, 0x00, 0x00, 0x80, 0x00, 0x00];
+    let audio: [u8; 9] = [0x00, 0x00, 0x01, 0xC0, 0x00, 0x03, 0x80, 0x00, 0x00];
+
+    // Continuation without a start is dropped
+    assert!(assembler.push(0x100, false, &[0x01, 0x02]).is_empty());
+    assert!(assembler.push(0x100, true, &video).is_empty());
+    assert!(assembler.push(0x101, true, &audio[0..7]).is_empty());
+    assert!(assembler.push(0x100, false, &[0x03]).is_empty());
+    let complete = assembler.push(0x101, false, &audio[7..9]);
+    assert_eq!(complete, vec![audio[0..].to_vec()]);
+    assert_eq!(assembler.flush(0x100), Some([video.to_vec(), vec![0x03]].concat()));
+  }
+}
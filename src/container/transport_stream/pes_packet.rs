@@ -18,83 +18,76 @@ impl<'a> PESPacket<'a> {
     PESPacket::parse_pes_packet(payload)
   }
 
+  /// Parse a complete PES packet. Use the PESAssembler to reassemble a PES packet that spans multiple TS packets.
   fn parse_pes_packet(payload: &[u8]) -> Result<PESPacket, CustomError> {
     let mut offset = 0usize;
-    // PES header is 19 bytes. Could be a chance that the paload is under 19 bytes, if so need to implement storing this, and
-    // wait for the next ts packet to merge these two pes packets
-    if payload.len() < 19 {
-      return Ok(PESPacket{
-        packet_start_code_prefix: 0,
-        stream_id: 0,
-        pes_packet_length: 0,
-        payload_data: payload,
-        dts: None,
-        pts: None,
-      })
-    }
     let data = util::get_u32(payload, offset)?;
     offset += 4;
     let start_prefix = (data & 0xFFFFFF00) >> 8;
     if start_prefix != PACKET_START_CODE_PREFIX {
-      return Ok(PESPacket{
-        packet_start_code_prefix: 0,
-        stream_id: 0,
-        pes_packet_length: 0,
-        payload_data: payload,
-        dts: None,
-        pts: None,
-      });
+      return Err(construct_error(
+        MajorCode::TRANSPORT_STREAM,
+        Box::new(TransportStreamMinorCode::PARSE_TS_ERROR),
+        "PES packet is missing the packet start code prefix".to_string(),
+        file!(),
+        line!()));
     }
     let stream_id = (data & 0xFF) as u8;
     let pes_packet_length = util::get_u16(payload, offset)?;
-    // Skip 1 byte + 2 bytes for packet length
-    offset += 3;
+    offset += 2;
+    // A pes_packet_length of 0 is only allowed for video and means the PES packet is unbounded
+    let pes_packet_end = match pes_packet_length {
+      0 => payload.len(),
+      _ => usize::min(offset + pes_packet_length as usize, payload.len()),
+    };
 
     if
       (stream_id) == 0b10111101 || // Checking if private_stream_1 for E-AC-3 or AC-3
       (stream_id >> 4) == 0b1110 || // Checking if ISO/IEC 13818-3 or ISO/IEC 11172-3 or ISO/IEC 13818-7 or ISO/IEC 14496-3 audio stream number x xxxx
       (stream_id >> 5) == 0b110 // Checking if ITU-T Rec. H.262 | ISO/IEC 13818-2 or ISO/IEC 11172-2 or ISO/IEC 14496-2 video stream number xxxx
     {
+      // Skip the '10', scrambling control, priority, data alignment, copyright and original_or_copy byte
+      offset += 1;
       let pts_dts_flags = util::get_u8(payload, offset)?;
       offset += 1;
       let pes_header_data_length = util::get_u8(payload, offset)?;
       offset += 1;
-      let mut pts = 0;
-      let mut dts = u64::MAX;
-      if (pts_dts_flags & 0xC0) != 0 {
-        let pts_section_64 = util::get_u64(payload, offset)?;
-        // Only 40 bits are being used.
+      let payload_start_offset = offset + pes_header_data_length as usize;
+      let mut pts = None;
+      let mut dts = None;
+      if (pts_dts_flags & 0x80) != 0 {
+        let pts_section = PESPacket::get_timestamp_section(payload, offset)?;
         offset += 5;
-        let pts_section = pts_section_64 >> 24;
         if !PESPacket::is_timestamp_section_valid(pts_section) {
           println!("Not a valid PTS");
         }
-        pts = PESPacket::convert_timestamp_section_to_timesamp(pts_section);
+        pts = Some(PESPacket::convert_timestamp_section_to_timesamp(pts_section));
       }
 
       if (pts_dts_flags & 0x40) != 0 {
-        let dts_section_64 = util::get_u64(payload, offset)?;
-        // Only 40 bits are being used.
-        offset += 5;
-        let dts_section = dts_section_64 >> 24;
+        let dts_section = PESPacket::get_timestamp_section(payload, offset)?;
         if !PESPacket::is_timestamp_section_valid(dts_section) {
           println!("Not a valid DTS");
         }
-        dts = PESPacket::convert_timestamp_section_to_timesamp(dts_section);
+        dts = Some(PESPacket::convert_timestamp_section_to_timesamp(dts_section));
       }
 
-      if dts == u64::MAX {
-        dts = pts
+      if payload_start_offset > pes_packet_end {
+        return Err(construct_error(
+          MajorCode::TRANSPORT_STREAM,
+          Box::new(TransportStreamMinorCode::PARSE_TS_ERROR),
+          "PES header is longer than the PES packet".to_string(),
+          file!(),
+          line!()));
       }
-      // 9 bytes : 6 bytes for PES header + 3 bytes for PES extension
-      let payload_start_offset = (pes_header_data_length + 9) as usize;
+
       return Ok(PESPacket{
         packet_start_code_prefix: start_prefix,
         stream_id,
-        pes_packet_length: 0,
-        payload_data: payload[payload_start_offset..payload.len()].as_ref(),
-        dts: Some(dts),
-        pts: Some(pts),
+        pes_packet_length,
+        payload_data: payload[payload_start_offset..pes_packet_end].as_ref(),
+        dts: dts.or(pts),
+        pts,
       });
     }
     
@@ -106,6 +99,13 @@ impl<'a> PESPacket<'a> {
         line!()));
   }
 
+  // Timestamps are 40 bit sections (33 bit timestamp + marker bits)
+  fn get_timestamp_section(payload: &[u8], offset: usize) -> Result<u64, CustomError> {
+    let high = util::get_u8(payload, offset)? as u64;
+    let low = util::get_u32(payload, offset + 1)? as u64;
+    Ok((high << 32) | low)
+  }
+
   // https://github.com/google/shaka-packager/blob/6c8ad30217c286d4eecadc9df12420767d389942/packager/media/formats/mp2t/ts_section_pes.cc#L63-L73
   fn is_timestamp_section_valid(timestamp_section: u64) -> bool {
     return ((timestamp_section & 0x1) != 0) &&
@@ -119,4 +119,53 @@ impl<'a> PESPacket<'a> {
          (((timestamp_section >> 17) & 0x7fff) << 15) |
          (((timestamp_section >> 1) & 0x7fff) << 0);
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_pes_packet_with_pts_and_dts() {
+    let pes: [u8; 24] = [
+      // packet_start_code_prefix + stream_id
+      0x00, 0x00, 0x01, 0xE0,
+      // PES_packet_length
+      0x00, 0x11,
+      // '10' + flags, PTS_DTS_flags, PES_header_data_length
+      0x80, 0xC0, 0x0A,
+      // PTS (133500)
+      0x31, 0x00, 0x09, 0x12, 0xF9,
+      // DTS (126000)
+      0x11, 0x00, 0x07, 0xD8, 0x61,
+      // payload
+      0x00, 0x00, 0x01, 0x09,
+      // Data past the PES_packet_length
+      0xFF,
+    ];
+
+    let pes = PESPacket::parse(&pes).unwrap();
+    assert_eq!(pes.stream_id, 0xE0);
+    assert_eq!(pes.pes_packet_length, 17);
+    assert_eq!(pes.pts, Some(133500));
+    assert_eq!(pes.dts, Some(126000));
+    assert_eq!(pes.payload_data, [0x00, 0x00, 0x01, 0x09]);
+  }
+
+  #[test]
+  fn test_parse_pes_packet_without_timestamps() {
+    let pes: [u8; 11] = [
+      0x00, 0x00, 0x01, 0xC0,
+      // PES_packet_length of 0 (unbounded)
+      0x00, 0x00,
+      0x80, 0x00, 0x00,
+      // payload
+      0xFF, 0xF1,
+    ];
+
+    let pes = PESPacket::parse(&pes).unwrap();
+    assert_eq!(pes.pts, None);
+    assert_eq!(pes.dts, None);
+    assert_eq!(pes.payload_data, [0xFF, 0xF1]);
+  }
+}
@@ -1,6 +1,12 @@
+use std::collections::BTreeMap;
 use std::u16;
 
 use crate::{error::CustomError, util};
+use super::psi_assembler::verify_section;
+
+pub static PAT_TABLE_ID: u8 = 0x00;
+// transport_stream_id, version_number, current_next_indicator, section_number and last_section_number
+static PAT_HEADER_LENGTH: usize = 5;
 
 
 #[derive(Debug)]
@@ -13,18 +19,22 @@ pub struct ProgramAssociationTable {
   current_next_indicator: bool,
   section_number: u8,
   last_section_number: u8,
-  pub program_map_pid: u16,               // u13
+  pub network_pid: Option<u16>,       // u13
+  pub programs: BTreeMap<u16, u16>,   // program_number -> program_map_PID (u13)
   crc_32: u32
 
 }
 
 impl ProgramAssociationTable {
 
+  /// Parse the section, checking its table_id and CRC_32. With the payload_unit_start_indicator the data starts with
+  /// the pointer_field.
   pub fn parse(data: &[u8], payload_unit_start_indicator: bool) -> Result<ProgramAssociationTable, CustomError> {
     let mut start = 0usize;
     if payload_unit_start_indicator {
       start = util::get_u8(data, start)? as usize + 1;
     }
+    let end = verify_section(data, start, PAT_TABLE_ID, PAT_HEADER_LENGTH)?;
     let table_id = util::get_u8(data, start)?;
 
     start = start + 1;
@@ -33,7 +43,6 @@ impl ProgramAssociationTable {
     let section_length = temp_16 & 0xFFF;
 
     start = start + 2;
-    let end = start + section_length as usize;
     let transport_stream_id = util::get_u16(data, start)?;
 
     start = start + 2;
@@ -48,15 +57,16 @@ impl ProgramAssociationTable {
     let last_section_number = util::get_u8(data, start)?;
     start = start + 1;
 
-    let mut program_map_pid = 0;
-    // Grabbing only one program map id. Don't really care if there's more
+    let mut network_pid = None;
+    let mut programs = BTreeMap::new();
     while start < (end - 4) {
       let program_number = util::get_u16(data, start)?;
       start = start + 2;
+      let pid = util::get_u16(data, start)? & 0x1FFF;
       if program_number == 0 {
-        // Ignore network _id
+        network_pid = Some(pid);
       } else {
-        program_map_pid = util::get_u16(data, start)? & 0x1FFF;
+        programs.insert(program_number, pid);
       }
       start = start + 2;
     }
@@ -73,9 +83,67 @@ impl ProgramAssociationTable {
         current_next_indicator,
         section_number,
         last_section_number,
-        program_map_pid,
+        network_pid,
+        programs,
         crc_32,
       }
     )
   }
-}
\ No newline at end of file
+
+  /// Get the program map PID for the program number. Returns None if the program is not listed in the PAT
+  pub fn get_program_map_pid(&self, program_number: u16) -> Option<u16> {
+    self.programs.get(&program_number).copied()
+  }
+
+  /// Get the program with the lowest program number. This is the program used when the caller doesn't select one
+  pub fn get_first_program(&self) -> Option<(u16, u16)> {
+    self.programs
+      .iter()
+      .next()
+      .map(|(program_number, pid)| (*program_number, *pid))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_pat_multiple_programs() {
+    let pat: [u8; 29] = [
+      // pointer_field
+      0x00,
+      // table_id
+      0x00,
+      // section_syntax_indicator + section_length
+      0xB0, 0x19,
+      // transport_stream_id
+      0x00, 0x01,
+      // version_number + current_next_indicator
+      0xC1,
+      // section_number
+      0x00,
+      // last_section_number
+      0x00,
+      // network_PID
+      0x00, 0x00, 0xE0, 0x10,
+      // program 1
+      0x00, 0x01, 0xE1, 0x00,
+      // program 2
+      0x00, 0x02, 0xE2, 0x00,
+      // program 3
+      0x00, 0x03, 0xE3, 0x00,
+      // CRC_32
+      0xD3, 0x13, 0xCF, 0xA3,
+    ];
+
+    let pat = ProgramAssociationTable::parse(&pat, true).unwrap();
+    assert_eq!(pat.network_pid, Some(0x10));
+    assert_eq!(pat.programs.len(), 3);
+    assert_eq!(pat.get_program_map_pid(1), Some(0x100));
+    assert_eq!(pat.get_program_map_pid(2), Some(0x200));
+    assert_eq!(pat.get_program_map_pid(3), Some(0x300));
+    assert_eq!(pat.get_program_map_pid(4), None);
+    assert_eq!(pat.get_first_program(), Some((1, 0x100)));
+  }
+}
@@ -2,12 +2,57 @@ use std::u16;
 
 use crate::error::CustomError;
 use crate::util;
+use super::descriptor::Descriptor;
+use super::psi_assembler::verify_section;
 use super::elementary_stream_type::ElementaryStreamType;
+use super::sample_aes::is_sample_aes_stream_type;
 
-#[derive(Debug)]
+// Stream type used by DVB for AC-3/E-AC-3 which are signaled through descriptors instead
+static PRIVATE_PES_STREAM_TYPE: u8 = 0x06;
+pub static PMT_TABLE_ID: u8 = 0x02;
+// program_number, version_number, current_next_indicator, section_number, last_section_number, PCR_PID and
+// program_info_length
+static PMT_HEADER_LENGTH: usize = 9;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StreamInfo {
   pub pid: u16,
   pub stream_type: ElementaryStreamType,
+  pub descriptors: Vec<Descriptor>,
+  /// Whether the samples are SAMPLE-AES encrypted, which has its own stream types
+  pub sample_aes: bool,
+}
+
+impl StreamInfo {
+  /// ISO 639-2 language of the elementary stream, if signaled
+  pub fn get_language(&self) -> Option<String> {
+    Descriptor::find_language(&self.descriptors)
+  }
+
+  fn resolve_stream_type(stream_type: u8, descriptors: &[Descriptor]) -> ElementaryStreamType {
+    let es_type = ElementaryStreamType::get_type(stream_type);
+    if es_type != ElementaryStreamType::UNKNOWN || stream_type != PRIVATE_PES_STREAM_TYPE {
+      return es_type;
+    }
+
+    let from_descriptor = descriptors
+      .iter()
+      .find_map(|descriptor| match descriptor {
+        Descriptor::AC3(_) => Some(ElementaryStreamType::AC3),
+        Descriptor::ENHANCED_AC3(_) => Some(ElementaryStreamType::E_AC3),
+        _ => None,
+      });
+    if let Some(es_type) = from_descriptor {
+      return es_type;
+    }
+
+    match Descriptor::find_registration(descriptors).as_deref() {
+      Some("AC-3") => ElementaryStreamType::AC3,
+      Some("EAC3") => ElementaryStreamType::E_AC3,
+      Some("HEVC") => ElementaryStreamType::H_265,
+      _ => ElementaryStreamType::UNKNOWN,
+    }
+  }
 }
 
 #[allow(non_snake_case)]
@@ -16,24 +61,27 @@ pub struct ProgramMapTable {
   table_id: u8,
   section_syntax_indicator: bool,
   section_length: u16,                    // 12 bit
-  program_number: u16,                    
+  pub program_number: u16,                    
   version_number: u8,                     // 5 bit
   current_next_indicator: bool,
   section_number: u8,
   last_section_number: u8,
   PCR_PID: u16,                           // 13 bit
   program_info_length: u16,               // 12 bit
-  pub audio_stream_info: Option<StreamInfo>,
-  pub video_stream_info: Option<StreamInfo>,
+  pub program_descriptors: Vec<Descriptor>,
+  pub streams: Vec<StreamInfo>,           // Every elementary stream in the order they are listed in the PMT
 }
 
 #[allow(non_snake_case)]
 impl ProgramMapTable {
+  /// Parse the section, checking its table_id and CRC_32. With the payload_unit_start_indicator the data starts with
+  /// the pointer_field.
   pub fn parse(data: &[u8], payload_unit_start_indicator: bool) -> Result<ProgramMapTable, CustomError> {
     let mut start = 0usize;
     if payload_unit_start_indicator {
       start = util::get_u8(data, start)? as usize + 1;
     }
+    let end = verify_section(data, start, PMT_TABLE_ID, PMT_HEADER_LENGTH)?;
 
     let table_id = util::get_u8(data,start)?;
 
@@ -43,7 +91,6 @@ impl ProgramMapTable {
     let section_length = buffer_16 & 0xFFF;
 
     start = start + 2;
-    let end = start + section_length as usize;
     let program_number = util::get_u16(data, start)?;
 
     start = start + 2;
@@ -66,11 +113,10 @@ impl ProgramMapTable {
     start = start + 2;
     let program_end = start + program_info_length as usize;
 
-    // NOTE (benjamintoofer@gmail.com): Skip descriptors for now
+    let program_descriptors = Descriptor::parse_descriptors(data.get(start..program_end).unwrap_or(&[]))?;
     start = program_end;
 
-    let mut video_stream_info = None;
-    let mut audio_stream_info = None;
+    let mut streams: Vec<StreamInfo> = vec![];
     while start < end - 4 {
       let stream_type = util::get_u8(data, start)?;
       start = start + 1;
@@ -79,27 +125,14 @@ impl ProgramMapTable {
       let es_info_length = util::get_u16(data, start)? & 0xFFF;
       start = start + 2;
       let es_info_end = start + es_info_length as usize;
-      // TODO (benjamintoofer@gmail.com): Skip es info for now
+      let descriptors = Descriptor::parse_descriptors(data.get(start..es_info_end).unwrap_or(&[]))?;
       start = es_info_end;
-      let stream = ElementaryStreamType::get_type(stream_type);
-      match stream {
-          ElementaryStreamType::AAC => {
-            audio_stream_info = Some(StreamInfo{pid: elementary_pid, stream_type: stream});
-          }
-          ElementaryStreamType::AC3 => {
-            audio_stream_info = Some(StreamInfo{pid: elementary_pid, stream_type: stream});
-          }
-          ElementaryStreamType::E_AC3 => {
-            audio_stream_info = Some(StreamInfo{pid: elementary_pid, stream_type: stream});
-          }
-          ElementaryStreamType::H_264 => {
-            video_stream_info = Some(StreamInfo{pid: elementary_pid, stream_type: stream});
-          }
-          ElementaryStreamType::H_265 => {
-            video_stream_info = Some(StreamInfo{pid: elementary_pid, stream_type: stream});
-          }
-          ElementaryStreamType::UNKNOWN => {}
-      }
+      streams.push(StreamInfo{
+        pid: elementary_pid,
+        stream_type: StreamInfo::resolve_stream_type(stream_type, &descriptors),
+        descriptors,
+        sample_aes: is_sample_aes_stream_type(stream_type),
+      });
     }
 
     Ok(
@@ -114,9 +147,115 @@ impl ProgramMapTable {
         last_section_number,
         PCR_PID,
         program_info_length,
-        audio_stream_info,
-        video_stream_info
+        program_descriptors,
+        streams,
       }
     )
   }
-}
\ No newline at end of file
+
+  pub fn get_video_streams(&self) -> Vec<&StreamInfo> {
+    self.streams
+      .iter()
+      .filter(|stream|stream.stream_type.is_video())
+      .collect()
+  }
+
+  pub fn get_audio_streams(&self) -> Vec<&StreamInfo> {
+    self.streams
+      .iter()
+      .filter(|stream|stream.stream_type.is_audio())
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_pmt_multiple_streams() {
+    let pmt: [u8; 37] = [
+      // pointer_field
+      0x00,
+      // table_id
+      0x02,
+      // section_syntax_indicator + section_length
+      0xB0, 0x21,
+      // program_number
+      0x00, 0x01,
+      // version_number + current_next_indicator
+      0xC1,
+      // section_number
+      0x00,
+      // last_section_number
+      0x00,
+      // PCR_PID
+      0xE1, 0x00,
+      // program_info_length
+      0xF0, 0x00,
+      // H.264
+      0x1B, 0xE1, 0x00, 0xF0, 0x00,
+      // AAC
+      0x0F, 0xE1, 0x01, 0xF0, 0x00,
+      // AAC
+      0x0F, 0xE1, 0x02, 0xF0, 0x00,
+      // AC-3
+      0x81, 0xE1, 0x03, 0xF0, 0x00,
+      // CRC_32
+      0x14, 0xCD, 0x00, 0x14,
+    ];
+
+    let pmt = ProgramMapTable::parse(&pmt, true).unwrap();
+    assert_eq!(pmt.program_number, 1);
+    assert_eq!(pmt.streams.len(), 4);
+    assert_eq!(pmt.streams[0].pid, 0x100);
+    assert_eq!(pmt.streams[0].stream_type, ElementaryStreamType::H_264);
+    assert_eq!(pmt.streams[3].pid, 0x103);
+    assert_eq!(pmt.streams[3].stream_type, ElementaryStreamType::AC3);
+    assert_eq!(pmt.get_video_streams().len(), 1);
+    assert_eq!(pmt.get_audio_streams().len(), 3);
+  }
+
+  #[test]
+  fn test_parse_pmt_descriptors() {
+    let pmt: [u8; 53] = [
+      // pointer_field
+      0x00,
+      // table_id
+      0x02,
+      // section_syntax_indicator + section_length
+      0xB0, 0x31,
+      // program_number
+      0x00, 0x01,
+      // version_number + current_next_indicator
+      0xC1,
+      // section_number
+      0x00,
+      // last_section_number
+      0x00,
+      // PCR_PID
+      0xE1, 0x00,
+      // program_info_length
+      0xF0, 0x06,
+      // registration descriptor ("CUEI")
+      0x05, 0x04, 0x43, 0x55, 0x45, 0x49,
+      // H.264
+      0x1B, 0xE1, 0x00, 0xF0, 0x00,
+      // AAC + ISO 639 language descriptor ("spa")
+      0x0F, 0xE1, 0x01, 0xF0, 0x06, 0x0A, 0x04, 0x73, 0x70, 0x61, 0x00,
+      // Private PES + DVB AC-3 descriptor + ISO 639 language descriptor ("ger")
+      0x06, 0xE1, 0x02, 0xF0, 0x09, 0x6A, 0x01, 0x00, 0x0A, 0x04, 0x67, 0x65, 0x72, 0x01,
+      // CRC_32
+      0x75, 0x2A, 0xD8, 0x4B,
+    ];
+
+    let pmt = ProgramMapTable::parse(&pmt, true).unwrap();
+    assert_eq!(pmt.streams.len(), 3);
+    assert_eq!(Descriptor::find_registration(&pmt.program_descriptors), Some("CUEI".to_string()));
+    assert_eq!(pmt.streams[0].get_language(), None);
+    assert_eq!(pmt.streams[1].get_language(), Some("spa".to_string()));
+    assert_eq!(pmt.streams[2].stream_type, ElementaryStreamType::AC3);
+    assert_eq!(pmt.streams[2].get_language(), Some("ger".to_string()));
+    assert_eq!(pmt.get_audio_streams().len(), 2);
+  }
+}
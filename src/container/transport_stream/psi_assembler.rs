@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use crate::error::{CustomError, construct_error, error_code::{MajorCode, TransportStreamMinorCode}};
+use crate::util;
+
+// table_id (8 bit) + section_syntax_indicator, '0', reserved and section_length (16 bit)
+static SECTION_LENGTH_END: usize = 3;
+// The stuffing bytes after the last section of a TS packet read as this table_id
+static STUFFING_TABLE_ID: u8 = 0xFF;
+static CRC_32_SIZE: usize = 4;
+
+/// Reassembles the PSI sections (PAT, PMT) that span multiple TS packets. The pointer_field of a TS packet with the
+/// payload_unit_start_indicator set tells where the first section starting in it begins, the bytes in front of it end
+/// the section in progress. A section is complete once section_length bytes after the section_length have been
+/// received, and more sections can follow it in the same TS packet until the stuffing bytes.
+/// ITU-T H.222.0; 2.4.4.1 and 2.4.4.2
+pub struct PSIAssembler {
+  buffers: HashMap<u16, Vec<u8>>,
+}
+
+impl PSIAssembler {
+  pub fn create() -> PSIAssembler {
+    PSIAssembler {
+      buffers: HashMap::new(),
+    }
+  }
+
+  /// Push the payload of a TS packet and get back every section (table_id to CRC_32) completed by it. Continuation
+  /// packets are ignored when there is no section in progress (ex. joined mid stream).
+  pub fn push(&mut self, pid: u16, payload_unit_start_indicator: bool, payload: &[u8]) -> Result<Vec<Vec<u8>>, CustomError> {
+    let mut sections: Vec<Vec<u8>> = vec![];
+    if !payload_unit_start_indicator {
+      if let Some(mut buffer) = self.buffers.remove(&pid) {
+        buffer.extend_from_slice(payload);
+        self.split_sections(pid, &buffer, &mut sections);
+      }
+      return Ok(sections);
+    }
+
+    let section_start = util::get_u8(payload, 0)? as usize + 1;
+    if section_start > payload.len() {
+      return Err(
+        construct_error(
+          MajorCode::TRANSPORT_STREAM,
+          Box::new(TransportStreamMinorCode::PARSE_TS_ERROR),
+          format!("pointer_field {} points past the end of the TS packet payload of PID {}", section_start - 1, pid),
+          file!(),
+          line!(),
+        )
+      );
+    }
+    if let Some(mut buffer) = self.buffers.remove(&pid) {
+      buffer.extend_from_slice(&payload[1..section_start]);
+      self.split_sections(pid, &buffer, &mut sections);
+      // A section started in the packet, so the one in progress can't be completed anymore
+      self.buffers.remove(&pid);
+    }
+    self.split_sections(pid, &payload[section_start..], &mut sections);
+    Ok(sections)
+  }
+
+  /// Throw away the section being assembled for the PID (ex. packets of it were lost)
+  pub fn discard(&mut self, pid: u16) {
+    self.buffers.remove(&pid);
+  }
+
+  // Take the complete sections off the front of the data, keeping the one that isn't complete yet
+  fn split_sections(&mut self, pid: u16, data: &[u8], sections: &mut Vec<Vec<u8>>) {
+    let mut start = 0usize;
+    while start < data.len() && data[start] != STUFFING_TABLE_ID {
+      let section_end = match data.get(start + 1..start + SECTION_LENGTH_END) {
+        Some(length) => start + SECTION_LENGTH_END + (((length[0] as usize & 0x0F) << 8) | length[1] as usize),
+        None => data.len() + 1,
+      };
+      if section_end > data.len() {
+        self.buffers.insert(pid, data[start..].to_vec());
+        return;
+      }
+      sections.push(data[start..section_end].to_vec());
+      start = section_end;
+    }
+  }
+}
+
+/// Check the table_id and the CRC_32 of the section starting at the start of the data, and that it holds at least
+/// min_section_length bytes after the section_length. Returns where the section ends.
+pub fn verify_section(data: &[u8], start: usize, table_id: u8, min_section_length: usize) -> Result<usize, CustomError> {
+  let section_table_id = util::get_u8(data, start)?;
+  let section_length = (util::get_u16(data, start + 1)? & 0xFFF) as usize;
+  let section_end = start + SECTION_LENGTH_END + section_length;
+  let message = if section_table_id != table_id {
+    format!("table_id {} instead of {}", section_table_id, table_id)
+  } else if section_length < min_section_length + CRC_32_SIZE {
+    format!("section_length {} is too short", section_length)
+  } else {
+    match data.get(start..section_end) {
+      Some(section) if util::crc_32_mpeg2(section) == 0 => return Ok(section_end),
+      Some(_) => "CRC_32 doesn't match".to_string(),
+      None => format!("section_length {} is past the end of the data", section_length),
+    }
+  };
+  Err(
+    construct_error(
+      MajorCode::TRANSPORT_STREAM,
+      Box::new(TransportStreamMinorCode::PARSE_TS_ERROR),
+      format!("Invalid PSI section: {}", message),
+      file!(),
+      line!(),
+    )
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn create_section(table_id: u8, data: &[u8]) -> Vec<u8> {
+    let section_length = data.len() + CRC_32_SIZE;
+    let section = [&[table_id, 0xB0 | (section_length >> 8) as u8, section_length as u8][..], data].concat();
+    let crc_32 = util::crc_32_mpeg2(&section);
+    [section, crc_32.to_be_bytes().to_vec()].concat()
+  }
+
+  #[test]
+  fn test_psi_assembler_section_across_packets() {
+    let mut assembler = PSIAssembler::create();
+    let section = create_section(0x02, &[0x11; 20]);
+    // Continuation without a section in progress
+    assert!(assembler.push(0x100, false, &section[10..]).unwrap().is_empty());
+
+    let first = [&[0x00][..], &section[..10]].concat();
+    assert!(assembler.push(0x100, true, &first).unwrap().is_empty());
+    assert_eq!(assembler.push(0x100, false, &[&section[10..], &[0xFF, 0xFF][..]].concat()).unwrap(), vec![section.clone()]);
+    assert_eq!(verify_section(&section, 0, 0x02, 9).unwrap(), section.len());
+  }
+
+  #[test]
+  fn test_psi_assembler_pointer_field() {
+    let mut assembler = PSIAssembler::create();
+    let first_section = create_section(0x00, &[0x22; 9]);
+    let second_section = create_section(0x00, &[0x33; 5]);
+    let third_section = create_section(0x00, &[0x44; 5]);
+    assert!(assembler.push(0, true, &[&[0x00][..], &first_section[..4]].concat()).unwrap().is_empty());
+    // The end of the first section in front of the pointer_field, then two more sections and the stuffing bytes
+    let payload = [
+      &[(first_section.len() - 4) as u8][..], &first_section[4..], &second_section, &third_section, &[0xFF; 4],
+    ].concat();
+    assert_eq!(assembler.push(0, true, &payload).unwrap(), vec![first_section, second_section, third_section.clone()]);
+
+    // A section that is cut short by the next one starting
+    assert!(assembler.push(0, true, &[&[0x00][..], &third_section[..4]].concat()).unwrap().is_empty());
+    assert_eq!(assembler.push(0, true, &[&[0x00][..], &third_section[..]].concat()).unwrap(), vec![third_section]);
+
+    assert!(assembler.push(0, true, &[0xC0, 0x00]).is_err());
+  }
+
+  #[test]
+  fn test_verify_section() {
+    let mut section = create_section(0x02, &[0x11; 9]);
+    assert!(verify_section(&section, 0, 0x00, 5).is_err());
+    assert!(verify_section(&section, 0, 0x02, 20).is_err());
+    assert!(verify_section(&section[..10], 0, 0x02, 5).is_err());
+    section[5] = 0x12;
+    assert!(verify_section(&section, 0, 0x02, 5).is_err());
+  }
+}
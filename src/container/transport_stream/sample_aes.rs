@@ -0,0 +1,234 @@
+use crate::codec::nal_unit_to_rbsp;
+use crate::container::transport_stream::elementary_stream_type::ElementaryStreamType;
+use crate::container::transport_stream::pes_packet::PESPacket;
+use crate::error::CustomError;
+use crate::util::aes::{AES128, AES_BLOCK_SIZE};
+
+// Stream types of SAMPLE-AES encrypted H.264 and ADTS AAC. Apple MPEG-2 Stream Encryption Format for HLS; 2.3
+pub static SAMPLE_AES_H_264_STREAM_TYPE: u8 = 0xDB;
+pub static SAMPLE_AES_AAC_STREAM_TYPE: u8 = 0xCF;
+
+// Clear bytes at the start of an encrypted NAL unit, the NAL unit type byte included
+static AVC_CLEAR_LEADER_SIZE: usize = 32;
+// An encrypted block followed by up to 9 clear blocks
+static AVC_PATTERN_SIZE: usize = 160;
+// NAL units of this size or less are left clear
+static AVC_MAX_CLEAR_NAL_UNIT_SIZE: usize = 48;
+// Clear bytes after the ADTS header of an encrypted frame
+static AAC_CLEAR_LEADER_SIZE: usize = 16;
+static ADTS_HEADER_SIZE: usize = 7;
+static ADTS_CRC_SIZE: usize = 2;
+
+pub fn is_sample_aes_stream_type(stream_type: u8) -> bool {
+  stream_type == SAMPLE_AES_H_264_STREAM_TYPE || stream_type == SAMPLE_AES_AAC_STREAM_TYPE
+}
+
+/// Key and IV of a SAMPLE-AES segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleAESKey {
+  pub key: [u8; 16],
+  pub iv: [u8; 16],
+}
+
+/// Decrypts the samples of SAMPLE-AES PES packets. Every NAL unit and every ADTS frame starts a new CBC chain from
+/// the IV, so a PES packet has to hold whole NAL units and frames (as HLS segments do).
+pub struct SampleAESDecrypter {
+  aes: AES128,
+  iv: [u8; 16],
+}
+
+impl SampleAESDecrypter {
+  pub fn create(sample_aes_key: &SampleAESKey) -> SampleAESDecrypter {
+    SampleAESDecrypter {
+      aes: AES128::create(&sample_aes_key.key),
+      iv: sample_aes_key.iv,
+    }
+  }
+
+  /// The PES packet with the payload decrypted. Only H.264 and ADTS AAC are encrypted, anything else is returned as is.
+  pub fn decrypt_pes(&self, pes_data: &[u8], stream_type: ElementaryStreamType) -> Result<Vec<u8>, CustomError> {
+    let pes = PESPacket::parse(pes_data)?;
+    let payload = match stream_type {
+      ElementaryStreamType::H_264 => self.decrypt_avc(pes.payload_data),
+      ElementaryStreamType::AAC => self.decrypt_adts(pes.payload_data),
+      _ => return Ok(pes_data.to_vec()),
+    };
+    // Audio and video PES packets have the optional PES header, so the payload starts after PES_header_data_length
+    let header_size = (9 + pes_data[8] as usize).min(pes_data.len());
+    let mut decrypted_pes = [&pes_data[..header_size], payload.as_slice()].concat();
+    // Removing the emulation prevention bytes can make the PES packet shorter
+    if pes.pes_packet_length != 0 {
+      let pes_packet_length = (decrypted_pes.len() - 6) as u16;
+      decrypted_pes[4..6].copy_from_slice(&pes_packet_length.to_be_bytes());
+    }
+    Ok(decrypted_pes)
+  }
+
+  // Every NAL unit of the Annex B byte stream is decrypted on its own, the start codes in between are kept
+  fn decrypt_avc(&self, annex_b: &[u8]) -> Vec<u8> {
+    let nal_unit_starts: Vec<usize> = (0..annex_b.len().saturating_sub(2))
+      .filter(|index| annex_b[*index..(*index + 3)] == [0x00, 0x00, 0x01])
+      .map(|index| index + 3)
+      .collect();
+    let mut decrypted_data: Vec<u8> = Vec::with_capacity(annex_b.len());
+    let mut copied_end = 0usize;
+    for (index, nal_unit_start) in nal_unit_starts.iter().enumerate() {
+      let mut nal_unit_end = nal_unit_starts
+        .get(index + 1)
+        .map(|next_start| next_start - 3)
+        .unwrap_or(annex_b.len());
+      // The zero bytes before the next start code aren't part of the NAL unit
+      while nal_unit_end > *nal_unit_start && annex_b[nal_unit_end - 1] == 0x00 {
+        nal_unit_end -= 1;
+      }
+      decrypted_data.extend_from_slice(&annex_b[copied_end..*nal_unit_start]);
+      decrypted_data.append(&mut self.decrypt_nal_unit(&annex_b[*nal_unit_start..nal_unit_end]));
+      copied_end = nal_unit_end;
+    }
+    decrypted_data.extend_from_slice(&annex_b[copied_end..]);
+    decrypted_data
+  }
+
+  // Only slices (non-IDR and IDR) longer than 48 bytes are encrypted. The emulation prevention bytes were added over
+  // the encrypted NAL unit, so they come out before decrypting, which leaves the NAL unit as it was before encryption.
+  // Apple MPEG-2 Stream Encryption Format for HLS; 2.2.2
+  fn decrypt_nal_unit(&self, nal_unit: &[u8]) -> Vec<u8> {
+    let nal_unit_type = nal_unit.first().map(|byte| byte & 0x1F).unwrap_or(0);
+    if !(nal_unit_type == 1 || nal_unit_type == 5) || nal_unit.len() <= AVC_MAX_CLEAR_NAL_UNIT_SIZE {
+      return nal_unit.to_vec();
+    }
+    let mut nal_unit = nal_unit_to_rbsp(nal_unit);
+    let mut iv = self.iv;
+    let mut position = AVC_CLEAR_LEADER_SIZE;
+    while position + AES_BLOCK_SIZE < nal_unit.len() {
+      iv = self.aes.decrypt_cbc_blocks(&iv, &mut nal_unit[position..(position + AES_BLOCK_SIZE)]);
+      position += AVC_PATTERN_SIZE;
+    }
+    nal_unit
+  }
+
+  // After the ADTS header and a clear leader, every whole block of the frame is encrypted. The rest of the data is
+  // left as is from the first thing that isn't an ADTS frame.
+  // Apple MPEG-2 Stream Encryption Format for HLS; 2.2.3
+  fn decrypt_adts(&self, data: &[u8]) -> Vec<u8> {
+    let mut decrypted_data = data.to_vec();
+    let mut index = 0usize;
+    while index + ADTS_HEADER_SIZE <= decrypted_data.len() {
+      let header = &decrypted_data[index..(index + ADTS_HEADER_SIZE)];
+      if header[0] != 0xFF || (header[1] & 0xF0) != 0xF0 {
+        break;
+      }
+      let protection_absent = (header[1] & 0x1) != 0;
+      let header_size = if protection_absent { ADTS_HEADER_SIZE } else { ADTS_HEADER_SIZE + ADTS_CRC_SIZE };
+      let frame_length = (((header[3] & 0x3) as usize) << 11) | ((header[4] as usize) << 3) | ((header[5] as usize) >> 5);
+      let frame_end = index + frame_length;
+      if frame_length < header_size || frame_end > decrypted_data.len() {
+        break;
+      }
+      let encrypted_start = index + header_size + AAC_CLEAR_LEADER_SIZE;
+      if encrypted_start < frame_end {
+        self.aes.decrypt_cbc_blocks(&self.iv, &mut decrypted_data[encrypted_start..frame_end]);
+      }
+      index = frame_end;
+    }
+    decrypted_data
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::container::transport_stream::test_util;
+
+  static KEY: SampleAESKey = SampleAESKey {
+    key: [0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6, 0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F, 0x3C],
+    iv: [0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F],
+  };
+
+  // CBC encryption of the blocks at the positions, chained from one to the next
+  fn encrypt_blocks(data: &mut [u8], positions: &[usize]) {
+    let mut blocks: Vec<u8> = positions.iter().flat_map(|position|data[*position..(position + 16)].to_vec()).collect();
+    test_util::encrypt_cbc(&KEY.key, &KEY.iv, &mut blocks);
+    for (position, block) in positions.iter().zip(blocks.chunks_exact(16)) {
+      data[*position..(position + 16)].copy_from_slice(block);
+    }
+  }
+
+  fn add_emulation_prevention_bytes(data: &[u8]) -> Vec<u8> {
+    let mut escaped_data: Vec<u8> = vec![];
+    let mut zero_count = 0usize;
+    for byte in data {
+      if zero_count >= 2 && *byte <= 0x03 {
+        escaped_data.push(0x03);
+        zero_count = 0;
+      }
+      zero_count = if *byte == 0x00 { zero_count + 1 } else { 0 };
+      escaped_data.push(*byte);
+    }
+    escaped_data
+  }
+
+  fn create_pes(stream_id: u8, pes_packet_length: bool, payload: &[u8]) -> Vec<u8> {
+    let mut pes = test_util::create_pes(stream_id, Some(0), payload);
+    if !pes_packet_length {
+      pes[4..6].copy_from_slice(&[0x00, 0x00]);
+    }
+    pes
+  }
+
+  #[test]
+  fn test_decrypt_avc_pes() {
+    // IDR slice with an emulation prevention byte in the clear leader
+    let mut idr_slice: Vec<u8> = (0..250u32).map(|index| (index * 7 + 1) as u8).collect();
+    idr_slice[0] = 0x65;
+    idr_slice[10..14].copy_from_slice(&[0x00, 0x00, 0x03, 0x01]);
+    let sps = vec![0x67, 0x42, 0x00, 0x00, 0x03, 0x01];
+    // Short slice that is left clear
+    let short_slice = vec![0x41; 40];
+
+    let mut encrypted_slice = idr_slice.clone();
+    encrypt_blocks(&mut encrypted_slice, &[32, 192]);
+    let encrypted_slice = add_emulation_prevention_bytes(&encrypted_slice);
+    let annex_b = |slice: &[u8]| [
+      vec![0x00, 0x00, 0x00, 0x01], sps.clone(),
+      vec![0x00, 0x00, 0x01], slice.to_vec(),
+      vec![0x00, 0x00, 0x00, 0x01], short_slice.clone(),
+    ].concat();
+
+    let decrypter = SampleAESDecrypter::create(&KEY);
+    let decrypted_pes = decrypter
+      .decrypt_pes(&create_pes(0xE0, false, &annex_b(&encrypted_slice)), ElementaryStreamType::H_264)
+      .unwrap();
+    assert_eq!(decrypted_pes, create_pes(0xE0, false, &annex_b(&idr_slice)));
+  }
+
+  #[test]
+  fn test_decrypt_adts_pes() {
+    // Two ADTS frames with 50 bytes of raw data, the second one with a CRC
+    let create_frame = |crc: bool| {
+      let header_size = if crc { 9 } else { 7 };
+      let frame_length = header_size + 50;
+      let mut frame = vec![
+        0xFF, if crc { 0xF0 } else { 0xF1 }, 0x4C, 0x80,
+        (frame_length >> 3) as u8, (((frame_length & 0x7) << 5) | 0x1F) as u8, 0xFC,
+      ];
+      if crc {
+        frame.append(&mut vec![0x12, 0x34]);
+      }
+      frame.extend((0..50u8).map(|index| index.wrapping_mul(3)));
+      frame
+    };
+    let clear_frames = [create_frame(false), create_frame(true)];
+    let mut encrypted_frames = clear_frames.clone();
+    // 16 clear bytes, 2 encrypted blocks and 2 clear bytes after the header
+    encrypt_blocks(&mut encrypted_frames[0], &[7 + 16, 7 + 32]);
+    encrypt_blocks(&mut encrypted_frames[1], &[9 + 16, 9 + 32]);
+    assert_ne!(encrypted_frames, clear_frames);
+
+    let decrypter = SampleAESDecrypter::create(&KEY);
+    let decrypted_pes = decrypter
+      .decrypt_pes(&create_pes(0xC0, true, &encrypted_frames.concat()), ElementaryStreamType::AAC)
+      .unwrap();
+    assert_eq!(decrypted_pes, create_pes(0xC0, true, &clear_frames.concat()));
+  }
+}
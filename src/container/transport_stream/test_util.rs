@@ -0,0 +1,48 @@
+use crate::util::aes::AES128;
+
+// Builders shared by the tests of the transport stream parsing and remuxing
+
+/// PES packet of the stream id holding the payload, with a PTS when one is given
+pub fn create_pes(stream_id: u8, pts: Option<u64>, payload: &[u8]) -> Vec<u8> {
+  let header_length = if pts.is_some() { 5 } else { 0 };
+  let pes_packet_length = 3 + header_length + payload.len();
+  let mut pes = vec![
+    0x00, 0x00, 0x01, stream_id,
+    (pes_packet_length >> 8) as u8, (pes_packet_length & 0xFF) as u8,
+    0x80, if pts.is_some() { 0x80 } else { 0x00 }, header_length as u8,
+  ];
+  if let Some(pts) = pts {
+    pes.append(&mut vec![
+      0x21 | (((pts >> 30) & 0x7) << 1) as u8,
+      ((pts >> 22) & 0xFF) as u8,
+      (((pts >> 15) & 0x7F) << 1) as u8 | 0x1,
+      ((pts >> 7) & 0xFF) as u8,
+      ((pts & 0x7F) << 1) as u8 | 0x1,
+    ]);
+  }
+  pes.extend_from_slice(payload);
+  pes
+}
+
+/// Annex B byte stream of the NAL units, each behind a 4 byte start code
+pub fn create_annex_b(nal_units: &[Vec<u8>]) -> Vec<u8> {
+  nal_units
+    .iter()
+    .flat_map(|nal_unit|[vec![0x00, 0x00, 0x00, 0x01], nal_unit.to_owned()].concat())
+    .collect()
+}
+
+/// AES-128 CBC encryption of the whole 16 byte blocks of the data
+pub fn encrypt_cbc(key: &[u8; 16], iv: &[u8; 16], data: &mut [u8]) {
+  let aes = AES128::create(key);
+  let mut previous_block = *iv;
+  for chunk in data.chunks_exact_mut(16) {
+    let mut block = [0u8; 16];
+    for (index, byte) in block.iter_mut().enumerate() {
+      *byte = chunk[index] ^ previous_block[index];
+    }
+    aes.encrypt_block(&mut block);
+    chunk.copy_from_slice(&block);
+    previous_block = block;
+  }
+}
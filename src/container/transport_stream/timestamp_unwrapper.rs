@@ -0,0 +1,87 @@
+// PTS/DTS are 33 bit values of a 90 kHz clock, so they wrap roughly every 26.5 hours
+static TIMESTAMP_ROLLOVER: i64 = 1 << 33;
+static TIMESTAMP_MASK: u64 = (1 << 33) - 1;
+
+/// Converts the 33 bit PTS/DTS of a stream into a monotonic 64 bit timeline. Each timestamp is placed on the side of
+/// the wrap closest to the previous timestamp of the stream, so one instance should be used per elementary stream.
+/// The instances of the streams of a program should be seeded with the same reference, so they share a timeline.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TimestampUnwrapper {
+  last_timestamp: Option<u64>,
+}
+
+impl TimestampUnwrapper {
+  pub fn create() -> TimestampUnwrapper {
+    TimestampUnwrapper::default()
+  }
+
+  /// Place the first timestamp of the stream on the side of the wrap closest to the reference (ex. the first timestamp
+  /// of the program) instead of taking it as is. Does nothing once a timestamp has been unwrapped.
+  pub fn seed(&mut self, reference: u64) {
+    if self.last_timestamp.is_none() {
+      self.last_timestamp = Some(reference & TIMESTAMP_MASK);
+    }
+  }
+
+  pub fn unwrap_timestamp(&mut self, timestamp: u64) -> u64 {
+    let timestamp = timestamp & TIMESTAMP_MASK;
+    let unwrapped_timestamp = match self.last_timestamp {
+      None => timestamp,
+      Some(last_timestamp) => {
+        let last_timestamp = last_timestamp as i64;
+        let candidate = (last_timestamp & !(TIMESTAMP_MASK as i64)) | timestamp as i64;
+        // Pick whichever of the candidate, the next wrap or the previous wrap is closest to the last timestamp
+        [candidate - TIMESTAMP_ROLLOVER, candidate, candidate + TIMESTAMP_ROLLOVER]
+          .iter()
+          .filter(|value|**value >= 0)
+          .min_by_key(|value|(**value - last_timestamp).abs())
+          .map(|value|*value as u64)
+          .unwrap_or(timestamp)
+      }
+    };
+    self.last_timestamp = Some(unwrapped_timestamp);
+    unwrapped_timestamp
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_unwrap_timestamp_across_wrap() {
+    let mut unwrapper = TimestampUnwrapper::create();
+    let max = TIMESTAMP_MASK;
+    assert_eq!(unwrapper.unwrap_timestamp(max - 3000), max - 3000);
+    assert_eq!(unwrapper.unwrap_timestamp(max - 1500), max - 1500);
+    // Wrapped around
+    assert_eq!(unwrapper.unwrap_timestamp(1499), max + 1500);
+    assert_eq!(unwrapper.unwrap_timestamp(3000), max + 3001);
+    // Timestamp slightly behind the last one (ex. PTS of a B frame) is placed before the wrap
+    assert_eq!(unwrapper.unwrap_timestamp(max - 100), max - 100);
+    assert_eq!(unwrapper.unwrap_timestamp(4500), max + 4501);
+  }
+
+  #[test]
+  fn test_unwrap_timestamp_seed() {
+    // The first timestamp of the program was right before the wrap, this stream starts right after it
+    let mut unwrapper = TimestampUnwrapper::create();
+    unwrapper.seed(TIMESTAMP_MASK - 3000);
+    assert_eq!(unwrapper.unwrap_timestamp(1500), TIMESTAMP_MASK + 1501);
+    // Seeding again doesn't change the timeline
+    unwrapper.seed(0);
+    assert_eq!(unwrapper.unwrap_timestamp(3000), TIMESTAMP_MASK + 3001);
+
+    let mut unwrapper = TimestampUnwrapper::create();
+    unwrapper.seed(9000);
+    assert_eq!(unwrapper.unwrap_timestamp(6000), 6000);
+  }
+
+  #[test]
+  fn test_unwrap_timestamp_never_negative() {
+    let mut unwrapper = TimestampUnwrapper::create();
+    assert_eq!(unwrapper.unwrap_timestamp(100), 100);
+    // Closest would be before 0, so it stays on the current timeline
+    assert_eq!(unwrapper.unwrap_timestamp(TIMESTAMP_MASK - 100), TIMESTAMP_MASK - 100);
+  }
+}
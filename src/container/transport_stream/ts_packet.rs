@@ -1,18 +1,21 @@
-use crate::error::{CustomError};
+use crate::error::{construct_error, CustomError};
+use crate::error::error_code::{MajorCode, TransportStreamMinorCode};
 use crate::util;
+use super::adaptation_field::AdaptationField;
 
 static TS_PACKET_SIZE: usize = 188;
 
 
 #[derive(Debug, Eq)]
 pub struct TransportPacket<'a> {
-  transport_error_indicator: bool,
+  pub transport_error_indicator: bool,
   pub payload_unit_start_indicator: bool,
   transport_priority: bool,
   pub pid: u16,                         // 13 bit
   transport_scrambling_control: u8,     // 2 bit
   pub adaptation_field_control: u8,     // 2 bit
-  continuity_counter: u8,               // 4 bit
+  pub continuity_counter: u8,           // 4 bit
+  pub adaptation_field: Option<AdaptationField<'a>>,
   pub data: &'a [u8]
 }
 
@@ -27,6 +30,31 @@ impl<'a> TransportPacket<'a> {
     TransportPacket::parse_transport_packet(ts)
   }
 
+  /// PID out of the fixed 4 byte header, for a packet that can't be parsed as a whole (ex. bad adaptation field)
+  pub fn read_pid(ts: &[u8]) -> u16 {
+    util::get_u16(ts, 1).map(|indicator_data|indicator_data & 0x1FFF).unwrap_or(0)
+  }
+
+  pub fn get_discontinuity_indicator(&self) -> bool {
+    self.adaptation_field
+      .as_ref()
+      .map(|adaptation_field|adaptation_field.discontinuity_indicator)
+      .unwrap_or(false)
+  }
+
+  /// Random access point (ex. start of a key frame) per the adaptation field. Can be used as a key frame hint.
+  pub fn get_random_access_indicator(&self) -> bool {
+    self.adaptation_field
+      .as_ref()
+      .map(|adaptation_field|adaptation_field.random_access_indicator)
+      .unwrap_or(false)
+  }
+
+  /// Whether the packet carries a payload (adaptation_field_control of '01' or '11')
+  pub fn has_payload(&self) -> bool {
+    (self.adaptation_field_control & 0x1) != 0
+  }
+
   fn parse_transport_packet(ts: &[u8]) -> Result<TransportPacket, CustomError> {
     let mut start = 1usize;
 
@@ -43,13 +71,22 @@ impl<'a> TransportPacket<'a> {
     let continuity_counter = control_data & 0xF;
     start = start + 1;
 
-    let mut adaptation_field_length = 0usize;
+    let mut adaptation_field = None;
     if adaptation_field_control > 1 {
-      adaptation_field_length = util::get_u8(ts, start)? as usize;
-      start = start + 1;
+      let packet_end = usize::min(ts.len(), TS_PACKET_SIZE);
+      let parsed_adaptation_field = AdaptationField::parse(&ts[start..packet_end])?;
+      start = start + 1 + parsed_adaptation_field.adaptation_field_length as usize;
+      adaptation_field = Some(parsed_adaptation_field);
     }
 
-    start = start + adaptation_field_length;
+    if start > TS_PACKET_SIZE || start > ts.len() {
+      return Err(construct_error(
+        MajorCode::TRANSPORT_STREAM,
+        Box::new(TransportStreamMinorCode::PARSE_TS_ERROR),
+        "Transport packet is truncated".to_string(),
+        file!(),
+        line!()));
+    }
     if start == TS_PACKET_SIZE {
       // Only an adaptation field. Just return nothing
       return Ok(TransportPacket {
@@ -60,6 +97,7 @@ impl<'a> TransportPacket<'a> {
         transport_scrambling_control,
         adaptation_field_control,
         continuity_counter,
+        adaptation_field,
         data: &[]
       })
     }
@@ -72,7 +110,51 @@ impl<'a> TransportPacket<'a> {
       transport_scrambling_control,
       adaptation_field_control,
       continuity_counter,
+      adaptation_field,
       data: ts[start..TS_PACKET_SIZE].as_ref()
     })
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_transport_packet_with_adaptation_field() {
+    let mut ts = vec![
+      // sync_byte
+      0x47,
+      // payload_unit_start_indicator + PID
+      0x41, 0x00,
+      // adaptation_field_control ('11') + continuity_counter
+      0x37,
+      // adaptation_field_length
+      0x07,
+      // random_access_indicator + PCR_flag
+      0x50,
+      // PCR
+      0x00, 0x00, 0x00, 0x01, 0x7E, 0x00,
+    ];
+    ts.resize(TS_PACKET_SIZE, 0xAB);
+
+    let packet = TransportPacket::parse(&ts).unwrap();
+    assert_eq!(packet.pid, 0x100);
+    assert!(packet.payload_unit_start_indicator);
+    assert_eq!(packet.continuity_counter, 7);
+    assert!(packet.has_payload());
+    assert!(packet.get_random_access_indicator());
+    assert!(!packet.get_discontinuity_indicator());
+    let adaptation_field = packet.adaptation_field.as_ref().unwrap();
+    assert_eq!(adaptation_field.pcr.unwrap().base, 2);
+    assert_eq!(packet.data.len(), TS_PACKET_SIZE - 12);
+    assert!(packet.data.iter().all(|byte|*byte == 0xAB));
+  }
+
+  #[test]
+  fn test_parse_transport_packet_invalid_adaptation_field_length() {
+    let mut ts = vec![0x47, 0x01, 0x00, 0x30, 0xC0];
+    ts.resize(TS_PACKET_SIZE, 0xFF);
+    assert!(TransportPacket::parse(&ts).is_err());
+  }
+}
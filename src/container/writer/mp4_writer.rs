@@ -1,7 +1,14 @@
 use crate::{container::{isobmff::{boxes::{ftyp::FTYPBuilder, hdlr::HDLRBuilder, mdat::MDATBuilder, mdhd::MDHDBuilder, mdia::MDIABuilder, minf::MINFBuilder, moof::MOOFBuilder, moov::MOOVBuilder, mvex::MVEXBuilder, mvhd::MVHDBuilder, stbl::STBLBuilder, stsd::STSDBuilder, tfdt::TFDTBuilder, tfhd::TFHDBuilder, tkhd::TKHDBuilder, traf::TRAFBuilder, trak::TRAKBuilder, trex::TREXBuilder, trun::TRUNBuilder, vmhd::VMHDBuilder, smhd::SMHDBuilder}}}, error::CustomError};
 use crate::container::isobmff::HandlerType;
+use crate::container::remux;
 use crate::error::{construct_error, error_code::{MajorCode, TransportStreamMinorCode}};
 use crate::container::isobmff::BoxBuilder;
+use std::ops::Range;
+
+/// Longest stretch of one track in the mdat of a muxed media segment before the samples of the other tracks
+const INTERLEAVE_DURATION_MS: u64 = 500;
+// sample_is_non_sync_sample of the sample flags
+const NON_SYNC_SAMPLE_FLAG: u32 = 0x00010000;
 
 #[derive(Clone)]
 pub struct SampleInfo {
@@ -12,16 +19,19 @@ pub struct SampleInfo {
   pub data: Vec<u8>,
 }
 
+#[derive(Clone)]
 pub struct Mp4Writer{
   samples: Vec<SampleInfo>,
   width: usize,
   height: usize,
   timescale: u32,
   track_id: usize,
+  sequence_number: u32,
   trun_version: u8,
   is_all_same_timestamps: bool,
   default_sample_duration: Option<u32>,
-  handler_type: Option<HandlerType>
+  handler_type: Option<HandlerType>,
+  language: String,
 }
 
 impl Mp4Writer {
@@ -35,8 +45,10 @@ impl Mp4Writer {
       is_all_same_timestamps: true,
       default_sample_duration: None,
       track_id: 1,
+      sequence_number: 1,
       samples: vec![],
-      handler_type: None
+      handler_type: None,
+      language: "und".to_string(),
     }
   }
 }
@@ -68,6 +80,12 @@ impl Mp4Writer {
     self
   }
 
+  /// mfhd sequence number of the media segment. Should increase with every media segment of the track.
+  pub fn sequence_number(mut self, sequence_number: u32) -> Mp4Writer {
+    self.sequence_number = sequence_number;
+    self
+  }
+
   pub fn default_sample_duration(mut self, default_sample_duration: u32) -> Mp4Writer {
     self.default_sample_duration = Some(default_sample_duration);
     self
@@ -78,6 +96,11 @@ impl Mp4Writer {
     self
   }
 
+  pub fn language(mut self, language: &str) -> Mp4Writer {
+    self.language = language.to_string();
+    self
+  }
+
   pub fn trun_version(mut self, version: u8) -> Mp4Writer {
     self.trun_version = version;
     self
@@ -89,6 +112,156 @@ impl Mp4Writer {
   }
 
   pub fn build_init_segment(self, sample_entry: Vec<u8>) -> Result<Vec<u8>, CustomError> {
+    Mp4Writer::build_muxed_init_segment(vec![(self, sample_entry)])
+  }
+
+  /// Init segment with a trak and a trex for every track (writer and its sample entry). The movie timescale is the
+  /// timescale of the first track.
+  pub fn build_muxed_init_segment(tracks: Vec<(Mp4Writer, Vec<u8>)>) -> Result<Vec<u8>, CustomError> {
+    let timescale = tracks
+      .first()
+      .map(|(writer, _)|writer.timescale)
+      .ok_or_else(||remux::generate_error(String::from("No tracks available to build the init segment")))?;
+    let mut moov = MOOVBuilder::create_builder()
+      .mvhd(
+        MVHDBuilder::create_builder()
+          .timescale(timescale)
+      );
+    let mut mvex = MVEXBuilder::create_builder();
+    for (writer, sample_entry) in tracks {
+      moov = moov.trak(writer.create_trak_builder(sample_entry)?);
+      // Every traf sets its own defaults in the tfhd and the size of every sample in the trun, so the trex defaults
+      // are never used
+      mvex = mvex.trex(
+        TREXBuilder::create_builder()
+          .track_id(writer.track_id)
+          .default_sample_size(0)
+          .default_sample_duration(0)
+          .default_sample_flags(0)
+      );
+    }
+
+    Ok([
+      FTYPBuilder::create_builder().build(),
+      moov
+        .mvex(mvex)
+        .build()?
+    ].concat())
+  }
+
+  pub fn build_media_segment(self) -> Result<Vec<u8>, CustomError> {
+    let sequence_number = self.sequence_number;
+    Mp4Writer::build_muxed_media_segment(vec![self], sequence_number)
+  }
+
+  /// Media segment with a traf for every track in one moof, followed by one mdat. The samples of every track are cut
+  /// into runs of up to INTERLEAVE_DURATION_MS, which go in the mdat in decode order. Each run has its own trun.
+  /// Tracks without samples are left out.
+  pub fn build_muxed_media_segment(tracks: Vec<Mp4Writer>, sequence_number: u32) -> Result<Vec<u8>, CustomError> {
+    let tracks: Vec<Mp4Writer> = tracks
+      .into_iter()
+      .filter(|writer|!writer.samples.is_empty())
+      .collect();
+    if tracks.is_empty() {
+      return Err(remux::generate_error(String::from("No samples available to build the media segment")));
+    }
+    let runs = Mp4Writer::interleave_runs(&tracks);
+    let mut track_runs: Vec<Vec<Range<usize>>> = vec![vec![]; tracks.len()];
+    let mut media_data: Vec<u8> = vec![];
+    for (track_index, run) in runs.iter() {
+      track_runs[*track_index].push(run.clone());
+      media_data.append(&mut MDATBuilder::merge_samples(tracks[*track_index].samples[run.clone()].to_vec()));
+    }
+    let mut moof = MOOFBuilder::create_builder()
+      .sequence_number(sequence_number)
+      .run_order(runs.iter().map(|(track_index, _)|*track_index).collect());
+    for (writer, runs) in tracks.iter().zip(track_runs) {
+      moof = moof.traf(writer.create_traf_builder(&runs));
+    }
+
+    Ok([
+      moof.build()?,
+      MDATBuilder::create_builder()
+        .media_data(media_data)
+        .build()?
+    ].concat())
+  }
+
+  /// Muxed media segments (moof and mdat) cut on the sync samples of the reference track once fragment_duration
+  /// (90 kHz) has passed. The other tracks are cut at the same decode time. The mfhd sequence numbers count up from
+  /// sequence_number.
+  pub fn build_fragmented_muxed_media_segments(
+    tracks: Vec<Mp4Writer>,
+    reference_track: usize,
+    fragment_duration: u64,
+    sequence_number: u32,
+  ) -> Result<Vec<u8>, CustomError> {
+    // Decode times (in the timescale of the reference track) the fragments after the first one start at
+    let mut cuts: Vec<u64> = vec![];
+    let reference_timescale = tracks.get(reference_track).map_or(1, |writer|writer.timescale.max(1)) as u64;
+    if let Some(reference) = tracks.get(reference_track) {
+      let mut fragment_start = reference.samples.first().map(|sample|sample.dts).unwrap_or_default();
+      for sample in reference.samples.iter() {
+        let is_sync_sample = sample.sample_flags.unwrap_or_default() & NON_SYNC_SAMPLE_FLAG == 0;
+        if is_sync_sample && sample.dts.saturating_sub(fragment_start) * 90000 / reference_timescale >= fragment_duration {
+          cuts.push(sample.dts);
+          fragment_start = sample.dts;
+        }
+      }
+    }
+
+    let mut fragments: Vec<Vec<Mp4Writer>> = vec![vec![]; cuts.len() + 1];
+    for mut writer in tracks {
+      let timescale = writer.timescale.max(1) as u64;
+      let mut fragment_samples: Vec<Vec<SampleInfo>> = vec![vec![]; cuts.len() + 1];
+      for sample in std::mem::take(&mut writer.samples) {
+        let fragment_index = cuts
+          .iter()
+          .take_while(|cut|sample.dts as u128 * reference_timescale as u128 >= **cut as u128 * timescale as u128)
+          .count();
+        fragment_samples[fragment_index].push(sample);
+      }
+      for (fragment, samples) in fragments.iter_mut().zip(fragment_samples) {
+        fragment.push(writer.clone().samples(samples));
+      }
+    }
+
+    let mut media_segments: Vec<u8> = vec![];
+    for (index, fragment) in fragments.into_iter().enumerate() {
+      media_segments.append(&mut Mp4Writer::build_muxed_media_segment(fragment, sequence_number + index as u32)?);
+    }
+    Ok(media_segments)
+  }
+
+  /// Runs of samples of the tracks (track index and sample range) in the order they go in the mdat. A run covers up to
+  /// INTERLEAVE_DURATION_MS, and the runs are sorted by the decode time of their first sample. Runs that start at the
+  /// same time keep the order of the tracks.
+  fn interleave_runs(tracks: &[Mp4Writer]) -> Vec<(usize, Range<usize>)> {
+    // Track index, sample range and the decode time of the first sample in the timescale of the track
+    let mut runs: Vec<(usize, Range<usize>, u64, u64)> = vec![];
+    for (track_index, writer) in tracks.iter().enumerate() {
+      let timescale = writer.timescale.max(1) as u64;
+      let max_duration = timescale * INTERLEAVE_DURATION_MS / 1000;
+      let mut run_start = 0usize;
+      for (index, sample) in writer.samples.iter().enumerate() {
+        let run_start_dts = writer.samples[run_start].dts;
+        if index > run_start && sample.dts.saturating_sub(run_start_dts) >= max_duration {
+          runs.push((track_index, run_start..index, run_start_dts, timescale));
+          run_start = index;
+        }
+      }
+      runs.push((track_index, run_start..writer.samples.len(), writer.samples[run_start].dts, timescale));
+    }
+    runs.sort_by(|(_, _, dts, timescale), (_, _, other_dts, other_timescale)| {
+      (*dts as u128 * *other_timescale as u128).cmp(&(*other_dts as u128 * *timescale as u128))
+    });
+    runs
+      .into_iter()
+      .map(|(track_index, run, _, _)|(track_index, run))
+      .collect()
+  }
+
+  fn create_trak_builder(&self, sample_entry: Vec<u8>) -> Result<TRAKBuilder, CustomError> {
     let handler_type = self.handler_type.ok_or_else(||construct_error(
       MajorCode::REMUX,
       Box::new(TransportStreamMinorCode::PARSE_TS_ERROR),
@@ -101,93 +274,265 @@ impl Mp4Writer {
       _ => Box::new(VMHDBuilder::create_builder())
     };
 
-    Ok([
-      FTYPBuilder::create_builder().build(),
-      MOOVBuilder::create_builder()
-        .mvhd(
-          MVHDBuilder::create_builder()
-            .timescale(self.timescale)
+    Ok(
+      TRAKBuilder::create_builder()
+        .tkhd(
+          TKHDBuilder::create_builder()
+            .track_id(self.track_id) 
+            .width(self.width)
+            .height(self.height)
         )
-        .trak(
-          TRAKBuilder::create_builder()
-            .tkhd(
-              TKHDBuilder::create_builder()
-                .track_id(self.track_id) 
-                .width(self.width)
-                .height(self.height)
+        .mdia(
+          MDIABuilder::create_builder()
+            .mdhd(
+              MDHDBuilder::create_builder()
+                .timescale(self.timescale)
+                .language(&self.language)
             )
-            .mdia(
-              MDIABuilder::create_builder()
-                .mdhd(
-                  MDHDBuilder::create_builder()
-                    .timescale(self.timescale)
-                )
-                .hdlr(
-                  HDLRBuilder::create_builder()
-                    .handler_type(handler_type) //CHANGE THIS
-                )
-                .minf(
-                  MINFBuilder::create_builder()
-                    .media_header(media_header)
-                    .stbl(
-                      STBLBuilder::create_builder()
-                        .stsd(
-                          STSDBuilder::create_builder()
-                            .sample_entry(
-                              sample_entry
-                          )
-                        )
+            .hdlr(
+              HDLRBuilder::create_builder()
+                .handler_type(handler_type) //CHANGE THIS
+            )
+            .minf(
+              MINFBuilder::create_builder()
+                .media_header(media_header)
+                .stbl(
+                  STBLBuilder::create_builder()
+                    .stsd(
+                      STSDBuilder::create_builder()
+                        .sample_entry(
+                          sample_entry
+                      )
                     )
                 )
             )
         )
-        .mvex(
-          MVEXBuilder::create_builder()
-            .trex(
-              TREXBuilder::create_builder()
-                .track_id(self.track_id)
-                .default_sample_size(0)// CHANGE THIS
-                .default_sample_duration(0) // CHANGE THIS
-                .default_sample_flags(0) // CHANGE THIS
-            )
-        )
-        .build()?
-    ].concat())
+    )
   }
 
-  pub fn build_media_segment(self) -> Result<Vec<u8>, CustomError> {
+  /// traf with a trun for every run of samples
+  fn create_traf_builder(&self, runs: &[Range<usize>]) -> TRAFBuilder {
+    let default_sample_flags = self.get_default_sample_flags();
+    let mut traf = TRAFBuilder::create_builder()
+      .tfhd(
+        TFHDBuilder::create_builder()
+          .sample_duration(self.default_sample_duration)
+          .sample_description_index(1)
+          .sample_flags(default_sample_flags)
+          .track_id(self.track_id) 
+      )
+      .tfdt(
+        TFDTBuilder::create_builder()
+          .base_media_decode_time(self.samples[0].dts as usize)
+      );
+    for run in runs {
+      let samples = &self.samples[run.clone()];
+      let (first_sample_flags, sample_flags_present) = Mp4Writer::get_run_sample_flags(samples, default_sample_flags);
+      let mut trun = TRUNBuilder::create_builder()
+        .version(self.trun_version as usize)
+        .sample_composition_time_offsets_present(!self.is_all_same_timestamps)
+        .sample_flags_present(sample_flags_present)
+        .samples(samples.to_vec());
+      if let Some(first_sample_flags) = first_sample_flags {
+        trun = trun.first_sample_flags(first_sample_flags as usize);
+      }
+      traf = traf.trun(trun);
+    }
+    traf
+  }
 
+  /// The tfhd default sample flags. The default comes from the second sample, so a segment that starts with a key
+  /// frame followed by non key frames only needs the first sample flags. Samples without flags are written as 0
+  /// (nothing known about them).
+  fn get_default_sample_flags(&self) -> u32 {
+    self.samples
+      .get(1)
+      .or(self.samples.first())
+      .and_then(|sample|sample.sample_flags)
+      .unwrap_or_default()
+  }
 
-    Ok([
-      MOOFBuilder::create_builder()
-        .traf(
-          TRAFBuilder::create_builder()
-            .tfhd(
-              TFHDBuilder::create_builder()
-                .sample_duration(self.default_sample_duration)
-                .sample_description_index(1)
-                .sample_flags(0x01010000) // CHANGE THIS
-                .track_id(self.track_id) 
-            )
-            .tfdt(
-              TFDTBuilder::create_builder()
-                .base_media_decode_time(self.samples[0].dts as usize)
-            )
-            .trun(
-              TRUNBuilder::create_builder()
-                .version(self.trun_version as usize)
-                // .flags(0x0205) // CHANGE THIS
-                .sample_composition_time_offsets_present(!self.is_all_same_timestamps)
-                .first_sample_flags(0x2000000) // CHANGE THIS
-                .samples(self.samples.clone())
-            )
-        )
-        .build()?,
-      MDATBuilder::create_builder()
-        .media_data(MDATBuilder::merge_samples(self.samples))
-        .build()?
-    ].concat())
+  /// The trun first sample flags of a run and whether the trun needs the flags of every sample
+  fn get_run_sample_flags(samples: &[SampleInfo], default_sample_flags: u32) -> (Option<u32>, bool) {
+    let sample_flags: Vec<u32> = samples
+      .iter()
+      .map(|sample|sample.sample_flags.unwrap_or_default())
+      .collect();
+    // Only samples after the first one differ from the default. The first sample flags and the per sample flags can't
+    // be used together.
+    if sample_flags.iter().skip(1).any(|flags|*flags != default_sample_flags) {
+      return (None, true);
+    }
+    if sample_flags[0] != default_sample_flags {
+      return (Some(sample_flags[0]), false);
+    }
+    (None, false)
   }
  }
 
  // ffmpeg -i ~/Desktop/seg_2_complete_v.ts -video_track_timescale 90000 ~/Desktop/seg_2_complete_v.mp4
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::container::isobmff::boxes::{iso_box::find_box, tfhd::TFHD, trun::TRUN, SYNC_SAMPLE_FLAGS, NON_SYNC_SAMPLE_FLAGS};
+  use crate::util;
+
+  fn create_samples(sample_flags: &[u32]) -> Vec<SampleInfo> {
+    sample_flags
+      .iter()
+      .enumerate()
+      .map(|(index, sample_flags)| SampleInfo {
+        dts: index as u64 * 3000,
+        pts: index as u64 * 3000,
+        sample_flags: Some(*sample_flags),
+        sample_duration: Some(3000),
+        data: vec![0; 8],
+      })
+      .collect()
+  }
+
+  fn build_media_segment(sample_flags: &[u32]) -> Vec<u8> {
+    Mp4Writer::create_mp4_writer()
+      .timescale(90000)
+      .default_sample_duration(3000)
+      .samples(create_samples(sample_flags))
+      .build_media_segment()
+      .unwrap()
+  }
+
+  #[test]
+  fn test_media_segment_sample_flags() {
+    // Key frame followed by non key frames only needs the first sample flags
+    let media_segment = build_media_segment(&[SYNC_SAMPLE_FLAGS, NON_SYNC_SAMPLE_FLAGS, NON_SYNC_SAMPLE_FLAGS]);
+    let trun = TRUN::parse(&media_segment).unwrap();
+    assert_eq!(trun.first_sample_flags, Some(SYNC_SAMPLE_FLAGS));
+    assert_eq!(TFHD::parse(&media_segment).unwrap().get_default_sample_flags(), Some(NON_SYNC_SAMPLE_FLAGS));
+
+    // Every audio sample is a sync sample, so the default covers all of them
+    let media_segment = build_media_segment(&[SYNC_SAMPLE_FLAGS, SYNC_SAMPLE_FLAGS]);
+    assert_eq!(TRUN::parse(&media_segment).unwrap().get_first_sample_flags(), None);
+    assert_eq!(TFHD::parse(&media_segment).unwrap().get_default_sample_flags(), Some(SYNC_SAMPLE_FLAGS));
+
+    // A key frame in the middle of the segment needs the flags of every sample
+    let media_segment = build_media_segment(&[NON_SYNC_SAMPLE_FLAGS, NON_SYNC_SAMPLE_FLAGS, SYNC_SAMPLE_FLAGS]);
+    let trun = TRUN::parse(&media_segment).unwrap();
+    assert_eq!(trun.first_sample_flags, None);
+    assert_eq!(trun.get_first_sample_flags(), Some(NON_SYNC_SAMPLE_FLAGS));
+  }
+
+  #[test]
+  fn test_muxed_init_segment() {
+    let video = Mp4Writer::create_mp4_writer()
+      .timescale(90000)
+      .handler(HandlerType::VIDE)
+      .track_id(1);
+    let audio = Mp4Writer::create_mp4_writer()
+      .timescale(48000)
+      .handler(HandlerType::SOUN)
+      .track_id(2);
+    let init_segment = Mp4Writer::build_muxed_init_segment(vec![(video, vec![]), (audio, vec![])]).unwrap();
+    let moov = find_box("moov", 0, &init_segment).unwrap();
+    let trak_offset = 8 + find_box("mvhd", 8, moov).unwrap().len();
+    let first_trak = find_box("trak", trak_offset, moov).unwrap();
+    assert!(find_box("trak", trak_offset + first_trak.len(), moov).is_some());
+    let mvex = find_box("mvex", 8, moov).unwrap();
+    // Two trex of 32 bytes
+    assert_eq!(mvex.len(), 8 + 32 * 2);
+    assert_eq!(util::get_u32(mvex, 20).unwrap(), 1);
+    assert_eq!(util::get_u32(mvex, 52).unwrap(), 2);
+  }
+
+  #[test]
+  fn test_muxed_media_segment() {
+    let video = Mp4Writer::create_mp4_writer()
+      .track_id(1)
+      .timescale(90000)
+      .default_sample_duration(3000)
+      .samples(create_samples(&[SYNC_SAMPLE_FLAGS, NON_SYNC_SAMPLE_FLAGS, NON_SYNC_SAMPLE_FLAGS]));
+    let audio = Mp4Writer::create_mp4_writer()
+      .track_id(2)
+      .timescale(48000)
+      .default_sample_duration(1024)
+      .samples(vec![SampleInfo {
+        dts: 0,
+        pts: 0,
+        sample_flags: Some(SYNC_SAMPLE_FLAGS),
+        sample_duration: None,
+        data: vec![0xAA; 5],
+      }]);
+    // Tracks without samples are left out
+    let empty = Mp4Writer::create_mp4_writer().track_id(3);
+    let media_segment = Mp4Writer::build_muxed_media_segment(vec![video, audio, empty], 4).unwrap();
+
+    let moof = find_box("moof", 0, &media_segment).unwrap();
+    // mfhd is 16 bytes, so the first traf starts at 24
+    let first_traf = find_box("traf", 24, moof).unwrap();
+    let second_traf = find_box("traf", 24 + first_traf.len(), moof).unwrap();
+    assert_eq!(24 + first_traf.len() + second_traf.len(), moof.len());
+    // track_ID of the tfhd
+    assert_eq!(util::get_u32(first_traf, 20).unwrap(), 1);
+    assert_eq!(util::get_u32(second_traf, 20).unwrap(), 2);
+
+    // The samples of the second track come after the 24 bytes of the first one in the mdat
+    let first_trun = TRUN::parse_trun(find_box("trun", 8, first_traf).unwrap()).unwrap();
+    let second_trun = TRUN::parse_trun(find_box("trun", 8, second_traf).unwrap()).unwrap();
+    let mdat_data_start = moof.len() + 8;
+    assert_eq!(first_trun.get_data_offset(), Some(mdat_data_start as i32));
+    assert_eq!(second_trun.get_data_offset(), Some((mdat_data_start + 24) as i32));
+    assert_eq!(media_segment[(mdat_data_start + 24)..], [0xAA; 5]);
+
+    assert!(Mp4Writer::build_muxed_media_segment(vec![Mp4Writer::create_mp4_writer()], 1).is_err());
+  }
+
+  #[test]
+  fn test_fragmented_muxed_media_segments() {
+    // Key frames at 0 and 9000. The fragment duration passes at 6000, so the cut waits for the key frame.
+    let mut video_samples = create_samples(&[SYNC_SAMPLE_FLAGS, NON_SYNC_SAMPLE_FLAGS, NON_SYNC_SAMPLE_FLAGS, SYNC_SAMPLE_FLAGS, NON_SYNC_SAMPLE_FLAGS]);
+    video_samples[3].data = vec![0x11; 8];
+    let video = Mp4Writer::create_mp4_writer()
+      .track_id(1)
+      .timescale(90000)
+      .default_sample_duration(3000)
+      .samples(video_samples);
+    // 50 ms frames at 48 kHz. The third one starts with the second fragment (100 ms).
+    let audio_samples: Vec<SampleInfo> = (0..4u64)
+      .map(|index| SampleInfo {
+        dts: index * 2400,
+        pts: index * 2400,
+        sample_flags: Some(SYNC_SAMPLE_FLAGS),
+        sample_duration: Some(2400),
+        data: vec![0xAA; 5],
+      })
+      .collect();
+    let audio = Mp4Writer::create_mp4_writer()
+      .track_id(2)
+      .timescale(48000)
+      .default_sample_duration(2400)
+      .samples(audio_samples);
+    let media_segments = Mp4Writer::build_fragmented_muxed_media_segments(vec![audio, video], 1, 6000, 5).unwrap();
+
+    let first_moof = find_box("moof", 0, &media_segments).unwrap();
+    let first_mdat = find_box("mdat", first_moof.len(), &media_segments).unwrap();
+    let second_offset = first_moof.len() + first_mdat.len();
+    let second_moof = find_box("moof", second_offset, &media_segments).unwrap();
+    let second_mdat = find_box("mdat", second_offset + second_moof.len(), &media_segments).unwrap();
+    assert_eq!(second_offset + second_moof.len() + second_mdat.len(), media_segments.len());
+    // mfhd sequence numbers
+    assert_eq!(util::get_u32(first_moof, 20).unwrap(), 5);
+    assert_eq!(util::get_u32(second_moof, 20).unwrap(), 6);
+    // 2 audio frames and 3 pictures, then 2 audio frames and 2 pictures
+    assert_eq!(first_mdat.len(), 8 + 2 * 5 + 3 * 8);
+    assert_eq!(second_mdat.len(), 8 + 2 * 5 + 2 * 8);
+
+    let audio_traf = find_box("traf", 24, second_moof).unwrap();
+    let video_traf = find_box("traf", 24 + audio_traf.len(), second_moof).unwrap();
+    // base_media_decode_time of the tfdt
+    assert_eq!(util::get_u64(find_box("tfdt", 8, audio_traf).unwrap(), 12).unwrap(), 4800);
+    assert_eq!(util::get_u64(find_box("tfdt", 8, video_traf).unwrap(), 12).unwrap(), 9000);
+    let video_trun = TRUN::parse_trun(find_box("trun", 8, video_traf).unwrap()).unwrap();
+    assert_eq!(video_trun.first_sample_flags, Some(SYNC_SAMPLE_FLAGS));
+    let video_offset = video_trun.get_data_offset().unwrap() as usize;
+    assert_eq!(media_segments[(second_offset + video_offset)..(second_offset + video_offset + 8)], [0x11; 8]);
+  }
+}
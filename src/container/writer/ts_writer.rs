@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+
+use crate::container::transport_stream::elementary_stream_type::ElementaryStreamType;
+use crate::container::transport_stream::packet_format::{SYNC_BYTE, TS_PACKET_SIZE};
+use crate::container::remux;
+use crate::error::CustomError;
+use crate::util;
+
+static TS_HEADER_SIZE: usize = 4;
+static PAT_PID: u16 = 0;
+/// PID of the PMT of the program
+pub static PMT_PID: u16 = 0x1000;
+static PROGRAM_NUMBER: u16 = 1;
+static TRANSPORT_STREAM_ID: u16 = 1;
+static ISO_639_LANGUAGE_DESCRIPTOR_TAG: u8 = 0x0A;
+// The PCR runs this far (90 kHz) behind the DTS, which leaves the decoder time to buffer the first frames
+static PCR_DELAY: u64 = 63000;
+static MAX_TIMESTAMP: u64 = 0x1FFFFFFFF;
+
+struct TSStream {
+  pid: u16,
+  stream_type: ElementaryStreamType,
+  language: String,
+}
+
+/// Writes a single program transport stream. ITU-T H.222.0
+///
+/// Every elementary stream is added to the PMT, and the first video stream (the first stream when there is no video)
+/// carries the PCR. The continuity counter of every PID is kept across writes so a stream can be written a PES packet
+/// at a time.
+pub struct TSWriter {
+  streams: Vec<TSStream>,
+  continuity_counters: HashMap<u16, u8>,
+}
+
+impl TSWriter {
+
+  pub fn create_ts_writer() -> TSWriter {
+    TSWriter {
+      streams: vec![],
+      continuity_counters: HashMap::new(),
+    }
+  }
+}
+
+impl TSWriter {
+
+  /// Add an elementary stream to the program. A language other than "und" is signaled with an ISO 639 descriptor.
+  pub fn stream(mut self, pid: u16, stream_type: ElementaryStreamType, language: &str) -> TSWriter {
+    self.streams.push(TSStream {
+      pid,
+      stream_type,
+      language: language.to_string(),
+    });
+    self
+  }
+
+  pub fn get_pcr_pid(&self) -> Option<u16> {
+    self.streams
+      .iter()
+      .find(|stream|stream.stream_type.is_video())
+      .or_else(||self.streams.first())
+      .map(|stream|stream.pid)
+  }
+
+  /// PAT and PMT, which have to come before the PES packets for a demuxer to find the elementary streams
+  pub fn write_program_tables(&mut self) -> Vec<u8> {
+    let pat = self.build_pat_section();
+    let pmt = self.build_pmt_section();
+    [
+      self.write_section(PAT_PID, pat),
+      self.write_section(PMT_PID, pmt),
+    ].concat()
+  }
+
+  /// Write a PES packet with one access unit (or audio frame) over as many TS packets as needed. The first packet of
+  /// the PCR PID gets a PCR, and the last packet is padded with adaptation field stuffing. The random access indicator
+  /// marks a PES packet a decoder can start from.
+  pub fn write_pes(&mut self, pid: u16, pts: u64, dts: Option<u64>, data: &[u8], random_access: bool) -> Result<Vec<u8>, CustomError> {
+    let stream_type = self.streams
+      .iter()
+      .find(|stream|stream.pid == pid)
+      .map(|stream|stream.stream_type)
+      .ok_or_else(||remux::generate_error(format!("No elementary stream with pid {} in the TSWriter", pid)))?;
+    let pes = TSWriter::build_pes(stream_type, pts, dts, data);
+    let pcr = if self.get_pcr_pid() == Some(pid) {
+      Some(dts.unwrap_or(pts).saturating_sub(PCR_DELAY))
+    } else {
+      None
+    };
+
+    let mut packets: Vec<u8> = vec![];
+    let mut offset = 0usize;
+    while offset < pes.len() {
+      let is_first_packet = offset == 0;
+      // adaptation_field() without the adaptation_field_length
+      let mut adaptation_field: Option<Vec<u8>> = None;
+      if is_first_packet && (pcr.is_some() || random_access) {
+        let mut flags = 0u8;
+        if random_access {
+          flags |= 0x40;
+        }
+        let mut fields = vec![0u8];
+        if let Some(pcr) = pcr {
+          flags |= 0x10;
+          fields.append(&mut TSWriter::build_pcr(pcr));
+        }
+        fields[0] = flags;
+        adaptation_field = Some(fields);
+      }
+      let adaptation_field_size = adaptation_field.as_ref().map(|fields|1 + fields.len()).unwrap_or(0);
+      let payload_space = TS_PACKET_SIZE - TS_HEADER_SIZE - adaptation_field_size;
+      let remaining = pes.len() - offset;
+      if remaining < payload_space {
+        // Stuffing bytes fill the adaptation field so the PES packet ends with the TS packet
+        let mut stuffing_size = payload_space - remaining;
+        let fields = adaptation_field.get_or_insert_with(||{
+          stuffing_size -= 1;
+          vec![]
+        });
+        if fields.is_empty() && stuffing_size > 0 {
+          fields.push(0x00);
+          stuffing_size -= 1;
+        }
+        fields.append(&mut vec![0xFF; stuffing_size]);
+      }
+      let payload_size = remaining.min(payload_space);
+
+      let continuity_counter = self.next_continuity_counter(pid);
+      let adaptation_field_control = if adaptation_field.is_some() { 0x30 } else { 0x10 };
+      packets.append(&mut vec![
+        SYNC_BYTE,
+        if is_first_packet { 0x40 } else { 0x00 } | ((pid >> 8) & 0x1F) as u8,
+        (pid & 0xFF) as u8,
+        adaptation_field_control | continuity_counter,
+      ]);
+      if let Some(mut fields) = adaptation_field {
+        packets.push(fields.len() as u8);
+        packets.append(&mut fields);
+      }
+      packets.extend_from_slice(&pes[offset..(offset + payload_size)]);
+      offset += payload_size;
+    }
+    Ok(packets)
+  }
+
+  // program_association_section(). ITU-T H.222.0; 2.4.4.3
+  fn build_pat_section(&self) -> Vec<u8> {
+    let section_length = 5 + 4 + 4;
+    let section = vec![
+      // table_id
+      0x00,
+      // section_syntax_indicator, '0', reserved, section_length
+      0xB0, section_length as u8,
+      (TRANSPORT_STREAM_ID >> 8) as u8, (TRANSPORT_STREAM_ID & 0xFF) as u8,
+      // reserved, version_number 0, current_next_indicator
+      0xC1,
+      // section_number, last_section_number
+      0x00, 0x00,
+      (PROGRAM_NUMBER >> 8) as u8, (PROGRAM_NUMBER & 0xFF) as u8,
+      0xE0 | (PMT_PID >> 8) as u8, (PMT_PID & 0xFF) as u8,
+    ];
+    TSWriter::append_crc_32(section)
+  }
+
+  // TS_program_map_section(). ITU-T H.222.0; 2.4.4.8
+  fn build_pmt_section(&self) -> Vec<u8> {
+    let pcr_pid = self.get_pcr_pid().unwrap_or(0x1FFF);
+    let mut stream_infos: Vec<u8> = vec![];
+    for stream in self.streams.iter() {
+      let descriptors = TSWriter::build_language_descriptor(&stream.language);
+      stream_infos.append(&mut vec![
+        stream.stream_type.get_value(),
+        0xE0 | (stream.pid >> 8) as u8, (stream.pid & 0xFF) as u8,
+        0xF0 | (descriptors.len() >> 8) as u8, (descriptors.len() & 0xFF) as u8,
+      ]);
+      stream_infos.extend(descriptors);
+    }
+    let section_length = 9 + stream_infos.len() + 4;
+    let section = [
+      vec![
+        // table_id
+        0x02,
+        0xB0 | (section_length >> 8) as u8, (section_length & 0xFF) as u8,
+        (PROGRAM_NUMBER >> 8) as u8, (PROGRAM_NUMBER & 0xFF) as u8,
+        0xC1,
+        0x00, 0x00,
+        0xE0 | (pcr_pid >> 8) as u8, (pcr_pid & 0xFF) as u8,
+        // program_info_length
+        0xF0, 0x00,
+      ],
+      stream_infos,
+    ].concat();
+    TSWriter::append_crc_32(section)
+  }
+
+  // ISO_639_language_descriptor with an undefined audio_type. ITU-T H.222.0; 2.6.18
+  fn build_language_descriptor(language: &str) -> Vec<u8> {
+    if language == "und" || language.len() != 3 {
+      return vec![];
+    }
+    [
+      vec![ISO_639_LANGUAGE_DESCRIPTOR_TAG, 4],
+      language.as_bytes().to_vec(),
+      vec![0x00],
+    ].concat()
+  }
+
+  fn append_crc_32(mut section: Vec<u8>) -> Vec<u8> {
+    let crc_32 = util::crc_32_mpeg2(&section);
+    section.extend_from_slice(&crc_32.to_be_bytes());
+    section
+  }
+
+  // A section that fits in one TS packet, after a pointer_field of 0 and followed by stuffing
+  fn write_section(&mut self, pid: u16, section: Vec<u8>) -> Vec<u8> {
+    let continuity_counter = self.next_continuity_counter(pid);
+    let mut packet = [
+      vec![
+        SYNC_BYTE,
+        0x40 | ((pid >> 8) & 0x1F) as u8,
+        (pid & 0xFF) as u8,
+        0x10 | continuity_counter,
+        // pointer_field
+        0x00,
+      ],
+      section,
+    ].concat();
+    packet.resize(TS_PACKET_SIZE, 0xFF);
+    packet
+  }
+
+  // PES packet header with the PTS and, when it differs, the DTS. ITU-T H.222.0; 2.4.3.6
+  fn build_pes(stream_type: ElementaryStreamType, pts: u64, dts: Option<u64>, data: &[u8]) -> Vec<u8> {
+    let stream_id = match stream_type {
+      ElementaryStreamType::H_264 | ElementaryStreamType::H_265 => 0xE0,
+      // private_stream_1
+      ElementaryStreamType::AC3 | ElementaryStreamType::E_AC3 => 0xBD,
+      _ => 0xC0,
+    };
+    let dts = dts.filter(|dts|*dts != pts);
+    let timestamps = match dts {
+      Some(dts) => [TSWriter::build_timestamp(0x3, pts), TSWriter::build_timestamp(0x1, dts)].concat(),
+      None => TSWriter::build_timestamp(0x2, pts),
+    };
+    let pts_dts_flags = if dts.is_some() { 0xC0 } else { 0x80 };
+    // PES_packet_length of 0 is only allowed for video, when the packet is too long for the field
+    let pes_packet_length = 3 + timestamps.len() + data.len();
+    let pes_packet_length = if pes_packet_length > 0xFFFF { 0 } else { pes_packet_length };
+    [
+      vec![
+        0x00, 0x00, 0x01, stream_id,
+        (pes_packet_length >> 8) as u8, (pes_packet_length & 0xFF) as u8,
+        // '10', data_alignment_indicator
+        0x84,
+        pts_dts_flags,
+        // PES_header_data_length
+        timestamps.len() as u8,
+      ],
+      timestamps,
+      data.to_vec(),
+    ].concat()
+  }
+
+  // 4 bit prefix and a 33 bit timestamp split up by marker bits
+  fn build_timestamp(prefix: u8, timestamp: u64) -> Vec<u8> {
+    let timestamp = timestamp & MAX_TIMESTAMP;
+    vec![
+      (prefix << 4) | (((timestamp >> 30) & 0x7) << 1) as u8 | 0x1,
+      ((timestamp >> 22) & 0xFF) as u8,
+      (((timestamp >> 15) & 0x7F) << 1) as u8 | 0x1,
+      ((timestamp >> 7) & 0xFF) as u8,
+      ((timestamp & 0x7F) << 1) as u8 | 0x1,
+    ]
+  }
+
+  // program_clock_reference_base (33 bit), reserved (6 bit) and a program_clock_reference_extension of 0
+  fn build_pcr(pcr: u64) -> Vec<u8> {
+    let pcr = pcr & MAX_TIMESTAMP;
+    vec![
+      (pcr >> 25) as u8,
+      (pcr >> 17) as u8,
+      (pcr >> 9) as u8,
+      (pcr >> 1) as u8,
+      ((pcr & 0x1) << 7) as u8 | 0x7E,
+      0x00,
+    ]
+  }
+
+  fn next_continuity_counter(&mut self, pid: u16) -> u8 {
+    let continuity_counter = self.continuity_counters.entry(pid).or_insert(0);
+    let current = *continuity_counter;
+    *continuity_counter = (current + 1) & 0xF;
+    current
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::container::transport_stream::{
+    pes_packet::PESPacket, program_association_table::ProgramAssociationTable, program_map_table::ProgramMapTable,
+    ts_packet::TransportPacket,
+  };
+
+  fn create_ts_writer() -> TSWriter {
+    TSWriter::create_ts_writer()
+      .stream(0x100, ElementaryStreamType::H_264, "und")
+      .stream(0x101, ElementaryStreamType::AAC, "eng")
+  }
+
+  #[test]
+  fn test_write_program_tables() {
+    let mut ts_writer = create_ts_writer();
+    let tables = ts_writer.write_program_tables();
+    assert_eq!(tables.len(), TS_PACKET_SIZE * 2);
+
+    let pat_packet = TransportPacket::parse(&tables[0..188]).unwrap();
+    assert_eq!(pat_packet.pid, 0);
+    let pat = ProgramAssociationTable::parse(pat_packet.data, pat_packet.payload_unit_start_indicator).unwrap();
+    assert_eq!(pat.get_first_program(), Some((1, PMT_PID)));
+    // The CRC_32 of the section
+    assert_eq!(&tables[17..21], &[0x2A, 0xB1, 0x04, 0xB2]);
+
+    let pmt_packet = TransportPacket::parse(&tables[188..]).unwrap();
+    assert_eq!(pmt_packet.pid, PMT_PID);
+    let pmt = ProgramMapTable::parse(pmt_packet.data, pmt_packet.payload_unit_start_indicator).unwrap();
+    assert_eq!(pmt.program_number, 1);
+    assert_eq!(pmt.streams.len(), 2);
+    assert_eq!(pmt.streams[0].stream_type, ElementaryStreamType::H_264);
+    assert_eq!(pmt.streams[0].get_language(), None);
+    assert_eq!(pmt.streams[1].pid, 0x101);
+    assert_eq!(pmt.streams[1].get_language(), Some("eng".to_string()));
+    // A CRC_32 over the whole section including the CRC_32 is 0
+    let section_length = (((tables[188 + 6] & 0x0F) as usize) << 8) | tables[188 + 7] as usize;
+    assert_eq!(util::crc_32_mpeg2(&tables[(188 + 5)..(188 + 8 + section_length)]), 0);
+  }
+
+  #[test]
+  fn test_write_pes() {
+    let mut ts_writer = create_ts_writer();
+    let data: Vec<u8> = (0..400).map(|index|index as u8).collect();
+    let packets = ts_writer.write_pes(0x100, 99000, Some(93000), &data, true).unwrap();
+    assert_eq!(packets.len(), TS_PACKET_SIZE * 3);
+
+    // First packet has the PCR and the random access indicator
+    let first = TransportPacket::parse(&packets[0..188]).unwrap();
+    assert!(first.payload_unit_start_indicator);
+    assert!(first.get_random_access_indicator());
+    let pcr = first.adaptation_field.as_ref().and_then(|adaptation_field|adaptation_field.pcr.as_ref()).unwrap();
+    assert_eq!(pcr.base, 93000 - PCR_DELAY);
+
+    // Last packet is padded with stuffing
+    let last = TransportPacket::parse(&packets[376..]).unwrap();
+    assert_eq!(last.continuity_counter, 2);
+    assert!(!last.payload_unit_start_indicator);
+
+    let pes_data: Vec<u8> = (0..3)
+      .flat_map(|index|TransportPacket::parse(&packets[(index * 188)..((index + 1) * 188)]).unwrap().data.to_vec())
+      .collect();
+    let pes = PESPacket::parse(&pes_data).unwrap();
+    assert_eq!(pes.stream_id, 0xE0);
+    assert_eq!(pes.pts, Some(99000));
+    assert_eq!(pes.dts, Some(93000));
+    assert_eq!(pes.payload_data, data.as_slice());
+
+    // Audio has no PCR and only a PTS. One byte of stuffing is only the adaptation_field_length.
+    let data = vec![0xAA; TS_PACKET_SIZE - TS_HEADER_SIZE - 14 - 1];
+    let packets = ts_writer.write_pes(0x101, 1000, Some(1000), &data, false).unwrap();
+    assert_eq!(packets.len(), TS_PACKET_SIZE);
+    let packet = TransportPacket::parse(&packets).unwrap();
+    assert_eq!(packet.adaptation_field.as_ref().unwrap().adaptation_field_length, 0);
+    let pes = PESPacket::parse(packet.data).unwrap();
+    assert_eq!(pes.stream_id, 0xC0);
+    // PTS_DTS_flags
+    assert_eq!(packet.data[7], 0x80);
+    assert_eq!(pes.payload_data, data.as_slice());
+
+    assert!(ts_writer.write_pes(0x102, 0, None, &data, false).is_err());
+  }
+}
@@ -27,12 +27,13 @@ pub enum ISOBMFFMinorCode {
 pub enum TransportStreamMinorCode {
   PARSE_TS_ERROR           = 0,
   UNSUPPORTED_ADTS_PARSING = 1,
+  CONTINUITY_ERROR         = 2,
 }
 
 #[allow(non_camel_case_types)]
 #[derive(Debug, Eq, PartialEq)]
 pub enum ManifestMinorCode {
-  // TODO
+  PARSE_TAG_ERROR = 0,
 }
 
 #[allow(non_camel_case_types)]
@@ -41,6 +42,7 @@ pub enum UtilMinorCode {
   PARSING_UNSIGNED_ERROR        = 0,
   PARSING_BIT_READER_ERROR      = 1,
   INVALID_ISO_639_2_CODE_ERROR  = 2,
+  DECRYPTION_ERROR              = 3,
 }
 
 #[allow(non_camel_case_types)]
@@ -49,6 +51,8 @@ pub enum NalMinorCode {
   UNEXPTED_NAL_UNIT_LENGTH_ERROR          = 0,
   BYTE_STREAM_MISSING_START_PREFIX_ERROR  = 1,
   UKNOWN_NAL_UNIT_TYPE_ERROR              = 2,
+  MISSING_PARAMETER_SET_ERROR             = 3,
+  INVALID_PARAMETER_SET_ERROR             = 4,
 }
 
 #[allow(non_camel_case_types)]
@@ -56,6 +60,10 @@ pub enum NalMinorCode {
 pub enum RemuxMinorCode {
   MISSING_BUILDER_DEPENDENCY_ERROR = 0,
   UNKNOWN_STREAM_TYPE =  1,
+  PROGRAM_NOT_FOUND_ERROR = 2,
+  NO_SUPPORTED_TRACK_ERROR = 3,
+  UNSUPPORTED_ENCRYPTION_ERROR = 4,
+  UNSUPPORTED_ADTS_ERROR = 5,
 }
 
 impl MinorError for ISOBMFFMinorCode {
@@ -79,6 +87,7 @@ impl MinorError for TransportStreamMinorCode {
       match self {
           TransportStreamMinorCode::PARSE_TS_ERROR => { "Unable to parse transport stream".to_string() }
           TransportStreamMinorCode::UNSUPPORTED_ADTS_PARSING => { "Unable to parse audio data transport stream".to_string() }
+          TransportStreamMinorCode::CONTINUITY_ERROR => { "Transport stream packets are missing, duplicated or out of order".to_string() }
       }
     }
 
@@ -86,17 +95,22 @@ impl MinorError for TransportStreamMinorCode {
       match self {
           TransportStreamMinorCode::PARSE_TS_ERROR => { TransportStreamMinorCode::PARSE_TS_ERROR as u8 }
           TransportStreamMinorCode::UNSUPPORTED_ADTS_PARSING => {TransportStreamMinorCode::UNSUPPORTED_ADTS_PARSING as u8 }
+          TransportStreamMinorCode::CONTINUITY_ERROR => { TransportStreamMinorCode::CONTINUITY_ERROR as u8 }
       }
     }
 }
 
 impl MinorError for ManifestMinorCode {
   fn message(&self) -> String {
-    todo!()
+    match self {
+      ManifestMinorCode::PARSE_TAG_ERROR => { "Unable to parse the playlist tag".to_string() }
+    }
   }
 
   fn code(&self) -> u8 {
-    todo!()
+    match self {
+      ManifestMinorCode::PARSE_TAG_ERROR => { ManifestMinorCode::PARSE_TAG_ERROR as u8 }
+    }
   }
 }
 
@@ -106,6 +120,7 @@ impl MinorError for UtilMinorCode {
       UtilMinorCode::PARSING_UNSIGNED_ERROR => { "An error occurred attempting to parse and unsigned value".to_string() }
       UtilMinorCode::PARSING_BIT_READER_ERROR => { "An error occurred attempting to parse with the bit reader".to_string() }
       UtilMinorCode::INVALID_ISO_639_2_CODE_ERROR => { "An error occurred attempting to parse an iso_639_2 value".to_string() }
+      UtilMinorCode::DECRYPTION_ERROR => { "An error occurred attempting to decrypt".to_string() }
     }
   }
 
@@ -114,6 +129,7 @@ impl MinorError for UtilMinorCode {
       UtilMinorCode::PARSING_UNSIGNED_ERROR => { UtilMinorCode::PARSING_UNSIGNED_ERROR as u8 }
       UtilMinorCode::PARSING_BIT_READER_ERROR => { UtilMinorCode::PARSING_BIT_READER_ERROR as u8 }
       UtilMinorCode::INVALID_ISO_639_2_CODE_ERROR => { UtilMinorCode::INVALID_ISO_639_2_CODE_ERROR as u8 }
+      UtilMinorCode::DECRYPTION_ERROR => { UtilMinorCode::DECRYPTION_ERROR as u8 }
     }
   }
 }
@@ -123,6 +139,8 @@ impl MinorError for NalMinorCode {
       NalMinorCode::UNEXPTED_NAL_UNIT_LENGTH_ERROR => { "Unexpected NAL unit length".to_string() }
       NalMinorCode::BYTE_STREAM_MISSING_START_PREFIX_ERROR => { "Byte stream is missing starting prefix of 0x00000001".to_string() }
       NalMinorCode::UKNOWN_NAL_UNIT_TYPE_ERROR => { "Uknown NAL Unit type".to_string() }
+      NalMinorCode::MISSING_PARAMETER_SET_ERROR => { "Parameter set referenced by the NAL unit is missing".to_string() }
+      NalMinorCode::INVALID_PARAMETER_SET_ERROR => { "Parameter set has a value out of range".to_string() }
     }
   }
 
@@ -131,6 +149,8 @@ impl MinorError for NalMinorCode {
       NalMinorCode::UNEXPTED_NAL_UNIT_LENGTH_ERROR => { NalMinorCode::UNEXPTED_NAL_UNIT_LENGTH_ERROR  as u8 }
       NalMinorCode::BYTE_STREAM_MISSING_START_PREFIX_ERROR => { NalMinorCode::BYTE_STREAM_MISSING_START_PREFIX_ERROR as u8 }
       NalMinorCode::UKNOWN_NAL_UNIT_TYPE_ERROR => { NalMinorCode::UKNOWN_NAL_UNIT_TYPE_ERROR as u8 }
+      NalMinorCode::MISSING_PARAMETER_SET_ERROR => { NalMinorCode::MISSING_PARAMETER_SET_ERROR as u8 }
+      NalMinorCode::INVALID_PARAMETER_SET_ERROR => { NalMinorCode::INVALID_PARAMETER_SET_ERROR as u8 }
     }
   }
 }
@@ -140,6 +160,10 @@ impl MinorError for RemuxMinorCode {
     match self {
       RemuxMinorCode::MISSING_BUILDER_DEPENDENCY_ERROR => { "Missing a dependency required for the builder".to_string() }
       RemuxMinorCode::UNKNOWN_STREAM_TYPE => { "Uknown elementary stream type".to_string() }
+      RemuxMinorCode::PROGRAM_NOT_FOUND_ERROR => { "Unable to find the selected program in the transport stream".to_string() }
+      RemuxMinorCode::NO_SUPPORTED_TRACK_ERROR => { "No track with a codec that can be remuxed".to_string() }
+      RemuxMinorCode::UNSUPPORTED_ENCRYPTION_ERROR => { "Encryption that can't be decrypted with a plain key".to_string() }
+      RemuxMinorCode::UNSUPPORTED_ADTS_ERROR => { "AAC that can't be carried in ADTS frames".to_string() }
     }
   }
 
@@ -147,6 +171,10 @@ impl MinorError for RemuxMinorCode {
     match self {
       RemuxMinorCode::MISSING_BUILDER_DEPENDENCY_ERROR => { RemuxMinorCode::MISSING_BUILDER_DEPENDENCY_ERROR as u8 }
       RemuxMinorCode::UNKNOWN_STREAM_TYPE => { RemuxMinorCode::UNKNOWN_STREAM_TYPE as u8 }
+      RemuxMinorCode::PROGRAM_NOT_FOUND_ERROR => { RemuxMinorCode::PROGRAM_NOT_FOUND_ERROR as u8 }
+      RemuxMinorCode::NO_SUPPORTED_TRACK_ERROR => { RemuxMinorCode::NO_SUPPORTED_TRACK_ERROR as u8 }
+      RemuxMinorCode::UNSUPPORTED_ENCRYPTION_ERROR => { RemuxMinorCode::UNSUPPORTED_ENCRYPTION_ERROR as u8 }
+      RemuxMinorCode::UNSUPPORTED_ADTS_ERROR => { RemuxMinorCode::UNSUPPORTED_ADTS_ERROR as u8 }
     }
   }
 }
\ No newline at end of file
@@ -91,48 +91,37 @@ fn tranmux_test(file_path: &str) {
         let temp = remux_ts_to_mp4(&ts_file);
         match temp {
             Ok(data) => {
-                // AUDIO
-                let mut file_audio_init =
-                    File::create("/Users/benjamintoofer/Desktop/ben_audio_init.mp4").unwrap();
-                match file_audio_init.write_all(&data.audio.init_segment.unwrap()) {
-                    Ok(_) => {
-                        println!("FINISHED WRITING AUDIO INIT SEGMENT!!!")
+                for track in data.tracks {
+                    if let Some(init_segment) = track.init_segment {
+                        let mut file_init = File::create(format!(
+                            "/Users/benjamintoofer/Desktop/ben_track_{}_init.mp4",
+                            track.track_id
+                        ))
+                        .unwrap();
+                        match file_init.write_all(&init_segment) {
+                            Ok(_) => {
+                                println!("FINISHED WRITING TRACK {} INIT SEGMENT!!!", track.track_id)
+                            }
+                            Err(_) => {
+                                println!("FUCKED UP WRITING TRACK {} INIT SEGMENT", track.track_id)
+                            }
+                        }
                     }
-                    Err(_) => {
-                        println!("FUCKED UP WRITING AUDIO SEGMENT")
-                    }
-                }
-
-                let mut file_audio_media =
-                    File::create("/Users/benjamintoofer/Desktop/ben_audio_media.mp4").unwrap();
-                match file_audio_media.write_all(&data.audio.media_segment.unwrap()) {
-                    Ok(_) => {
-                        println!("FINISHED WRITING AUDIO MEDIA SEGMENT!!!")
-                    }
-                    Err(_) => {
-                        println!("FUCKED UP WRITING AUDIO SEGMENT")
-                    }
-                }
-                // VIDEO
-                let mut file_video_init =
-                    File::create("/Users/benjamintoofer/Desktop/ben_video_init.mp4").unwrap();
-                match file_video_init.write_all(&data.video.init_segment.unwrap()) {
-                    Ok(_) => {
-                        println!("FINISHED WRITING VIDEO INIT SEGMENT!!!")
-                    }
-                    Err(_) => {
-                        println!("FUCKED UP WRITING VIDEO SEGMENT")
-                    }
-                }
 
-                let mut file_video_media =
-                    File::create("/Users/benjamintoofer/Desktop/ben_video_media.mp4").unwrap();
-                match file_video_media.write_all(&data.video.media_segment.unwrap()) {
-                    Ok(_) => {
-                        println!("FINISHED WRITING VIDEO MEDIA SEGMENT!!!")
-                    }
-                    Err(_) => {
-                        println!("FUCKED UP WRITING VIDEO SEGMENT")
+                    if let Some(media_segment) = track.media_segment {
+                        let mut file_media = File::create(format!(
+                            "/Users/benjamintoofer/Desktop/ben_track_{}_media.mp4",
+                            track.track_id
+                        ))
+                        .unwrap();
+                        match file_media.write_all(&media_segment) {
+                            Ok(_) => {
+                                println!("FINISHED WRITING TRACK {} MEDIA SEGMENT!!!", track.track_id)
+                            }
+                            Err(_) => {
+                                println!("FUCKED UP WRITING TRACK {} MEDIA SEGMENT", track.track_id)
+                            }
+                        }
                     }
                 }
             }
@@ -56,19 +56,20 @@ impl HLSGenerator {
     .new_line();
 
     audio_tracks.iter().for_each(|track|{
+      let language = ISO639::map_iso_639_2_to_language_tag(&track.language);
       writer.media(
         HLSMediaType::AUDIO, 
         track.audio_group_id.unwrap_or_default(), 
         &ISO639::map_iso_639_2_to_name( &track.language), 
         Some(&track.path), 
-        Some(&ISO639::map_iso_639_2_to_639_1(&track.language)), 
+        language.as_deref(), 
         None, 
         None, 
         None, 
         None, 
         None, 
         None, 
-        Some(track.audio_channels));
+        Some(track.audio_channels.as_str()).filter(|channels|!channels.is_empty()));
     });
 
     writer.new_line();
@@ -0,0 +1,169 @@
+use crate::error::{construct_error, error_code::{MajorCode, ManifestMinorCode}, CustomError};
+
+static EXT_X_KEY_PREFIX: &str = "#EXT-X-KEY:";
+// KEYFORMAT of a key that is the 16 bytes served from the URI. RFC 8216; 4.3.2.4
+pub static IDENTITY_KEY_FORMAT: &str = "identity";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum HLSKeyMethod {
+  NONE,
+  /// The whole segment is encrypted with AES-128-CBC and PKCS#7 padding
+  AES_128,
+  /// Only the media samples are encrypted, with the HLS Sample Encryption pattern
+  SAMPLE_AES,
+  /// SAMPLE-AES with AES-CTR, for fMP4 segments only
+  SAMPLE_AES_CTR,
+}
+
+impl HLSKeyMethod {
+  pub fn value(&self) -> &str {
+    match self {
+      HLSKeyMethod::NONE => {"NONE"}
+      HLSKeyMethod::AES_128 => {"AES-128"}
+      HLSKeyMethod::SAMPLE_AES => {"SAMPLE-AES"}
+      HLSKeyMethod::SAMPLE_AES_CTR => {"SAMPLE-AES-CTR"}
+    }
+  }
+}
+
+/// Attributes of an EXT-X-KEY tag. RFC 8216; 4.3.2.4
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HLSKey {
+  pub method: HLSKeyMethod,
+  pub uri: Option<String>,
+  pub iv: Option<[u8; 16]>,
+  pub key_format: String,
+}
+
+impl HLSKey {
+  /// Parse the EXT-X-KEY tag line, or only its attribute list
+  pub fn parse(tag: &str) -> Result<HLSKey, CustomError> {
+    let attribute_list = tag.trim().trim_start_matches(EXT_X_KEY_PREFIX);
+    let mut method: Option<HLSKeyMethod> = None;
+    let mut uri: Option<String> = None;
+    let mut iv: Option<[u8; 16]> = None;
+    let mut key_format = IDENTITY_KEY_FORMAT.to_string();
+    for (name, value) in HLSKey::parse_attributes(attribute_list)? {
+      match name.as_str() {
+        "METHOD" => {
+          method = Some(match value.as_str() {
+            "NONE" => HLSKeyMethod::NONE,
+            "AES-128" => HLSKeyMethod::AES_128,
+            "SAMPLE-AES" => HLSKeyMethod::SAMPLE_AES,
+            "SAMPLE-AES-CTR" => HLSKeyMethod::SAMPLE_AES_CTR,
+            _ => return Err(generate_error(format!("Unknown EXT-X-KEY METHOD: {}", value))),
+          });
+        }
+        "URI" => uri = Some(value),
+        "IV" => iv = Some(HLSKey::parse_iv(&value)?),
+        "KEYFORMAT" => key_format = value,
+        _ => {}
+      }
+    }
+    let method = method.ok_or_else(|| generate_error("EXT-X-KEY is missing the METHOD attribute".to_string()))?;
+    if method != HLSKeyMethod::NONE && uri.is_none() {
+      return Err(generate_error(format!("EXT-X-KEY with METHOD {} is missing the URI attribute", method.value())));
+    }
+    Ok(HLSKey {
+      method,
+      uri,
+      iv,
+      key_format,
+    })
+  }
+
+  /// The IV of the segment. Without an IV attribute it is the media sequence number of the segment as a big-endian
+  /// 128 bit integer. RFC 8216; 5.2
+  pub fn get_iv(&self, media_sequence: u64) -> [u8; 16] {
+    self.iv.unwrap_or_else(|| (media_sequence as u128).to_be_bytes())
+  }
+
+  /// Whether the key is the 16 bytes served from the URI, rather than something only a DRM system can use
+  pub fn is_identity_key_format(&self) -> bool {
+    self.key_format == IDENTITY_KEY_FORMAT
+  }
+
+  // Attribute list of NAME=VALUE pairs separated by commas. Quoted string values can hold commas and lose the quotes.
+  fn parse_attributes(attribute_list: &str) -> Result<Vec<(String, String)>, CustomError> {
+    let mut attributes: Vec<(String, String)> = vec![];
+    let mut rest = attribute_list.trim();
+    while !rest.is_empty() {
+      let (name, after_name) = rest
+        .split_once('=')
+        .ok_or_else(|| generate_error(format!("EXT-X-KEY attribute without a value: {}", rest)))?;
+      let (value, after_value) = match after_name.strip_prefix('"') {
+        Some(quoted) => {
+          let end = quoted
+            .find('"')
+            .ok_or_else(|| generate_error(format!("EXT-X-KEY attribute {} has an unterminated quoted string", name)))?;
+          (&quoted[..end], &quoted[(end + 1)..])
+        }
+        None => after_name.split_at(after_name.find(',').unwrap_or(after_name.len())),
+      };
+      attributes.push((name.trim().to_string(), value.to_string()));
+      rest = after_value.trim_start().trim_start_matches(',').trim_start();
+    }
+    Ok(attributes)
+  }
+
+  // Hexadecimal-sequence (0x prefix) of up to 128 bits
+  fn parse_iv(value: &str) -> Result<[u8; 16], CustomError> {
+    let hex = value
+      .strip_prefix("0x")
+      .or_else(|| value.strip_prefix("0X"))
+      .ok_or_else(|| generate_error(format!("EXT-X-KEY IV is not a hexadecimal sequence: {}", value)))?;
+    if hex.is_empty() || hex.len() > 32 {
+      return Err(generate_error(format!("EXT-X-KEY IV is not 128 bits: {}", value)));
+    }
+    let iv = u128::from_str_radix(hex, 16)
+      .map_err(|err| generate_error(format!("EXT-X-KEY IV {} :: {}", value, err)))?;
+    Ok(iv.to_be_bytes())
+  }
+}
+
+fn generate_error(message: String) -> CustomError {
+  construct_error(
+    MajorCode::MANIFEST,
+    Box::new(ManifestMinorCode::PARSE_TAG_ERROR),
+    message,
+    file!(),
+    line!())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_hls_key() {
+    let key = HLSKey::parse("#EXT-X-KEY:METHOD=AES-128,URI=\"https://domain.com/key?id=1,2\",IV=0x000102030405060708090A0B0C0D0E0F").unwrap();
+    assert_eq!(key.method, HLSKeyMethod::AES_128);
+    assert_eq!(key.uri.as_deref(), Some("https://domain.com/key?id=1,2"));
+    assert_eq!(key.get_iv(7), [0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F]);
+    assert!(key.is_identity_key_format());
+
+    // Without an IV the media sequence number is the IV
+    let key = HLSKey::parse("METHOD=SAMPLE-AES,URI=\"skd://key\",KEYFORMAT=\"com.apple.streamingkeydelivery\",KEYFORMATVERSIONS=\"1\"").unwrap();
+    assert_eq!(key.method, HLSKeyMethod::SAMPLE_AES);
+    assert_eq!(key.get_iv(0x1234), [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x12, 0x34]);
+    assert!(!key.is_identity_key_format());
+
+    assert_eq!(HLSKey::parse("#EXT-X-KEY:METHOD=NONE").unwrap().method, HLSKeyMethod::NONE);
+  }
+
+  #[test]
+  fn test_parse_hls_key_errors() {
+    for tag in [
+      "#EXT-X-KEY:URI=\"https://domain.com/key\"",
+      "#EXT-X-KEY:METHOD=AES-128",
+      "#EXT-X-KEY:METHOD=AES-256,URI=\"https://domain.com/key\"",
+      "#EXT-X-KEY:METHOD=AES-128,URI=\"https://domain.com/key\",IV=0x0001020304050607080910111213141516",
+      "#EXT-X-KEY:METHOD=AES-128,URI=\"https://domain.com/key",
+    ] {
+      let err = HLSKey::parse(tag).unwrap_err();
+      assert_eq!(err.major, MajorCode::MANIFEST);
+      assert_eq!(err.minor, ManifestMinorCode::PARSE_TAG_ERROR as u8);
+    }
+  }
+}
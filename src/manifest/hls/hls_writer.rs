@@ -115,7 +115,7 @@ impl HLSWriter {
     forced: Option<HLSBool>,
     instream_id: Option<CCInstreamId>,          // ONLY CLOSED-CAPTIONS
     characteristics: Option<&str>,
-    channels: Option<u8>,
+    channels: Option<&str>,
   ) -> &mut HLSWriter {
     self.hls_manifest_str.push_str(format!("{}-X-MEDIA:TYPE={},GROUP-ID=\"{}\",NAME=\"{}\"", EXT_TAG_PREFIX, media_type.value(), group_id, name).as_str());  
     if let Some(language) = language {
@@ -458,7 +458,7 @@ mod tests {
       Option::Some(HLSBool::NO),
       Option::Some(CCInstreamId::CC1),
       Option::Some("some,value"),
-      Option::Some(2)
+      Option::Some("2")
     );
 
     assert_eq!(writer.finish(), expected_manifest);
@@ -1,5 +1,6 @@
 pub mod hls_generator;
 pub mod hls_writer;
+pub mod hls_key;
 
 pub enum HLSVersion {
   _4,
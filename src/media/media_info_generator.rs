@@ -2,7 +2,7 @@ use super::{InitSegmentInfo, MediaInfo, MediaSegmentInfo, TrackInfo, TrackType};
 // TODO (benjamintoofer@gmail.com): Clean these imports
 use crate::error::CustomError;
 use crate::container::isobmff::HandlerType;
-use crate::container::isobmff::boxes::{SampleFlag, hdlr::HDLR, iso_box::{find_box, get_box, get_media_start, get_init_segment_end}, sidx::{ SIDX, SIDXReference}, stsd::STSD, tkhd::TKHDReader, trun::TRUN, mvhd::MVHD, mdhd::MDHDReader};
+use crate::container::isobmff::boxes::{SampleFlag, hdlr::HDLR, iso_box::{get_box, get_media_start, get_init_segment_end}, sidx::{ SIDX, SIDXReference}, stsd::STSD, tkhd::TKHDReader, tfhd::TFHD, trun::TRUN, mvhd::MVHD, mdhd::MDHDReader};
 use crate::container::isobmff::{get_codec, get_channel_count};
 use crate::container::isobmff::sample_entry::avc_sample_entry::AVCSampleEntry;
 
@@ -69,7 +69,7 @@ impl MediaInfoGenerator {
     let width = tkhd_reader.get_width()? as f32 / 65536.0;
     let height = tkhd_reader.get_height()? as f32 / 65536.0;
     let language = mdhd_reader.get_language()?;
-    let audio_channels = if track_type == TrackType::AUDIO { get_channel_count(&mp4)? } else { 0u8 };
+    let audio_channels = if track_type == TrackType::AUDIO { get_channel_count(mp4)? } else { String::new() };
 
     // Init segment information
     let init_segment = InitSegmentInfo {
@@ -84,12 +84,14 @@ impl MediaInfoGenerator {
       // Segment information
       let duration: f32 = sr.subsegment_duration as f32 / timescale as f32;
       let mut start_with_i_frame = MediaInfoGenerator::determine_start_with_i_frame_with_sap(sr);
-      let trun = find_box("moof", offset, mp4)
-          .map(TRUN::parse).unwrap()?;
+      let trun = get_box("moof", offset, mp4)
+          .and_then(TRUN::parse)?;
       if !start_with_i_frame {
         // If we cannot determine that the fragment starts with an iframe we will need to look into the fragment's
-        // trun to determine the first_sample_flags (if available)
-        start_with_i_frame = MediaInfoGenerator::determine_start_with_i_frame_with_trun(&trun)
+        // trun to determine the first_sample_flags (if available), falling back to the tfhd default_sample_flags
+        let tfhd = get_box("moof", offset, mp4)
+          .and_then(TFHD::parse)?;
+        start_with_i_frame = MediaInfoGenerator::determine_start_with_i_frame_with_trun(&trun, &tfhd)
       }
       // Check if this a segment doesnt start with an iframe. This will update the track to know that
       // the track doesn't have segments that start with iframes 
@@ -154,8 +156,9 @@ impl MediaInfoGenerator {
     sidx_ref.starts_with_sap && (sidx_ref.sap_type == 1 || sidx_ref.sap_type == 2)
   }
 
-  fn determine_start_with_i_frame_with_trun(trun: &TRUN) -> bool {
-    trun.first_sample_flags
+  fn determine_start_with_i_frame_with_trun(trun: &TRUN, tfhd: &TFHD) -> bool {
+    trun.get_first_sample_flags()
+      .or(tfhd.get_default_sample_flags())
       .map(|x|SampleFlag::parse(x))
       .as_mut()
       .map(|f| f.get_sample_depends_on() == 2) // Is an I-Frame
@@ -174,7 +177,7 @@ fn get_captions(mp4: &[u8]) -> Result<STSD, CustomError> {
   let mdat = get_box("mdat", 0, mp4)?;
   if HandlerType::VIDE.eq(&hdlr.get_handler_type()) {
     let avc1_sample_entry = stsd.read_sample_entry("avc1")
-      .map(AVCSampleEntry::parse)?;
+      .and_then(AVCSampleEntry::parse)?;
     let nal_unit_size = avc1_sample_entry.config.length_size_minus_one + 1;
     // AVCSampleEntry::parse(avc1_sample_entry_data)
   } else if HandlerType::SOUN.eq(&hdlr.get_handler_type()) {
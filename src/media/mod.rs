@@ -47,7 +47,8 @@ pub struct TrackInfo<'a> {
   pub width: f32,
   pub height: f32,
   pub language: String,
-  pub audio_channels: u8,
+  /// Value of the HLS CHANNELS attribute (e.g. "2" or "16/JOC"). Empty for non audio tracks.
+  pub audio_channels: String,
   pub duration: f32,
   // instream_id: &'a str,
   // Playlist manifest related
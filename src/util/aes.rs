@@ -0,0 +1,244 @@
+use crate::error::{error_code::{MajorCode, UtilMinorCode}, construct_error, CustomError};
+
+pub static AES_BLOCK_SIZE: usize = 16;
+static ROUNDS: usize = 10;
+static ROUND_CONSTANTS: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1B, 0x36];
+
+static S_BOX: [u8; 256] = [
+  0x63, 0x7C, 0x77, 0x7B, 0xF2, 0x6B, 0x6F, 0xC5, 0x30, 0x01, 0x67, 0x2B, 0xFE, 0xD7, 0xAB, 0x76,
+  0xCA, 0x82, 0xC9, 0x7D, 0xFA, 0x59, 0x47, 0xF0, 0xAD, 0xD4, 0xA2, 0xAF, 0x9C, 0xA4, 0x72, 0xC0,
+  0xB7, 0xFD, 0x93, 0x26, 0x36, 0x3F, 0xF7, 0xCC, 0x34, 0xA5, 0xE5, 0xF1, 0x71, 0xD8, 0x31, 0x15,
+  0x04, 0xC7, 0x23, 0xC3, 0x18, 0x96, 0x05, 0x9A, 0x07, 0x12, 0x80, 0xE2, 0xEB, 0x27, 0xB2, 0x75,
+  0x09, 0x83, 0x2C, 0x1A, 0x1B, 0x6E, 0x5A, 0xA0, 0x52, 0x3B, 0xD6, 0xB3, 0x29, 0xE3, 0x2F, 0x84,
+  0x53, 0xD1, 0x00, 0xED, 0x20, 0xFC, 0xB1, 0x5B, 0x6A, 0xCB, 0xBE, 0x39, 0x4A, 0x4C, 0x58, 0xCF,
+  0xD0, 0xEF, 0xAA, 0xFB, 0x43, 0x4D, 0x33, 0x85, 0x45, 0xF9, 0x02, 0x7F, 0x50, 0x3C, 0x9F, 0xA8,
+  0x51, 0xA3, 0x40, 0x8F, 0x92, 0x9D, 0x38, 0xF5, 0xBC, 0xB6, 0xDA, 0x21, 0x10, 0xFF, 0xF3, 0xD2,
+  0xCD, 0x0C, 0x13, 0xEC, 0x5F, 0x97, 0x44, 0x17, 0xC4, 0xA7, 0x7E, 0x3D, 0x64, 0x5D, 0x19, 0x73,
+  0x60, 0x81, 0x4F, 0xDC, 0x22, 0x2A, 0x90, 0x88, 0x46, 0xEE, 0xB8, 0x14, 0xDE, 0x5E, 0x0B, 0xDB,
+  0xE0, 0x32, 0x3A, 0x0A, 0x49, 0x06, 0x24, 0x5C, 0xC2, 0xD3, 0xAC, 0x62, 0x91, 0x95, 0xE4, 0x79,
+  0xE7, 0xC8, 0x37, 0x6D, 0x8D, 0xD5, 0x4E, 0xA9, 0x6C, 0x56, 0xF4, 0xEA, 0x65, 0x7A, 0xAE, 0x08,
+  0xBA, 0x78, 0x25, 0x2E, 0x1C, 0xA6, 0xB4, 0xC6, 0xE8, 0xDD, 0x74, 0x1F, 0x4B, 0xBD, 0x8B, 0x8A,
+  0x70, 0x3E, 0xB5, 0x66, 0x48, 0x03, 0xF6, 0x0E, 0x61, 0x35, 0x57, 0xB9, 0x86, 0xC1, 0x1D, 0x9E,
+  0xE1, 0xF8, 0x98, 0x11, 0x69, 0xD9, 0x8E, 0x94, 0x9B, 0x1E, 0x87, 0xE9, 0xCE, 0x55, 0x28, 0xDF,
+  0x8C, 0xA1, 0x89, 0x0D, 0xBF, 0xE6, 0x42, 0x68, 0x41, 0x99, 0x2D, 0x0F, 0xB0, 0x54, 0xBB, 0x16,
+];
+
+static INVERSE_S_BOX: [u8; 256] = [
+  0x52, 0x09, 0x6A, 0xD5, 0x30, 0x36, 0xA5, 0x38, 0xBF, 0x40, 0xA3, 0x9E, 0x81, 0xF3, 0xD7, 0xFB,
+  0x7C, 0xE3, 0x39, 0x82, 0x9B, 0x2F, 0xFF, 0x87, 0x34, 0x8E, 0x43, 0x44, 0xC4, 0xDE, 0xE9, 0xCB,
+  0x54, 0x7B, 0x94, 0x32, 0xA6, 0xC2, 0x23, 0x3D, 0xEE, 0x4C, 0x95, 0x0B, 0x42, 0xFA, 0xC3, 0x4E,
+  0x08, 0x2E, 0xA1, 0x66, 0x28, 0xD9, 0x24, 0xB2, 0x76, 0x5B, 0xA2, 0x49, 0x6D, 0x8B, 0xD1, 0x25,
+  0x72, 0xF8, 0xF6, 0x64, 0x86, 0x68, 0x98, 0x16, 0xD4, 0xA4, 0x5C, 0xCC, 0x5D, 0x65, 0xB6, 0x92,
+  0x6C, 0x70, 0x48, 0x50, 0xFD, 0xED, 0xB9, 0xDA, 0x5E, 0x15, 0x46, 0x57, 0xA7, 0x8D, 0x9D, 0x84,
+  0x90, 0xD8, 0xAB, 0x00, 0x8C, 0xBC, 0xD3, 0x0A, 0xF7, 0xE4, 0x58, 0x05, 0xB8, 0xB3, 0x45, 0x06,
+  0xD0, 0x2C, 0x1E, 0x8F, 0xCA, 0x3F, 0x0F, 0x02, 0xC1, 0xAF, 0xBD, 0x03, 0x01, 0x13, 0x8A, 0x6B,
+  0x3A, 0x91, 0x11, 0x41, 0x4F, 0x67, 0xDC, 0xEA, 0x97, 0xF2, 0xCF, 0xCE, 0xF0, 0xB4, 0xE6, 0x73,
+  0x96, 0xAC, 0x74, 0x22, 0xE7, 0xAD, 0x35, 0x85, 0xE2, 0xF9, 0x37, 0xE8, 0x1C, 0x75, 0xDF, 0x6E,
+  0x47, 0xF1, 0x1A, 0x71, 0x1D, 0x29, 0xC5, 0x89, 0x6F, 0xB7, 0x62, 0x0E, 0xAA, 0x18, 0xBE, 0x1B,
+  0xFC, 0x56, 0x3E, 0x4B, 0xC6, 0xD2, 0x79, 0x20, 0x9A, 0xDB, 0xC0, 0xFE, 0x78, 0xCD, 0x5A, 0xF4,
+  0x1F, 0xDD, 0xA8, 0x33, 0x88, 0x07, 0xC7, 0x31, 0xB1, 0x12, 0x10, 0x59, 0x27, 0x80, 0xEC, 0x5F,
+  0x60, 0x51, 0x7F, 0xA9, 0x19, 0xB5, 0x4A, 0x0D, 0x2D, 0xE5, 0x7A, 0x9F, 0x93, 0xC9, 0x9C, 0xEF,
+  0xA0, 0xE0, 0x3B, 0x4D, 0xAE, 0x2A, 0xF5, 0xB0, 0xC8, 0xEB, 0xBB, 0x3C, 0x83, 0x53, 0x99, 0x61,
+  0x17, 0x2B, 0x04, 0x7E, 0xBA, 0x77, 0xD6, 0x26, 0xE1, 0x69, 0x14, 0x63, 0x55, 0x21, 0x0C, 0x7D,
+];
+
+/// AES-128 block cipher. FIPS 197
+pub struct AES128 {
+  round_keys: [[u8; 16]; 11],
+}
+
+impl AES128 {
+  pub fn create(key: &[u8; 16]) -> AES128 {
+    AES128 {
+      round_keys: AES128::expand_key(key),
+    }
+  }
+
+  pub fn encrypt_block(&self, block: &mut [u8; 16]) {
+    AES128::add_round_key(block, &self.round_keys[0]);
+    for round in 1..=ROUNDS {
+      block.iter_mut().for_each(|byte|*byte = S_BOX[*byte as usize]);
+      AES128::shift_rows(block);
+      if round != ROUNDS {
+        AES128::mix_columns(block);
+      }
+      AES128::add_round_key(block, &self.round_keys[round]);
+    }
+  }
+
+  pub fn decrypt_block(&self, block: &mut [u8; 16]) {
+    AES128::add_round_key(block, &self.round_keys[ROUNDS]);
+    for round in (0..ROUNDS).rev() {
+      AES128::inverse_shift_rows(block);
+      block.iter_mut().for_each(|byte|*byte = INVERSE_S_BOX[*byte as usize]);
+      AES128::add_round_key(block, &self.round_keys[round]);
+      if round != 0 {
+        AES128::inverse_mix_columns(block);
+      }
+    }
+  }
+
+  /// CBC decryption in place of the whole blocks of the data, the bytes after the last whole block are left as they
+  /// are. Returns the last ciphertext block, the IV to carry on the chain with.
+  pub fn decrypt_cbc_blocks(&self, iv: &[u8; 16], data: &mut [u8]) -> [u8; 16] {
+    let mut previous_block = *iv;
+    for chunk in data.chunks_exact_mut(AES_BLOCK_SIZE) {
+      let mut block = [0u8; 16];
+      block.copy_from_slice(chunk);
+      let ciphertext_block = block;
+      self.decrypt_block(&mut block);
+      for (index, byte) in chunk.iter_mut().enumerate() {
+        *byte = block[index] ^ previous_block[index];
+      }
+      previous_block = ciphertext_block;
+    }
+    previous_block
+  }
+
+  /// CBC decryption of data padded with PKCS#7, as whole HLS AES-128 segments are. RFC 8216; 5.2
+  pub fn decrypt_cbc(&self, iv: &[u8; 16], data: &[u8]) -> Result<Vec<u8>, CustomError> {
+    if data.is_empty() || !data.len().is_multiple_of(AES_BLOCK_SIZE) {
+      return Err(AES128::generate_error(format!("Encrypted data of {} bytes isn't made of whole blocks", data.len())));
+    }
+    let mut decrypted_data = data.to_vec();
+    self.decrypt_cbc_blocks(iv, &mut decrypted_data);
+    let padding = *decrypted_data.last().unwrap_or(&0) as usize;
+    let padding_start = decrypted_data.len().saturating_sub(padding);
+    if padding == 0 || padding > AES_BLOCK_SIZE || decrypted_data[padding_start..].iter().any(|byte|*byte as usize != padding) {
+      return Err(AES128::generate_error("Decrypted data has invalid PKCS#7 padding. Wrong key or IV?".to_string()));
+    }
+    decrypted_data.truncate(padding_start);
+    Ok(decrypted_data)
+  }
+
+  fn expand_key(key: &[u8; 16]) -> [[u8; 16]; 11] {
+    let mut round_keys = [[0u8; 16]; 11];
+    round_keys[0] = *key;
+    for round in 1..=ROUNDS {
+      let previous_key = round_keys[round - 1];
+      let mut word = [previous_key[13], previous_key[14], previous_key[15], previous_key[12]];
+      word.iter_mut().for_each(|byte|*byte = S_BOX[*byte as usize]);
+      word[0] ^= ROUND_CONSTANTS[round - 1];
+      for index in 0..16 {
+        let byte = previous_key[index] ^ word[index % 4];
+        round_keys[round][index] = byte;
+        word[index % 4] = byte;
+      }
+    }
+    round_keys
+  }
+
+  fn add_round_key(block: &mut [u8; 16], round_key: &[u8; 16]) {
+    block.iter_mut().zip(round_key.iter()).for_each(|(byte, key_byte)|*byte ^= key_byte);
+  }
+
+  // The block is stored column by column, so row r of column c is at c * 4 + r
+  fn shift_rows(block: &mut [u8; 16]) {
+    let state = *block;
+    for (index, byte) in block.iter_mut().enumerate() {
+      let (column, row) = (index / 4, index % 4);
+      *byte = state[((column + row) % 4) * 4 + row];
+    }
+  }
+
+  fn inverse_shift_rows(block: &mut [u8; 16]) {
+    let state = *block;
+    for (index, byte) in state.iter().enumerate() {
+      let (column, row) = (index / 4, index % 4);
+      block[((column + row) % 4) * 4 + row] = *byte;
+    }
+  }
+
+  fn mix_columns(block: &mut [u8; 16]) {
+    for column in block.chunks_exact_mut(4) {
+      let state = [column[0], column[1], column[2], column[3]];
+      for row in 0..4 {
+        column[row] = multiply(state[row], 2) ^ multiply(state[(row + 1) % 4], 3) ^ state[(row + 2) % 4] ^ state[(row + 3) % 4];
+      }
+    }
+  }
+
+  fn inverse_mix_columns(block: &mut [u8; 16]) {
+    for column in block.chunks_exact_mut(4) {
+      let state = [column[0], column[1], column[2], column[3]];
+      for row in 0..4 {
+        column[row] = multiply(state[row], 14) ^ multiply(state[(row + 1) % 4], 11) ^
+          multiply(state[(row + 2) % 4], 13) ^ multiply(state[(row + 3) % 4], 9);
+      }
+    }
+  }
+
+  fn generate_error(message: String) -> CustomError {
+    construct_error(
+      MajorCode::UTIL,
+      Box::new(UtilMinorCode::DECRYPTION_ERROR),
+      message,
+      file!(),
+      line!())
+  }
+}
+
+// Multiplication in GF(2^8) with the AES polynomial x^8 + x^4 + x^3 + x + 1
+fn multiply(value: u8, factor: u8) -> u8 {
+  let mut result = 0u8;
+  let mut value = value;
+  let mut factor = factor;
+  while factor != 0 {
+    if factor & 0x1 != 0 {
+      result ^= value;
+    }
+    value = if value & 0x80 != 0 { (value << 1) ^ 0x1B } else { value << 1 };
+    factor >>= 1;
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len()).step_by(2).map(|index|u8::from_str_radix(&hex[index..index + 2], 16).unwrap()).collect()
+  }
+
+  #[test]
+  fn test_aes_128_block() {
+    // FIPS 197; C.1
+    let key: [u8; 16] = [0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F];
+    let aes = AES128::create(&key);
+    let mut block = [0u8; 16];
+    block.copy_from_slice(&from_hex("00112233445566778899aabbccddeeff"));
+    aes.encrypt_block(&mut block);
+    assert_eq!(block.to_vec(), from_hex("69c4e0d86a7b0430d8cdb78070b4c55a"));
+    aes.decrypt_block(&mut block);
+    assert_eq!(block.to_vec(), from_hex("00112233445566778899aabbccddeeff"));
+  }
+
+  #[test]
+  fn test_aes_128_cbc_decryption() {
+    // NIST SP 800-38A; F.2.2
+    let mut key = [0u8; 16];
+    key.copy_from_slice(&from_hex("2b7e151628aed2a6abf7158809cf4f3c"));
+    let mut iv = [0u8; 16];
+    iv.copy_from_slice(&from_hex("000102030405060708090a0b0c0d0e0f"));
+    let aes = AES128::create(&key);
+    let mut data = from_hex("7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2");
+    let next_iv = aes.decrypt_cbc_blocks(&iv, &mut data);
+    assert_eq!(data, from_hex("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51"));
+    assert_eq!(next_iv.to_vec(), from_hex("5086cb9b507219ee95db113a917678b2"));
+
+    // A block of PKCS#7 padding after the 16 bytes of data
+    let mut padding_block = [0x10u8; 16];
+    for (index, byte) in padding_block.iter_mut().enumerate() {
+      *byte ^= next_iv[index];
+    }
+    aes.encrypt_block(&mut padding_block);
+    let encrypted_data = [from_hex("7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2"), padding_block.to_vec()].concat();
+    let decrypted_data = aes.decrypt_cbc(&iv, &encrypted_data).unwrap();
+    assert_eq!(decrypted_data, from_hex("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51"));
+
+    let err = aes.decrypt_cbc(&iv, &encrypted_data[..32]).unwrap_err();
+    assert_eq!(err.major, MajorCode::UTIL);
+    assert!(aes.decrypt_cbc(&iv, &encrypted_data[..20]).is_err());
+  }
+}
@@ -22,8 +22,8 @@ impl<'a> BitReader<'a> {
   }
 
   pub fn read_bits(&mut self, count: usize) -> Result<usize, CustomError> {
-    let num_of_bits = self.data.len() * 8;
-    if self.data_index + count > num_of_bits {
+    let remaining_bits = (self.data.len() - self.data_index) * 8 + self.bit_counter;
+    if count > remaining_bits {
       return Err(
         construct_error(
           MajorCode::UTIL,
@@ -34,6 +34,26 @@ impl<'a> BitReader<'a> {
         )
       )
     }
+    if count == 0 {
+      return Ok(0);
+    }
+    if count > usize::BITS as usize {
+      return Err(
+        construct_error(
+          MajorCode::UTIL,
+          Box::new(UtilMinorCode::PARSING_BIT_READER_ERROR),
+          format!("Could not parse count of {}. Exceeds the {} bits that can be read at once", count, usize::BITS),
+          file!(),
+          line!()
+        )
+      )
+    }
+    // The word is only topped up with whole bytes, so it can hold as few as 57 bits after a load
+    if count > 56 {
+      let high_bits = self.read_bits(count - 32)?;
+      let low_bits = self.read_bits(32)?;
+      return Ok((high_bits << 32) | low_bits);
+    }
 
     if self.bit_counter < count {
       self.load_word()
@@ -47,37 +67,23 @@ impl<'a> BitReader<'a> {
     Ok(read_data)
   }
 
-  fn load_word(&mut self) {
-
-    let mut holder = 0usize;
-    let mut index_offset = 0usize;
-    let bytes = 8usize;
-
-    let temp_word = loop {
-      let index = self.data_index + index_offset;
-      if index >= self.data.len() {
-        break holder;
-      }
-
-      let temp = self.data[index] as usize;
-      let offset = bytes - 1 - index_offset;
-      holder = holder | (temp << (offset * 8));
-
-
-      index_offset += 1;
-
-      if index_offset == bytes {
-        break holder;
-      }
-    };
+  /// Number of bits that haven't been read yet
+  pub fn remaining_bits(&self) -> usize {
+    (self.data.len() - self.data_index) * 8 + self.bit_counter
+  }
 
-    self.word |= temp_word >> self.bit_counter;
-    self.data_index += 8 - (self.bit_counter / 8);
-    self.bit_counter = index_offset * 8;
+  fn load_word(&mut self) {
+    // Only whole bytes are loaded, so a byte is never split between the word and the data still to be loaded
+    while self.bit_counter <= 56 && self.data_index < self.data.len() {
+      let byte = self.data[self.data_index] as usize;
+      self.word |= byte << (56 - self.bit_counter);
+      self.bit_counter += 8;
+      self.data_index += 1;
+    }
   }
 
   fn clear_bits(&mut self, count: usize) {
-    self.word = self.word << count;
+    self.word = self.word.checked_shl(count as u32).unwrap_or(0);
     self.bit_counter -= count;
   }
 
@@ -86,11 +92,33 @@ impl<'a> BitReader<'a> {
     if leading_zero_count == 0 {
       return Ok(0)
     }
+    // ue(v) values are at most 32 bits. More leading zeroes than that is corrupt data.
+    if leading_zero_count > 32 {
+      return Err(
+        construct_error(
+          MajorCode::UTIL,
+          Box::new(UtilMinorCode::PARSING_BIT_READER_ERROR),
+          format!("Invalid exp-Golomb code with {} leading zeroes", leading_zero_count),
+          file!(),
+          line!()
+        )
+      )
+    }
     let add = self.read_bits(leading_zero_count)?;
     let exp_golomb_value = (1 << leading_zero_count) - 1 + add;
     Ok(exp_golomb_value)
   }
 
+  /// se(v): 1, 2, 3, 4 ... map to 1, -1, 2, -2 ... ITU-T H.264; 9.1.1
+  pub fn signed_exp_golomb(&mut self) -> Result<isize, CustomError> {
+    let code_num = self.unsigned_exp_golomb()? as isize;
+    if code_num % 2 == 1 {
+      Ok((code_num + 1) / 2)
+    } else {
+      Ok(-(code_num / 2))
+    }
+  }
+
   fn leading_zeroes(&mut self) -> Result<usize, CustomError> {
     let mut leading_zeroes = 0usize;
     let mut b = self.read_bits(1)?;
@@ -166,7 +194,32 @@ mod tests {
 
     value = bit_reader.read_bits(11).unwrap();
     assert_eq!(value, 0x710);
-    assert_eq!(bit_reader.bit_counter, 53);
+    assert_eq!(bit_reader.bit_counter, 48);
+
+    // Nothing is lost when a load happens in the middle of a byte
+    value = bit_reader.read_bits(32).unwrap();
+    assert_eq!(value, 0x20304050);
+    value = bit_reader.read_bits(24).unwrap();
+    assert_eq!(value, 0x607080);
+    assert!(bit_reader.read_bits(9).is_err());
+  }
+
+  #[test]
+  fn test_read_wide_bits_unaligned() {
+    let data: [u8; 16] = [
+      0xF1, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF,
+      0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF,
+    ];
+
+    let mut bit_reader = BitReader::create_bit_reader(&data);
+    assert_eq!(bit_reader.read_bits(4).unwrap(), 0xF);
+    assert_eq!(bit_reader.read_bits(60).unwrap(), 0x123456789ABCDEF);
+    assert_eq!(bit_reader.read_bits(3).unwrap(), 0x0);
+    assert_eq!(bit_reader.read_bits(57).unwrap(), 0x123456789ABCDE);
+    assert_eq!(bit_reader.read_bits(4).unwrap(), 0xF);
+
+    let mut bit_reader = BitReader::create_bit_reader(&data);
+    assert!(bit_reader.read_bits(65).is_err());
   }
 
   #[test]
@@ -200,4 +253,16 @@ mod tests {
     value = bit_reader.unsigned_exp_golomb().unwrap();
     assert_eq!(value, 4);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_signed_exp_golomb() {
+    // 1 (0), 010 (1), 011 (-1), 00100 (2), 00101 (-2)
+    let data: [u8; 3] = [0b10100110u8, 0b01000010u8, 0b10000000u8];
+    let mut bit_reader = BitReader::create_bit_reader(&data);
+    let values: Vec<isize> = (0..5).map(|_|bit_reader.signed_exp_golomb().unwrap()).collect();
+    assert_eq!(values, vec![0, 1, -1, 2, -2]);
+
+    let corrupt: [u8; 8] = [0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0xFF, 0xFF];
+    assert!(BitReader::create_bit_reader(&corrupt).unsigned_exp_golomb().is_err());
+  }
+}
@@ -0,0 +1,57 @@
+/// Writes values of any bit length one after the other, most significant bit first. The counterpart of BitReader.
+#[derive(Debug, Default)]
+pub struct BitWriter {
+  data: Vec<u8>,
+  bit_counter: usize,
+}
+
+impl BitWriter {
+  pub fn create_bit_writer() -> BitWriter {
+    BitWriter::default()
+  }
+
+  pub fn write_bits(&mut self, value: usize, count: usize) -> &mut BitWriter {
+    for bit_index in (0..count).rev() {
+      if self.bit_counter.is_multiple_of(8) {
+        self.data.push(0);
+      }
+      let bit = ((value >> bit_index) & 0x1) as u8;
+      let last_index = self.data.len() - 1;
+      self.data[last_index] |= bit << (7 - (self.bit_counter % 8));
+      self.bit_counter += 1;
+    }
+    self
+  }
+
+  pub fn write_bool(&mut self, value: bool) -> &mut BitWriter {
+    self.write_bits(value as usize, 1)
+  }
+
+  /// Number of bits written so far
+  pub fn get_bit_count(&self) -> usize {
+    self.bit_counter
+  }
+
+  /// The written data. The last byte is padded with zeroes.
+  pub fn finish(&self) -> Vec<u8> {
+    self.data.clone()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_write_bits() {
+    let mut bit_writer = BitWriter::create_bit_writer();
+    bit_writer
+      .write_bits(0x2, 2)
+      .write_bits(0x1F, 5)
+      .write_bool(true)
+      .write_bits(0x0304, 16)
+      .write_bits(0x5, 3);
+    assert_eq!(bit_writer.get_bit_count(), 27);
+    assert_eq!(bit_writer.finish(), vec![0xBF, 0x03, 0x04, 0xA0]);
+  }
+}
@@ -3,6 +3,50 @@ use std::str;
 use crate::util::bit_reader::BitReader;
 use crate::error::{CustomError, construct_error, error_code::MajorCode, error_code::UtilMinorCode};
 
+// (ISO-639-2/T, ISO-639-2/B, ISO-639-1, name)
+static ISO_639_LANGUAGES: [(&str, &str, &str, &str); 40] = [
+  ("ara", "ara", "ar", "Arabic"),
+  ("bul", "bul", "bg", "Bulgarian"),
+  ("ces", "cze", "cs", "Czech"),
+  ("cym", "wel", "cy", "Welsh"),
+  ("dan", "dan", "da", "Danish"),
+  ("deu", "ger", "de", "German"),
+  ("ell", "gre", "el", "Greek"),
+  ("eng", "eng", "en", "English"),
+  ("est", "est", "et", "Estonian"),
+  ("fas", "per", "fa", "Persian"),
+  ("fin", "fin", "fi", "Finnish"),
+  ("fra", "fre", "fr", "French"),
+  ("gle", "gle", "ga", "Irish"),
+  ("heb", "heb", "he", "Hebrew"),
+  ("hin", "hin", "hi", "Hindi"),
+  ("hrv", "hrv", "hr", "Croatian"),
+  ("hun", "hun", "hu", "Hungarian"),
+  ("ind", "ind", "id", "Indonesian"),
+  ("isl", "ice", "is", "Icelandic"),
+  ("ita", "ita", "it", "Italian"),
+  ("jpn", "jpn", "ja", "Japanese"),
+  ("kor", "kor", "ko", "Korean"),
+  ("lav", "lav", "lv", "Latvian"),
+  ("lit", "lit", "lt", "Lithuanian"),
+  ("msa", "may", "ms", "Malay"),
+  ("nld", "dut", "nl", "Dutch"),
+  ("nor", "nor", "no", "Norwegian"),
+  ("pol", "pol", "pl", "Polish"),
+  ("por", "por", "pt", "Portuguese"),
+  ("ron", "rum", "ro", "Romanian"),
+  ("rus", "rus", "ru", "Russian"),
+  ("slk", "slo", "sk", "Slovak"),
+  ("slv", "slv", "sl", "Slovenian"),
+  ("spa", "spa", "es", "Spanish"),
+  ("srp", "srp", "sr", "Serbian"),
+  ("swe", "swe", "sv", "Swedish"),
+  ("tha", "tha", "th", "Thai"),
+  ("tur", "tur", "tr", "Turkish"),
+  ("ukr", "ukr", "uk", "Ukrainian"),
+  ("zho", "chi", "zh", "Chinese"),
+];
+
 pub struct ISO639 {}
  
 impl ISO639 {
@@ -43,20 +87,50 @@ impl ISO639 {
     Ok([((added_together & 0xFF00) >> 8) as u8, (added_together & 0xFF) as u8])
   }
 
+  /// Look up the ISO-639-1 code and name of a ISO-639-2 language code. Transport streams commonly signal the
+  /// ISO-639-2/B (bibliographic) codes, so those are accepted alongside the ISO-639-2/T codes.
+  fn find_language(language_code: &str) -> Option<(&'static str, &'static str)> {
+    ISO_639_LANGUAGES
+      .iter()
+      .find(|(t_code, b_code, _, _)| *t_code == language_code || *b_code == language_code)
+      .map(|(_, _, code, name)| (*code, *name))
+  }
+
+  /// Convert ISO-639-2/B (bibliographic) language code to the ISO-639-2/T (terminology) code. Codes without a
+  /// different /T code are returned as is.
+  pub fn map_iso_639_2_to_639_2_t(language_code: &str) -> String {
+    ISO_639_LANGUAGES
+      .iter()
+      .find(|(_, b_code, _, _)| *b_code == language_code)
+      .map(|(t_code, _, _, _)|t_code.to_string())
+      .unwrap_or_else(||language_code.to_string())
+  }
+
   /// Convert ISO-639-2/T language code to a name
   pub fn map_iso_639_2_to_name(language_code: &String) -> String {
-    match language_code.as_str() {
-      "eng" => {"English".to_string()}
-      _ => {"Unknown".to_string()}
+    ISO639::find_language(language_code)
+      .map(|(_, name)|name.to_string())
+      .unwrap_or_else(||"Unknown".to_string())
+  }
+
+  /// Convert ISO-639-2 language code to a RFC 5646 language tag (ex. HLS LANGUAGE attribute). The ISO-639-1 code
+  /// is preferred and the ISO-639-2 code is used when there isn't one. None when the language is undetermined.
+  pub fn map_iso_639_2_to_language_tag(language_code: &str) -> Option<String> {
+    match language_code {
+      "und" => None,
+      _ => Some(
+        ISO639::find_language(language_code)
+          .map(|(code, _)|code.to_string())
+          .unwrap_or_else(||language_code.to_string())
+      ),
     }
   }
 
   /// Convert ISO-639-2/T language code to a ISO-639-1/T language code
   pub fn map_iso_639_2_to_639_1(language_code: &String) -> String {
-    match language_code.as_str() {
-      "eng" => {"en".to_string()}
-      _ => {"un".to_string()}
-    }
+    ISO639::find_language(language_code)
+      .map(|(code, _)|code.to_string())
+      .unwrap_or_else(||"un".to_string())
   }
 }
 
@@ -75,6 +149,22 @@ mod tests {
   fn test_map_iso_639_2_to_639_1() {
     assert_eq!(ISO639::map_iso_639_2_to_639_1(&String::from("eng")), String::from("en"));
     assert_eq!(ISO639::map_iso_639_2_to_639_1(&String::from("und")), String::from("un"));
+    assert_eq!(ISO639::map_iso_639_2_to_639_1(&String::from("spa")), String::from("es"));
+    assert_eq!(ISO639::map_iso_639_2_to_639_1(&String::from("ger")), String::from("de"));
+  }
+
+  #[test]
+  fn test_map_iso_639_2_to_language_tag() {
+    assert_eq!(ISO639::map_iso_639_2_to_language_tag("fre"), Some(String::from("fr")));
+    assert_eq!(ISO639::map_iso_639_2_to_language_tag("haw"), Some(String::from("haw")));
+    assert_eq!(ISO639::map_iso_639_2_to_language_tag("und"), None);
+  }
+
+  #[test]
+  fn test_map_iso_639_2_to_639_2_t() {
+    assert_eq!(ISO639::map_iso_639_2_to_639_2_t("ger"), String::from("deu"));
+    assert_eq!(ISO639::map_iso_639_2_to_639_2_t("deu"), String::from("deu"));
+    assert_eq!(ISO639::map_iso_639_2_to_639_2_t("und"), String::from("und"));
   }
 
   #[test]
@@ -1,6 +1,8 @@
 pub mod logger;
 pub mod bit_reader;
+pub mod bit_writer;
 pub mod iso_639;
+pub mod aes;
 
 use std::{convert::TryInto};
 use crate::error::{error_code:: {MajorCode, UtilMinorCode}, construct_error, CustomError};
@@ -10,7 +12,7 @@ use crate::error::{error_code:: {MajorCode, UtilMinorCode}, construct_error, Cus
  */
 
 pub fn get_u64(data: &[u8], start: usize) -> Result<u64, CustomError> {
-  if data.len() < 8 {
+  if data.len() < start + 8 {
     return Err(
       construct_error(
         MajorCode::UTIL, 
@@ -38,7 +40,7 @@ pub fn get_u64(data: &[u8], start: usize) -> Result<u64, CustomError> {
 }
 
 pub fn get_u32(data: &[u8], start: usize) -> Result<u32, CustomError> {
-  if data.len() < 4 {
+  if data.len() < start + 4 {
     return Err(
       construct_error(
         MajorCode::UTIL, 
@@ -66,7 +68,7 @@ pub fn get_u32(data: &[u8], start: usize) -> Result<u32, CustomError> {
 }
 
 pub fn get_u16(data: &[u8], start: usize) -> Result<u16, CustomError> {
-  if data.len() < 2 {
+  if data.len() < start + 2 {
     return Err(
       construct_error(
         MajorCode::UTIL, 
@@ -94,7 +96,7 @@ pub fn get_u16(data: &[u8], start: usize) -> Result<u16, CustomError> {
 }
 
 pub fn get_u8(data: &[u8], start: usize) -> Result<u8, CustomError> {
-  if data.len() == 0 {
+  if data.len() <= start {
     return Err(
       construct_error(
         MajorCode::UTIL, 
@@ -112,7 +114,7 @@ pub fn get_u8(data: &[u8], start: usize) -> Result<u8, CustomError> {
  * Signed operations
  */
 pub fn get_i32(data: &[u8], start: usize)-> Result<i32, CustomError> {
-   if data.len() < 4 {
+   if data.len() < start + 4 {
     return Err(
       construct_error(
         MajorCode::UTIL, 
@@ -170,6 +172,34 @@ pub fn transform_u32_to_u8_array(x: u32) -> [u8;4] {
   [b4, b3, b2, b1]
 }
 
+/**
+ * CRC_32 that ends the PSI sections of a transport stream (CRC-32/MPEG-2). ITU-T H.222.0; Annex A
+ */
+pub fn crc_32_mpeg2(data: &[u8]) -> u32 {
+  let mut crc = 0xFFFFFFFFu32;
+  for byte in data {
+    crc ^= (*byte as u32) << 24;
+    for _ in 0..8 {
+      crc = if crc & 0x80000000 != 0 { (crc << 1) ^ 0x04C11DB7 } else { crc << 1 };
+    }
+  }
+  crc
+}
+
+/**
+ * CRC-16 of the MPEG audio headers, also used for the ADTS crc_check. ISO/IEC 11172-3; 2.4.3.1
+ */
+pub fn crc_16_mpeg(data: &[u8]) -> u16 {
+  let mut crc = 0xFFFFu16;
+  for byte in data {
+    crc ^= (*byte as u16) << 8;
+    for _ in 0..8 {
+      crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+    }
+  }
+  crc
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -256,4 +286,16 @@ mod tests {
     let val: [u8; 3] = [1,0,0];
     assert_eq!(get_i32(&val, 0).is_err(), true)
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_crc_32_mpeg2() {
+    // PAT section with program 1 on PID 0x1000
+    let pat: [u8; 12] = [0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00];
+    assert_eq!(crc_32_mpeg2(&pat), 0x2AB104B2);
+  }
+
+  #[test]
+  fn test_crc_16_mpeg() {
+    assert_eq!(crc_16_mpeg(b"123456789"), 0xAEE7);
+  }
+}